@@ -3,15 +3,18 @@
 //! The core data types for defining 2D and 3D integer lattices:
 //! - `PointN`: an N-dimensional point, most importantly `Point2i` and `Point3i`
 //! - `ExtentN`: an N-dimensional extent, most importantly `Extent2i` and `Extent3i`
+//! - `Orientation2` and `Orientation3`: the axis-aligned rotations and reflections of the lattice
 
 pub mod axis;
 pub mod extent;
+pub mod orientation;
 pub mod point;
 
 pub use axis::{Axis2, Axis3, Axis3Permutation, SignedAxis2, SignedAxis3};
 pub use extent::{
     bounding_extent, Extent2, Extent2f, Extent2i, Extent3, Extent3f, Extent3i, ExtentN,
 };
+pub use orientation::{Orientation, Orientation2, Orientation3};
 pub use point::{point_traits::*, Point2, Point2f, Point2i, Point3, Point3f, Point3i, PointN};
 
 pub use num;
@@ -20,8 +23,8 @@ pub mod prelude {
     pub use super::{
         point::point_traits::*, Axis2, Axis3, Bounded, ConstZero, Distance, DotProduct, Extent2,
         Extent2f, Extent2i, Extent3, Extent3f, Extent3i, ExtentN, GetComponent, IntegerPoint,
-        MapComponents, Neighborhoods, Norm, Ones, Point, Point2, Point2f, Point2i, Point3, Point3f,
-        Point3i, PointN,
+        MapComponents, Neighborhoods, Norm, Ones, Orientation, Orientation2, Orientation3, Point,
+        Point2, Point2f, Point2i, Point3, Point3f, Point3i, PointN,
     };
 }
 
@@ -0,0 +1,440 @@
+//! Axis-aligned orientations of the integer lattice, i.e. the rotations and reflections that map lattice points onto lattice
+//! points.
+//!
+//! There are 8 such orientations in 2D (`Orientation2`) and 48 in 3D (`Orientation3`). Each one is a signed permutation of the
+//! axes, stored as the `SignedAxis` that each of the X, Y (and Z) axes gets mapped onto.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//!
+//! let quarter_turn = Orientation3::quarter_turns(Axis3::Z, 1);
+//! assert_eq!(quarter_turn.transform_point(PointN([1, 2, 3])), PointN([-2, 1, 3]));
+//! assert_eq!(quarter_turn.then(quarter_turn.inverse()), Orientation3::IDENTITY);
+//!
+//! let mirror = Orientation3::mirror(Axis3::X);
+//! assert!(!mirror.is_rotation());
+//! assert_eq!(Orientation3::all().count(), 48);
+//! ```
+
+use crate::{Axis2, Axis3, Axis3Permutation, ExtentN, PointN, SignedAxis2, SignedAxis3};
+
+/// An axis-aligned orientation of N-dimensional space, used to reorient lattice maps.
+pub trait Orientation<N>: Copy {
+    /// The orientation that undoes this one.
+    fn inverse(&self) -> Self;
+
+    /// Maps `p` to its new position. Orientations are always about the origin.
+    fn transform_point(&self, p: PointN<N>) -> PointN<N>;
+
+    /// The extent containing exactly the transformed points of `extent`.
+    fn transform_extent(&self, extent: &ExtentN<N>) -> ExtentN<N>;
+}
+
+/// One of the 8 axis-aligned orientations of 2D space.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Orientation2 {
+    // The images of the X and Y axes.
+    axes: [SignedAxis2; 2],
+}
+
+impl Orientation2 {
+    pub const IDENTITY: Self = Self {
+        axes: [
+            SignedAxis2 {
+                sign: 1,
+                axis: Axis2::X,
+            },
+            SignedAxis2 {
+                sign: 1,
+                axis: Axis2::Y,
+            },
+        ],
+    };
+
+    /// The orientation that maps the X and Y axes onto `images`. Returns `None` unless both images have a sign of +/-1 and they
+    /// are on different axes.
+    #[inline]
+    pub fn from_signed_axes(images: [SignedAxis2; 2]) -> Option<Self> {
+        let valid = images.iter().all(|a| a.sign.abs() == 1) && images[0].axis != images[1].axis;
+
+        if valid {
+            Some(Self { axes: images })
+        } else {
+            None
+        }
+    }
+
+    /// The images of the X and Y axes.
+    #[inline]
+    pub fn signed_axes(&self) -> [SignedAxis2; 2] {
+        self.axes
+    }
+
+    /// Counterclockwise rotation by `turns` multiples of 90 degrees. Negative turns are clockwise.
+    #[inline]
+    pub fn quarter_turns(turns: i32) -> Self {
+        let one_turn = Self {
+            axes: [
+                SignedAxis2::new(1, Axis2::Y),
+                SignedAxis2::new(-1, Axis2::X),
+            ],
+        };
+
+        (0..turns.rem_euclid(4)).fold(Self::IDENTITY, |o, _| o.then(one_turn))
+    }
+
+    /// Reflection that negates `axis`.
+    #[inline]
+    pub fn mirror(axis: Axis2) -> Self {
+        let mut o = Self::IDENTITY;
+        o.axes[axis.index()].sign = -1;
+
+        o
+    }
+
+    /// Reflection that swaps the X and Y axes.
+    #[inline]
+    pub fn transpose() -> Self {
+        Self {
+            axes: [SignedAxis2::new(1, Axis2::Y), SignedAxis2::new(1, Axis2::X)],
+        }
+    }
+
+    /// All 8 orientations.
+    #[inline]
+    pub fn all() -> impl Iterator<Item = Self> {
+        (0..4).flat_map(|turns| {
+            let rotation = Self::quarter_turns(turns);
+
+            [rotation, Self::mirror(Axis2::X).then(rotation)]
+        })
+    }
+
+    /// The orientation that applies `self` and then `next`.
+    #[inline]
+    pub fn then(&self, next: Self) -> Self {
+        Self {
+            axes: [
+                next.transform_signed_axis(self.axes[0]),
+                next.transform_signed_axis(self.axes[1]),
+            ],
+        }
+    }
+
+    /// +1 for rotations, -1 for reflections.
+    #[inline]
+    pub fn determinant(&self) -> i32 {
+        let permutation_sign = if self.axes[0].axis == Axis2::X { 1 } else { -1 };
+
+        permutation_sign * self.axes[0].sign * self.axes[1].sign
+    }
+
+    #[inline]
+    pub fn is_rotation(&self) -> bool {
+        self.determinant() > 0
+    }
+
+    /// Useful for reorienting directional voxel data.
+    #[inline]
+    pub fn transform_signed_axis(&self, a: SignedAxis2) -> SignedAxis2 {
+        let image = self.axes[a.axis.index()];
+
+        SignedAxis2::new(a.sign * image.sign, image.axis)
+    }
+}
+
+impl Orientation<[i32; 2]> for Orientation2 {
+    #[inline]
+    fn inverse(&self) -> Self {
+        let mut inverse = Self::IDENTITY;
+        for (axis, image) in [Axis2::X, Axis2::Y].iter().zip(self.axes.iter()) {
+            inverse.axes[image.axis.index()] = SignedAxis2::new(image.sign, *axis);
+        }
+
+        inverse
+    }
+
+    #[inline]
+    fn transform_point(&self, p: PointN<[i32; 2]>) -> PointN<[i32; 2]> {
+        let mut out = [0; 2];
+        for (c, image) in p.0.iter().zip(self.axes.iter()) {
+            out[image.axis.index()] = image.sign * c;
+        }
+
+        PointN(out)
+    }
+
+    #[inline]
+    fn transform_extent(&self, extent: &ExtentN<[i32; 2]>) -> ExtentN<[i32; 2]> {
+        ExtentN::from_corners(
+            self.transform_point(extent.minimum),
+            self.transform_point(extent.max()),
+        )
+    }
+}
+
+/// One of the 48 axis-aligned orientations of 3D space.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Orientation3 {
+    // The images of the X, Y, and Z axes.
+    axes: [SignedAxis3; 3],
+}
+
+const ALL_PERMUTATIONS: [Axis3Permutation; 6] = [
+    Axis3Permutation::Xyz,
+    Axis3Permutation::Zxy,
+    Axis3Permutation::Yzx,
+    Axis3Permutation::Zyx,
+    Axis3Permutation::Xzy,
+    Axis3Permutation::Yxz,
+];
+
+impl Orientation3 {
+    pub const IDENTITY: Self = Self::new(Axis3Permutation::Xyz, [1, 1, 1]);
+
+    /// The orientation that maps the X, Y, and Z axes onto `permutation.axes()`, negated where `signs` are negative.
+    #[inline]
+    pub const fn new(permutation: Axis3Permutation, signs: [i32; 3]) -> Self {
+        let [x, y, z] = permutation.axes();
+
+        Self {
+            axes: [
+                SignedAxis3::new(if signs[0] < 0 { -1 } else { 1 }, x),
+                SignedAxis3::new(if signs[1] < 0 { -1 } else { 1 }, y),
+                SignedAxis3::new(if signs[2] < 0 { -1 } else { 1 }, z),
+            ],
+        }
+    }
+
+    /// The orientation that maps the X, Y, and Z axes onto `images`. Returns `None` unless every image has a sign of +/-1 and
+    /// they are all on different axes.
+    #[inline]
+    pub fn from_signed_axes(images: [SignedAxis3; 3]) -> Option<Self> {
+        if images.iter().any(|a| a.sign.abs() != 1) {
+            return None;
+        }
+
+        Self::find_permutation([images[0].axis, images[1].axis, images[2].axis])
+            .map(|_| Self { axes: images })
+    }
+
+    /// The images of the X, Y, and Z axes.
+    #[inline]
+    pub fn signed_axes(&self) -> [SignedAxis3; 3] {
+        self.axes
+    }
+
+    /// The permutation of axes, ignoring signs.
+    #[inline]
+    pub fn permutation(&self) -> Axis3Permutation {
+        Self::find_permutation([self.axes[0].axis, self.axes[1].axis, self.axes[2].axis]).unwrap()
+    }
+
+    fn find_permutation(axes: [Axis3; 3]) -> Option<Axis3Permutation> {
+        ALL_PERMUTATIONS.iter().copied().find(|p| p.axes() == axes)
+    }
+
+    /// Counterclockwise rotation (by the right hand rule) about `axis` by `turns` multiples of 90 degrees. Negative turns are
+    /// clockwise.
+    #[inline]
+    pub fn quarter_turns(axis: Axis3, turns: i32) -> Self {
+        // Maps b -> c and c -> -b, leaving a fixed.
+        let [a, b, c] = Axis3Permutation::even_with_normal_axis(axis).axes();
+        let mut one_turn = Self::IDENTITY;
+        one_turn.axes[a.index()] = SignedAxis3::new(1, a);
+        one_turn.axes[b.index()] = SignedAxis3::new(1, c);
+        one_turn.axes[c.index()] = SignedAxis3::new(-1, b);
+
+        (0..turns.rem_euclid(4)).fold(Self::IDENTITY, |o, _| o.then(one_turn))
+    }
+
+    /// Reflection that negates `axis`.
+    #[inline]
+    pub fn mirror(axis: Axis3) -> Self {
+        let mut o = Self::IDENTITY;
+        o.axes[axis.index()].sign = -1;
+
+        o
+    }
+
+    /// Reflection that swaps axes `a` and `b`. This is the identity if `a == b`.
+    #[inline]
+    pub fn transpose(a: Axis3, b: Axis3) -> Self {
+        let mut o = Self::IDENTITY;
+        o.axes[a.index()].axis = b;
+        o.axes[b.index()].axis = a;
+
+        o
+    }
+
+    /// All 48 orientations.
+    #[inline]
+    pub fn all() -> impl Iterator<Item = Self> {
+        ALL_PERMUTATIONS.iter().flat_map(|&permutation| {
+            (0..8).map(move |i| {
+                let sign = |bit: i32| if i & (1 << bit) == 0 { 1 } else { -1 };
+
+                Self::new(permutation, [sign(0), sign(1), sign(2)])
+            })
+        })
+    }
+
+    /// The orientation that applies `self` and then `next`.
+    #[inline]
+    pub fn then(&self, next: Self) -> Self {
+        Self {
+            axes: [
+                next.transform_signed_axis(self.axes[0]),
+                next.transform_signed_axis(self.axes[1]),
+                next.transform_signed_axis(self.axes[2]),
+            ],
+        }
+    }
+
+    /// +1 for rotations, -1 for reflections.
+    #[inline]
+    pub fn determinant(&self) -> i32 {
+        self.permutation().sign() * self.axes[0].sign * self.axes[1].sign * self.axes[2].sign
+    }
+
+    #[inline]
+    pub fn is_rotation(&self) -> bool {
+        self.determinant() > 0
+    }
+
+    /// Useful for reorienting directional voxel data.
+    #[inline]
+    pub fn transform_signed_axis(&self, a: SignedAxis3) -> SignedAxis3 {
+        let image = self.axes[a.axis.index()];
+
+        SignedAxis3::new(a.sign * image.sign, image.axis)
+    }
+}
+
+impl Orientation<[i32; 3]> for Orientation3 {
+    #[inline]
+    fn inverse(&self) -> Self {
+        let mut inverse = Self::IDENTITY;
+        for (axis, image) in [Axis3::X, Axis3::Y, Axis3::Z].iter().zip(self.axes.iter()) {
+            inverse.axes[image.axis.index()] = SignedAxis3::new(image.sign, *axis);
+        }
+
+        inverse
+    }
+
+    #[inline]
+    fn transform_point(&self, p: PointN<[i32; 3]>) -> PointN<[i32; 3]> {
+        let mut out = [0; 3];
+        for (c, image) in p.0.iter().zip(self.axes.iter()) {
+            out[image.axis.index()] = image.sign * c;
+        }
+
+        PointN(out)
+    }
+
+    #[inline]
+    fn transform_extent(&self, extent: &ExtentN<[i32; 3]>) -> ExtentN<[i32; 3]> {
+        ExtentN::from_corners(
+            self.transform_point(extent.minimum),
+            self.transform_point(extent.max()),
+        )
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Extent2i, Extent3i, Point2i, Point3i};
+
+    #[test]
+    fn all_orientations_are_distinct_and_invertible_2d() {
+        let all: Vec<_> = Orientation2::all().collect();
+        assert_eq!(all.len(), 8);
+        assert_eq!(all.iter().filter(|o| o.is_rotation()).count(), 4);
+
+        let p = PointN([1, 2]);
+        for (i, o) in all.iter().enumerate() {
+            assert!(all[..i].iter().all(|other| other != o));
+            assert_eq!(o.then(o.inverse()), Orientation2::IDENTITY);
+            assert_eq!(o.inverse().transform_point(o.transform_point(p)), p);
+        }
+
+        assert_eq!(
+            Orientation2::quarter_turns(1).transform_point(p),
+            PointN([-2, 1])
+        );
+        assert_eq!(
+            Orientation2::quarter_turns(-1),
+            Orientation2::quarter_turns(3)
+        );
+        assert_eq!(Orientation2::transpose().transform_point(p), PointN([2, 1]));
+    }
+
+    #[test]
+    fn all_orientations_are_distinct_and_invertible_3d() {
+        let all: Vec<_> = Orientation3::all().collect();
+        assert_eq!(all.len(), 48);
+        assert_eq!(all.iter().filter(|o| o.is_rotation()).count(), 24);
+
+        let p = PointN([1, 2, 3]);
+        for (i, o) in all.iter().enumerate() {
+            assert!(all[..i].iter().all(|other| other != o));
+            assert_eq!(o.then(o.inverse()), Orientation3::IDENTITY);
+            assert_eq!(o.inverse().transform_point(o.transform_point(p)), p);
+            assert_eq!(Orientation3::from_signed_axes(o.signed_axes()), Some(*o));
+        }
+    }
+
+    #[test]
+    fn quarter_turns_follow_right_hand_rule() {
+        let p = PointN([1, 2, 3]);
+        assert_eq!(
+            Orientation3::quarter_turns(Axis3::X, 1).transform_point(p),
+            PointN([1, -3, 2])
+        );
+        assert_eq!(
+            Orientation3::quarter_turns(Axis3::Y, 1).transform_point(p),
+            PointN([3, 2, -1])
+        );
+        assert_eq!(
+            Orientation3::quarter_turns(Axis3::Z, 1).transform_point(p),
+            PointN([-2, 1, 3])
+        );
+        assert_eq!(
+            Orientation3::quarter_turns(Axis3::Z, 2),
+            Orientation3::mirror(Axis3::X).then(Orientation3::mirror(Axis3::Y))
+        );
+        assert_eq!(
+            Orientation3::transpose(Axis3::X, Axis3::Z).transform_point(p),
+            PointN([3, 2, 1])
+        );
+    }
+
+    #[test]
+    fn transform_extent_contains_transformed_points() {
+        let extent = Extent3i::from_min_and_shape(PointN([1, -2, 3]), PointN([2, 3, 4]));
+        for o in Orientation3::all() {
+            let transformed = o.transform_extent(&extent);
+            assert_eq!(transformed.num_points(), extent.num_points());
+            assert!(extent
+                .iter_points()
+                .all(|p: Point3i| transformed.contains(o.transform_point(p))));
+        }
+
+        let extent = Extent2i::from_min_and_shape(PointN([1, -2]), PointN([2, 3]));
+        for o in Orientation2::all() {
+            let transformed = o.transform_extent(&extent);
+            assert_eq!(transformed.num_points(), extent.num_points());
+            assert!(extent
+                .iter_points()
+                .all(|p: Point2i| transformed.contains(o.transform_point(p))));
+        }
+    }
+}
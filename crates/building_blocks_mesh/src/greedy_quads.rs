@@ -4,7 +4,7 @@ use super::{
 };
 
 use building_blocks_core::{prelude::*, Axis3Permutation};
-use building_blocks_storage::prelude::*;
+use building_blocks_storage::{prelude::*, ArrayIndexer};
 
 /// Contains the output from the `greedy_quads` algorithm. The quads can be used to generate a mesh. See the methods on
 /// `OrientedCubeFace` and `UnorientedQuad` for details.
@@ -14,8 +14,8 @@ pub struct GreedyQuadsBuffer {
     /// One group of quads per cube face.
     pub quad_groups: [QuadGroup; 6],
 
-    // A single array is used for the visited mask because it allows us to index by the same strides as the voxels array. It
-    // also only requires a single allocation.
+    // A single array is used for the visited mask because it only requires a single allocation. It only covers the meshed
+    // extent, so unless the voxels array has exactly that extent, it has its own strides. See `FaceStrides::visited_stride`.
     visited: Array3x1<bool>,
 }
 
@@ -126,7 +126,7 @@ pub fn padded_greedy_quads_chunk_extent(chunk_extent: &Extent3i) -> Extent3i {
 /// into meshes as the user sees fit.
 pub fn greedy_quads<A, T>(voxels: &A, extent: &Extent3i, output: &mut GreedyQuadsBuffer)
 where
-    A: IndexedArray<[i32; 3]>
+    A: IndexedArray<[i32; 3], Indexer = [i32; 3]>
         + ForEach<[i32; 3], (Point3i, Stride), Item = T>
         + Get<Stride, Item = T>,
     T: IsEmpty + IsOpaque + MergeVoxel,
@@ -140,7 +140,7 @@ pub fn greedy_quads_with_merge_strategy<A, T, Merger>(
     extent: &Extent3i,
     output: &mut GreedyQuadsBuffer,
 ) where
-    A: IndexedArray<[i32; 3]>
+    A: IndexedArray<[i32; 3], Indexer = [i32; 3]>
         + ForEach<[i32; 3], (Point3i, Stride), Item = T>
         + Get<Stride, Item = T>,
     T: IsEmpty + IsOpaque,
@@ -165,7 +165,7 @@ fn greedy_quads_for_group<A, T, Merger>(
     visited: &mut Array3x1<bool>,
     quad_group: &mut QuadGroup,
 ) where
-    A: IndexedArray<[i32; 3]>
+    A: IndexedArray<[i32; 3], Indexer = [i32; 3]>
         + ForEach<[i32; 3], (Point3i, Stride), Item = T>
         + Get<Stride, Item = T>,
     T: IsEmpty + IsOpaque,
@@ -202,6 +202,11 @@ fn greedy_quads_for_group<A, T, Merger>(
         n_stride,
         u_stride,
         v_stride,
+        visited_u_stride: visited.stride_from_local_point(Local(*u)),
+        visited_v_stride: visited.stride_from_local_point(Local(*v)),
+        voxels_layout_shape: voxels.layout_extent().shape,
+        voxels_to_visited: voxels.layout_extent().minimum - visited.extent().minimum,
+        visited_shape: visited.extent().shape,
         // The offset to the voxel sharing this cube face.
         visibility_offset: if *n_sign > 0 {
             n_stride
@@ -218,9 +223,12 @@ fn greedy_quads_for_group<A, T, Merger>(
         voxels.for_each(
             &slice_extent,
             |(quad_min, quad_min_stride): (Point3i, Stride), quad_min_voxel| {
+                let quad_min_visited_stride =
+                    visited.stride_from_local_point(Local(quad_min - visited.extent().minimum));
                 if !face_needs_mesh(
                     &quad_min_voxel,
                     quad_min_stride,
+                    quad_min_visited_stride,
                     face_strides.visibility_offset,
                     voxels,
                     visited,
@@ -271,6 +279,7 @@ fn greedy_quads_for_group<A, T, Merger>(
 fn face_needs_mesh<A, T>(
     voxel: &T,
     voxel_stride: Stride,
+    visited_stride: Stride,
     visibility_offset: Stride,
     voxels: &A,
     visited: &Array3x1<bool>,
@@ -279,7 +288,7 @@ where
     A: Get<Stride, Item = T>,
     T: IsEmpty + IsOpaque,
 {
-    if voxel.is_empty() || visited.get(voxel_stride) {
+    if voxel.is_empty() || visited.get(visited_stride) {
         return false;
     }
 
@@ -313,7 +322,7 @@ pub trait MergeStrategy {
 
     /// Return the width and height of the quad that should be constructed.
     ///
-    /// `min_stride`: The `Stride` of `min`.
+    /// `min_stride`: The `Stride` of `min` in `voxels`.
     ///
     /// `min_value`: The voxel value for `min`.
     ///
@@ -326,7 +335,9 @@ pub trait MergeStrategy {
     /// `voxels`: The entire array of voxel data, indexed by `Stride`.
     ///
     /// `visited`: The bitmask of which voxels have already been meshed. A quad's extent will be marked as visited (`true`)
-    ///            after `find_quad` returns.
+    ///            after `find_quad` returns. It only covers the meshed extent, so if `voxels` is larger, e.g. when meshing an
+    ///            `ArrayView`, it doesn't share the strides of `voxels`. Use `FaceStrides::visited_stride` and the
+    ///            `FaceStrides::visited_*_stride` methods to index it.
     fn find_quad<A>(
         min_stride: Stride,
         min_value: &Self::Voxel,
@@ -337,7 +348,7 @@ pub trait MergeStrategy {
         visited: &Array3x1<bool>,
     ) -> (i32, i32)
     where
-        A: IndexedArray<[i32; 3]> + Get<Stride, Item = Self::Voxel>,
+        A: IndexedArray<[i32; 3], Indexer = [i32; 3]> + Get<Stride, Item = Self::Voxel>,
         Self::Voxel: IsEmpty + IsOpaque;
 }
 
@@ -346,6 +357,39 @@ pub struct FaceStrides {
     pub u_stride: Stride,
     pub v_stride: Stride,
     pub visibility_offset: Stride,
+    visited_u_stride: Stride,
+    visited_v_stride: Stride,
+    voxels_layout_shape: Point3i,
+    voxels_to_visited: Point3i,
+    visited_shape: Point3i,
+}
+
+impl FaceStrides {
+    /// The `u_stride` of the visited mask.
+    #[inline]
+    pub fn visited_u_stride(&self) -> Stride {
+        self.visited_u_stride
+    }
+
+    /// The `v_stride` of the visited mask.
+    #[inline]
+    pub fn visited_v_stride(&self) -> Stride {
+        self.visited_v_stride
+    }
+
+    /// Converts a `Stride` of the voxels array into the `Stride` of the same point in the visited mask.
+    #[inline]
+    pub fn visited_stride(&self, voxel_stride: Stride) -> Stride {
+        let i = voxel_stride.0 as i32;
+        let layout_x = self.voxels_layout_shape.x();
+        let layout_xy = layout_x * self.voxels_layout_shape.y();
+        let layout_p = PointN([i % layout_x, (i % layout_xy) / layout_x, i / layout_xy]);
+
+        <[i32; 3]>::stride_from_local_point(
+            self.visited_shape,
+            Local(layout_p + self.voxels_to_visited),
+        )
+    }
 }
 
 /// A per-voxel value used for merging quads.
@@ -384,28 +428,30 @@ where
 
         // Start by finding the widest quad in the U direction.
         let mut row_start_stride = min_stride;
+        let mut row_start_visited_stride = face_strides.visited_stride(min_stride);
         let quad_width = Self::get_row_width(
             voxels,
             visited,
             &quad_value,
-            face_strides.visibility_offset,
+            face_strides,
             row_start_stride,
-            face_strides.u_stride,
+            row_start_visited_stride,
             max_width,
         );
 
         // Now see how tall we can make the quad in the V direction without changing the width.
         max_width = max_width.min(quad_width);
         row_start_stride += face_strides.v_stride;
+        row_start_visited_stride += face_strides.visited_v_stride;
         let mut quad_height = 1;
         while quad_height < max_height {
             let row_width = Self::get_row_width(
                 voxels,
                 visited,
                 &quad_value,
-                face_strides.visibility_offset,
+                face_strides,
                 row_start_stride,
-                face_strides.u_stride,
+                row_start_visited_stride,
                 max_width,
             );
             if row_width < quad_width {
@@ -413,6 +459,7 @@ where
             }
             quad_height += 1;
             row_start_stride += face_strides.v_stride;
+            row_start_visited_stride += face_strides.visited_v_stride;
         }
 
         (quad_width, quad_height)
@@ -424,9 +471,9 @@ impl<T> VoxelMerger<T> {
         voxels: &A,
         visited: &Array3x1<bool>,
         quad_merge_voxel_value: &T::VoxelValue,
-        visibility_offset: Stride,
+        face_strides: &FaceStrides,
         start_stride: Stride,
-        delta_stride: Stride,
+        start_visited_stride: Stride,
         max_width: i32,
     ) -> i32
     where
@@ -435,15 +482,23 @@ impl<T> VoxelMerger<T> {
     {
         let mut quad_width = 0;
         let mut row_stride = start_stride;
+        let mut row_visited_stride = start_visited_stride;
         while quad_width < max_width {
-            if visited.get(row_stride) {
+            if visited.get(row_visited_stride) {
                 // Already have a quad for this voxel face.
                 break;
             }
 
             let voxel = voxels.get(row_stride);
 
-            if !face_needs_mesh(&voxel, row_stride, visibility_offset, voxels, visited) {
+            if !face_needs_mesh(
+                &voxel,
+                row_stride,
+                row_visited_stride,
+                face_strides.visibility_offset,
+                voxels,
+                visited,
+            ) {
                 break;
             }
 
@@ -453,7 +508,8 @@ impl<T> VoxelMerger<T> {
             }
 
             quad_width += 1;
-            row_stride += delta_stride;
+            row_stride += face_strides.u_stride;
+            row_visited_stride += face_strides.visited_u_stride;
         }
 
         quad_width
@@ -461,3 +517,68 @@ impl<T> VoxelMerger<T> {
 }
 
 // TODO: implement a MergeStrategy for voxels with an ambient occlusion value at each vertex
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn view_has_same_quads_as_copied_sub_array() {
+        let extent = Extent3i::from_min_and_shape(PointN([-5, -3, 0]), PointN([20, 16, 18]));
+        let voxels = Array3x1::fill_with(extent, |p: Point3i| {
+            let solid = (p.x() * 3 + p.y() * 5 + p.z() * 7) % 11 < 4 || p.y() < 2;
+            let material = (p.x().abs() / 4) as u8;
+
+            TestVoxel(solid, material)
+        });
+
+        let sub_extent = Extent3i::from_min_and_shape(PointN([-1, 0, 3]), PointN([9, 11, 7]));
+        let sub_array = Array3x1::fill_with(sub_extent, |p| voxels.get(p));
+
+        let mut view_buffer =
+            GreedyQuadsBuffer::new(sub_extent, RIGHT_HANDED_Y_UP_CONFIG.quad_groups());
+        greedy_quads(&voxels.view(&sub_extent), &sub_extent, &mut view_buffer);
+        let mut copy_buffer =
+            GreedyQuadsBuffer::new(sub_extent, RIGHT_HANDED_Y_UP_CONFIG.quad_groups());
+        greedy_quads(&sub_array, &sub_extent, &mut copy_buffer);
+
+        assert!(copy_buffer.num_quads() > 0);
+        for (view_group, copy_group) in view_buffer
+            .quad_groups
+            .iter()
+            .zip(copy_buffer.quad_groups.iter())
+        {
+            assert_eq!(view_group.quads, copy_group.quads);
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    struct TestVoxel(bool, u8);
+
+    impl MergeVoxel for TestVoxel {
+        type VoxelValue = u8;
+
+        fn voxel_merge_value(&self) -> Self::VoxelValue {
+            self.1
+        }
+    }
+
+    impl IsEmpty for TestVoxel {
+        fn is_empty(&self) -> bool {
+            !self.0
+        }
+    }
+
+    impl IsOpaque for TestVoxel {
+        fn is_opaque(&self) -> bool {
+            true
+        }
+    }
+}
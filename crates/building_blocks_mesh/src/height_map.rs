@@ -59,12 +59,12 @@ pub fn triangulate_height_map<A, H>(
     extent: &Extent2i,
     output: &mut HeightMapMeshBuffer,
 ) where
-    A: IndexedArray<[i32; 2]>
+    A: IndexedArray<[i32; 2], Indexer = [i32; 2]>
         + ForEach<[i32; 2], (Point2i, Stride), Item = H>
         + Get<Stride, Item = H>,
     H: Height,
 {
-    output.reset(height_map.extent().num_points());
+    output.reset(height_map.layout_extent().num_points());
 
     // Avoid accessing out of bounds with a 3x3x3 kernel.
     let interior_extent = extent.padded(-1);
@@ -111,7 +111,7 @@ pub fn triangulate_height_map<A, H>(
     // Only add a quad when p is the bottom-left corner of a quad that fits in the interior.
     let quads_extent = interior_extent.add_to_shape(PointN([-1; 2]));
 
-    let visitor = ArrayForEach::new_global(height_map.extent(), quads_extent);
+    let visitor = ArrayForEach::new_global(height_map.layout_extent(), quads_extent);
     visitor.for_each_point_and_stride(|_p, bl_stride| {
         let br_stride = bl_stride + x_stride;
         let tl_stride = bl_stride + y_stride;
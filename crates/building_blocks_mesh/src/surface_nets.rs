@@ -64,10 +64,10 @@ pub fn surface_nets<A, T>(
     voxel_size: f32,
     output: &mut SurfaceNetsBuffer,
 ) where
-    A: IndexedArray<[i32; 3]> + Get<Stride, Item = T>,
+    A: IndexedArray<[i32; 3], Indexer = [i32; 3]> + Get<Stride, Item = T>,
     T: SignedDistance,
 {
-    output.reset(sdf.extent().num_points());
+    output.reset(sdf.layout_extent().num_points());
 
     estimate_surface(sdf, extent, voxel_size, output);
     make_all_quads(sdf, extent, output);
@@ -81,7 +81,7 @@ fn estimate_surface<A, T>(
     voxel_size: f32,
     output: &mut SurfaceNetsBuffer,
 ) where
-    A: IndexedArray<[i32; 3]> + Get<Stride, Item = T>,
+    A: IndexedArray<[i32; 3], Indexer = [i32; 3]> + Get<Stride, Item = T>,
     T: SignedDistance,
 {
     // Precalculate these offsets to do faster linear indexing.
@@ -92,7 +92,7 @@ fn estimate_surface<A, T>(
     // Avoid accessing out of bounds with a 2x2x2 kernel.
     let iter_extent = extent.add_to_shape(Point3i::fill(-1));
 
-    let visitor = ArrayForEach::new_global(sdf.extent(), iter_extent);
+    let visitor = ArrayForEach::new_global(sdf.layout_extent(), iter_extent);
     visitor.for_each_point_and_stride(|p, p_stride| {
         // Get the corners of the cube with minimal corner p.
         let mut corner_strides = [Stride(0); 8];
@@ -227,7 +227,7 @@ fn sdf_gradient(dists: &[f32; 8], s: &Point3f) -> [f32; 3] {
 // comments on `maybe_make_quad` to help with understanding the indexing.
 fn make_all_quads<A, T>(sdf: &A, extent: &Extent3i, output: &mut SurfaceNetsBuffer)
 where
-    A: IndexedArray<[i32; 3]> + Get<Stride, Item = T>,
+    A: IndexedArray<[i32; 3], Indexer = [i32; 3]> + Get<Stride, Item = T>,
     T: SignedDistance,
 {
     let mut xyz_strides = [Stride(0); 3];
@@ -372,3 +372,40 @@ fn sq_dist(a: [f32; 3], b: [f32; 3]) -> f32 {
 
     d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
 }
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn view_has_same_mesh_as_copied_sub_array() {
+        let extent = Extent3i::from_min_and_shape(Point3i::fill(-12), Point3i::fill(24));
+        let sdf = Array3x1::fill_with(extent, |p: Point3i| {
+            let center = PointN([2.0, -1.0, 1.5]);
+            let d = Point3f::from(p) - center;
+
+            d.norm() - 6.0
+        });
+
+        let sub_extent = Extent3i::from_min_and_shape(PointN([-4, -9, 0]), PointN([10, 12, 9]));
+        let sub_array = Array3x1::fill_with(sub_extent, |p| sdf.get(p));
+
+        let mut view_buffer = SurfaceNetsBuffer::default();
+        surface_nets(&sdf.view(&sub_extent), &sub_extent, 1.0, &mut view_buffer);
+        let mut copy_buffer = SurfaceNetsBuffer::default();
+        surface_nets(&sub_array, &sub_extent, 1.0, &mut copy_buffer);
+
+        assert!(!copy_buffer.mesh.indices.is_empty());
+        assert_eq!(view_buffer.surface_points, copy_buffer.surface_points);
+        assert_eq!(view_buffer.mesh.positions, copy_buffer.mesh.positions);
+        assert_eq!(view_buffer.mesh.normals, copy_buffer.mesh.normals);
+        assert_eq!(view_buffer.mesh.indices, copy_buffer.mesh.indices);
+    }
+}
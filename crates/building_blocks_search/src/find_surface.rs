@@ -9,7 +9,9 @@ pub fn find_surface_points<Map, N, T>(
     extent: &ExtentN<N>,
 ) -> (Vec<PointN<N>>, Vec<Stride>)
 where
-    Map: IndexedArray<N> + ForEach<N, (PointN<N>, Stride), Item = T> + Get<Stride, Item = T>,
+    Map: IndexedArray<N, Indexer = N>
+        + ForEach<N, (PointN<N>, Stride), Item = T>
+        + Get<Stride, Item = T>,
     T: IsEmpty,
     PointN<N>: IntegerPoint<N>,
     Local<N>: Copy,
@@ -88,6 +90,25 @@ mod test {
         assert_elements_eq(&surface_points, &expected_surface_points);
     }
 
+    #[test]
+    fn find_surface_points_in_array_view() {
+        let map = Array3x1::fill_with(
+            Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(16)),
+            |p| Voxel(p.y() < 8),
+        );
+
+        // The view is padded by 1, so only its interior is searched.
+        let window = Extent3i::from_min_and_shape(Point3i::fill(4), Point3i::fill(6));
+        let interior = window.padded(-1);
+        let (surface_points, surface_strides) = find_surface_points(&map.view(&window), &interior);
+
+        let expected_surface_points = interior.iter_points().filter(|p| p.y() == 7).collect();
+        assert_elements_eq(&surface_points, &expected_surface_points);
+        for (p, s) in surface_points.iter().zip(surface_strides.iter()) {
+            assert_eq!(map.stride_from_local_point(Local(*p)), *s);
+        }
+    }
+
     fn assert_elements_eq<T: Clone + Debug + Eq + Hash>(v1: &Vec<T>, v2: &Vec<T>) {
         let set1: HashSet<T> = HashSet::from_iter(v1.iter().cloned());
         let set2: HashSet<T> = HashSet::from_iter(v2.iter().cloned());
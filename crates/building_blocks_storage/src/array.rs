@@ -98,6 +98,39 @@
 //! box_array.for_each(&extent, |p: Point3i, value| assert_eq!(value, 1));
 //! ```
 //!
+//! # Layout
+//!
+//! By default, the values are stored in row-major order, i.e. X is the fastest-changing coordinate. The layout is chosen by
+//! the `ArrayIndexer` type parameter of `Array`, so an `Array<N, Chan, Morton>` stores the values along the Z-order curve
+//! instead, which keeps every neighborhood of points close together in memory.
+//!
+//! # Views
+//!
+//! An `ArrayView` (or `ArrayViewMut`) borrows a sub-extent of an `Array` without copying it. A view keeps the strides of the
+//! array it borrows, so kernel-based algorithms like the ones in `building_blocks_mesh` can run directly on a window of a larger
+//! array.
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::prelude::*;
+//! let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(64));
+//! let mut array = Array3x1::fill_with(extent, |p| p.x());
+//!
+//! let window = Extent3i::from_min_and_shape(Point3i::fill(10), Point3i::fill(4));
+//! let view = array.view(&window);
+//! assert_eq!(view.extent(), &window);
+//! assert_eq!(view.get(Point3i::fill(12)), 12);
+//!
+//! // Iteration is clamped to the window.
+//! let mut count = 0;
+//! view.for_each(&extent, |_: Point3i, _| count += 1);
+//! assert_eq!(count, 4 * 4 * 4);
+//!
+//! array.view_mut(&window).for_each_mut(&extent, |_: (), value| *value = -1);
+//! assert_eq!(array.get(Point3i::fill(11)), -1);
+//! assert_eq!(array.get(Point3i::fill(14)), 14);
+//! ```
+//!
 //! # Multichannel
 //!
 //! It's often the case that you have multiple data types to store per spatial dimension. For example, you might store geometry
@@ -141,6 +174,8 @@ mod coords;
 #[macro_use]
 mod for_each;
 mod indexer;
+mod morton;
+mod view;
 
 pub mod channels;
 pub mod compression;
@@ -155,24 +190,32 @@ pub use compression::*;
 pub use coords::*;
 pub use for_each::*;
 pub use indexer::*;
+pub use morton::*;
+pub use view::*;
 
 use crate::{
-    ChunkCopySrc, ForEach, ForEachMut, ForEachMutPtr, Get, GetMut, GetMutPtr, GetRef, IntoMultiMut,
-    IntoMultiMutPtr, MultiMutPtr, ReadExtent, TransformMap, WriteExtent,
+    ByteSize, ChunkCopySrc, ForEach, ForEachMut, ForEachMutPtr, Get, GetMut, GetMutPtr, GetRef,
+    IntoMultiMut, IntoMultiMutPtr, MultiMutPtr, ReadExtent, TransformMap, WriteExtent,
 };
 
 use building_blocks_core::prelude::*;
 
 use core::iter::{once, Once};
+use core::marker::PhantomData;
 use core::ops::{Add, Deref};
 use either::Either;
 use serde::{Deserialize, Serialize};
 
 /// A map from lattice location `PointN<N>` to data `T`, stored as a flat array.
+///
+/// The layout of the flat array is determined by the `ArrayIndexer` `Idx`. By default, this is the row-major layout
+/// implemented by `N` itself, but it can also be the Z-order curve implemented by `Morton`.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub struct Array<N, Chan> {
+pub struct Array<N, Chan, Idx = N> {
     channels: Chan,
     extent: ExtentN<N>,
+    #[serde(skip)]
+    indexer: PhantomData<Idx>,
 }
 
 macro_rules! array_n_type_alias {
@@ -214,12 +257,16 @@ pub mod multichannel_aliases {
 
 pub use multichannel_aliases::*;
 
-impl<N, Chan> Array<N, Chan> {
+impl<N, Chan, Idx> Array<N, Chan, Idx> {
     /// Create a new `Array` directly from the extent and values. This asserts that the number of points in the extent matches
     /// the length of the values `Vec`.
     pub fn new(extent: ExtentN<N>, channels: Chan) -> Self {
         // TODO: assert that channels has length matching extent
-        Self { extent, channels }
+        Self {
+            extent,
+            channels,
+            indexer: PhantomData,
+        }
     }
 
     /// Moves the raw extent and values storage out of `self`.
@@ -244,11 +291,34 @@ impl<N, Chan> Array<N, Chan> {
     }
 }
 
-impl<N, Chan> IndexedArray<N> for Array<N, Chan>
+impl<N, Chan, Idx> ChannelTypeNames for Array<N, Chan, Idx>
 where
-    N: ArrayIndexer<N>,
+    Chan: ChannelTypeNames,
+    Idx: ArrayIndexer<N>,
 {
-    type Indexer = N;
+    fn channel_type_names() -> Vec<String> {
+        Chan::channel_type_names()
+    }
+
+    fn array_layout() -> Option<&'static str> {
+        Some(Idx::LAYOUT_NAME)
+    }
+}
+
+impl<N, Chan, Idx> ByteSize for Array<N, Chan, Idx>
+where
+    Chan: ByteSize,
+{
+    fn byte_size(&self) -> usize {
+        core::mem::size_of::<ExtentN<N>>() + self.channels.byte_size()
+    }
+}
+
+impl<N, Chan, Idx> IndexedArray<N> for Array<N, Chan, Idx>
+where
+    Idx: ArrayIndexer<N>,
+{
+    type Indexer = Idx;
 
     #[inline]
     fn extent(&self) -> &ExtentN<N> {
@@ -256,7 +326,7 @@ where
     }
 }
 
-impl<N, Chan> Array<N, Chan>
+impl<N, Chan, Idx> Array<N, Chan, Idx>
 where
     PointN<N>: IntegerPoint<N>,
 {
@@ -279,11 +349,11 @@ where
     }
 }
 
-impl<N, Chan> Array<N, Chan>
+impl<N, Chan, Idx> Array<N, Chan, Idx>
 where
-    Self: ForEachMutPtr<N, (), Item = Chan::Ptr>,
+    Idx: ArrayIndexer<N>,
     PointN<N>: IntegerPoint<N>,
-    Chan: FillChannels,
+    Chan: FillChannels + GetMutPtr<usize, Item = <Chan as Channels>::Ptr>,
 {
     /// Fill the entire `extent` with the same `value`.
     pub fn fill_extent(&mut self, extent: &ExtentN<N>, value: Chan::Data)
@@ -293,14 +363,16 @@ where
         if self.extent.eq(extent) {
             self.channels.reset_values(value);
         } else {
-            unsafe {
-                self.for_each_mut_ptr(extent, |_: (), v| v.write(value.clone()));
-            }
+            let visitor = ArrayForEach::new_global(self.extent(), *extent);
+            let channels = &mut self.channels;
+            Idx::for_each_run_unchecked(visitor, |start, length| {
+                channels.fill_range(start.0..start.0 + length, value.clone())
+            });
         }
     }
 }
 
-impl<N, Chan> Array<N, Chan>
+impl<N, Chan, Idx> Array<N, Chan, Idx>
 where
     Chan: FillChannels,
 {
@@ -314,7 +386,7 @@ where
     }
 }
 
-impl<N, Chan> Array<N, Chan>
+impl<N, Chan, Idx> Array<N, Chan, Idx>
 where
     PointN<N>: IntegerPoint<N>,
     Chan: FillChannels,
@@ -328,9 +400,37 @@ where
     }
 }
 
-impl<N, Chan, UninitChan> Array<N, Chan>
+impl<N, Chan, Idx> Array<N, Chan, Idx>
+where
+    PointN<N>: IntegerPoint<N>,
+    Chan: Get<usize>,
+    Chan::Item: PartialEq,
+{
+    /// Returns `true` iff every point in the array has the same `value`.
+    pub fn is_filled_with(&self, value: &Chan::Item) -> bool {
+        (0..self.extent.num_points()).all(|i| self.channels.get(i) == *value)
+    }
+
+    /// Returns the value shared by all points in the array, or `None` if there are at least two distinct values (or no points
+    /// at all).
+    pub fn homogeneous_value(&self) -> Option<Chan::Item> {
+        if self.extent.num_points() == 0 {
+            return None;
+        }
+
+        let first = self.channels.get(0);
+
+        if self.is_filled_with(&first) {
+            Some(first)
+        } else {
+            None
+        }
+    }
+}
+
+impl<N, Chan, Idx, UninitChan> Array<N, Chan, Idx>
 where
-    Array<N, UninitChan>: ForEachMutPtr<N, PointN<N>, Item = UninitChan::Ptr>,
+    Array<N, UninitChan, Idx>: ForEachMutPtr<N, PointN<N>, Item = UninitChan::Ptr>,
     PointN<N>: IntegerPoint<N>,
     Chan: Channels<UninitSelf = UninitChan>,
     UninitChan: UninitChannels<InitSelf = Chan>,
@@ -339,7 +439,7 @@ where
     /// Create a new array for `extent` where each point's value is determined by the `filler` function.
     pub fn fill_with(extent: ExtentN<N>, mut filler: impl FnMut(PointN<N>) -> Chan::Data) -> Self {
         unsafe {
-            let mut array = Array::<_, UninitChan>::maybe_uninit(extent);
+            let mut array = Array::<_, UninitChan, Idx>::maybe_uninit(extent);
 
             array.for_each_mut_ptr(&extent, |p, val| {
                 val.into_multi_mut_ptr().write(filler(p));
@@ -350,7 +450,7 @@ where
     }
 }
 
-impl<N, Chan> Array<N, Chan>
+impl<N, Chan, Idx> Array<N, Chan, Idx>
 where
     PointN<N>: IntegerPoint<N>,
     Chan: UninitChannels,
@@ -358,7 +458,7 @@ where
     /// Creates an uninitialized map, mainly for performance.
     /// # Safety
     /// Call `assume_init` after manually initializing all of the values.
-    pub unsafe fn maybe_uninit(extent: ExtentN<N>) -> Array<N, Chan> {
+    pub unsafe fn maybe_uninit(extent: ExtentN<N>) -> Array<N, Chan, Idx> {
         Array::new(extent, Chan::maybe_uninit(extent.num_points()))
     }
 
@@ -366,14 +466,14 @@ where
     /// the internal `Vec` after transmuting the data pointer, so the overhead is minimal.
     /// # Safety
     /// All elements of the map must be initialized.
-    pub unsafe fn assume_init(self) -> Array<N, Chan::InitSelf> {
+    pub unsafe fn assume_init(self) -> Array<N, Chan::InitSelf, Idx> {
         let (extent, channel) = self.into_parts();
 
         Array::new(extent, channel.assume_init())
     }
 }
 
-impl<N, T, Store> ArrayNx1<N, T, Store>
+impl<N, T, Store, Idx> Array<N, Channel<T, Store>, Idx>
 where
     PointN<N>: IntegerPoint<N>,
     Store: Deref<Target = [T]>,
@@ -392,7 +492,7 @@ where
 // ╚██████╔╝███████╗   ██║      ██║   ███████╗██║  ██║███████║
 //  ╚═════╝ ╚══════╝   ╚═╝      ╚═╝   ╚══════╝╚═╝  ╚═╝╚══════╝
 
-impl<N, Chan> Get<Stride> for Array<N, Chan>
+impl<N, Chan, Idx> Get<Stride> for Array<N, Chan, Idx>
 where
     Chan: Get<usize>,
 {
@@ -404,7 +504,7 @@ where
     }
 }
 
-impl<'a, N, Chan> GetRef<'a, Stride> for Array<N, Chan>
+impl<'a, N, Chan, Idx> GetRef<'a, Stride> for Array<N, Chan, Idx>
 where
     Chan: GetRef<'a, usize>,
 {
@@ -416,7 +516,7 @@ where
     }
 }
 
-impl<'a, N, Chan> GetMut<'a, Stride> for Array<N, Chan>
+impl<'a, N, Chan, Idx> GetMut<'a, Stride> for Array<N, Chan, Idx>
 where
     Chan: GetMut<'a, usize>,
 {
@@ -428,7 +528,7 @@ where
     }
 }
 
-impl<N, Chan> GetMutPtr<Stride> for Array<N, Chan>
+impl<N, Chan, Idx> GetMutPtr<Stride> for Array<N, Chan, Idx>
 where
     Chan: GetMutPtr<usize>,
 {
@@ -440,7 +540,7 @@ where
     }
 }
 
-impl<N, Chan> Get<Local<N>> for Array<N, Chan>
+impl<N, Chan, Idx> Get<Local<N>> for Array<N, Chan, Idx>
 where
     Self: IndexedArray<N> + Get<Stride>,
     PointN<N>: Copy,
@@ -453,7 +553,7 @@ where
     }
 }
 
-impl<'a, N, Chan> GetRef<'a, Local<N>> for Array<N, Chan>
+impl<'a, N, Chan, Idx> GetRef<'a, Local<N>> for Array<N, Chan, Idx>
 where
     Self: IndexedArray<N> + GetRef<'a, Stride>,
     PointN<N>: Copy,
@@ -466,7 +566,7 @@ where
     }
 }
 
-impl<'a, N, Chan> GetMut<'a, Local<N>> for Array<N, Chan>
+impl<'a, N, Chan, Idx> GetMut<'a, Local<N>> for Array<N, Chan, Idx>
 where
     Self: IndexedArray<N> + GetMut<'a, Stride>,
     PointN<N>: Copy,
@@ -479,7 +579,7 @@ where
     }
 }
 
-impl<N, Chan> Get<PointN<N>> for Array<N, Chan>
+impl<N, Chan, Idx> Get<PointN<N>> for Array<N, Chan, Idx>
 where
     Self: IndexedArray<N> + Get<Local<N>>,
     PointN<N>: Point,
@@ -494,7 +594,7 @@ where
     }
 }
 
-impl<'a, N, Chan> GetRef<'a, PointN<N>> for Array<N, Chan>
+impl<'a, N, Chan, Idx> GetRef<'a, PointN<N>> for Array<N, Chan, Idx>
 where
     Self: IndexedArray<N> + GetRef<'a, Local<N>>,
     PointN<N>: Point,
@@ -509,7 +609,7 @@ where
     }
 }
 
-impl<'a, N, Chan> GetMut<'a, PointN<N>> for Array<N, Chan>
+impl<'a, N, Chan, Idx> GetMut<'a, PointN<N>> for Array<N, Chan, Idx>
 where
     Self: IndexedArray<N> + GetMut<'a, Local<N>>,
     PointN<N>: Point,
@@ -533,10 +633,10 @@ where
 
 macro_rules! impl_array_for_each {
     (coords: $coords:ty; forwarder = |$p:ident, $stride:ident| $forward_coords:expr;) => {
-        impl<N, Chan> ForEach<N, $coords> for Array<N, Chan>
+        impl<N, Chan, Idx> ForEach<N, $coords> for Array<N, Chan, Idx>
         where
             Self: Get<Stride>,
-            N: ArrayIndexer<N>,
+            Idx: ArrayIndexer<N>,
             PointN<N>: IntegerPoint<N>,
         {
             type Item = <Self as Get<Stride>>::Item;
@@ -544,15 +644,16 @@ macro_rules! impl_array_for_each {
             #[inline]
             fn for_each(&self, iter_extent: &ExtentN<N>, mut f: impl FnMut($coords, Self::Item)) {
                 let visitor = ArrayForEach::new_global(self.extent(), *iter_extent);
-                visitor
-                    .for_each_point_and_stride(|$p, $stride| f($forward_coords, self.get($stride)));
+                Idx::for_each_point_and_stride_unchecked(visitor, |$p, $stride| {
+                    f($forward_coords, self.get($stride))
+                });
             }
         }
 
-        impl<'a, N, Chan> ForEachMutPtr<N, $coords> for Array<N, Chan>
+        impl<'a, N, Chan, Idx> ForEachMutPtr<N, $coords> for Array<N, Chan, Idx>
         where
             Self: GetMutPtr<Stride, Item = Chan::Ptr>,
-            N: ArrayIndexer<N>,
+            Idx: ArrayIndexer<N>,
             PointN<N>: IntegerPoint<N>,
             Chan: Channels,
         {
@@ -565,13 +666,13 @@ macro_rules! impl_array_for_each {
                 mut f: impl FnMut($coords, Self::Item),
             ) {
                 let visitor = ArrayForEach::new_global(self.extent(), *iter_extent);
-                visitor.for_each_point_and_stride(|$p, $stride| {
+                Idx::for_each_point_and_stride_unchecked(visitor, |$p, $stride| {
                     f($forward_coords, self.get_mut_ptr($stride));
                 });
             }
         }
 
-        impl<'a, N, Chan> ForEachMut<'a, N, $coords> for Array<N, Chan>
+        impl<'a, N, Chan, Idx> ForEachMut<'a, N, $coords> for Array<N, Chan, Idx>
         where
             Self: ForEachMutPtr<N, $coords, Item = Chan::Ptr>,
             Chan: Channels,
@@ -622,11 +723,11 @@ impl_array_for_each!(
 #[derive(Copy, Clone)]
 pub struct ArrayCopySrc<Map>(pub Map);
 
-impl<'a, N: 'a, Chan: 'a> ReadExtent<'a, N> for Array<N, Chan>
+impl<'a, N: 'a, Chan: 'a, Idx: 'a> ReadExtent<'a, N> for Array<N, Chan, Idx>
 where
     PointN<N>: IntegerPoint<N>,
 {
-    type Src = ArrayCopySrc<&'a Array<N, Chan>>;
+    type Src = ArrayCopySrc<&'a Array<N, Chan, Idx>>;
     type SrcIter = Once<(ExtentN<N>, Self::Src)>;
 
     fn read_extent(&'a self, extent: &ExtentN<N>) -> Self::SrcIter {
@@ -636,10 +737,10 @@ where
     }
 }
 
-impl<'a, N, Chan> WriteExtent<N, ArrayCopySrc<&'a Self>> for Array<N, Chan>
+impl<'a, N, Chan, Idx> WriteExtent<N, ArrayCopySrc<&'a Self>> for Array<N, Chan, Idx>
 where
     Self: Get<Stride, Item = Chan::Data> + GetMutPtr<Stride, Item = Chan::Ptr>,
-    N: ArrayIndexer<N>,
+    Idx: ArrayIndexer<N>,
     PointN<N>: IntegerPoint<N>,
     ExtentN<N>: Copy,
     Chan: Channels + Clone,
@@ -661,11 +762,11 @@ where
     }
 }
 
-impl<'a, N, Chan, Delegate, F> WriteExtent<N, ArrayCopySrc<TransformMap<'a, Delegate, F>>>
-    for Array<N, Chan>
+impl<'a, N, Chan, Idx, Delegate, F> WriteExtent<N, ArrayCopySrc<TransformMap<'a, Delegate, F>>>
+    for Array<N, Chan, Idx>
 where
-    Self: IndexedArray<N> + GetMutPtr<Stride, Item = Chan::Ptr>,
-    TransformMap<'a, Delegate, F>: IndexedArray<N> + Get<Stride, Item = Chan::Data>,
+    Self: IndexedArray<N, Indexer = Idx> + GetMutPtr<Stride, Item = Chan::Ptr>,
+    TransformMap<'a, Delegate, F>: IndexedArray<N, Indexer = Idx> + Get<Stride, Item = Chan::Data>,
     PointN<N>: IntegerPoint<N>,
     ExtentN<N>: Copy,
     Chan: Channels,
@@ -690,12 +791,12 @@ fn unchecked_copy_extent_between_arrays<Dst, Src, N, Ptr>(
     extent: &ExtentN<N>,
 ) where
     Dst: IndexedArray<N> + GetMutPtr<Stride, Item = Ptr>,
-    Src: IndexedArray<N> + Get<Stride, Item = Ptr::Data>,
+    Src: IndexedArray<N, Indexer = Dst::Indexer> + Get<Stride, Item = Ptr::Data>,
     ExtentN<N>: Copy,
     Ptr: MultiMutPtr,
 {
     let dst_extent = *dst.extent();
-    // It shoudn't matter which type we use for the indexer.
+    // Both arrays have the same layout, so it doesn't matter which type we use for the indexer.
     Dst::Indexer::for_each_stride_parallel_global_unchecked(
         &extent,
         &dst_extent,
@@ -710,11 +811,12 @@ fn unchecked_copy_extent_between_arrays<Dst, Src, N, Ptr>(
     );
 }
 
-impl<N, Chan, Ch> WriteExtent<N, ChunkCopySrc<N, Chan::Data, Ch>> for Array<N, Chan>
+impl<N, Chan, Idx, Ch> WriteExtent<N, ChunkCopySrc<N, Chan::Data, Ch>> for Array<N, Chan, Idx>
 where
-    Self: ForEachMutPtr<N, (), Item = Chan::Ptr> + WriteExtent<N, ArrayCopySrc<Ch>>,
+    Self: WriteExtent<N, ArrayCopySrc<Ch>>,
+    Idx: ArrayIndexer<N>,
     PointN<N>: IntegerPoint<N>,
-    Chan: FillChannels,
+    Chan: FillChannels + GetMutPtr<usize, Item = <Chan as Channels>::Ptr>,
     Chan::Data: Clone,
 {
     fn write_extent(&mut self, extent: &ExtentN<N>, src: ChunkCopySrc<N, Chan::Data, Ch>) {
@@ -725,7 +827,7 @@ where
     }
 }
 
-impl<N, Chan, F> WriteExtent<N, F> for Array<N, Chan>
+impl<N, Chan, Idx, F> WriteExtent<N, F> for Array<N, Chan, Idx>
 where
     Self: ForEachMutPtr<N, PointN<N>, Item = Chan::Ptr>,
     F: Fn(PointN<N>) -> Chan::Data,
@@ -1,12 +1,18 @@
+pub mod bit_channel;
 pub mod channel;
 pub mod compression;
 pub mod multichannel;
+pub mod palette_channel;
 
+pub use bit_channel::*;
 pub use channel::*;
 pub use compression::*;
 pub use multichannel::*;
+pub use palette_channel::*;
 
-use crate::MultiMutPtr;
+use crate::{GetMutPtr, MultiMutPtr};
+
+use core::ops::Range;
 
 pub trait Channels {
     type Data;
@@ -17,8 +23,66 @@ pub trait Channels {
 pub trait FillChannels: Channels {
     fn fill(value: Self::Data, length: usize) -> Self;
     fn reset_values(&mut self, value: Self::Data);
+    /// Sets all of the values in the linear `range` to `value`.
+    ///
+    /// The default implementation writes one value at a time. Override it if the channel can fill a whole range faster.
+    fn fill_range(&mut self, range: Range<usize>, value: Self::Data)
+    where
+        Self: GetMutPtr<usize, Item = Self::Ptr>,
+        Self::Data: Clone,
+    {
+        for i in range {
+            unsafe { self.get_mut_ptr(i).write(value.clone()) }
+        }
+    }
 }
 
+/// The names of the value types stored in each channel, in order. This describes the memory layout of a chunk, e.g. in a
+/// `ChunkFileHeader`.
+pub trait ChannelTypeNames {
+    fn channel_type_names() -> Vec<String>;
+
+    /// The `ArrayIndexer::LAYOUT_NAME` of the values, if they are stored in an `Array`.
+    fn array_layout() -> Option<&'static str> {
+        None
+    }
+}
+
+/// A name for a channel value type that is written into `ChunkFileHeader`s.
+///
+/// Unlike `std::any::type_name`, this name must not change between compiler versions or when the type is moved to a different
+/// module, since that would make old saves look like they have a different chunk layout. Implement this for your own voxel
+/// types to use them in `ChunkStreamWriter` or `SerializableChunks`:
+///
+/// ```
+/// # use building_blocks_storage::StableTypeName;
+/// struct Voxel {
+///     material: u8,
+///     density: f32,
+/// }
+///
+/// impl StableTypeName for Voxel {
+///     const STABLE_TYPE_NAME: &'static str = "my_game::Voxel";
+/// }
+/// ```
+pub trait StableTypeName {
+    const STABLE_TYPE_NAME: &'static str;
+}
+
+macro_rules! impl_stable_type_name {
+    ($($t:ty),+) => {
+        $(
+            impl StableTypeName for $t {
+                const STABLE_TYPE_NAME: &'static str = stringify!($t);
+            }
+        )+
+    };
+}
+
+impl_stable_type_name!(
+    bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64
+);
+
 pub trait UninitChannels: Channels {
     type InitSelf;
 
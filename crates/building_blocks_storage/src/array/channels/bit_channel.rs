@@ -0,0 +1,484 @@
+//! A channel of `bool` values packed into bits.
+//!
+//! A `Channel<bool>` spends a full byte per value, which is wasteful for masks and occupancy grids. A `BitChannel` packs 64
+//! values into each `u64` word, so it uses 8x less memory, and whole words can be counted, filled, and combined at once.
+//!
+//! Since a single bit can't be borrowed, mutable access goes through proxies: `GetMut` returns a `BitMut` that dereferences to a
+//! `bool` and writes it back when it's dropped.
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::prelude::*;
+//! # use building_blocks_storage::BitArray3x1;
+//! let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(16));
+//! let mut a = BitArray3x1::fill(extent, false);
+//! a.fill_extent(&Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(8)), true);
+//! *a.get_mut(Point3i::fill(15)) = true;
+//! assert_eq!(a.count_ones(), 8 * 8 * 8 + 1);
+//!
+//! let mut b = BitArray3x1::fill_with(extent, |p| p.x() < 4);
+//! b &= &a;
+//! assert_eq!(b.count_ones(), 4 * 8 * 8);
+//! ```
+
+use crate::{
+    Array, ByteSize, ChannelTypeNames, Channels, FillChannels, Get, GetMut, GetMutPtr, GetRef,
+    IntoMultiMut, IntoMultiMutPtr, MultiMutPtr, UninitChannels,
+};
+
+use building_blocks_core::ExtentN;
+
+use core::marker::PhantomData;
+use core::ops::{BitAndAssign, BitOrAssign, BitXorAssign, Deref, DerefMut, Range};
+use serde::{Deserialize, Serialize};
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// A channel of `bool` values, packed into bits. See the [module docs](crate::array::channels::bit_channel).
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BitChannel {
+    // The bits past `len` in the last word are always zero, so they don't affect counting or equality.
+    words: Vec<u64>,
+    len: usize,
+}
+
+/// An `Array` of bit-packed `bool` values.
+pub type BitArrayNx1<N, Idx = N> = Array<N, BitChannel, Idx>;
+/// A 2D `BitArrayNx1`.
+pub type BitArray2x1 = BitArrayNx1<[i32; 2]>;
+/// A 3D `BitArrayNx1`.
+pub type BitArray3x1 = BitArrayNx1<[i32; 3]>;
+
+impl BitChannel {
+    pub fn fill(value: bool, length: usize) -> Self {
+        let mut channel = Self {
+            words: vec![0; length.div_ceil(WORD_BITS)],
+            len: length,
+        };
+        if value {
+            channel.reset_values(true);
+        }
+
+        channel
+    }
+
+    /// The number of values.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The packed bits. Value `i` is bit `i % 64` of word `i / 64`.
+    #[inline]
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    #[inline]
+    pub fn get_bit(&self, index: usize) -> bool {
+        assert!(index < self.len);
+
+        self.words[index / WORD_BITS] & bit_mask(index) != 0
+    }
+
+    #[inline]
+    pub fn set_bit(&mut self, index: usize, value: bool) {
+        assert!(index < self.len);
+
+        set_masked(&mut self.words[index / WORD_BITS], bit_mask(index), value);
+    }
+
+    pub fn reset_values(&mut self, value: bool) {
+        let len = self.len;
+        self.fill_range(0..len, value);
+    }
+
+    /// Sets every value in `range` to `value`, a whole word at a time.
+    pub fn fill_range(&mut self, range: Range<usize>, value: bool) {
+        let Range { start, end } = range;
+        if start >= end {
+            return;
+        }
+        assert!(end <= self.len);
+
+        let first_word = start / WORD_BITS;
+        let last_word = (end - 1) / WORD_BITS;
+        let first_mask = !0 << (start % WORD_BITS);
+        let last_mask = !0 >> (WORD_BITS - 1 - (end - 1) % WORD_BITS);
+
+        if first_word == last_word {
+            set_masked(&mut self.words[first_word], first_mask & last_mask, value);
+        } else {
+            set_masked(&mut self.words[first_word], first_mask, value);
+            let middle_word = if value { !0 } else { 0 };
+            self.words[first_word + 1..last_word].fill(middle_word);
+            set_masked(&mut self.words[last_word], last_mask, value);
+        }
+    }
+
+    /// The number of `true` values.
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// The number of `false` values.
+    pub fn count_zeros(&self) -> usize {
+        self.len - self.count_ones()
+    }
+
+    fn combine_words(&mut self, other: &Self, op: impl Fn(u64, u64) -> u64) {
+        assert_eq!(self.len, other.len);
+
+        for (w, &o) in self.words.iter_mut().zip(other.words.iter()) {
+            *w = op(*w, o);
+        }
+    }
+}
+
+#[inline]
+fn bit_mask(index: usize) -> u64 {
+    1 << (index % WORD_BITS)
+}
+
+#[inline]
+fn set_masked(word: &mut u64, mask: u64, value: bool) {
+    if value {
+        *word |= mask;
+    } else {
+        *word &= !mask;
+    }
+}
+
+impl BitAndAssign<&BitChannel> for BitChannel {
+    fn bitand_assign(&mut self, rhs: &BitChannel) {
+        self.combine_words(rhs, |a, b| a & b);
+    }
+}
+
+impl BitOrAssign<&BitChannel> for BitChannel {
+    fn bitor_assign(&mut self, rhs: &BitChannel) {
+        self.combine_words(rhs, |a, b| a | b);
+    }
+}
+
+impl BitXorAssign<&BitChannel> for BitChannel {
+    fn bitxor_assign(&mut self, rhs: &BitChannel) {
+        self.combine_words(rhs, |a, b| a ^ b);
+    }
+}
+
+impl Channels for BitChannel {
+    type Data = bool;
+    type Ptr = BitPtr;
+    // Every bit pattern is a valid `bool`, so there is no need for a separate uninitialized type.
+    type UninitSelf = BitChannel;
+}
+
+impl ChannelTypeNames for BitChannel {
+    fn channel_type_names() -> Vec<String> {
+        vec!["bit".to_string()]
+    }
+}
+
+impl ByteSize for BitChannel {
+    fn byte_size(&self) -> usize {
+        core::mem::size_of::<Self>() + self.words.len() * core::mem::size_of::<u64>()
+    }
+}
+
+impl FillChannels for BitChannel {
+    fn fill(value: Self::Data, length: usize) -> Self {
+        Self::fill(value, length)
+    }
+
+    fn reset_values(&mut self, value: Self::Data) {
+        self.reset_values(value)
+    }
+
+    fn fill_range(&mut self, range: Range<usize>, value: Self::Data) {
+        self.fill_range(range, value)
+    }
+}
+
+impl UninitChannels for BitChannel {
+    type InitSelf = BitChannel;
+
+    unsafe fn maybe_uninit(size: usize) -> Self {
+        Self::fill(false, size)
+    }
+
+    unsafe fn assume_init(self) -> Self::InitSelf {
+        self
+    }
+}
+
+//  ██████╗ ███████╗████████╗████████╗███████╗██████╗ ███████╗
+// ██╔════╝ ██╔════╝╚══██╔══╝╚══██╔══╝██╔════╝██╔══██╗██╔════╝
+// ██║  ███╗█████╗     ██║      ██║   █████╗  ██████╔╝███████╗
+// ██║   ██║██╔══╝     ██║      ██║   ██╔══╝  ██╔══██╗╚════██║
+// ╚██████╔╝███████╗   ██║      ██║   ███████╗██║  ██║███████║
+//  ╚═════╝ ╚══════╝   ╚═╝      ╚═╝   ╚══════╝╚═╝  ╚═╝╚══════╝
+
+impl Get<usize> for BitChannel {
+    type Item = bool;
+
+    #[inline]
+    fn get(&self, offset: usize) -> Self::Item {
+        self.get_bit(offset)
+    }
+}
+
+impl<'a> GetRef<'a, usize> for BitChannel {
+    type Item = &'a bool;
+
+    #[inline]
+    fn get_ref(&'a self, offset: usize) -> Self::Item {
+        // A bit can't be borrowed, but there are only two possible values to borrow instead.
+        if self.get_bit(offset) {
+            &true
+        } else {
+            &false
+        }
+    }
+}
+
+impl<'a> GetMut<'a, usize> for BitChannel {
+    type Item = BitMut<'a>;
+
+    #[inline]
+    fn get_mut(&'a mut self, offset: usize) -> Self::Item {
+        unsafe { self.get_mut_ptr(offset).into_multi_mut() }
+    }
+}
+
+impl GetMutPtr<usize> for BitChannel {
+    type Item = BitPtr;
+
+    #[inline]
+    unsafe fn get_mut_ptr(&mut self, offset: usize) -> Self::Item {
+        debug_assert!(offset < self.len);
+
+        BitPtr {
+            word: self.words.as_mut_ptr().add(offset / WORD_BITS),
+            mask: bit_mask(offset),
+        }
+    }
+}
+
+/// A pointer to a single bit of a `BitChannel`.
+#[derive(Clone, Copy, Debug)]
+pub struct BitPtr {
+    word: *mut u64,
+    mask: u64,
+}
+
+impl MultiMutPtr for BitPtr {
+    type Data = bool;
+
+    #[inline]
+    unsafe fn write(self, data: Self::Data) {
+        set_masked(&mut *self.word, self.mask, data);
+    }
+}
+
+impl IntoMultiMutPtr for BitPtr {
+    type Data = bool;
+    type Ptr = BitPtr;
+
+    #[inline]
+    unsafe fn into_multi_mut_ptr(self) -> Self::Ptr {
+        self
+    }
+}
+
+impl<'a> IntoMultiMut<'a> for BitPtr {
+    type MultiMut = BitMut<'a>;
+
+    #[inline]
+    fn into_multi_mut(self) -> Self::MultiMut {
+        let value = unsafe { *self.word & self.mask != 0 };
+
+        BitMut {
+            ptr: self,
+            value,
+            marker: PhantomData,
+        }
+    }
+}
+
+/// A mutable proxy for a single bit of a `BitChannel`. It dereferences to a `bool`, which is written back to the channel when
+/// the proxy is dropped.
+#[derive(Debug)]
+pub struct BitMut<'a> {
+    ptr: BitPtr,
+    value: bool,
+    marker: PhantomData<&'a mut u64>,
+}
+
+impl<'a> Deref for BitMut<'a> {
+    type Target = bool;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<'a> DerefMut for BitMut<'a> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+impl<'a> Drop for BitMut<'a> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { self.ptr.write(self.value) }
+    }
+}
+
+//  █████╗ ██████╗ ██████╗  █████╗ ██╗   ██╗
+// ██╔══██╗██╔══██╗██╔══██╗██╔══██╗╚██╗ ██╔╝
+// ███████║██████╔╝██████╔╝███████║ ╚████╔╝
+// ██╔══██║██╔══██╗██╔══██╗██╔══██║  ╚██╔╝
+// ██║  ██║██║  ██║██║  ██║██║  ██║   ██║
+// ╚═╝  ╚═╝╚═╝  ╚═╝╚═╝  ╚═╝╚═╝  ╚═╝   ╚═╝
+
+impl<N, Idx> Array<N, BitChannel, Idx> {
+    /// The number of `true` values.
+    pub fn count_ones(&self) -> usize {
+        self.channels().count_ones()
+    }
+
+    /// The number of `false` values.
+    pub fn count_zeros(&self) -> usize {
+        self.channels().count_zeros()
+    }
+}
+
+macro_rules! impl_bit_array_op {
+    ($op_trait:ident, $op_fn:ident) => {
+        impl<N, Idx> $op_trait<&Array<N, BitChannel, Idx>> for Array<N, BitChannel, Idx>
+        where
+            ExtentN<N>: PartialEq + core::fmt::Debug,
+        {
+            /// Combines each value with the value at the same point in `rhs`, which must have the same extent.
+            fn $op_fn(&mut self, rhs: &Array<N, BitChannel, Idx>) {
+                assert_eq!(self.extent(), rhs.extent());
+
+                self.channels_mut().$op_fn(rhs.channels());
+            }
+        }
+    };
+}
+
+impl_bit_array_op!(BitAndAssign, bitand_assign);
+impl_bit_array_op!(BitOrAssign, bitor_assign);
+impl_bit_array_op!(BitXorAssign, bitxor_assign);
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{copy_extent, prelude::*};
+
+    use building_blocks_core::prelude::*;
+
+    #[test]
+    fn fill_range_matches_single_bits() {
+        for (start, end) in [
+            (0, 0),
+            (3, 9),
+            (0, 64),
+            (60, 70),
+            (5, 190),
+            (64, 128),
+            (130, 199),
+        ] {
+            let mut fast = BitChannel::fill(false, 199);
+            let mut slow = BitChannel::fill(false, 199);
+            fast.fill_range(start..end, true);
+            for i in start..end {
+                slow.set_bit(i, true);
+            }
+            assert_eq!(fast, slow);
+            assert_eq!(fast.count_ones(), end - start);
+
+            fast.reset_values(true);
+            fast.fill_range(start..end, false);
+            assert_eq!(fast.count_zeros(), end - start);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn get_bit_past_len_panics() {
+        // Index 70 is still inside the second word, so only the length check catches it.
+        let bits = BitChannel::fill(true, 65);
+        bits.get_bit(70);
+    }
+
+    #[test]
+    fn array_access_and_fill_extent() {
+        let extent = Extent3i::from_min_and_shape(Point3i::fill(-5), PointN([13, 7, 5]));
+        let mut bits = BitArray3x1::fill(extent, false);
+        let mut bytes = Array3x1::fill(extent, false);
+
+        let fill = Extent3i::from_min_and_shape(Point3i::fill(-3), PointN([9, 3, 2]));
+        bits.fill_extent(&fill, true);
+        bytes.fill_extent(&fill, true);
+        bits.for_each_mut(&extent, |p: Point3i, mut value| {
+            if p.x() == p.y() {
+                *value = !*value;
+            }
+        });
+        bytes.for_each_mut(&extent, |p: Point3i, value| {
+            if p.x() == p.y() {
+                *value = !*value;
+            }
+        });
+
+        bytes.for_each(&extent, |p: Point3i, value| {
+            assert_eq!(bits.get(p), value);
+            assert_eq!(*bits.get_ref(p), value);
+        });
+        assert_eq!(
+            bits.count_ones(),
+            bytes.channels().store().iter().filter(|b| **b).count()
+        );
+    }
+
+    #[test]
+    fn bitwise_ops_and_copy() {
+        let extent = Extent2i::from_min_and_shape(Point2i::ZERO, Point2i::fill(20));
+        let mut a = BitArray2x1::fill_with(extent, |p| p.x() < 10);
+        let b = BitArray2x1::fill_with(extent, |p| p.y() < 10);
+
+        let mut and = a.clone();
+        and &= &b;
+        let mut or = a.clone();
+        or |= &b;
+        let mut xor = a.clone();
+        xor ^= &b;
+        assert_eq!(and.count_ones(), 100);
+        assert_eq!(or.count_ones(), 300);
+        assert_eq!(xor.count_ones(), 200);
+        assert_eq!(xor.count_zeros(), 200);
+
+        copy_extent(&extent, &b, &mut a);
+        assert_eq!(a, b);
+    }
+}
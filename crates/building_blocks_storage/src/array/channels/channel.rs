@@ -1,7 +1,10 @@
-use crate::{AsRawBytes, Channels, FillChannels, GetMut, GetMutPtr, GetRef, UninitChannels};
+use crate::{
+    AsRawBytes, ByteSize, ChannelTypeNames, Channels, FillChannels, GetMut, GetMutPtr, GetRef,
+    StableTypeName, UninitChannels,
+};
 
 use core::mem::MaybeUninit;
-use core::ops::{Deref, DerefMut};
+use core::ops::{Deref, DerefMut, Range};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -75,6 +78,24 @@ impl<T> Channels for Channel<T> {
     type UninitSelf = Channel<MaybeUninit<T>>;
 }
 
+impl<T, Store> ChannelTypeNames for Channel<T, Store>
+where
+    T: StableTypeName,
+{
+    fn channel_type_names() -> Vec<String> {
+        vec![T::STABLE_TYPE_NAME.to_string()]
+    }
+}
+
+impl<T, Store> ByteSize for Channel<T, Store>
+where
+    Store: Deref<Target = [T]>,
+{
+    fn byte_size(&self) -> usize {
+        core::mem::size_of::<Store>() + self.store.len() * core::mem::size_of::<T>()
+    }
+}
+
 impl<T> FillChannels for Channel<T>
 where
     T: Clone,
@@ -86,6 +107,10 @@ where
     fn reset_values(&mut self, value: Self::Data) {
         self.reset_values(value)
     }
+
+    fn fill_range(&mut self, range: Range<usize>, value: Self::Data) {
+        self.store[range].fill(value)
+    }
 }
 
 impl<T> UninitChannels for Channel<MaybeUninit<T>> {
@@ -1,8 +1,22 @@
-use crate::{AsRawBytes, BytesCompression, Channel, Compressed, Compression, FromBytesCompression};
+mod filter;
+mod palette;
+mod run_length;
+
+pub use filter::*;
+pub use palette::*;
+pub use run_length::*;
+
+use crate::{
+    AsRawBytes, ByteSize, BytesCompression, Channel, Compressed, Compression, FromBytesCompression,
+};
 
 /// Compresses a tuple of `Channel`s into a tuple of `FastCompressedChannel`s.
+///
+/// The raw bytes of each channel can be preprocessed with `ChannelFilters` before they are compressed, which often improves
+/// the compression rate for multi-byte value types like `f32` or `Sd16`.
 pub struct FastChannelsCompression<By, Chan> {
     bytes_compression: By,
+    filters: ChannelFilters,
     marker: std::marker::PhantomData<Chan>,
 }
 
@@ -13,6 +27,7 @@ where
     fn clone(&self) -> Self {
         Self {
             bytes_compression: self.bytes_compression.clone(),
+            filters: self.filters,
             marker: Default::default(),
         }
     }
@@ -22,8 +37,14 @@ impl<By, Chan> Copy for FastChannelsCompression<By, Chan> where By: Copy {}
 
 impl<By, Chan> FastChannelsCompression<By, Chan> {
     pub fn new(bytes_compression: By) -> Self {
+        Self::with_filters(bytes_compression, ChannelFilters::NONE)
+    }
+
+    /// Applies `filters` to the raw bytes of each channel before compressing them with `bytes_compression`.
+    pub fn with_filters(bytes_compression: By, filters: ChannelFilters) -> Self {
         Self {
             bytes_compression,
+            filters,
             marker: Default::default(),
         }
     }
@@ -31,6 +52,18 @@ impl<By, Chan> FastChannelsCompression<By, Chan> {
     pub fn bytes_compression(&self) -> &By {
         &self.bytes_compression
     }
+
+    pub fn filters(&self) -> ChannelFilters {
+        self.filters
+    }
+
+    /// The same compression for a different type of channels, e.g. one channel from a tuple.
+    pub fn for_channels<Ch>(&self) -> FastChannelsCompression<By, Ch>
+    where
+        By: Clone,
+    {
+        FastChannelsCompression::with_filters(self.bytes_compression.clone(), self.filters)
+    }
 }
 
 impl<By, Chan> FromBytesCompression<By> for FastChannelsCompression<By, Chan> {
@@ -43,6 +76,7 @@ impl<By, Chan> FromBytesCompression<By> for FastChannelsCompression<By, Chan> {
 #[derive(Clone)]
 pub struct FastCompressedChannel<T> {
     compressed_bytes: Vec<u8>,
+    filters: ChannelFilters,
     decompressed_length: usize, // TODO: we should be able to remove this with some refactoring of the Compression trait
     marker: std::marker::PhantomData<T>,
 }
@@ -51,6 +85,17 @@ impl<T> FastCompressedChannel<T> {
     pub fn compressed_bytes(&self) -> &[u8] {
         &self.compressed_bytes
     }
+
+    /// The filters that were applied before compression.
+    pub fn filters(&self) -> ChannelFilters {
+        self.filters
+    }
+}
+
+impl<T> ByteSize for FastCompressedChannel<T> {
+    fn byte_size(&self) -> usize {
+        core::mem::size_of::<Self>() + self.compressed_bytes.len()
+    }
 }
 
 impl<By, T> Compression for FastChannelsCompression<By, Channel<T>>
@@ -67,11 +112,20 @@ where
     // not compatible across platforms.
     fn compress(&self, data: &Self::Data) -> Compressed<Self> {
         let mut compressed_bytes = Vec::new();
-        self.bytes_compression
-            .compress_bytes(&data.as_raw_bytes(), &mut compressed_bytes);
+        if self.filters.is_none() {
+            self.bytes_compression
+                .compress_bytes(&data.as_raw_bytes(), &mut compressed_bytes);
+        } else {
+            let filtered_bytes = self
+                .filters
+                .apply(&data.as_raw_bytes(), core::mem::size_of::<T>());
+            self.bytes_compression
+                .compress_bytes(&filtered_bytes, &mut compressed_bytes);
+        }
 
         Compressed::new(FastCompressedChannel {
             compressed_bytes,
+            filters: self.filters,
             decompressed_length: data.store().len(),
             marker: Default::default(),
         })
@@ -89,8 +143,76 @@ where
                 num_values * core::mem::size_of::<T>(),
             )
         };
-        By::decompress_bytes(&compressed.compressed_bytes, &mut decompressed_bytes);
+        if compressed.filters.is_none() {
+            By::decompress_bytes(&compressed.compressed_bytes, &mut decompressed_bytes);
+        } else {
+            let mut filtered_bytes = Vec::with_capacity(decompressed_bytes.len());
+            By::decompress_bytes(&compressed.compressed_bytes, &mut filtered_bytes);
+            compressed.filters.reverse(
+                &filtered_bytes,
+                decompressed_bytes,
+                core::mem::size_of::<T>(),
+            );
+        }
 
         Channel::new(decompressed_values)
     }
 }
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(all(test, feature = "lz4"))]
+mod test {
+    use super::*;
+
+    use crate::{FastChannelsCompression1, Lz4};
+
+    #[test]
+    fn filters_improve_compression_of_smooth_values() {
+        // Something like a signed distance field.
+        let channel: Channel<f32> =
+            Channel::new((0..4096).map(|i| (i % 16) as f32 * 0.25 - 2.0).collect());
+
+        let unfiltered = FastChannelsCompression1::new(Lz4 { level: 10 }).compress(&channel);
+        let filtered = FastChannelsCompression1::with_filters(
+            Lz4 { level: 10 },
+            ChannelFilters::DELTA_SHUFFLE,
+        )
+        .compress(&channel);
+        assert_eq!(
+            filtered.compressed_data.filters(),
+            ChannelFilters::DELTA_SHUFFLE
+        );
+        assert!(
+            filtered.compressed_data.compressed_bytes().len()
+                < unfiltered.compressed_data.compressed_bytes().len()
+        );
+
+        assert_eq!(filtered.decompress(), channel);
+        assert_eq!(unfiltered.decompress(), channel);
+    }
+
+    #[test]
+    fn filters_apply_to_every_channel_of_a_tuple() {
+        let channels = (
+            Channel::new((0..100).map(|i| i as i16 * 3).collect::<Vec<_>>()),
+            Channel::new((0..100).map(|i| i as f32).collect::<Vec<_>>()),
+        );
+
+        let compression = FastChannelsCompression::<_, (Channel<i16>, Channel<f32>)>::with_filters(
+            Lz4 { level: 10 },
+            ChannelFilters::SHUFFLE,
+        );
+        let compressed = compression.compress(&channels);
+        assert_eq!(
+            compressed.compressed_data.0.compressed_data.filters(),
+            ChannelFilters::SHUFFLE
+        );
+        assert_eq!(compressed.decompress(), channels);
+    }
+}
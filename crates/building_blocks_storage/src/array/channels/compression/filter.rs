@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+
+/// Reversible transformations applied to the raw bytes of a channel before they are compressed by some `BytesCompression`.
+///
+/// Generic byte compressors find repeated byte sequences, but the bytes of multi-byte values like `f32` or `Sd16` are
+/// interleaved, so neighboring values that are merely close to each other have few bytes in common. These filters rearrange
+/// the bytes so the compressor sees longer runs of similar bytes. The filters are recorded with the compressed data, so
+/// decompression always reverses exactly the filters that were applied.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ChannelFilters {
+    /// Replace every byte with its (wrapping) difference from the same byte of the previous value in linear order, i.e. along
+    /// the X axis. The difference is taken bytewise, without carrying between the bytes of a value, so it's not the numeric
+    /// difference of multi-byte values. But it's just as reversible, and smoothly varying data still becomes mostly small
+    /// bytes, especially in the low-order bytes.
+    pub delta: bool,
+    /// Transpose the bytes so that the first byte of every value comes first, then the second byte of every value, etc. This
+    /// is the "shuffle" filter from Blosc.
+    pub shuffle: bool,
+}
+
+impl ChannelFilters {
+    pub const NONE: Self = Self {
+        delta: false,
+        shuffle: false,
+    };
+    pub const DELTA: Self = Self {
+        delta: true,
+        shuffle: false,
+    };
+    pub const SHUFFLE: Self = Self {
+        delta: false,
+        shuffle: true,
+    };
+    pub const DELTA_SHUFFLE: Self = Self {
+        delta: true,
+        shuffle: true,
+    };
+
+    pub fn is_none(&self) -> bool {
+        !self.delta && !self.shuffle
+    }
+
+    /// Applies the filters to `bytes`, which hold values of `value_size` bytes each.
+    ///
+    /// # Panics
+    ///
+    /// If `value_size` is 0 or doesn't divide `bytes.len()`.
+    pub fn apply(&self, bytes: &[u8], value_size: usize) -> Vec<u8> {
+        assert_value_size(bytes.len(), value_size);
+
+        let mut filtered = bytes.to_vec();
+        if self.delta {
+            // Iterate backwards so each value is subtracted from its unfiltered predecessor.
+            for i in (value_size..filtered.len()).rev() {
+                filtered[i] = filtered[i].wrapping_sub(filtered[i - value_size]);
+            }
+        }
+        if self.shuffle {
+            filtered = shuffle(&filtered, value_size);
+        }
+
+        filtered
+    }
+
+    /// Reverses the filters on `filtered`, writing the original bytes into `bytes`, which must have the same length.
+    ///
+    /// # Panics
+    ///
+    /// If `value_size` is 0 or doesn't divide `bytes.len()`.
+    pub fn reverse(&self, filtered: &[u8], bytes: &mut [u8], value_size: usize) {
+        assert_value_size(bytes.len(), value_size);
+
+        if self.shuffle {
+            unshuffle(filtered, bytes, value_size);
+        } else {
+            bytes.copy_from_slice(filtered);
+        }
+        if self.delta {
+            for i in value_size..bytes.len() {
+                bytes[i] = bytes[i].wrapping_add(bytes[i - value_size]);
+            }
+        }
+    }
+}
+
+fn assert_value_size(num_bytes: usize, value_size: usize) {
+    assert!(value_size > 0, "Can't filter zero-sized values");
+    assert_eq!(
+        num_bytes % value_size,
+        0,
+        "Filtered bytes must be a whole number of {}-byte values",
+        value_size
+    );
+}
+
+fn shuffle(bytes: &[u8], value_size: usize) -> Vec<u8> {
+    let num_values = bytes.len() / value_size;
+    let mut shuffled = vec![0; bytes.len()];
+    for (i, value) in bytes.chunks_exact(value_size).enumerate() {
+        for (b, &byte) in value.iter().enumerate() {
+            shuffled[b * num_values + i] = byte;
+        }
+    }
+
+    shuffled
+}
+
+fn unshuffle(shuffled: &[u8], bytes: &mut [u8], value_size: usize) {
+    let num_values = bytes.len() / value_size;
+    for (i, value) in bytes.chunks_exact_mut(value_size).enumerate() {
+        for (b, byte) in value.iter_mut().enumerate() {
+            *byte = shuffled[b * num_values + i];
+        }
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn filters_are_reversible() {
+        let bytes: Vec<u8> = (0..60u32).flat_map(|i| (i * 1000).to_le_bytes()).collect();
+
+        for filters in [
+            ChannelFilters::NONE,
+            ChannelFilters::DELTA,
+            ChannelFilters::SHUFFLE,
+            ChannelFilters::DELTA_SHUFFLE,
+        ] {
+            let filtered = filters.apply(&bytes, 4);
+            let mut reversed = vec![0; bytes.len()];
+            filters.reverse(&filtered, &mut reversed, 4);
+            assert_eq!(reversed, bytes);
+        }
+    }
+
+    #[test]
+    fn shuffle_groups_bytes_by_significance() {
+        let bytes = [1, 2, 3, 4, 5, 6];
+        assert_eq!(
+            ChannelFilters::SHUFFLE.apply(&bytes, 2),
+            vec![1, 3, 5, 2, 4, 6]
+        );
+    }
+
+    #[test]
+    fn delta_is_bytewise() {
+        let bytes: Vec<u8> = [0x00ffu16, 0x0100]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        assert_eq!(
+            ChannelFilters::DELTA.apply(&bytes, 2),
+            vec![0xff, 0x00, 0x01, 0x01]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_value_size_is_rejected() {
+        ChannelFilters::SHUFFLE.apply(&[], 0);
+    }
+}
@@ -0,0 +1,270 @@
+use crate::{ByteSize, BytesCompression, Channel, Compressed, Compression, FromBytesCompression};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::hash::Hash;
+
+/// Replaces the values of a `Channel` with indices into a palette of its distinct values, producing a
+/// `PaletteCompressedChannel`. Each channel of a tuple gets its own palette, so a channel with few distinct values stays small
+/// even if another channel has many.
+///
+/// Each channel is encoded as a palette of its distinct values, plus one index into the palette for every value, packed with
+/// the minimum number of bits. A channel with only 4 distinct `u32` values takes 2 bits per value instead of 32, regardless of
+/// how the values are arranged. The encoding is then compressed with some `By: BytesCompression`, which can be
+/// `NoCompression` to skip that step.
+///
+/// Values are looked up in the palette by hash, so they must be `Eq + Hash`. The index width grows with the palette, so this is
+/// only a good choice for channels with a small number of distinct values, like block types.
+///
+/// Only the palette is serialized with `bincode`. The indices are packed into bytes directly, in a fixed bit order, so the
+/// encoding doesn't depend on the platform either.
+pub struct PaletteChannelsCompression<By, Chan> {
+    bytes_compression: By,
+    marker: std::marker::PhantomData<Chan>,
+}
+
+impl<By, Chan> Clone for PaletteChannelsCompression<By, Chan>
+where
+    By: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            bytes_compression: self.bytes_compression.clone(),
+            marker: Default::default(),
+        }
+    }
+}
+
+impl<By, Chan> Copy for PaletteChannelsCompression<By, Chan> where By: Copy {}
+
+impl<By, Chan> PaletteChannelsCompression<By, Chan> {
+    pub fn new(bytes_compression: By) -> Self {
+        Self {
+            bytes_compression,
+            marker: Default::default(),
+        }
+    }
+
+    pub fn bytes_compression(&self) -> &By {
+        &self.bytes_compression
+    }
+
+    /// The same compression for a different type of channels, e.g. one channel from a tuple.
+    pub fn for_channels<Ch>(&self) -> PaletteChannelsCompression<By, Ch>
+    where
+        By: Clone,
+    {
+        PaletteChannelsCompression::new(self.bytes_compression.clone())
+    }
+}
+
+impl<By, Chan> FromBytesCompression<By> for PaletteChannelsCompression<By, Chan> {
+    fn from_bytes_compression(bytes_compression: By) -> Self {
+        Self::new(bytes_compression)
+    }
+}
+
+/// A `Channel` compressed with `PaletteChannelsCompression`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PaletteCompressedChannel<T> {
+    compressed_bytes: Vec<u8>,
+    marker: std::marker::PhantomData<T>,
+}
+
+impl<T> PaletteCompressedChannel<T> {
+    pub fn compressed_bytes(&self) -> &[u8] {
+        &self.compressed_bytes
+    }
+}
+
+impl<T> ByteSize for PaletteCompressedChannel<T> {
+    fn byte_size(&self) -> usize {
+        core::mem::size_of::<Self>() + self.compressed_bytes.len()
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct PaletteEncoding<T> {
+    palette: Vec<T>,
+    bits_per_index: u8,
+    num_values: u32,
+    packed_indices: Vec<u8>,
+}
+
+impl<By, T> Compression for PaletteChannelsCompression<By, Channel<T>>
+where
+    By: BytesCompression,
+    T: Clone + DeserializeOwned + Eq + Hash + Serialize,
+{
+    type Data = Channel<T>;
+    type CompressedData = PaletteCompressedChannel<T>;
+
+    fn compress(&self, data: &Self::Data) -> Compressed<Self> {
+        let encoding = encode_palette(data.store());
+
+        let mut compressed_bytes = Vec::new();
+        self.bytes_compression.compress_bytes(
+            &bincode::serialize(&encoding).unwrap(),
+            &mut compressed_bytes,
+        );
+
+        Compressed::new(PaletteCompressedChannel {
+            compressed_bytes,
+            marker: Default::default(),
+        })
+    }
+
+    fn decompress(compressed: &Self::CompressedData) -> Self::Data {
+        let mut serialized_encoding = Vec::new();
+        By::decompress_bytes(&compressed.compressed_bytes, &mut serialized_encoding);
+        let encoding: PaletteEncoding<T> = bincode::deserialize(&serialized_encoding).unwrap();
+
+        Channel::new(decode_palette(&encoding))
+    }
+}
+
+fn encode_palette<T>(values: &[T]) -> PaletteEncoding<T>
+where
+    T: Clone + Eq + Hash,
+{
+    let mut palette: Vec<T> = Vec::new();
+    let mut palette_indices: ahash::AHashMap<&T, u32> = Default::default();
+    let mut indices = Vec::with_capacity(values.len());
+    // Neighboring values are usually the same, so check the last index before hashing.
+    let mut last_index = 0;
+    for value in values.iter() {
+        if palette.get(last_index as usize) != Some(value) {
+            last_index = *palette_indices.entry(value).or_insert_with(|| {
+                palette.push(value.clone());
+
+                palette.len() as u32 - 1
+            });
+        }
+        indices.push(last_index);
+    }
+
+    let bits_per_index = bits_for_palette_len(palette.len());
+
+    PaletteEncoding {
+        palette,
+        bits_per_index,
+        num_values: values.len() as u32,
+        packed_indices: pack_indices(&indices, bits_per_index),
+    }
+}
+
+fn decode_palette<T>(encoding: &PaletteEncoding<T>) -> Vec<T>
+where
+    T: Clone,
+{
+    unpack_indices(
+        &encoding.packed_indices,
+        encoding.bits_per_index,
+        encoding.num_values as usize,
+    )
+    .map(|i| encoding.palette[i as usize].clone())
+    .collect()
+}
+
+/// The minimum number of bits needed to index a palette of `len` values. A palette with a single value needs no bits at all.
+fn bits_for_palette_len(len: usize) -> u8 {
+    if len <= 1 {
+        0
+    } else {
+        (usize::BITS - (len - 1).leading_zeros()) as u8
+    }
+}
+
+/// Packs each index into `bits_per_index` bits, least significant bit first.
+fn pack_indices(indices: &[u32], bits_per_index: u8) -> Vec<u8> {
+    let bits_per_index = bits_per_index as usize;
+    let mut packed = vec![0u8; (indices.len() * bits_per_index).div_ceil(8)];
+    for (i, &index) in indices.iter().enumerate() {
+        for bit in 0..bits_per_index {
+            if index & (1 << bit) != 0 {
+                let packed_bit = i * bits_per_index + bit;
+                packed[packed_bit / 8] |= 1 << (packed_bit % 8);
+            }
+        }
+    }
+
+    packed
+}
+
+fn unpack_indices(
+    packed: &[u8],
+    bits_per_index: u8,
+    num_indices: usize,
+) -> impl '_ + Iterator<Item = u32> {
+    let bits_per_index = bits_per_index as usize;
+
+    (0..num_indices).map(move |i| {
+        (0..bits_per_index).fold(0, |index, bit| {
+            let packed_bit = i * bits_per_index + bit;
+            let bit_value = (packed[packed_bit / 8] >> (packed_bit % 8)) & 1;
+
+            index | ((bit_value as u32) << bit)
+        })
+    })
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::NoCompression;
+
+    #[test]
+    fn palette_round_trip() {
+        let values: Vec<u32> = (0..4096)
+            .map(|i| [7, 1_000_000, 42, 9][(i * 31) % 4])
+            .collect();
+        let channel = Channel::new(values);
+
+        let compression = PaletteChannelsCompression::<_, Channel<_>>::new(NoCompression);
+        let compressed = compression.compress(&channel);
+
+        // 4 distinct values need 2 bits each.
+        assert!(compressed.compressed_data.compressed_bytes().len() < 4096 * 2 / 8 + 64);
+        assert_eq!(compressed.decompress(), channel);
+    }
+
+    #[test]
+    fn palette_with_single_value_has_no_indices() {
+        let channel = Channel::fill(5u8, 4096);
+
+        let compression = PaletteChannelsCompression::<_, Channel<_>>::new(NoCompression);
+        let compressed = compression.compress(&channel);
+
+        assert!(compressed.compressed_data.compressed_bytes().len() < 32);
+        assert_eq!(compressed.decompress(), channel);
+    }
+
+    #[test]
+    fn palette_with_many_values_round_trip() {
+        let values: Vec<i32> = (0..4096).map(|i| (i * 7919) % 1000).collect();
+        let channel = Channel::new(values);
+
+        let compression = PaletteChannelsCompression::<_, Channel<_>>::new(NoCompression);
+        let compressed = compression.compress(&channel);
+
+        assert_eq!(compressed.decompress(), channel);
+    }
+
+    #[test]
+    fn bits_for_palette() {
+        assert_eq!(bits_for_palette_len(1), 0);
+        assert_eq!(bits_for_palette_len(2), 1);
+        assert_eq!(bits_for_palette_len(3), 2);
+        assert_eq!(bits_for_palette_len(4), 2);
+        assert_eq!(bits_for_palette_len(5), 3);
+        assert_eq!(bits_for_palette_len(256), 8);
+        assert_eq!(bits_for_palette_len(257), 9);
+    }
+}
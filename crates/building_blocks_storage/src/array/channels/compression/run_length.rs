@@ -0,0 +1,172 @@
+use crate::{ByteSize, BytesCompression, Channel, Compressed, Compression, FromBytesCompression};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Run-length encodes a `Channel` into a `RunLengthCompressedChannel`. Each channel of a tuple gets its own runs, since values
+/// in different channels rarely change at the same points.
+///
+/// Each channel is encoded as a sequence of `(value, run_length)` pairs along the linear order of the array, so it's
+/// especially effective for chunks with large regions of the same value, like terrain with layers of air, dirt and stone. The
+/// encoded runs are then compressed with some `By: BytesCompression`, which can be `NoCompression` to skip that step.
+///
+/// The runs are serialized with `bincode` instead of being copied as raw memory like `FastChannelsCompression` does, so the
+/// compressed runs can be read on any platform.
+pub struct RunLengthChannelsCompression<By, Chan> {
+    bytes_compression: By,
+    marker: std::marker::PhantomData<Chan>,
+}
+
+impl<By, Chan> Clone for RunLengthChannelsCompression<By, Chan>
+where
+    By: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            bytes_compression: self.bytes_compression.clone(),
+            marker: Default::default(),
+        }
+    }
+}
+
+impl<By, Chan> Copy for RunLengthChannelsCompression<By, Chan> where By: Copy {}
+
+impl<By, Chan> RunLengthChannelsCompression<By, Chan> {
+    pub fn new(bytes_compression: By) -> Self {
+        Self {
+            bytes_compression,
+            marker: Default::default(),
+        }
+    }
+
+    pub fn bytes_compression(&self) -> &By {
+        &self.bytes_compression
+    }
+
+    /// The same compression for a different type of channels, e.g. one channel from a tuple.
+    pub fn for_channels<Ch>(&self) -> RunLengthChannelsCompression<By, Ch>
+    where
+        By: Clone,
+    {
+        RunLengthChannelsCompression::new(self.bytes_compression.clone())
+    }
+}
+
+impl<By, Chan> FromBytesCompression<By> for RunLengthChannelsCompression<By, Chan> {
+    fn from_bytes_compression(bytes_compression: By) -> Self {
+        Self::new(bytes_compression)
+    }
+}
+
+/// A `Channel` compressed with `RunLengthChannelsCompression`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RunLengthCompressedChannel<T> {
+    compressed_bytes: Vec<u8>,
+    marker: std::marker::PhantomData<T>,
+}
+
+impl<T> RunLengthCompressedChannel<T> {
+    pub fn compressed_bytes(&self) -> &[u8] {
+        &self.compressed_bytes
+    }
+}
+
+impl<T> ByteSize for RunLengthCompressedChannel<T> {
+    fn byte_size(&self) -> usize {
+        core::mem::size_of::<Self>() + self.compressed_bytes.len()
+    }
+}
+
+impl<By, T> Compression for RunLengthChannelsCompression<By, Channel<T>>
+where
+    By: BytesCompression,
+    T: Clone + DeserializeOwned + PartialEq + Serialize,
+{
+    type Data = Channel<T>;
+    type CompressedData = RunLengthCompressedChannel<T>;
+
+    fn compress(&self, data: &Self::Data) -> Compressed<Self> {
+        let runs = encode_runs(data.store());
+
+        let mut compressed_bytes = Vec::new();
+        self.bytes_compression
+            .compress_bytes(&bincode::serialize(&runs).unwrap(), &mut compressed_bytes);
+
+        Compressed::new(RunLengthCompressedChannel {
+            compressed_bytes,
+            marker: Default::default(),
+        })
+    }
+
+    fn decompress(compressed: &Self::CompressedData) -> Self::Data {
+        let mut serialized_runs = Vec::new();
+        By::decompress_bytes(&compressed.compressed_bytes, &mut serialized_runs);
+        let runs: Vec<(T, u32)> = bincode::deserialize(&serialized_runs).unwrap();
+
+        Channel::new(decode_runs(&runs))
+    }
+}
+
+fn encode_runs<T>(values: &[T]) -> Vec<(&T, u32)>
+where
+    T: PartialEq,
+{
+    let mut runs: Vec<(&T, u32)> = Vec::new();
+    for value in values.iter() {
+        match runs.last_mut() {
+            Some((run_value, run_length)) if *run_value == value && *run_length < u32::MAX => {
+                *run_length += 1;
+            }
+            _ => runs.push((value, 1)),
+        }
+    }
+
+    runs
+}
+
+fn decode_runs<T>(runs: &[(T, u32)]) -> Vec<T>
+where
+    T: Clone,
+{
+    let num_values = runs
+        .iter()
+        .map(|(_, run_length)| *run_length as usize)
+        .sum();
+    let mut values = Vec::with_capacity(num_values);
+    for (value, run_length) in runs.iter() {
+        values.extend(std::iter::repeat_n(value.clone(), *run_length as usize));
+    }
+
+    values
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::NoCompression;
+
+    #[test]
+    fn run_length_round_trip() {
+        let mut values = vec![0u16; 1000];
+        values.extend(vec![3u16; 3000]);
+        values.extend((0..96).map(|i| i % 3));
+        let channel = Channel::new(values);
+
+        let compression = RunLengthChannelsCompression::<_, Channel<_>>::new(NoCompression);
+        let compressed = compression.compress(&channel);
+
+        // Two long runs, then 96 runs of length 1.
+        assert_eq!(
+            compressed.compressed_data.compressed_bytes().len(),
+            8 + 98 * (2 + 4)
+        );
+        assert_eq!(compressed.decompress(), channel);
+    }
+}
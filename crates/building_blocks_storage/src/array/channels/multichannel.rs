@@ -1,8 +1,38 @@
 use crate::{
-    Channel, Channels, Compressed, Compression, FastChannelsCompression, FillChannels,
+    Channel, ChannelTypeNames, Channels, Compressed, Compression, FastChannelsCompression,
+    FillChannels, GetMutPtr, PaletteChannelsCompression, RunLengthChannelsCompression,
     UninitChannels,
 };
 
+/// Compresses each channel of a tuple separately with the same kind of channel compression.
+macro_rules! impl_compression_for_tuple {
+    ( $compression:ident; $( $var1:ident, $var2:ident : $t:ident ),+ ) => {
+        impl<$($t),+, By> Compression for $compression<By, ($(Channel<$t>,)+)>
+        where
+            $( $compression<By, Channel<$t>>: Compression<Data = Channel<$t>>, )+
+            By: Clone,
+        {
+            type Data = ($(Channel<$t>,)+);
+            type CompressedData = ($(Compressed<$compression<By, Channel<$t>>>,)+);
+
+            fn compress(&self, data: &Self::Data) -> Compressed<Self> {
+                let ($($var1,)+) = data;
+
+                // Have to make compression objects for each channel.
+                let ($($var2,)+) = ($(self.for_channels::<Channel<$t>>(),)+);
+
+                Compressed::new(($($var2.compress($var1),)+))
+            }
+
+            fn decompress(compressed: &Self::CompressedData) -> Self::Data {
+                let ($($var1,)+) = compressed;
+
+                ( $($var1.decompress(),)+ )
+            }
+        }
+    };
+}
+
 macro_rules! impl_channels_for_tuple {
     ( $( $var1:ident, $var2:ident : $t:ident ),+ ) => {
 
@@ -15,9 +45,22 @@ macro_rules! impl_channels_for_tuple {
             type UninitSelf = ($($t::UninitSelf,)+);
         }
 
+        impl<$($t),+> ChannelTypeNames for ($($t,)+)
+        where
+            $($t: ChannelTypeNames),+
+        {
+            fn channel_type_names() -> Vec<String> {
+                let mut names = Vec::new();
+                $( names.extend($t::channel_type_names()); )+
+
+                names
+            }
+        }
+
         impl<$($t),+> FillChannels for ($($t,)+)
         where
-            $($t: FillChannels),+
+            $($t: FillChannels + GetMutPtr<usize, Item = <$t as Channels>::Ptr>),+,
+            $(<$t as Channels>::Data: Clone),+
         {
             fn fill(value: Self::Data, length: usize) -> Self {
                 let ($($var1,)+) = value;
@@ -31,6 +74,13 @@ macro_rules! impl_channels_for_tuple {
 
                 $( $var1.reset_values($var2); )+
             }
+
+            fn fill_range(&mut self, range: core::ops::Range<usize>, value: Self::Data) {
+                let ($($var1,)+) = self;
+                let ($($var2,)+) = value;
+
+                $( $var1.fill_range(range.clone(), $var2); )+
+            }
         }
 
         impl<$($t),+> UninitChannels for ($($t,)+)
@@ -50,29 +100,9 @@ macro_rules! impl_channels_for_tuple {
             }
         }
 
-        impl<$($t),+, By> Compression for FastChannelsCompression<By, ($(Channel<$t>,)+)>
-        where
-            $( FastChannelsCompression<By, Channel<$t>>: Compression<Data = Channel<$t>>, )+
-            By: Clone,
-        {
-            type Data = ($(Channel<$t>,)+);
-            type CompressedData = ($(Compressed<FastChannelsCompression<By, Channel<$t>>>,)+);
-
-            fn compress(&self, data: &Self::Data) -> Compressed<Self> {
-                let ($($var1,)+) = data;
-
-                // Have to make compression objects for each channel.
-                let ($($var2,)+) = ($(FastChannelsCompression::<By, Channel<$t>>::new(self.bytes_compression().clone()),)+);
-
-                Compressed::new(($($var2.compress($var1),)+))
-            }
-
-            fn decompress(compressed: &Self::CompressedData) -> Self::Data {
-                let ($($var1,)+) = compressed;
-
-                ( $($var1.decompress(),)+ )
-            }
-        }
+        impl_compression_for_tuple! { FastChannelsCompression; $($var1, $var2: $t),+ }
+        impl_compression_for_tuple! { RunLengthChannelsCompression; $($var1, $var2: $t),+ }
+        impl_compression_for_tuple! { PaletteChannelsCompression; $($var1, $var2: $t),+ }
     }
 }
 
@@ -0,0 +1,614 @@
+//! A channel of values stored as indices into a palette.
+//!
+//! Voxel maps usually have only a few distinct values in each chunk, like a handful of block types, but a `Channel<T>` stores
+//! a full `T` for every point. A `PaletteChannel<T>` instead stores each distinct value once in a palette, and packs one index
+//! into the palette for every point. The indices start with 1 bit each, and they're widened to 2, 4, 8, and then 16 bits as new
+//! values are written. Writing a value that isn't in the palette yet appends it, so the palette can hold values that are no
+//! longer used anywhere; `compact` removes them and narrows the indices again. The palette is also compacted automatically
+//! whenever it's full, before the indices are widened.
+//!
+//! Looking up a value in the palette is linear in the number of distinct values, so this is only a good choice for channels
+//! with a small number of distinct values. At most 2^16 distinct values can be in use at the same time.
+//!
+//! Since a packed index can't be borrowed mutably, mutable access goes through proxies: `GetMut` returns a `PaletteMut` that
+//! dereferences to a `T` and writes it back when it's dropped.
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::prelude::*;
+//! # use building_blocks_storage::PaletteArray3x1;
+//! let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(16));
+//! let mut a = PaletteArray3x1::fill(extent, 0u32);
+//! assert_eq!(a.channels().bits_per_index(), 1);
+//!
+//! a.fill_extent(&Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(8)), 100);
+//! *a.get_mut(Point3i::fill(15)) = 200;
+//! assert_eq!(a.get(Point3i::fill(15)), 200);
+//! assert_eq!(a.channels().palette(), &[0, 100, 200]);
+//! assert_eq!(a.channels().bits_per_index(), 2);
+//!
+//! a.fill_extent(&extent, 100);
+//! a.compact_palette();
+//! assert_eq!(a.channels().palette(), &[100]);
+//! assert_eq!(a.channels().bits_per_index(), 1);
+//! ```
+
+use crate::{
+    Array, ByteSize, ChannelTypeNames, Channels, FillChannels, Get, GetMut, GetMutPtr, GetRef,
+    IntoMultiMut, IntoMultiMutPtr, MultiMutPtr, StableTypeName, UninitChannels,
+};
+
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut, Range};
+use serde::{Deserialize, Serialize};
+
+const WORD_BITS: usize = u64::BITS as usize;
+const MAX_BITS_PER_INDEX: u8 = 16;
+
+/// A channel of palette indices. See the [module docs](crate::array::channels::palette_channel).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PaletteChannel<T> {
+    palette: Vec<T>,
+    // Always one of 1, 2, 4, 8, or 16, so an index never straddles two words.
+    bits_per_index: u8,
+    words: Vec<u64>,
+    len: usize,
+}
+
+/// An `Array` of palette-indexed values.
+pub type PaletteArrayNx1<N, T, Idx = N> = Array<N, PaletteChannel<T>, Idx>;
+/// A 2D `PaletteArrayNx1`.
+pub type PaletteArray2x1<T> = PaletteArrayNx1<[i32; 2], T>;
+/// A 3D `PaletteArrayNx1`.
+pub type PaletteArray3x1<T> = PaletteArrayNx1<[i32; 3], T>;
+
+impl<T> PaletteChannel<T> {
+    fn with_palette(palette: Vec<T>, length: usize) -> Self {
+        let bits_per_index = bits_for_palette_len(palette.len());
+
+        Self {
+            palette,
+            bits_per_index,
+            words: vec![0; num_words(bits_per_index, length)],
+            len: length,
+        }
+    }
+
+    pub fn fill(value: T, length: usize) -> Self {
+        Self::with_palette(vec![value], length)
+    }
+
+    /// The number of values.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The distinct values that have been written, in the order they were first written (or kept by `compact`). This can
+    /// include values that are no longer used.
+    #[inline]
+    pub fn palette(&self) -> &[T] {
+        &self.palette
+    }
+
+    /// The number of bits used for each palette index.
+    #[inline]
+    pub fn bits_per_index(&self) -> u8 {
+        self.bits_per_index
+    }
+
+    /// The index into the palette of the value at `offset`.
+    #[inline]
+    pub fn palette_index(&self, offset: usize) -> usize {
+        debug_assert!(offset < self.len);
+
+        let (word, shift) = self.index_location(offset);
+
+        ((self.words[word] >> shift) & index_mask(self.bits_per_index)) as usize
+    }
+
+    #[inline]
+    pub fn get_value(&self, offset: usize) -> &T {
+        &self.palette[self.palette_index(offset)]
+    }
+
+    pub fn reset_values(&mut self, value: T) {
+        *self = Self::fill(value, self.len);
+    }
+
+    #[inline]
+    fn index_location(&self, offset: usize) -> (usize, usize) {
+        let bit = offset * self.bits_per_index as usize;
+
+        (bit / WORD_BITS, bit % WORD_BITS)
+    }
+
+    #[inline]
+    fn set_palette_index(&mut self, offset: usize, index: usize) {
+        debug_assert!(offset < self.len);
+        debug_assert!(index < self.palette.len());
+
+        let (word, shift) = self.index_location(offset);
+        let mask = index_mask(self.bits_per_index) << shift;
+        self.words[word] = (self.words[word] & !mask) | ((index as u64) << shift);
+    }
+
+    /// Removes the values that are no longer used from the palette, and narrows the indices if the remaining palette fits in
+    /// fewer bits.
+    pub fn compact(&mut self) {
+        self.compact_reserving(0);
+    }
+
+    /// Like `compact`, but leaves room in the indices for `additional` more palette values.
+    fn compact_reserving(&mut self, additional: usize) {
+        let mut used = vec![false; self.palette.len()];
+        for offset in 0..self.len {
+            used[self.palette_index(offset)] = true;
+        }
+
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = Vec::new();
+        for (old_index, value) in self.palette.drain(..).enumerate() {
+            if used[old_index] {
+                remap[old_index] = palette.len();
+                palette.push(value);
+            }
+        }
+        self.palette = palette;
+
+        self.repack(bits_for_palette_len(self.palette.len() + additional), |i| {
+            remap[i]
+        });
+    }
+
+    /// Rewrites every index with `bits_per_index` bits, after mapping it with `f`.
+    fn repack(&mut self, bits_per_index: u8, f: impl Fn(usize) -> usize) {
+        let mut repacked = Self {
+            palette: Vec::new(),
+            bits_per_index,
+            words: vec![0; num_words(bits_per_index, self.len)],
+            len: self.len,
+        };
+        for offset in 0..self.len {
+            let (word, shift) = repacked.index_location(offset);
+            repacked.words[word] |= (f(self.palette_index(offset)) as u64) << shift;
+        }
+        self.bits_per_index = repacked.bits_per_index;
+        self.words = repacked.words;
+    }
+}
+
+impl<T> PaletteChannel<T>
+where
+    T: PartialEq,
+{
+    /// Returns the palette index of `value`, adding it to the palette (and widening the indices) if it's not there yet.
+    pub fn palette_index_or_insert(&mut self, value: T) -> usize {
+        if let Some(index) = self.palette.iter().position(|v| v.eq(&value)) {
+            return index;
+        }
+
+        if self.palette.len() >= 1 << self.bits_per_index {
+            // Stale values are dropped before widening, since the indices need to be rewritten either way.
+            self.compact_reserving(1);
+        }
+
+        self.palette.push(value);
+        let bits_per_index = bits_for_palette_len(self.palette.len());
+        if bits_per_index > self.bits_per_index {
+            self.repack(bits_per_index, |i| i);
+        }
+
+        self.palette.len() - 1
+    }
+
+    #[inline]
+    pub fn set_value(&mut self, offset: usize, value: T) {
+        let index = self.palette_index_or_insert(value);
+        self.set_palette_index(offset, index);
+    }
+
+    /// Sets every value in `range` to `value`.
+    pub fn fill_range(&mut self, range: Range<usize>, value: T) {
+        if range.is_empty() {
+            return;
+        }
+        debug_assert!(range.end <= self.len);
+
+        let index = self.palette_index_or_insert(value);
+        for offset in range {
+            self.set_palette_index(offset, index);
+        }
+    }
+}
+
+/// The number of bits per index that can address a palette of `len` values.
+fn bits_for_palette_len(len: usize) -> u8 {
+    assert!(
+        len <= 1 << MAX_BITS_PER_INDEX,
+        "PaletteChannel can't hold more than 2^16 distinct values"
+    );
+
+    let mut bits = 1;
+    while (1 << bits) < len {
+        bits *= 2;
+    }
+
+    bits
+}
+
+fn num_words(bits_per_index: u8, length: usize) -> usize {
+    (length * bits_per_index as usize).div_ceil(WORD_BITS)
+}
+
+#[inline]
+fn index_mask(bits_per_index: u8) -> u64 {
+    (1 << bits_per_index) - 1
+}
+
+impl<T> PartialEq for PaletteChannel<T>
+where
+    T: PartialEq,
+{
+    /// Compares the values, regardless of how they're indexed.
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && (0..self.len).all(|i| self.get_value(i) == other.get_value(i))
+    }
+}
+
+impl<T> Eq for PaletteChannel<T> where T: Eq {}
+
+impl<T> Channels for PaletteChannel<T>
+where
+    T: PartialEq,
+{
+    type Data = T;
+    type Ptr = PalettePtr<T>;
+    // Writes go through the palette, so an uninitialized channel is just one with an empty palette.
+    type UninitSelf = PaletteChannel<T>;
+}
+
+impl<T> ChannelTypeNames for PaletteChannel<T>
+where
+    T: StableTypeName,
+{
+    fn channel_type_names() -> Vec<String> {
+        vec![format!("palette<{}>", T::STABLE_TYPE_NAME)]
+    }
+}
+
+impl<T> ByteSize for PaletteChannel<T> {
+    fn byte_size(&self) -> usize {
+        core::mem::size_of::<Self>()
+            + self.palette.len() * core::mem::size_of::<T>()
+            + self.words.len() * core::mem::size_of::<u64>()
+    }
+}
+
+impl<T> FillChannels for PaletteChannel<T>
+where
+    T: PartialEq,
+{
+    fn fill(value: Self::Data, length: usize) -> Self {
+        Self::fill(value, length)
+    }
+
+    fn reset_values(&mut self, value: Self::Data) {
+        self.reset_values(value)
+    }
+
+    fn fill_range(&mut self, range: Range<usize>, value: Self::Data) {
+        self.fill_range(range, value)
+    }
+}
+
+impl<T> UninitChannels for PaletteChannel<T>
+where
+    T: PartialEq,
+{
+    type InitSelf = PaletteChannel<T>;
+
+    unsafe fn maybe_uninit(size: usize) -> Self {
+        Self::with_palette(Vec::new(), size)
+    }
+
+    unsafe fn assume_init(self) -> Self::InitSelf {
+        self
+    }
+}
+
+//  ██████╗ ███████╗████████╗████████╗███████╗██████╗ ███████╗
+// ██╔════╝ ██╔════╝╚══██╔══╝╚══██╔══╝██╔════╝██╔══██╗██╔════╝
+// ██║  ███╗█████╗     ██║      ██║   █████╗  ██████╔╝███████╗
+// ██║   ██║██╔══╝     ██║      ██║   ██╔══╝  ██╔══██╗╚════██║
+// ╚██████╔╝███████╗   ██║      ██║   ███████╗██║  ██║███████║
+//  ╚═════╝ ╚══════╝   ╚═╝      ╚═╝   ╚══════╝╚═╝  ╚═╝╚══════╝
+
+impl<T> Get<usize> for PaletteChannel<T>
+where
+    T: Clone,
+{
+    type Item = T;
+
+    #[inline]
+    fn get(&self, offset: usize) -> Self::Item {
+        self.get_value(offset).clone()
+    }
+}
+
+impl<'a, T> GetRef<'a, usize> for PaletteChannel<T>
+where
+    T: 'a,
+{
+    type Item = &'a T;
+
+    #[inline]
+    fn get_ref(&'a self, offset: usize) -> Self::Item {
+        self.get_value(offset)
+    }
+}
+
+impl<'a, T> GetMut<'a, usize> for PaletteChannel<T>
+where
+    T: 'a + Clone + PartialEq,
+{
+    type Item = PaletteMut<'a, T>;
+
+    #[inline]
+    fn get_mut(&'a mut self, offset: usize) -> Self::Item {
+        unsafe { self.get_mut_ptr(offset).into_multi_mut() }
+    }
+}
+
+impl<T> GetMutPtr<usize> for PaletteChannel<T> {
+    type Item = PalettePtr<T>;
+
+    #[inline]
+    unsafe fn get_mut_ptr(&mut self, offset: usize) -> Self::Item {
+        debug_assert!(offset < self.len);
+
+        PalettePtr {
+            channel: self as *mut Self,
+            offset,
+        }
+    }
+}
+
+/// A pointer to a single value of a `PaletteChannel`.
+///
+/// Writing a new value can grow the palette and repack the indices, so this points at the whole channel rather than at the
+/// packed index.
+#[derive(Debug)]
+pub struct PalettePtr<T> {
+    channel: *mut PaletteChannel<T>,
+    offset: usize,
+}
+
+impl<T> Clone for PalettePtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for PalettePtr<T> {}
+
+impl<T> MultiMutPtr for PalettePtr<T>
+where
+    T: PartialEq,
+{
+    type Data = T;
+
+    #[inline]
+    unsafe fn write(self, data: Self::Data) {
+        (*self.channel).set_value(self.offset, data);
+    }
+}
+
+impl<T> IntoMultiMutPtr for PalettePtr<T>
+where
+    T: PartialEq,
+{
+    type Data = T;
+    type Ptr = PalettePtr<T>;
+
+    #[inline]
+    unsafe fn into_multi_mut_ptr(self) -> Self::Ptr {
+        self
+    }
+}
+
+impl<'a, T> IntoMultiMut<'a> for PalettePtr<T>
+where
+    T: 'a + Clone + PartialEq,
+{
+    type MultiMut = PaletteMut<'a, T>;
+
+    #[inline]
+    fn into_multi_mut(self) -> Self::MultiMut {
+        let value = unsafe { (*self.channel).get_value(self.offset).clone() };
+
+        PaletteMut {
+            ptr: self,
+            value: ManuallyDrop::new(value),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// A mutable proxy for a single value of a `PaletteChannel`. It dereferences to a `T`, which is written back to the channel
+/// when the proxy is dropped.
+#[derive(Debug)]
+pub struct PaletteMut<'a, T>
+where
+    T: PartialEq,
+{
+    ptr: PalettePtr<T>,
+    value: ManuallyDrop<T>,
+    marker: PhantomData<&'a mut PaletteChannel<T>>,
+}
+
+impl<'a, T> Deref for PaletteMut<'a, T>
+where
+    T: PartialEq,
+{
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<'a, T> DerefMut for PaletteMut<'a, T>
+where
+    T: PartialEq,
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+impl<'a, T> Drop for PaletteMut<'a, T>
+where
+    T: PartialEq,
+{
+    #[inline]
+    fn drop(&mut self) {
+        // SAFE: `value` is never used again.
+        unsafe { self.ptr.write(ManuallyDrop::take(&mut self.value)) }
+    }
+}
+
+//  █████╗ ██████╗ ██████╗  █████╗ ██╗   ██╗
+// ██╔══██╗██╔══██╗██╔══██╗██╔══██╗╚██╗ ██╔╝
+// ███████║██████╔╝██████╔╝███████║ ╚████╔╝
+// ██╔══██║██╔══██╗██╔══██╗██╔══██║  ╚██╔╝
+// ██║  ██║██║  ██║██║  ██║██║  ██║   ██║
+// ╚═╝  ╚═╝╚═╝  ╚═╝╚═╝  ╚═╝╚═╝  ╚═╝   ╚═╝
+
+impl<N, T, Idx> Array<N, PaletteChannel<T>, Idx> {
+    /// Removes unused values from the palette. See `PaletteChannel::compact`.
+    pub fn compact_palette(&mut self) {
+        self.channels_mut().compact()
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{copy_extent, prelude::*};
+
+    use building_blocks_core::prelude::*;
+
+    #[test]
+    fn indices_grow_and_shrink_with_palette() {
+        let mut channel = PaletteChannel::fill(0u32, 300);
+        assert_eq!(channel.bits_per_index(), 1);
+
+        let expected_bits = [(2, 1), (3, 2), (5, 4), (17, 8), (257, 16)];
+        let mut next_value = 1;
+        for (palette_len, bits) in expected_bits {
+            while channel.palette().len() < palette_len {
+                channel.set_value(next_value as usize, next_value);
+                next_value += 1;
+            }
+            assert_eq!(channel.bits_per_index(), bits);
+        }
+        for i in 0..300 {
+            let expected = if (1..=256).contains(&i) { i } else { 0 };
+            assert_eq!(channel.get(i as usize), expected);
+        }
+
+        channel.fill_range(0..250, 7);
+        channel.compact();
+        assert_eq!(channel.palette().len(), 9);
+        assert_eq!(channel.bits_per_index(), 4);
+        assert_eq!(channel.get(249), 7);
+        assert_eq!(channel.get(255), 255);
+        assert_eq!(channel.get(280), 0);
+    }
+
+    #[test]
+    fn full_palette_is_compacted_before_widening() {
+        let mut channel = PaletteChannel::fill(0u32, 4);
+
+        // Far more distinct values than fit in 16 bits, but only 4 of them are ever in use.
+        for value in 0..100_000 {
+            channel.set_value(value as usize % 4, value);
+            assert!(channel.palette().len() <= 16);
+        }
+
+        assert_eq!(channel.bits_per_index(), 4);
+        for i in 0..4 {
+            assert_eq!(channel.get(i), 99_996 + i as u32);
+        }
+    }
+
+    #[test]
+    fn array_matches_unpaletted_array() {
+        let extent = Extent3i::from_min_and_shape(Point3i::fill(-5), PointN([13, 7, 5]));
+        let mut palette = PaletteArray3x1::fill_with(extent, |p| p.x() % 3);
+        let mut plain = Array3x1::fill_with(extent, |p| p.x() % 3);
+
+        let fill = Extent3i::from_min_and_shape(Point3i::fill(-3), PointN([9, 3, 2]));
+        palette.fill_extent(&fill, 10);
+        plain.fill_extent(&fill, 10);
+        palette.for_each_mut(&extent, |p: Point3i, mut value| {
+            if p.y() == 0 {
+                *value += 1;
+            }
+        });
+        plain.for_each_mut(&extent, |p: Point3i, value| {
+            if p.y() == 0 {
+                *value += 1;
+            }
+        });
+
+        palette.for_each(&extent, |p: Point3i, value| {
+            assert_eq!(value, plain.get(p));
+        });
+        assert_eq!(palette.channels().palette().len(), 7);
+        assert_eq!(palette.channels().bits_per_index(), 4);
+    }
+
+    #[test]
+    fn copy_between_palette_and_plain_arrays() {
+        let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(8));
+        let src = Array3x1::fill_with(extent, |p| p.z());
+        let mut dst = PaletteArray3x1::fill(extent, 0);
+
+        // Different channel types are copied through a `TransformMap`.
+        let copy = Extent3i::from_min_and_shape(Point3i::fill(2), Point3i::fill(4));
+        copy_extent(&copy, &TransformMap::new(&src, |v: i32| v), &mut dst);
+
+        dst.for_each(&extent, |p: Point3i, value| {
+            let expected = if copy.contains(p) { p.z() } else { 0 };
+            assert_eq!(value, expected);
+        });
+
+        let mut other = PaletteArray3x1::fill(extent, -1);
+        copy_extent(&copy, &dst, &mut other);
+        assert_eq!(other.get(PointN([3, 3, 5])), 5);
+        assert_eq!(other.get(PointN([7, 7, 7])), -1);
+        assert_eq!(other.channels().palette(), &[-1, 2, 3, 4, 5]);
+
+        let mut back = Array3x1::fill(extent, -1);
+        copy_extent(&extent, &TransformMap::new(&other, |v: i32| v), &mut back);
+        other.for_each(&extent, |p: Point3i, value| assert_eq!(value, back.get(p)));
+    }
+}
@@ -1,16 +1,26 @@
-use crate::{Array, Compressed, Compression, FromBytesCompression};
+mod homogeneous;
+
+pub use homogeneous::*;
+
+use crate::{
+    Array, ByteSize, Compressed, Compression, FromBytesCompression, PaletteChannelsCompression,
+    RunLengthChannelsCompression,
+};
 
 use building_blocks_core::prelude::*;
 
 /// A compression algorithm for arrays that avoid the overhead of serialization but ignores endianness and therefore isn't
 /// portable.
+///
+/// The channels are compressed in the order of the array's layout, so the `ArrayIndexer` `Idx` must match the arrays being
+/// compressed.
 #[derive(Clone, Copy, Debug)]
-pub struct FastArrayCompression<N, C> {
+pub struct FastArrayCompression<N, C, Idx = N> {
     pub channels_compression: C,
-    marker: std::marker::PhantomData<N>,
+    marker: std::marker::PhantomData<(N, Idx)>,
 }
 
-impl<N, C> FastArrayCompression<N, C> {
+impl<N, C, Idx> FastArrayCompression<N, C, Idx> {
     pub fn new(channels_compression: C) -> Self {
         Self {
             channels_compression,
@@ -23,7 +33,7 @@ impl<N, C> FastArrayCompression<N, C> {
     }
 }
 
-impl<N, C, B> FromBytesCompression<B> for FastArrayCompression<N, C>
+impl<N, C, Idx, B> FromBytesCompression<B> for FastArrayCompression<N, C, Idx>
 where
     C: FromBytesCompression<B>,
 {
@@ -59,12 +69,22 @@ where
     }
 }
 
-impl<N, C> Compression for FastArrayCompression<N, C>
+impl<N, C> ByteSize for FastCompressedArray<N, C>
+where
+    C: Compression,
+    C::CompressedData: ByteSize,
+{
+    fn byte_size(&self) -> usize {
+        core::mem::size_of::<ExtentN<N>>() + self.compressed_channels.byte_size()
+    }
+}
+
+impl<N, C, Idx> Compression for FastArrayCompression<N, C, Idx>
 where
     PointN<N>: IntegerPoint<N>,
     C: Compression,
 {
-    type Data = Array<N, C::Data>;
+    type Data = Array<N, C::Data, Idx>;
     type CompressedData = FastCompressedArray<N, C>;
 
     fn compress(&self, data: &Self::Data) -> Compressed<Self> {
@@ -84,6 +104,16 @@ where
     }
 }
 
+/// Compresses every channel of an `Array` with `RunLengthChannelsCompression`, which is best for arrays with long runs of the
+/// same value along the linear order.
+pub type RunLengthArrayCompression<N, By, Chan> =
+    FastArrayCompression<N, RunLengthChannelsCompression<By, Chan>>;
+
+/// Compresses every channel of an `Array` with `PaletteChannelsCompression`, which is best for arrays with only a few distinct
+/// values.
+pub type PaletteArrayCompression<N, By, Chan> =
+    FastArrayCompression<N, PaletteChannelsCompression<By, Chan>>;
+
 pub mod multichannel_aliases {
     use super::*;
     use crate::array::channels::multichannel::multichannel_aliases::*;
@@ -114,7 +144,7 @@ pub use multichannel_aliases::*;
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{Array3x1, BytesCompression};
+    use crate::{Array3x1, Array3x2, BytesCompression, Channel, NoCompression};
 
     use crate::test_utilities::sphere_bit_array;
     use utilities::test::test_print;
@@ -123,6 +153,8 @@ mod test {
     use crate::Lz4;
     #[cfg(feature = "snap")]
     use crate::Snappy;
+    #[cfg(feature = "zstd")]
+    use crate::Zstd;
 
     #[cfg(feature = "snap")]
     #[test]
@@ -140,6 +172,22 @@ mod test {
         homogeneous_array_compression_rate(Snappy, 128);
     }
 
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn sphere_array_compression_rate_zstd() {
+        sphere_array_compression_rate(Zstd::new(10), 32);
+        sphere_array_compression_rate(Zstd::new(10), 64);
+        sphere_array_compression_rate(Zstd::new(10), 128);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn homogeneous_array_compression_rate_zstd() {
+        homogeneous_array_compression_rate(Zstd::new(10), 32);
+        homogeneous_array_compression_rate(Zstd::new(10), 64);
+        homogeneous_array_compression_rate(Zstd::new(10), 128);
+    }
+
     #[cfg(feature = "lz4")]
     #[test]
     fn sphere_array_compression_rate_lz4() {
@@ -167,6 +215,70 @@ mod test {
         array_compression_rate(&array, compression);
     }
 
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn homogeneous_array_stores_single_value() {
+        let compression = HomogeneousArrayCompression::new(
+            FastArrayCompressionNx1::from_bytes_compression(Lz4 { level: 10 }),
+        );
+
+        let extent = Extent3i::from_min_and_shape(Point3i::fill(-8), Point3i::fill(16));
+        let array = Array3x1::fill(extent, 7u16);
+
+        let compressed = compression.compress(&array);
+        assert!(compressed.compressed_data.is_homogeneous());
+        assert_eq!(compressed.decompress(), array);
+
+        let array = sphere_bit_array(16, 1u16, 0u16).0;
+
+        let compressed = compression.compress(&array);
+        assert!(!compressed.compressed_data.is_homogeneous());
+        assert_eq!(compressed.decompress(), array);
+    }
+
+    #[test]
+    fn run_length_and_palette_array_round_trip() {
+        let array = sphere_bit_array(32, 1u16, 0u16).0;
+        let source_size_bytes = array.extent().num_points() * 2;
+
+        let compression =
+            RunLengthArrayCompression::<_, _, Channel<u16>>::from_bytes_compression(NoCompression);
+        let compressed = compression.compress(&array);
+        let compressed_size_bytes = compressed
+            .compressed_data
+            .compressed_channels()
+            .compressed_bytes()
+            .len();
+        assert!(compressed_size_bytes < source_size_bytes / 4);
+        assert_eq!(compressed.decompress(), array);
+
+        let compression =
+            PaletteArrayCompression::<_, _, Channel<u16>>::from_bytes_compression(NoCompression);
+        let compressed = compression.compress(&array);
+        let compressed_size_bytes = compressed
+            .compressed_data
+            .compressed_channels()
+            .compressed_bytes()
+            .len();
+        // 1 bit per point.
+        assert!(compressed_size_bytes < source_size_bytes / 15);
+        assert_eq!(compressed.decompress(), array);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn run_length_chained_with_bytes_compression() {
+        let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(16));
+        let array = Array3x2::fill_with(extent, |p: Point3i| ((p.y() / 4) as u8, p.y() as f32));
+
+        let compression =
+            RunLengthArrayCompression::<_, _, (Channel<u8>, Channel<f32>)>::from_bytes_compression(
+                Lz4 { level: 10 },
+            );
+        let compressed = compression.compress(&array);
+        assert_eq!(compressed.decompress(), array);
+    }
+
     fn array_compression_rate<B: BytesCompression>(array: &Array3x1<u16>, bytes_compression: B) {
         let source_size_bytes = array.extent().num_points() * 2;
 
@@ -0,0 +1,99 @@
+use crate::{
+    Array, ByteSize, Channels, Compressed, Compression, FillChannels, FromBytesCompression, Get,
+};
+
+use building_blocks_core::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+/// A compression algorithm for arrays that only stores a single value when every point of the array has the same value. Any
+/// other array is compressed with the wrapped array compression `C`.
+///
+/// This is especially useful for chunks that were completely filled (or carved out) with the same value, since those become
+/// nearly free to store, regardless of the chunk shape.
+#[derive(Clone, Copy, Debug)]
+pub struct HomogeneousArrayCompression<C> {
+    pub array_compression: C,
+}
+
+impl<C> HomogeneousArrayCompression<C> {
+    pub fn new(array_compression: C) -> Self {
+        Self { array_compression }
+    }
+
+    pub fn array_compression(&self) -> &C {
+        &self.array_compression
+    }
+}
+
+impl<C, B> FromBytesCompression<B> for HomogeneousArrayCompression<C>
+where
+    C: FromBytesCompression<B>,
+{
+    fn from_bytes_compression(bytes_compression: B) -> Self {
+        Self::new(C::from_bytes_compression(bytes_compression))
+    }
+}
+
+/// An `Array` compressed with `HomogeneousArrayCompression`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum MaybeHomogeneousArray<N, T, C> {
+    /// Every point in `extent` has `value`.
+    Homogeneous { extent: ExtentN<N>, value: T },
+    /// The compressed data of an array with at least two distinct values.
+    Heterogeneous(C),
+}
+
+impl<N, T, C> MaybeHomogeneousArray<N, T, C> {
+    pub fn is_homogeneous(&self) -> bool {
+        matches!(self, MaybeHomogeneousArray::Homogeneous { .. })
+    }
+}
+
+impl<N, T, C> ByteSize for MaybeHomogeneousArray<N, T, C>
+where
+    C: ByteSize,
+{
+    fn byte_size(&self) -> usize {
+        match self {
+            MaybeHomogeneousArray::Homogeneous { .. } => core::mem::size_of::<Self>(),
+            MaybeHomogeneousArray::Heterogeneous(compressed) => compressed.byte_size(),
+        }
+    }
+}
+
+impl<N, C, Chan, Idx> Compression for HomogeneousArrayCompression<C>
+where
+    PointN<N>: IntegerPoint<N>,
+    C: Compression<Data = Array<N, Chan, Idx>>,
+    Chan: FillChannels + Get<usize, Item = <Chan as Channels>::Data>,
+    Chan::Data: Clone + PartialEq,
+{
+    type Data = Array<N, Chan, Idx>;
+    type CompressedData = MaybeHomogeneousArray<N, Chan::Data, C::CompressedData>;
+
+    fn compress(&self, data: &Self::Data) -> Compressed<Self> {
+        let compressed = match data.homogeneous_value() {
+            Some(value) => MaybeHomogeneousArray::Homogeneous {
+                extent: *data.extent(),
+                value,
+            },
+            None => {
+                MaybeHomogeneousArray::Heterogeneous(self.array_compression.compress(data).take())
+            }
+        };
+
+        Compressed::new(compressed)
+    }
+
+    fn decompress(compressed: &Self::CompressedData) -> Self::Data {
+        match compressed {
+            MaybeHomogeneousArray::Homogeneous { extent, value } => {
+                Array::fill(*extent, value.clone())
+            }
+            MaybeHomogeneousArray::Heterogeneous(compressed_array) => {
+                C::decompress(compressed_array)
+            }
+        }
+    }
+}
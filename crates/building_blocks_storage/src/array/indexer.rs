@@ -6,20 +6,29 @@ use crate::{
 use building_blocks_core::prelude::*;
 
 /// When a lattice map implements `IndexedArray`, that means there is some underlying array with the location and shape dictated
-/// by the extent.
+/// by the layout extent.
 ///
 /// For the sake of generic impls, if the same map also implements `Get*<Stride>`, it must use the same data layout as `Array`.
 pub trait IndexedArray<N> {
     type Indexer: ArrayIndexer<N>;
 
+    /// The points that can be accessed.
     fn extent(&self) -> &ExtentN<N>;
 
+    /// The extent of the underlying array, which determines the `Stride` of every point. This is usually the same as
+    /// `extent`, but an `ArrayView` only covers part of the array it borrows.
+    #[inline]
+    fn layout_extent(&self) -> &ExtentN<N> {
+        self.extent()
+    }
+
+    /// `p` is relative to the minimum of the layout extent.
     #[inline]
     fn stride_from_local_point(&self, p: Local<N>) -> Stride
     where
         PointN<N>: Copy,
     {
-        Self::Indexer::stride_from_local_point(self.extent().shape, p)
+        Self::Indexer::stride_from_local_point(self.layout_extent().shape, p)
     }
 
     #[inline]
@@ -27,11 +36,15 @@ pub trait IndexedArray<N> {
     where
         PointN<N>: Copy,
     {
-        Self::Indexer::strides_from_local_points(self.extent().shape, points, strides)
+        Self::Indexer::strides_from_local_points(self.layout_extent().shape, points, strides)
     }
 }
 
 pub trait ArrayIndexer<N> {
+    /// Identifies this layout in a `ChunkFileHeader`, so arrays are never read with a different layout than they were written
+    /// with.
+    const LAYOUT_NAME: &'static str;
+
     fn stride_from_local_point(shape: PointN<N>, point: Local<N>) -> Stride;
 
     fn for_each_point_and_stride_unchecked(
@@ -55,9 +68,20 @@ pub trait ArrayIndexer<N> {
             strides[i] = Self::stride_from_local_point(shape, *p);
         }
     }
+
+    /// Calls `f` with the first stride and the length of every run of consecutive strides that together cover the iteration
+    /// extent. This lets bulk operations like `Array::fill_extent` work on whole runs at a time.
+    ///
+    /// By default, every run has a single stride.
+    #[inline]
+    fn for_each_run_unchecked(for_each: ArrayForEach<N>, mut f: impl FnMut(Stride, usize)) {
+        Self::for_each_point_and_stride_unchecked(for_each, |_p, stride| f(stride, 1));
+    }
 }
 
 impl ArrayIndexer<[i32; 2]> for [i32; 2] {
+    const LAYOUT_NAME: &'static str = "row_major";
+
     #[inline]
     fn stride_from_local_point(s: Point2i, p: Local2i) -> Stride {
         Stride((p.y() * s.x() + p.x()) as usize)
@@ -80,9 +104,32 @@ impl ArrayIndexer<[i32; 2]> for [i32; 2] {
     ) {
         for_each_stride_parallel_global_unchecked2(iter_extent, array1_extent, array2_extent, f)
     }
+
+    #[inline]
+    fn for_each_run_unchecked(for_each: Array2ForEach, mut f: impl FnMut(Stride, usize)) {
+        // Rows along the X axis are contiguous.
+        let row_length = for_each.iter_extent.shape.x();
+        if row_length <= 0 {
+            return;
+        }
+        let Array2ForEach {
+            iter_extent,
+            array_shape,
+            index_min,
+        } = for_each;
+        for y in 0..iter_extent.shape.y() {
+            let row_start = Local(index_min.0 + PointN([0, y]));
+            f(
+                Self::stride_from_local_point(array_shape, row_start),
+                row_length as usize,
+            );
+        }
+    }
 }
 
 impl ArrayIndexer<[i32; 3]> for [i32; 3] {
+    const LAYOUT_NAME: &'static str = "row_major";
+
     #[inline]
     fn stride_from_local_point(s: Point3i, p: Local3i) -> Stride {
         Stride((p.z() * s.y() * s.x() + p.y() * s.x() + p.x()) as usize)
@@ -105,4 +152,27 @@ impl ArrayIndexer<[i32; 3]> for [i32; 3] {
     ) {
         for_each_stride_parallel_global_unchecked3(iter_extent, array1_extent, array2_extent, f);
     }
+
+    #[inline]
+    fn for_each_run_unchecked(for_each: Array3ForEach, mut f: impl FnMut(Stride, usize)) {
+        // Rows along the X axis are contiguous.
+        let row_length = for_each.iter_extent.shape.x();
+        if row_length <= 0 {
+            return;
+        }
+        let Array3ForEach {
+            iter_extent,
+            array_shape,
+            index_min,
+        } = for_each;
+        for z in 0..iter_extent.shape.z() {
+            for y in 0..iter_extent.shape.y() {
+                let row_start = Local(index_min.0 + PointN([0, y, z]));
+                f(
+                    Self::stride_from_local_point(array_shape, row_start),
+                    row_length as usize,
+                );
+            }
+        }
+    }
 }
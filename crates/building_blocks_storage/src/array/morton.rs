@@ -0,0 +1,364 @@
+use crate::{Array2ForEach, Array3ForEach, ArrayIndexer, Local2i, Local3i, Stride};
+
+use building_blocks_core::prelude::*;
+
+/// An `ArrayIndexer` for the Z-order curve, also known as Morton order.
+///
+/// A row-major array stores neighbors along the Y and Z axes far apart from each other. A Morton array interleaves the bits of
+/// the X, Y, and Z coordinates to get the index, so every aligned, power-of-two cube of points is contiguous in memory. This
+/// improves cache locality for kernels that read entire neighborhoods, and it helps compression of data that is organized
+/// hierarchically, like an octree.
+///
+/// The shape of a Morton array must be a power of two along every axis, but it doesn't need to be a cube.
+///
+/// Algorithms that compute the strides of neighboring points themselves, like kernels, meshers and `OctreeSet::from_array3`,
+/// require `IndexedArray<N, Indexer = N>`, i.e. the row-major layout, so they don't accept Morton arrays.
+///
+/// ```
+/// # use building_blocks_core::prelude::*;
+/// # use building_blocks_storage::prelude::*;
+/// # use building_blocks_storage::{Array, Channel, Morton};
+/// let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(16));
+/// let array = Array::<_, Channel<i32>, Morton>::fill_with(extent, |p| p.x() + p.y() + p.z());
+///
+/// // All of the usual access traits still work with the same coordinates.
+/// assert_eq!(array.get(PointN([1, 2, 3])), 6);
+///
+/// // But the first 8 values are the 2x2x2 cube at the minimum.
+/// assert_eq!(array.get(Stride(7)), 3);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Morton;
+
+impl ArrayIndexer<[i32; 2]> for Morton {
+    const LAYOUT_NAME: &'static str = "morton";
+
+    #[inline]
+    fn stride_from_local_point(s: Point2i, p: Local2i) -> Stride {
+        if s.x() == s.y() {
+            // Square arrays have the same number of bits in each axis, so we can use the fast bit spreading method.
+            Stride((spread_bits2(p.x() as u64) | spread_bits2(p.y() as u64) << 1) as usize)
+        } else {
+            let [mx, my] = axis_masks(s.0);
+
+            Stride(deposit_bits(p.x() as usize, mx) | deposit_bits(p.y() as usize, my))
+        }
+    }
+
+    #[inline]
+    fn for_each_point_and_stride_unchecked(
+        for_each: Array2ForEach,
+        mut f: impl FnMut(Point2i, Stride),
+    ) {
+        let Array2ForEach {
+            iter_extent,
+            array_shape,
+            index_min,
+        } = for_each;
+        let [mx, my] = axis_masks(array_shape.0);
+        let iter_lub = iter_extent.least_upper_bound();
+
+        let mut sy = deposit_bits(index_min.y() as usize, my);
+        for y in iter_extent.minimum.y()..iter_lub.y() {
+            let mut sx = deposit_bits(index_min.x() as usize, mx);
+            for x in iter_extent.minimum.x()..iter_lub.x() {
+                f(PointN([x, y]), Stride(sx | sy));
+                sx = increment_masked(sx, mx);
+            }
+            sy = increment_masked(sy, my);
+        }
+    }
+
+    #[inline]
+    fn for_each_stride_parallel_global_unchecked(
+        iter_extent: &Extent2i,
+        array1_extent: &Extent2i,
+        array2_extent: &Extent2i,
+        mut f: impl FnMut(Stride, Stride),
+    ) {
+        let [mx1, my1] = axis_masks(array1_extent.shape.0);
+        let [mx2, my2] = axis_masks(array2_extent.shape.0);
+        // Translate to local coordinates.
+        let min1 = iter_extent.minimum - array1_extent.minimum;
+        let min2 = iter_extent.minimum - array2_extent.minimum;
+
+        let mut sy1 = deposit_bits(min1.y() as usize, my1);
+        let mut sy2 = deposit_bits(min2.y() as usize, my2);
+        for _y in 0..iter_extent.shape.y() {
+            let mut sx1 = deposit_bits(min1.x() as usize, mx1);
+            let mut sx2 = deposit_bits(min2.x() as usize, mx2);
+            for _x in 0..iter_extent.shape.x() {
+                f(Stride(sx1 | sy1), Stride(sx2 | sy2));
+                sx1 = increment_masked(sx1, mx1);
+                sx2 = increment_masked(sx2, mx2);
+            }
+            sy1 = increment_masked(sy1, my1);
+            sy2 = increment_masked(sy2, my2);
+        }
+    }
+}
+
+impl ArrayIndexer<[i32; 3]> for Morton {
+    const LAYOUT_NAME: &'static str = "morton";
+
+    #[inline]
+    fn stride_from_local_point(s: Point3i, p: Local3i) -> Stride {
+        if s.x() == s.y() && s.y() == s.z() {
+            // Cubic arrays have the same number of bits in each axis, so we can use the fast bit spreading method.
+            Stride(
+                (spread_bits3(p.x() as u64)
+                    | spread_bits3(p.y() as u64) << 1
+                    | spread_bits3(p.z() as u64) << 2) as usize,
+            )
+        } else {
+            let [mx, my, mz] = axis_masks(s.0);
+
+            Stride(
+                deposit_bits(p.x() as usize, mx)
+                    | deposit_bits(p.y() as usize, my)
+                    | deposit_bits(p.z() as usize, mz),
+            )
+        }
+    }
+
+    #[inline]
+    fn for_each_point_and_stride_unchecked(
+        for_each: Array3ForEach,
+        mut f: impl FnMut(Point3i, Stride),
+    ) {
+        let Array3ForEach {
+            iter_extent,
+            array_shape,
+            index_min,
+        } = for_each;
+        let [mx, my, mz] = axis_masks(array_shape.0);
+        let iter_lub = iter_extent.least_upper_bound();
+
+        let mut sz = deposit_bits(index_min.z() as usize, mz);
+        for z in iter_extent.minimum.z()..iter_lub.z() {
+            let mut sy = deposit_bits(index_min.y() as usize, my);
+            for y in iter_extent.minimum.y()..iter_lub.y() {
+                let mut sx = deposit_bits(index_min.x() as usize, mx);
+                for x in iter_extent.minimum.x()..iter_lub.x() {
+                    f(PointN([x, y, z]), Stride(sx | sy | sz));
+                    sx = increment_masked(sx, mx);
+                }
+                sy = increment_masked(sy, my);
+            }
+            sz = increment_masked(sz, mz);
+        }
+    }
+
+    #[inline]
+    fn for_each_stride_parallel_global_unchecked(
+        iter_extent: &Extent3i,
+        array1_extent: &Extent3i,
+        array2_extent: &Extent3i,
+        mut f: impl FnMut(Stride, Stride),
+    ) {
+        let [mx1, my1, mz1] = axis_masks(array1_extent.shape.0);
+        let [mx2, my2, mz2] = axis_masks(array2_extent.shape.0);
+        // Translate to local coordinates.
+        let min1 = iter_extent.minimum - array1_extent.minimum;
+        let min2 = iter_extent.minimum - array2_extent.minimum;
+
+        let mut sz1 = deposit_bits(min1.z() as usize, mz1);
+        let mut sz2 = deposit_bits(min2.z() as usize, mz2);
+        for _z in 0..iter_extent.shape.z() {
+            let mut sy1 = deposit_bits(min1.y() as usize, my1);
+            let mut sy2 = deposit_bits(min2.y() as usize, my2);
+            for _y in 0..iter_extent.shape.y() {
+                let mut sx1 = deposit_bits(min1.x() as usize, mx1);
+                let mut sx2 = deposit_bits(min2.x() as usize, mx2);
+                for _x in 0..iter_extent.shape.x() {
+                    f(Stride(sx1 | sy1 | sz1), Stride(sx2 | sy2 | sz2));
+                    sx1 = increment_masked(sx1, mx1);
+                    sx2 = increment_masked(sx2, mx2);
+                }
+                sy1 = increment_masked(sy1, my1);
+                sy2 = increment_masked(sy2, my2);
+            }
+            sz1 = increment_masked(sz1, mz1);
+            sz2 = increment_masked(sz2, mz2);
+        }
+    }
+}
+
+/// For each axis, the mask of index bits that hold the bits of that axis's coordinate.
+///
+/// Bits are interleaved from least to most significant, cycling through the axes. Once an axis runs out of bits because it's
+/// shorter than the others, it's skipped, so the indices are exactly `0..num_points` for any power-of-two shape.
+#[inline]
+fn axis_masks<const D: usize>(shape: [i32; D]) -> [usize; D] {
+    let mut axis_bits = [0; D];
+    for (bits, &s) in axis_bits.iter_mut().zip(shape.iter()) {
+        assert!(
+            s > 0 && (s & (s - 1)) == 0,
+            "Morton arrays must have a power-of-two shape, got {:?}",
+            shape
+        );
+        *bits = s.trailing_zeros();
+    }
+    let max_bits = axis_bits.iter().copied().max().unwrap_or(0);
+
+    let mut masks = [0; D];
+    let mut index_bit = 0;
+    for level in 0..max_bits {
+        for (mask, &bits) in masks.iter_mut().zip(axis_bits.iter()) {
+            if level < bits {
+                *mask |= 1 << index_bit;
+                index_bit += 1;
+            }
+        }
+    }
+
+    masks
+}
+
+/// Scatters the low bits of `value` into the set bits of `mask`, like the x86 `pdep` instruction.
+#[inline]
+fn deposit_bits(mut value: usize, mut mask: usize) -> usize {
+    let mut result = 0;
+    while mask != 0 {
+        let lowest_bit = mask & mask.wrapping_neg();
+        if value & 1 != 0 {
+            result |= lowest_bit;
+        }
+        value >>= 1;
+        mask &= mask - 1;
+    }
+
+    result
+}
+
+/// Increments the coordinate stored in the `mask` bits of `masked_index`. The carry skips over the bits of the other axes.
+#[inline]
+fn increment_masked(masked_index: usize, mask: usize) -> usize {
+    (masked_index | !mask).wrapping_add(1) & mask
+}
+
+/// Inserts a zero bit between each of the low 32 bits of `x`.
+#[inline]
+fn spread_bits2(x: u64) -> u64 {
+    let mut x = x & 0xffff_ffff;
+    x = (x | x << 16) & 0x0000_ffff_0000_ffff;
+    x = (x | x << 8) & 0x00ff_00ff_00ff_00ff;
+    x = (x | x << 4) & 0x0f0f_0f0f_0f0f_0f0f;
+    x = (x | x << 2) & 0x3333_3333_3333_3333;
+    x = (x | x << 1) & 0x5555_5555_5555_5555;
+
+    x
+}
+
+/// Inserts two zero bits between each of the low 21 bits of `x`.
+#[inline]
+fn spread_bits3(x: u64) -> u64 {
+    let mut x = x & 0x1f_ffff;
+    x = (x | x << 32) & 0x001f_0000_0000_ffff;
+    x = (x | x << 16) & 0x001f_0000_ff00_00ff;
+    x = (x | x << 8) & 0x100f_00f0_0f00_f00f;
+    x = (x | x << 4) & 0x10c3_0c30_c30c_30c3;
+    x = (x | x << 2) & 0x1249_2492_4924_9249;
+
+    x
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{
+        copy_extent, prelude::*, Array, Channel, FastArrayCompression, FastChannelsCompression1,
+        Local, NoCompression,
+    };
+
+    type MortonArray3<T> = Array<[i32; 3], Channel<T>, Morton>;
+
+    #[test]
+    fn cube_strides_match_general_interleaving() {
+        let shape = Point3i::fill(8);
+        let [mx, my, mz] = axis_masks(shape.0);
+        let mut strides = Vec::new();
+        for p in Extent3i::from_min_and_shape(Point3i::ZERO, shape).iter_points() {
+            let stride = Morton::stride_from_local_point(shape, Local(p));
+            let general = deposit_bits(p.x() as usize, mx)
+                | deposit_bits(p.y() as usize, my)
+                | deposit_bits(p.z() as usize, mz);
+            assert_eq!(stride.0, general);
+            strides.push(stride.0);
+        }
+        strides.sort_unstable();
+        assert_eq!(strides, (0..512).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn non_cube_strides_are_a_permutation() {
+        let extent = Extent2i::from_min_and_shape(PointN([-3, 5]), PointN([16, 4]));
+        let array = Array::<_, Channel<usize>, Morton>::fill(extent, 0);
+
+        let mut strides = Vec::new();
+        array.for_each(&extent, |(p, stride): (Point2i, Stride), _| {
+            assert_eq!(
+                array.stride_from_local_point(Local(p - extent.minimum)),
+                stride
+            );
+            strides.push(stride.0);
+        });
+        strides.sort_unstable();
+        assert_eq!(strides, (0..64).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn same_values_as_row_major() {
+        let extent = Extent3i::from_min_and_shape(PointN([-8, 0, 4]), PointN([16, 8, 4]));
+        let filler = |p: Point3i| p.x() * 10_000 + p.y() * 100 + p.z();
+        let morton = MortonArray3::fill_with(extent, filler);
+        let row_major = Array3x1::fill_with(extent, filler);
+
+        row_major.for_each(&extent, |p: Point3i, value| {
+            assert_eq!(morton.get(p), value);
+        });
+        let subextent = Extent3i::from_min_and_shape(PointN([-3, 1, 5]), Point3i::fill(3));
+        morton.for_each(&subextent, |p: Point3i, value| assert_eq!(value, filler(p)));
+    }
+
+    #[test]
+    fn copy_between_morton_arrays() {
+        let src_extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(16));
+        let src = MortonArray3::fill_with(src_extent, |p| p.x() + 2 * p.y() + 3 * p.z());
+
+        let dst_extent = Extent3i::from_min_and_shape(Point3i::fill(4), PointN([8, 16, 4]));
+        let mut dst = MortonArray3::fill(dst_extent, -1);
+        let copy_extent_ = Extent3i::from_min_and_shape(Point3i::fill(6), Point3i::fill(5));
+        copy_extent(&copy_extent_, &src, &mut dst);
+
+        dst.for_each(&dst_extent, |p: Point3i, value| {
+            if copy_extent_.contains(p) {
+                assert_eq!(value, src.get(p));
+            } else {
+                assert_eq!(value, -1);
+            }
+        });
+    }
+
+    #[test]
+    fn compression_round_trip() {
+        let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(16));
+        let array = MortonArray3::fill_with(extent, |p| p.x() / 4 + p.y() / 4 + p.z() / 4);
+
+        let compression =
+            FastArrayCompression::<_, FastChannelsCompression1<_, i32>, Morton>::from_bytes_compression(
+                NoCompression,
+            );
+        let compressed = compression.compress(&array);
+
+        assert_eq!(compressed.decompress(), array);
+    }
+}
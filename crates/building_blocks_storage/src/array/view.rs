@@ -0,0 +1,414 @@
+use crate::{
+    Array, ArrayCopySrc, ArrayIndexer, Channels, FillChannels, ForEach, ForEachMut, ForEachMutPtr,
+    Get, GetMut, GetMutPtr, GetRef, IndexedArray, Local, ReadExtent, Stride, WriteExtent,
+};
+
+use building_blocks_core::prelude::*;
+
+use core::iter::{once, Once};
+
+/// A borrowed sub-extent of an `Array`.
+///
+/// The view uses the same `Stride`s as the array it borrows, so strides and `Local` points are relative to `layout_extent`, the
+/// parent's extent. Every other way of accessing the view is restricted to its own `extent`.
+///
+/// Algorithms that keep their own buffers indexed by `Stride`, like `surface_nets`, must size those buffers to the
+/// `layout_extent`.
+pub struct ArrayView<'a, N, Chan, Idx = N> {
+    array: &'a Array<N, Chan, Idx>,
+    extent: ExtentN<N>,
+}
+
+/// A mutably borrowed sub-extent of an `Array`. See `ArrayView`.
+pub struct ArrayViewMut<'a, N, Chan, Idx = N> {
+    array: &'a mut Array<N, Chan, Idx>,
+    extent: ExtentN<N>,
+}
+
+impl<N, Chan, Idx> Array<N, Chan, Idx>
+where
+    PointN<N>: IntegerPoint<N>,
+{
+    /// Borrows the part of `self` that intersects `extent`.
+    pub fn view(&self, extent: &ExtentN<N>) -> ArrayView<'_, N, Chan, Idx> {
+        ArrayView {
+            extent: extent.intersection(self.extent()),
+            array: self,
+        }
+    }
+
+    /// Mutably borrows the part of `self` that intersects `extent`.
+    pub fn view_mut(&mut self, extent: &ExtentN<N>) -> ArrayViewMut<'_, N, Chan, Idx> {
+        ArrayViewMut {
+            extent: extent.intersection(self.extent()),
+            array: self,
+        }
+    }
+}
+
+impl<'a, N, Chan, Idx> Clone for ArrayView<'a, N, Chan, Idx>
+where
+    ExtentN<N>: Copy,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, N, Chan, Idx> Copy for ArrayView<'a, N, Chan, Idx> where ExtentN<N>: Copy {}
+
+impl<'a, N, Chan, Idx> ArrayView<'a, N, Chan, Idx> {
+    /// The borrowed array.
+    #[inline]
+    pub fn array(&self) -> &'a Array<N, Chan, Idx> {
+        self.array
+    }
+}
+
+impl<'a, N, Chan, Idx> ArrayViewMut<'a, N, Chan, Idx> {
+    /// The borrowed array.
+    #[inline]
+    pub fn array(&self) -> &Array<N, Chan, Idx> {
+        self.array
+    }
+
+    /// The borrowed array. Writing outside of the view's extent is allowed, since the whole array is borrowed.
+    #[inline]
+    pub fn array_mut(&mut self) -> &mut Array<N, Chan, Idx> {
+        self.array
+    }
+
+    /// Fill the part of `extent` that's in the view with the same `value`.
+    pub fn fill_extent(&mut self, extent: &ExtentN<N>, value: Chan::Data)
+    where
+        Idx: ArrayIndexer<N>,
+        PointN<N>: IntegerPoint<N>,
+        Chan: FillChannels + GetMutPtr<usize, Item = <Chan as Channels>::Ptr>,
+        Chan::Data: Clone,
+    {
+        self.array
+            .fill_extent(&extent.intersection(&self.extent), value)
+    }
+
+    #[inline]
+    pub fn as_view(&self) -> ArrayView<'_, N, Chan, Idx>
+    where
+        ExtentN<N>: Copy,
+    {
+        ArrayView {
+            array: self.array,
+            extent: self.extent,
+        }
+    }
+}
+
+//  ██████╗ ███████╗████████╗████████╗███████╗██████╗ ███████╗
+// ██╔════╝ ██╔════╝╚══██╔══╝╚══██╔══╝██╔════╝██╔══██╗██╔════╝
+// ██║  ███╗█████╗     ██║      ██║   █████╗  ██████╔╝███████╗
+// ██║   ██║██╔══╝     ██║      ██║   ██╔══╝  ██╔══██╗╚════██║
+// ╚██████╔╝███████╗   ██║      ██║   ███████╗██║  ██║███████║
+//  ╚═════╝ ╚══════╝   ╚═╝      ╚═╝   ╚══════╝╚═╝  ╚═╝╚══════╝
+
+macro_rules! impl_view_read_access {
+    ($view:ident) => {
+        impl<'a, N, Chan, Idx> IndexedArray<N> for $view<'a, N, Chan, Idx>
+        where
+            Idx: ArrayIndexer<N>,
+        {
+            type Indexer = Idx;
+
+            #[inline]
+            fn extent(&self) -> &ExtentN<N> {
+                &self.extent
+            }
+
+            #[inline]
+            fn layout_extent(&self) -> &ExtentN<N> {
+                self.array.extent()
+            }
+        }
+
+        impl<'a, N, Chan, Idx> Get<Stride> for $view<'a, N, Chan, Idx>
+        where
+            Array<N, Chan, Idx>: Get<Stride>,
+        {
+            type Item = <Array<N, Chan, Idx> as Get<Stride>>::Item;
+
+            #[inline]
+            fn get(&self, stride: Stride) -> Self::Item {
+                self.array.get(stride)
+            }
+        }
+
+        impl<'a, 'b, N, Chan, Idx> GetRef<'b, Stride> for $view<'a, N, Chan, Idx>
+        where
+            Array<N, Chan, Idx>: GetRef<'b, Stride>,
+        {
+            type Item = <Array<N, Chan, Idx> as GetRef<'b, Stride>>::Item;
+
+            #[inline]
+            fn get_ref(&'b self, stride: Stride) -> Self::Item {
+                self.array.get_ref(stride)
+            }
+        }
+
+        impl<'a, N, Chan, Idx> Get<PointN<N>> for $view<'a, N, Chan, Idx>
+        where
+            Array<N, Chan, Idx>: Get<PointN<N>>,
+            PointN<N>: IntegerPoint<N>,
+        {
+            type Item = <Array<N, Chan, Idx> as Get<PointN<N>>>::Item;
+
+            #[inline]
+            fn get(&self, p: PointN<N>) -> Self::Item {
+                debug_assert!(self.extent.contains(p));
+
+                self.array.get(p)
+            }
+        }
+
+        impl<'a, 'b, N, Chan, Idx> GetRef<'b, PointN<N>> for $view<'a, N, Chan, Idx>
+        where
+            Array<N, Chan, Idx>: GetRef<'b, PointN<N>>,
+            PointN<N>: IntegerPoint<N>,
+        {
+            type Item = <Array<N, Chan, Idx> as GetRef<'b, PointN<N>>>::Item;
+
+            #[inline]
+            fn get_ref(&'b self, p: PointN<N>) -> Self::Item {
+                debug_assert!(self.extent.contains(p));
+
+                self.array.get_ref(p)
+            }
+        }
+
+        /// `Local` points are relative to the minimum of the `layout_extent`, like `Stride`s.
+        impl<'a, N, Chan, Idx> Get<Local<N>> for $view<'a, N, Chan, Idx>
+        where
+            Array<N, Chan, Idx>: Get<Local<N>>,
+            PointN<N>: IntegerPoint<N>,
+        {
+            type Item = <Array<N, Chan, Idx> as Get<Local<N>>>::Item;
+
+            #[inline]
+            fn get(&self, p: Local<N>) -> Self::Item {
+                debug_assert!(self.extent.contains(p.0 + self.array.extent().minimum));
+
+                self.array.get(p)
+            }
+        }
+
+        impl<'a, 'b, N, Chan, Idx> GetRef<'b, Local<N>> for $view<'a, N, Chan, Idx>
+        where
+            Array<N, Chan, Idx>: GetRef<'b, Local<N>>,
+            PointN<N>: IntegerPoint<N>,
+        {
+            type Item = <Array<N, Chan, Idx> as GetRef<'b, Local<N>>>::Item;
+
+            #[inline]
+            fn get_ref(&'b self, p: Local<N>) -> Self::Item {
+                debug_assert!(self.extent.contains(p.0 + self.array.extent().minimum));
+
+                self.array.get_ref(p)
+            }
+        }
+
+        impl<'a, N, Chan, Idx, Coord> ForEach<N, Coord> for $view<'a, N, Chan, Idx>
+        where
+            Array<N, Chan, Idx>: ForEach<N, Coord>,
+            PointN<N>: IntegerPoint<N>,
+        {
+            type Item = <Array<N, Chan, Idx> as ForEach<N, Coord>>::Item;
+
+            #[inline]
+            fn for_each(&self, extent: &ExtentN<N>, f: impl FnMut(Coord, Self::Item)) {
+                self.array.for_each(&extent.intersection(&self.extent), f)
+            }
+        }
+    };
+}
+
+impl_view_read_access!(ArrayView);
+impl_view_read_access!(ArrayViewMut);
+
+impl<'a, 'b, N, Chan, Idx> GetMut<'b, Stride> for ArrayViewMut<'a, N, Chan, Idx>
+where
+    Array<N, Chan, Idx>: GetMut<'b, Stride>,
+{
+    type Item = <Array<N, Chan, Idx> as GetMut<'b, Stride>>::Item;
+
+    #[inline]
+    fn get_mut(&'b mut self, stride: Stride) -> Self::Item {
+        self.array.get_mut(stride)
+    }
+}
+
+impl<'a, N, Chan, Idx> GetMutPtr<Stride> for ArrayViewMut<'a, N, Chan, Idx>
+where
+    Array<N, Chan, Idx>: GetMutPtr<Stride>,
+{
+    type Item = <Array<N, Chan, Idx> as GetMutPtr<Stride>>::Item;
+
+    #[inline]
+    unsafe fn get_mut_ptr(&mut self, stride: Stride) -> Self::Item {
+        self.array.get_mut_ptr(stride)
+    }
+}
+
+impl<'a, 'b, N, Chan, Idx> GetMut<'b, PointN<N>> for ArrayViewMut<'a, N, Chan, Idx>
+where
+    Array<N, Chan, Idx>: GetMut<'b, PointN<N>>,
+    PointN<N>: IntegerPoint<N>,
+{
+    type Item = <Array<N, Chan, Idx> as GetMut<'b, PointN<N>>>::Item;
+
+    #[inline]
+    fn get_mut(&'b mut self, p: PointN<N>) -> Self::Item {
+        debug_assert!(self.extent.contains(p));
+
+        self.array.get_mut(p)
+    }
+}
+
+impl<'a, 'b, N, Chan, Idx> GetMut<'b, Local<N>> for ArrayViewMut<'a, N, Chan, Idx>
+where
+    Array<N, Chan, Idx>: GetMut<'b, Local<N>>,
+    PointN<N>: IntegerPoint<N>,
+{
+    type Item = <Array<N, Chan, Idx> as GetMut<'b, Local<N>>>::Item;
+
+    #[inline]
+    fn get_mut(&'b mut self, p: Local<N>) -> Self::Item {
+        debug_assert!(self.extent.contains(p.0 + self.array.extent().minimum));
+
+        self.array.get_mut(p)
+    }
+}
+
+impl<'a, N, Chan, Idx, Coord> ForEachMutPtr<N, Coord> for ArrayViewMut<'a, N, Chan, Idx>
+where
+    Array<N, Chan, Idx>: ForEachMutPtr<N, Coord>,
+    PointN<N>: IntegerPoint<N>,
+{
+    type Item = <Array<N, Chan, Idx> as ForEachMutPtr<N, Coord>>::Item;
+
+    #[inline]
+    unsafe fn for_each_mut_ptr(&mut self, extent: &ExtentN<N>, f: impl FnMut(Coord, Self::Item)) {
+        let extent = extent.intersection(&self.extent);
+        self.array.for_each_mut_ptr(&extent, f)
+    }
+}
+
+impl<'a, 'b, N, Chan, Idx, Coord> ForEachMut<'b, N, Coord> for ArrayViewMut<'a, N, Chan, Idx>
+where
+    Array<N, Chan, Idx>: ForEachMut<'b, N, Coord>,
+    PointN<N>: IntegerPoint<N>,
+{
+    type Item = <Array<N, Chan, Idx> as ForEachMut<'b, N, Coord>>::Item;
+
+    #[inline]
+    fn for_each_mut(&'b mut self, extent: &ExtentN<N>, f: impl FnMut(Coord, Self::Item)) {
+        let extent = extent.intersection(&self.extent);
+        self.array.for_each_mut(&extent, f)
+    }
+}
+
+//  ██████╗ ██████╗ ██████╗ ██╗   ██╗
+// ██╔════╝██╔═══██╗██╔══██╗╚██╗ ██╔╝
+// ██║     ██║   ██║██████╔╝ ╚████╔╝
+// ██║     ██║   ██║██╔═══╝   ╚██╔╝
+// ╚██████╗╚██████╔╝██║        ██║
+//  ╚═════╝ ╚═════╝ ╚═╝        ╚═╝
+
+impl<'a, 'b, N: 'b, Chan: 'b, Idx: 'b> ReadExtent<'b, N> for ArrayView<'a, N, Chan, Idx>
+where
+    PointN<N>: IntegerPoint<N>,
+{
+    type Src = ArrayCopySrc<&'b Array<N, Chan, Idx>>;
+    type SrcIter = Once<(ExtentN<N>, Self::Src)>;
+
+    fn read_extent(&'b self, extent: &ExtentN<N>) -> Self::SrcIter {
+        once((extent.intersection(&self.extent), ArrayCopySrc(self.array)))
+    }
+}
+
+impl<'a, N, Chan, Idx, Src> WriteExtent<N, Src> for ArrayViewMut<'a, N, Chan, Idx>
+where
+    Array<N, Chan, Idx>: WriteExtent<N, Src>,
+    PointN<N>: IntegerPoint<N>,
+{
+    fn write_extent(&mut self, extent: &ExtentN<N>, src: Src) {
+        self.array
+            .write_extent(&extent.intersection(&self.extent), src)
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use crate::{copy_extent, prelude::*};
+
+    use building_blocks_core::prelude::*;
+
+    #[test]
+    fn view_uses_parent_strides() {
+        let extent = Extent3i::from_min_and_shape(Point3i::fill(-8), Point3i::fill(16));
+        let array = Array3x1::fill_with(extent, |p| p.x() + 100 * p.y() + 10_000 * p.z());
+        let window = Extent3i::from_min_and_shape(Point3i::fill(-2), PointN([3, 4, 5]));
+        let view = array.view(&window);
+
+        assert_eq!(view.layout_extent(), &extent);
+        let window_min_local = Local(window.minimum - extent.minimum);
+        assert_eq!(view.get(window_min_local), array.get(window.minimum));
+        assert_eq!(
+            view.get(view.stride_from_local_point(window_min_local)),
+            array.get(window.minimum)
+        );
+
+        let offsets = Local::localize_points_array(&Point3i::VON_NEUMANN_OFFSETS);
+        let mut view_strides = [Stride(0); 6];
+        let mut array_strides = [Stride(0); 6];
+        view.strides_from_local_points(&offsets, &mut view_strides);
+        array.strides_from_local_points(&offsets, &mut array_strides);
+        assert_eq!(view_strides, array_strides);
+
+        let mut num_points = 0;
+        view.for_each(&extent, |(p, s): (Point3i, Stride), value| {
+            assert!(window.contains(p));
+            assert_eq!(value, array.get(p));
+            assert_eq!(view.get(s), value);
+            num_points += 1;
+        });
+        assert_eq!(num_points, window.num_points());
+
+        let mut dst = Array3x1::fill(extent, 0);
+        copy_extent(&extent, &view, &mut dst);
+        dst.for_each(&extent, |p: Point3i, value| {
+            let expected = if window.contains(p) { array.get(p) } else { 0 };
+            assert_eq!(value, expected);
+        });
+    }
+
+    #[test]
+    fn mutable_view_only_writes_window() {
+        let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(8));
+        let mut array = Array3x1::fill(extent, 0);
+        let window = Extent3i::from_min_and_shape(Point3i::fill(2), Point3i::fill(3));
+
+        let mut view = array.view_mut(&window);
+        view.for_each_mut(&extent, |p: Point3i, value| *value = p.x());
+        *view.get_mut(Local(window.minimum - extent.minimum)) = -1;
+        view.fill_extent(&extent, 7);
+
+        array.for_each(&extent, |p: Point3i, value| {
+            let expected = if window.contains(p) { 7 } else { 0 };
+            assert_eq!(value, expected);
+        });
+    }
+}
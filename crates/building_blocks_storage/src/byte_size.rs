@@ -0,0 +1,48 @@
+//! Approximate memory usage of chunks, compressed or not, used for enforcing memory budgets.
+
+use crate::{Compressed, Compression};
+
+/// The approximate number of bytes of memory used by a value, including its heap allocations.
+///
+/// This doesn't need to be exact; it's used for keeping chunk storage under a memory budget.
+pub trait ByteSize {
+    fn byte_size(&self) -> usize;
+}
+
+impl<T> ByteSize for Vec<T> {
+    fn byte_size(&self) -> usize {
+        core::mem::size_of::<Self>() + self.len() * core::mem::size_of::<T>()
+    }
+}
+
+impl<A> ByteSize for Compressed<A>
+where
+    A: Compression,
+    A::CompressedData: ByteSize,
+{
+    fn byte_size(&self) -> usize {
+        self.compressed_data.byte_size()
+    }
+}
+
+macro_rules! impl_byte_size_for_tuple {
+    ( $( $var:ident : $t:ident ),+ ) => {
+        impl<$($t),+> ByteSize for ($($t,)+)
+        where
+            $($t: ByteSize),+
+        {
+            fn byte_size(&self) -> usize {
+                let ($($var,)+) = self;
+
+                0 $( + $var.byte_size() )+
+            }
+        }
+    };
+}
+
+impl_byte_size_for_tuple! { a: A }
+impl_byte_size_for_tuple! { a: A, b: B }
+impl_byte_size_for_tuple! { a: A, b: B, c: C }
+impl_byte_size_for_tuple! { a: A, b: B, c: C, d: D }
+impl_byte_size_for_tuple! { a: A, b: B, c: C, d: D, e: E }
+impl_byte_size_for_tuple! { a: A, b: B, c: C, d: D, e: E, f: F }
@@ -1,5 +1,9 @@
 mod local_cache;
 mod lru_cache;
+mod replacement_policy;
+mod shared_cache;
 
 pub use local_cache::*;
 pub use lru_cache::*;
+pub use replacement_policy::*;
+pub use shared_cache::*;
@@ -1,12 +1,15 @@
 use core::hash::{BuildHasher, Hash};
 use std::cell::UnsafeCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::pin::Pin;
 
 /// A cache with a very specific niche. When reading from shared, two-tier storage, if you miss the cache and need to fetch from
 /// the cold tier, then you also need a place to store the fetched data. Rather than doing interior mutation of the storage,
 /// which requires synchronization, the fetched data can be stored in a thread-local cache, the `LocalCache`.
 ///
+/// The cache can also remember which keys were accessed in the hot tier, so the storage can update its replacement policy
+/// when the cache is flushed.
+///
 /// SAFE: We guarantee in these APIs that all references returned are valid for the lifetime of the `LocalCache`, even as new
 /// values are added to the map. The invariants are:
 ///   1. Once a value is placed here, it will never get dropped or moved until calling `into_iter`.
@@ -15,6 +18,7 @@ use std::pin::Pin;
 #[derive(Default)]
 pub struct LocalCache<K, V, H> {
     store: UnsafeCell<HashMap<K, Pin<Box<V>>, H>>,
+    touched: UnsafeCell<HashSet<K, H>>,
 }
 
 impl<K, V, H> LocalCache<K, V, H>
@@ -25,6 +29,7 @@ where
     pub fn new() -> Self {
         LocalCache {
             store: UnsafeCell::new(HashMap::with_hasher(Default::default())),
+            touched: UnsafeCell::new(HashSet::with_hasher(Default::default())),
         }
     }
 
@@ -35,6 +40,22 @@ where
         mut_store.entry(key).or_insert_with(|| Box::pin(f()))
     }
 
+    /// Remember that `key` was accessed without fetching it, e.g. because it was already in the hot tier.
+    pub fn touch(&self, key: K) {
+        // SAFE: No references into the set are ever returned.
+        let touched = unsafe { &mut *self.touched.get() };
+
+        touched.insert(key);
+    }
+
+    /// Take the keys that were touched since the last call.
+    pub fn take_touched(&mut self) -> HashSet<K, H> {
+        std::mem::replace(
+            self.touched.get_mut(),
+            HashSet::with_hasher(Default::default()),
+        )
+    }
+
     // TODO: impl IntoIterator instead
     /// Consume self and iterate over all (key, value) pairs.
     pub fn flush_iter(self) -> impl Iterator<Item = (K, V)> {
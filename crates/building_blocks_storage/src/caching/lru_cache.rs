@@ -1,4 +1,4 @@
-use crate::SmallKeyBuildHasher;
+use crate::{ReplacementPolicy, SmallKeyBuildHasher};
 
 use core::hash::{BuildHasher, Hash};
 use std::collections::{hash_map, HashMap};
@@ -7,6 +7,10 @@ use std::collections::{hash_map, HashMap};
 ///
 /// For the purpose of fast, repeated random access, LRU order is only updated on insertion or by calling "touch_if_cached."
 ///
+/// Which element is evicted next is actually decided by the `ReplacementPolicy` `P`, which is the `LruPolicy` by default. Other
+/// policies like `ClockPolicy` and `LfuPolicy` can perform better for access patterns that touch many elements only once. All
+/// of the methods named after "LRU" refer to the element chosen by the policy.
+///
 /// Eviction does not happen inline; the user must explicitly call `evict_lru` to evict the LRU element. Thus the cache may grow
 /// unbounded unless evictions or explicit removals occur.
 ///
@@ -15,43 +19,49 @@ use std::collections::{hash_map, HashMap};
 /// `Some(CacheEntry::Evicted)`, they know that the data exists somewhere else. If they get `None`, then they don't have to look
 /// elsewhere; the data simply doesn't exist anywhere.
 #[derive(Clone, Debug)]
-pub struct LruCache<K, V, E, H> {
+pub struct LruCache<K, V, E, H, P = LruPolicy<K>> {
     store: HashMap<K, CacheEntry<(V, usize), E>, H>,
-    order: LruList<K>,
+    policy: P,
     num_evicted: usize,
 }
 
 /// An `LruCache` using a hashing algorithm that's optimal for small keys.
 pub type SmallKeyLruCache<K, V, E = ()> = LruCache<K, V, E, SmallKeyBuildHasher>;
 
-impl<K, V, E, H> Default for LruCache<K, V, E, H>
+/// An `LruCache` with replacement policy `P`, using a hashing algorithm that's optimal for small keys.
+pub type SmallKeyPolicyCache<K, V, E, P> = LruCache<K, V, E, SmallKeyBuildHasher, P>;
+
+impl<K, V, E, H, P> Default for LruCache<K, V, E, H, P>
 where
     H: Default,
     K: Hash + Eq,
+    P: ReplacementPolicy<K>,
 {
     fn default() -> Self {
         Self::with_hasher(Default::default())
     }
 }
 
-impl<K, V, E, H> LruCache<K, V, E, H>
+impl<K, V, E, H, P> LruCache<K, V, E, H, P>
 where
     K: Hash + Eq,
+    P: ReplacementPolicy<K>,
 {
-    pub fn with_hasher(hasher_builder: H) -> LruCache<K, V, E, H> {
+    pub fn with_hasher(hasher_builder: H) -> LruCache<K, V, E, H, P> {
         LruCache {
             store: HashMap::with_hasher(hasher_builder),
-            order: LruList::new(),
+            policy: P::default(),
             num_evicted: 0,
         }
     }
 }
 
-impl<K, V, E, H> LruCache<K, V, E, H>
+impl<K, V, E, H, P> LruCache<K, V, E, H, P>
 where
     K: Hash + Eq + Clone,
     E: Copy,
     H: BuildHasher,
+    P: ReplacementPolicy<K>,
 {
     /// Borrow the entry for `key`. This will not update the LRU order.
     #[inline]
@@ -72,16 +82,16 @@ where
     /// Inserts a `new_val` for `key`, returning the old entry if it exists. `key` becomes the most recently used.
     #[inline]
     pub fn insert(&mut self, key: K, new_val: V) -> Option<CacheEntry<V, E>> {
-        let Self { store, order, .. } = self;
+        let Self { store, policy, .. } = self;
         match store.entry(key.clone()) {
             hash_map::Entry::Occupied(occupied) => match occupied.into_mut() {
                 CacheEntry::Cached((old_val, i)) => {
-                    order.move_to_front(*i);
+                    policy.touch(*i);
 
                     Some(CacheEntry::Cached(std::mem::replace(old_val, new_val)))
                 }
                 x => {
-                    let new_i = order.push_front(Some(key));
+                    let new_i = policy.insert(key);
                     let old_entry = std::mem::replace(x, CacheEntry::Cached((new_val, new_i)));
                     self.num_evicted -= 1;
 
@@ -89,7 +99,7 @@ where
                 }
             },
             hash_map::Entry::Vacant(vacant) => {
-                let new_i = order.push_front(Some(key));
+                let new_i = policy.insert(key);
                 vacant.insert(CacheEntry::Cached((new_val, new_i)));
 
                 None
@@ -103,12 +113,12 @@ where
     /// updates the LRU order for the values that didn't need fetching (but were nonetheless desired).
     #[inline]
     pub fn touch_if_cached(&mut self, key: K) -> bool {
-        let Self { store, order, .. } = self;
+        let Self { store, policy, .. } = self;
 
         if let Some(entry) = store.get(&key) {
             match entry {
                 CacheEntry::Cached((_, i)) => {
-                    order.move_to_front(*i);
+                    policy.touch(*i);
 
                     true
                 }
@@ -127,13 +137,13 @@ where
         key: K,
         on_evicted: impl FnOnce(E) -> V,
     ) -> Option<&mut V> {
-        let Self { store, order, .. } = self;
+        let Self { store, policy, .. } = self;
         match store.entry(key.clone()) {
             hash_map::Entry::Occupied(occupied) => match occupied.into_mut() {
                 CacheEntry::Cached((val, _)) => Some(val),
                 x => {
                     let repop_val = on_evicted(x.unwrap_evicted());
-                    let new_i = order.push_front(Some(key));
+                    let new_i = policy.insert(key);
                     std::mem::swap(x, &mut CacheEntry::Cached((repop_val, new_i)));
                     self.num_evicted -= 1;
 
@@ -154,13 +164,13 @@ where
         on_evicted: impl FnOnce(E) -> V,
         on_missing: impl FnOnce() -> V,
     ) -> &mut V {
-        let Self { store, order, .. } = self;
+        let Self { store, policy, .. } = self;
         match store.entry(key.clone()) {
             hash_map::Entry::Occupied(occupied) => match occupied.into_mut() {
                 CacheEntry::Cached((val, _)) => val,
                 x => {
                     let repop_val = on_evicted(x.unwrap_evicted());
-                    let new_i = order.push_front(Some(key));
+                    let new_i = policy.insert(key);
                     std::mem::swap(x, &mut CacheEntry::Cached((repop_val, new_i)));
                     self.num_evicted -= 1;
 
@@ -169,7 +179,7 @@ where
             },
             hash_map::Entry::Vacant(vacant) => {
                 let new_val = on_missing();
-                let new_i = order.push_front(Some(key));
+                let new_i = policy.insert(key);
 
                 vacant
                     .insert(CacheEntry::Cached((new_val, new_i)))
@@ -184,7 +194,7 @@ where
     pub fn remove(&mut self, key: &K) -> Option<CacheEntry<V, E>> {
         self.store.remove(key).map(|entry| match entry {
             CacheEntry::Cached((val, i)) => {
-                self.order.remove(i);
+                self.policy.remove(i);
 
                 CacheEntry::Cached(val)
             }
@@ -206,7 +216,7 @@ where
             .insert(key, CacheEntry::Evicted(new_location))
             .map(|entry| match entry {
                 CacheEntry::Cached((val, i)) => {
-                    self.order.remove(i);
+                    self.policy.remove(i);
 
                     CacheEntry::Cached(val)
                 }
@@ -225,28 +235,37 @@ where
     /// Nothing happens if the cache is empty.
     #[inline]
     pub fn evict_lru(&mut self, new_location: E) -> Option<(K, V)> {
-        if self.len_cached() == 0 {
-            return None;
-        }
+        let key = self.policy.pop_victim()?;
+
+        Some(self.evict_victim(key, new_location))
+    }
 
-        let key = self.order.pop_back();
+    /// Like `evict_lru`, but never evicts `keep`, even if it's the least-recently used.
+    #[inline]
+    pub fn evict_lru_except(&mut self, keep: &K, new_location: E) -> Option<(K, V)> {
+        let key = match self.store.get(keep) {
+            Some(CacheEntry::Cached((_, i))) => self.policy.pop_victim_except(*i),
+            _ => self.policy.pop_victim(),
+        }?;
+
+        Some(self.evict_victim(key, new_location))
+    }
+
+    // Replaces the cached entry for `key`, which was just popped from the policy, with `new_location`.
+    fn evict_victim(&mut self, key: K, new_location: E) -> (K, V) {
         let entry = std::mem::replace(
             self.store.get_mut(&key).unwrap(),
             CacheEntry::Evicted(new_location),
         );
         self.num_evicted += 1;
 
-        Some((key, entry.unwrap_value()))
+        (key, entry.unwrap_value())
     }
 
     /// Removes the least-recently used value, leaving no trace.
     #[inline]
     pub fn remove_lru(&mut self) -> Option<(K, V)> {
-        if self.len_cached() == 0 {
-            return None;
-        }
-
-        let key = self.order.pop_back();
+        let key = self.policy.pop_victim()?;
         let val = self.store.remove(&key).unwrap().unwrap_value();
 
         Some((key, val))
@@ -256,7 +275,7 @@ where
     #[inline]
     pub fn clear(&mut self) {
         self.store.clear();
-        self.order.clear();
+        self.policy.clear();
         self.num_evicted = 0;
     }
 
@@ -293,7 +312,7 @@ where
     }
 }
 
-impl<K, V, E, H> IntoIterator for LruCache<K, V, E, H>
+impl<K, V, E, H, P> IntoIterator for LruCache<K, V, E, H, P>
 where
     E: Copy,
 {
@@ -422,6 +441,64 @@ where
     }
 }
 
+/// The least-recently-used replacement policy.
+#[derive(Clone, Debug)]
+pub struct LruPolicy<K> {
+    order: LruList<K>,
+}
+
+impl<K> Default for LruPolicy<K> {
+    fn default() -> Self {
+        Self {
+            order: LruList::new(),
+        }
+    }
+}
+
+impl<K> ReplacementPolicy<K> for LruPolicy<K> {
+    #[inline]
+    fn insert(&mut self, key: K) -> usize {
+        self.order.push_front(Some(key))
+    }
+
+    #[inline]
+    fn touch(&mut self, slot: usize) {
+        self.order.move_to_front(slot);
+    }
+
+    #[inline]
+    fn remove(&mut self, slot: usize) -> K {
+        self.order.remove(slot)
+    }
+
+    #[inline]
+    fn pop_victim(&mut self) -> Option<K> {
+        if self.order.is_empty() {
+            return None;
+        }
+
+        Some(self.order.pop_back())
+    }
+
+    #[inline]
+    fn pop_victim_except(&mut self, keep: usize) -> Option<K> {
+        let mut index = self.order.back();
+        if index == keep {
+            index = self.order.prev(index);
+        }
+        if index == LruList::<K>::OCCUPIED {
+            return None;
+        }
+
+        Some(self.order.remove(index))
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.order.clear();
+    }
+}
+
 /// Doubly-linked list using Vec as storage.
 #[derive(Clone, Debug)]
 struct LruList<T> {
@@ -502,10 +579,18 @@ impl<T> LruList<T> {
         self.entries[index].value.take().expect("invalid index")
     }
 
+    fn is_empty(&self) -> bool {
+        self.back() == Self::OCCUPIED
+    }
+
     fn back(&self) -> usize {
         self.entries[Self::OCCUPIED].prev
     }
 
+    fn prev(&self, index: usize) -> usize {
+        self.entries[index].prev
+    }
+
     fn pop_back(&mut self) -> T {
         let index = self.back();
 
@@ -0,0 +1,322 @@
+use std::collections::BTreeSet;
+
+/// Decides which cached entry of an `LruCache` should be evicted next.
+///
+/// The cache notifies the policy when keys become cached, when they're touched and when they stop being cached. Each cached key
+/// is identified by the slot returned from `insert`, which the cache stores alongside the value.
+///
+/// Implementations:
+///   - `LruPolicy`: evicts the least-recently-used entry (the default)
+///   - `ClockPolicy`: approximates LRU with a single reference bit per entry, giving touched entries a second chance
+///   - `LfuPolicy`: evicts the least-frequently-used entry, which protects frequently used entries from one-time scans
+pub trait ReplacementPolicy<K>: Default {
+    /// Starts tracking `key`, which just became cached. Returns the slot that identifies it.
+    fn insert(&mut self, key: K) -> usize;
+
+    /// Records an access to the cached key in `slot`.
+    fn touch(&mut self, slot: usize);
+
+    /// Stops tracking the key in `slot`, returning it.
+    fn remove(&mut self, slot: usize) -> K;
+
+    /// Chooses the next key to evict and stops tracking it. Returns `None` if no keys are tracked.
+    fn pop_victim(&mut self) -> Option<K>;
+
+    /// Like `pop_victim`, but never chooses the key in slot `keep`. Returns `None` if no other keys are tracked.
+    fn pop_victim_except(&mut self, keep: usize) -> Option<K>;
+
+    /// Stops tracking all keys.
+    fn clear(&mut self);
+}
+
+/// The CLOCK (a.k.a. second-chance) replacement policy.
+///
+/// Slots are arranged in a circle with a "hand" pointing at the next candidate for eviction. Touching an entry sets its
+/// reference bit. When choosing a victim, the hand clears the reference bits that it passes over and stops at the first entry
+/// without one. New entries start without a reference bit, so entries that are only touched once by a scan are evicted before
+/// entries that were used repeatedly.
+#[derive(Clone, Debug)]
+pub struct ClockPolicy<K> {
+    slots: Vec<Option<ClockSlot<K>>>,
+    free_slots: Vec<usize>,
+    hand: usize,
+    len: usize,
+}
+
+#[derive(Clone, Debug)]
+struct ClockSlot<K> {
+    key: K,
+    referenced: bool,
+}
+
+impl<K> Default for ClockPolicy<K> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            hand: 0,
+            len: 0,
+        }
+    }
+}
+
+impl<K> ClockPolicy<K> {
+    // Moves the hand to the next victim, skipping the slot `keep`, and removes it.
+    fn sweep(&mut self, keep: Option<usize>) -> Option<K> {
+        let num_candidates = match keep {
+            Some(keep) if self.slots[keep].is_some() => self.len - 1,
+            _ => self.len,
+        };
+        if num_candidates == 0 {
+            return None;
+        }
+
+        loop {
+            let i = self.hand;
+            self.hand = (self.hand + 1) % self.slots.len();
+            if Some(i) == keep {
+                continue;
+            }
+
+            if let Some(slot) = &mut self.slots[i] {
+                if slot.referenced {
+                    slot.referenced = false;
+                } else {
+                    return Some(self.remove(i));
+                }
+            }
+        }
+    }
+}
+
+impl<K> ReplacementPolicy<K> for ClockPolicy<K> {
+    fn insert(&mut self, key: K) -> usize {
+        let slot = ClockSlot {
+            key,
+            referenced: false,
+        };
+        self.len += 1;
+
+        if let Some(i) = self.free_slots.pop() {
+            self.slots[i] = Some(slot);
+
+            i
+        } else {
+            self.slots.push(Some(slot));
+
+            self.slots.len() - 1
+        }
+    }
+
+    fn touch(&mut self, slot: usize) {
+        self.slots[slot].as_mut().expect("invalid slot").referenced = true;
+    }
+
+    fn remove(&mut self, slot: usize) -> K {
+        let removed = self.slots[slot].take().expect("invalid slot");
+        self.free_slots.push(slot);
+        self.len -= 1;
+
+        removed.key
+    }
+
+    fn pop_victim(&mut self) -> Option<K> {
+        self.sweep(None)
+    }
+
+    fn pop_victim_except(&mut self, keep: usize) -> Option<K> {
+        self.sweep(Some(keep))
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// The least-frequently-used replacement policy.
+///
+/// Every entry counts how many times it has been touched since it was cached. The entry with the lowest count is evicted, and
+/// ties are broken by evicting the entry that was touched (or inserted) the longest time ago.
+#[derive(Clone, Debug)]
+pub struct LfuPolicy<K> {
+    slots: Vec<Option<LfuSlot<K>>>,
+    free_slots: Vec<usize>,
+    // Ordered by (count, last_tick, slot), so the first element is the victim.
+    order: BTreeSet<(u64, u64, usize)>,
+    tick: u64,
+}
+
+#[derive(Clone, Debug)]
+struct LfuSlot<K> {
+    key: K,
+    count: u64,
+    last_tick: u64,
+}
+
+impl<K> Default for LfuPolicy<K> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            order: BTreeSet::new(),
+            tick: 0,
+        }
+    }
+}
+
+impl<K> LfuPolicy<K> {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+
+        self.tick
+    }
+}
+
+impl<K> ReplacementPolicy<K> for LfuPolicy<K> {
+    fn insert(&mut self, key: K) -> usize {
+        let last_tick = self.next_tick();
+        let slot = LfuSlot {
+            key,
+            count: 0,
+            last_tick,
+        };
+
+        let i = if let Some(i) = self.free_slots.pop() {
+            self.slots[i] = Some(slot);
+
+            i
+        } else {
+            self.slots.push(Some(slot));
+
+            self.slots.len() - 1
+        };
+        self.order.insert((0, last_tick, i));
+
+        i
+    }
+
+    fn touch(&mut self, slot: usize) {
+        let tick = self.next_tick();
+        let entry = self.slots[slot].as_mut().expect("invalid slot");
+        self.order.remove(&(entry.count, entry.last_tick, slot));
+        entry.count += 1;
+        entry.last_tick = tick;
+        self.order.insert((entry.count, entry.last_tick, slot));
+    }
+
+    fn remove(&mut self, slot: usize) -> K {
+        let removed = self.slots[slot].take().expect("invalid slot");
+        self.order.remove(&(removed.count, removed.last_tick, slot));
+        self.free_slots.push(slot);
+
+        removed.key
+    }
+
+    fn pop_victim(&mut self) -> Option<K> {
+        let &(_, _, slot) = self.order.iter().next()?;
+
+        Some(self.remove(slot))
+    }
+
+    fn pop_victim_except(&mut self, keep: usize) -> Option<K> {
+        let &(_, _, slot) = self.order.iter().find(|&&(_, _, slot)| slot != keep)?;
+
+        Some(self.remove(slot))
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+// ████████╗███████╗███████╗████████╗███████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝██╔════╝
+//    ██║   █████╗  ███████╗   ██║   ███████╗
+//    ██║   ██╔══╝  ╚════██║   ██║   ╚════██║
+//    ██║   ███████╗███████║   ██║   ███████║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝   ╚══════╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{CacheEntry, LruPolicy, SmallKeyPolicyCache};
+
+    type ClockCache = SmallKeyPolicyCache<i32, i32, (), ClockPolicy<i32>>;
+    type LfuCache = SmallKeyPolicyCache<i32, i32, (), LfuPolicy<i32>>;
+
+    #[test]
+    fn clock_gives_touched_entries_a_second_chance() {
+        let mut cache = ClockCache::default();
+        for i in 0..4 {
+            cache.insert(i, i);
+        }
+        cache.touch_if_cached(0);
+        cache.touch_if_cached(2);
+
+        assert_eq!(cache.evict_lru(()), Some((1, 1)));
+        assert_eq!(cache.evict_lru(()), Some((3, 3)));
+        // The reference bits were cleared by the first pass of the hand.
+        assert_eq!(cache.evict_lru(()), Some((0, 0)));
+        assert_eq!(cache.evict_lru(()), Some((2, 2)));
+        assert_eq!(cache.evict_lru(()), None);
+        assert_eq!(cache.len_evicted(), 4);
+    }
+
+    #[test]
+    fn lfu_protects_frequently_used_entries_from_scans() {
+        let mut cache = LfuCache::default();
+        cache.insert(100, 0);
+        for _ in 0..3 {
+            cache.touch_if_cached(100);
+        }
+
+        // A scan that touches every other entry once.
+        for i in 0..4 {
+            cache.insert(i, i);
+            cache.touch_if_cached(i);
+        }
+
+        for i in 0..4 {
+            assert_eq!(cache.evict_lru(()), Some((i, i)));
+        }
+        assert_eq!(cache.evict_lru(()), Some((100, 0)));
+    }
+
+    #[test]
+    fn victims_skip_the_kept_key() {
+        fn check<P: ReplacementPolicy<i32>>() {
+            let mut cache = SmallKeyPolicyCache::<i32, i32, (), P>::default();
+            for i in 0..3 {
+                cache.insert(i, i);
+            }
+            cache.touch_if_cached(1);
+            cache.touch_if_cached(2);
+
+            // Every policy would choose 0 next.
+            assert_eq!(cache.evict_lru_except(&0, ()), Some((1, 1)));
+            assert_eq!(cache.evict_lru_except(&0, ()), Some((2, 2)));
+            assert_eq!(cache.evict_lru_except(&0, ()), None);
+            assert_eq!(cache.get(&0), Some(CacheEntry::Cached(&0)));
+            assert_eq!(cache.evict_lru(()), Some((0, 0)));
+        }
+
+        check::<LruPolicy<i32>>();
+        check::<ClockPolicy<i32>>();
+        check::<LfuPolicy<i32>>();
+    }
+
+    #[test]
+    fn removed_slots_are_reused() {
+        let mut cache = LfuCache::default();
+        cache.insert(1, 1);
+        cache.insert(2, 2);
+        cache.remove(&1);
+        cache.insert(3, 3);
+        cache.touch_if_cached(2);
+
+        assert_eq!(cache.remove_lru(), Some((3, 3)));
+        assert_eq!(cache.remove_lru(), Some((2, 2)));
+        assert_eq!(cache.remove_lru(), None);
+    }
+}
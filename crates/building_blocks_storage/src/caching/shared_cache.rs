@@ -0,0 +1,148 @@
+use core::hash::{BuildHasher, Hash};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// The thread-safe counterpart of `LocalCache`. When many threads read from shared, two-tier storage at once, they can share
+/// one `SharedCache` for the data fetched from the cold tier, so each value is only fetched once, no matter how many threads
+/// need it.
+///
+/// Every key gets its own once-initialized slot. The map of slots is only locked while finding the slot, so fetching
+/// different keys happens in parallel. If multiple threads fetch the same key at the same time, only one of them calls its
+/// closure, and the others block until the value is ready.
+///
+/// Like the `LocalCache`, it can also remember which keys were accessed in the hot tier.
+///
+/// SAFE: We guarantee in these APIs that all references returned are valid for the lifetime of the `SharedCache`, even as new
+/// values are added to the map. The invariants are:
+///   1. Once a slot is placed here, it will never get dropped or moved until calling `flush_iter`.
+///   2. The slots are placed into `Arc`s so the memory address is guaranteed stable when the map is resized.
+///   3. Returned references must be dropped before calling `flush_iter`.
+pub struct SharedCache<K, V, H> {
+    store: Mutex<HashMap<K, Arc<OnceLock<V>>, H>>,
+    touched: Mutex<HashSet<K, H>>,
+}
+
+impl<K, V, H> Default for SharedCache<K, V, H>
+where
+    K: Eq + Hash,
+    H: Default + BuildHasher,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, H> SharedCache<K, V, H>
+where
+    K: Eq + Hash,
+    H: Default + BuildHasher,
+{
+    pub fn new() -> Self {
+        SharedCache {
+            store: Mutex::new(HashMap::with_hasher(Default::default())),
+            touched: Mutex::new(HashSet::with_hasher(Default::default())),
+        }
+    }
+
+    /// Fetch the value for `key`. If it's not here, call `f` to fetch it.
+    pub fn get_or_insert_with(&self, key: K, f: impl FnOnce() -> V) -> &V {
+        let slot = self
+            .store
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_insert_with(|| Arc::new(OnceLock::new()))
+            .clone();
+
+        // SAFE: The map keeps its own `Arc` to the slot until `flush_iter` consumes `self`.
+        let slot = unsafe { &*Arc::as_ptr(&slot) };
+
+        slot.get_or_init(f)
+    }
+
+    /// Remember that `key` was accessed without fetching it, e.g. because it was already in the hot tier.
+    pub fn touch(&self, key: K) {
+        self.touched.lock().unwrap().insert(key);
+    }
+
+    /// Take the keys that were touched since the last call.
+    pub fn take_touched(&mut self) -> HashSet<K, H> {
+        std::mem::replace(
+            self.touched.get_mut().unwrap(),
+            HashSet::with_hasher(Default::default()),
+        )
+    }
+
+    /// The number of values that have been fetched.
+    pub fn len(&self) -> usize {
+        self.store
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|slot| slot.get().is_some())
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Consume self and iterate over all (key, value) pairs.
+    pub fn flush_iter(self) -> impl Iterator<Item = (K, V)> {
+        self.store
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .filter_map(|(k, slot)| {
+                // No references to the slots remain, since they were all borrowed from `self`.
+                let value = Arc::try_unwrap(slot).ok().unwrap().into_inner()?;
+
+                Some((k, value))
+            })
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::SmallKeyBuildHasher;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn each_key_is_fetched_once_across_threads() {
+        let cache = SharedCache::<i32, Vec<i32>, SmallKeyBuildHasher>::new();
+        let num_fetches = AtomicUsize::new(0);
+
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for key in 0..100 {
+                        let value = cache.get_or_insert_with(key, || {
+                            num_fetches.fetch_add(1, Ordering::Relaxed);
+
+                            vec![key; 10]
+                        });
+                        assert_eq!(value, &vec![key; 10]);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(num_fetches.load(Ordering::Relaxed), 100);
+        assert_eq!(cache.len(), 100);
+
+        let mut flushed: Vec<_> = cache.flush_iter().collect();
+        flushed.sort();
+        assert_eq!(flushed.len(), 100);
+        assert_eq!(flushed[42], (42, vec![42; 10]));
+    }
+}
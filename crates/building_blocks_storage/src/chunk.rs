@@ -1,6 +1,12 @@
+mod header;
+mod region_file;
 mod serialization;
+mod stream;
 
+pub use header::*;
+pub use region_file::*;
 pub use serialization::*;
+pub use stream::*;
 
 use crate::Array;
 
@@ -17,7 +23,7 @@ pub trait Chunk {
     fn array_mut(&mut self) -> &mut Self::Array;
 }
 
-impl<N, Chan> Chunk for Array<N, Chan> {
+impl<N, Chan, Idx> Chunk for Array<N, Chan, Idx> {
     type Array = Self;
 
     #[inline]
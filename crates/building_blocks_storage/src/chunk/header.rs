@@ -0,0 +1,371 @@
+//! A versioned, self-describing header for files of serialized chunks.
+//!
+//! A bincode blob has no way to tell whether it was written with the same chunk type that is reading it, so changing a voxel
+//! type would silently corrupt old saves. The `ChunkFileHeader` records everything needed to decode the chunks that follow it:
+//! the format version, the dimensionality, the chunk shape, the type of each channel, the array layout and the compression
+//! codec. Channel types and codecs are identified by `StableTypeName::STABLE_TYPE_NAME` and `BytesCompression::CODEC_ID`
+//! respectively, so the header doesn't depend on the compiler version or where the types are defined.
+//!
+//! The chunk keys in a file only make sense for the chunk shape they were written with, so a file with a different
+//! dimensionality or chunk shape is always rejected.
+//!
+//! When the header doesn't match the current chunk layout, chunks are passed through a `ChunkMigration`, which can deserialize
+//! the old layout and convert it to the new one. Chunks compressed with a different codec are first decompressed by the
+//! migration, which supports all of the codecs in this crate by default.
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::{prelude::*, deserialize_old_chunk, BincodeCompression, ChunkFileHeader, ChunkStreamReader, ChunkStreamWriter};
+//! # use std::io;
+//! let builder = ChunkMapBuilder3x1::new(Point3i::fill(16), 0u8);
+//! let mut old_map = builder.clone().build_with_hash_map_storage();
+//! old_map.fill_extent(&Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(32)), 1);
+//!
+//! let compression = BincodeCompression::new(Lz4 { level: 10 });
+//! let mut writer = ChunkStreamWriter::new(Vec::new(), builder.chunk_shape, compression).unwrap();
+//! writer.write_chunks(old_map.take_storage()).unwrap();
+//! let old_save = writer.finish().unwrap();
+//!
+//! // Later, the voxel type was changed from u8 to u16.
+//! let migrate = |_header: &ChunkFileHeader, serialized_chunk: &[u8]| -> io::Result<Array3x1<u16>> {
+//!     let old_chunk: Array3x1<u8> = deserialize_old_chunk(serialized_chunk)?;
+//!     let mut new_chunk = Array3x1::fill(*old_chunk.extent(), 0u16);
+//!     new_chunk.for_each_mut(old_chunk.extent(), |p: Point3i, value| *value = 1000 * old_chunk.get(p) as u16);
+//!
+//!     Ok(new_chunk)
+//! };
+//!
+//! // Without a migration, the old save is rejected.
+//! assert!(ChunkStreamReader::<_, [i32; 3], Array3x1<u16>, Lz4>::new(old_save.as_slice(), builder.chunk_shape).is_err());
+//!
+//! let mut reader = ChunkStreamReader::<_, [i32; 3], Array3x1<u16>, Lz4, _>::with_migration(
+//!     old_save.as_slice(), builder.chunk_shape, migrate
+//! ).unwrap();
+//! assert_eq!(reader.header().channel_types, vec!["u8".to_string()]);
+//! let mut new_map = ChunkMapBuilder3x1::new(Point3i::fill(16), 0u16).build_with_hash_map_storage();
+//! reader.fill_storage(new_map.storage_mut()).unwrap();
+//! assert_eq!(new_map.get(Point3i::fill(1)), 1000);
+//! ```
+
+use crate::{BytesCompression, ChannelTypeNames, NoCompression};
+
+use building_blocks_core::prelude::*;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io::{self, Read, Write};
+
+/// The magic bytes at the start of every chunk file.
+pub const CHUNK_FILE_MAGIC: [u8; 4] = *b"BBCF";
+
+/// The current version of the chunk file format. Files with an older version are loaded through a `ChunkMigration`.
+pub const CHUNK_FILE_VERSION: u32 = 1;
+
+/// Describes the chunks that follow it in a file or stream.
+///
+/// It's serialized as the `CHUNK_FILE_MAGIC`, then the little-endian `u32` version, then the rest of the fields with `bincode`.
+/// Only the magic and version are guaranteed to keep this layout in future versions.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ChunkFileHeader {
+    #[serde(skip)]
+    pub version: u32,
+    /// The number of dimensions of the chunks.
+    pub dimensions: u32,
+    /// The shape of each chunk, one component per dimension.
+    pub chunk_shape: Vec<i32>,
+    /// The names of the values in each channel of a chunk, in order, as given by `ChannelTypeNames`.
+    pub channel_types: Vec<String>,
+    /// The `ArrayIndexer::LAYOUT_NAME` of each chunk, if the chunks are arrays.
+    pub array_layout: Option<String>,
+    /// The `CODEC_ID` of the `BytesCompression` applied to each chunk.
+    pub codec: String,
+}
+
+impl ChunkFileHeader {
+    /// Creates a header at the current version for chunks of type `Ch` and shape `chunk_shape`, compressed with `B`.
+    pub fn new<N, Ch, B>(chunk_shape: PointN<N>) -> Self
+    where
+        PointN<N>: IntegerPoint<N>,
+        Ch: ChannelTypeNames,
+        B: BytesCompression,
+    {
+        let dimensions = PointN::<N>::basis().len();
+
+        Self {
+            version: CHUNK_FILE_VERSION,
+            dimensions: dimensions as u32,
+            chunk_shape: (0..dimensions).map(|i| chunk_shape.at(i)).collect(),
+            channel_types: Ch::channel_type_names(),
+            array_layout: Ch::array_layout().map(String::from),
+            codec: B::CODEC_ID.to_string(),
+        }
+    }
+
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(&CHUNK_FILE_MAGIC)?;
+        writer.write_all(&self.version.to_le_bytes())?;
+
+        bincode::serialize_into(writer, self).map_err(|e| bincode_to_io_error(*e))
+    }
+
+    /// Reads a header. Returns an `InvalidData` error if the magic bytes are missing or the file is from a newer version.
+    pub fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != CHUNK_FILE_MAGIC {
+            return Err(invalid_data("Missing chunk file magic bytes"));
+        }
+
+        let mut version = [0; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version > CHUNK_FILE_VERSION {
+            return Err(invalid_data(format!(
+                "Chunk file version {} is newer than the supported version {}",
+                version, CHUNK_FILE_VERSION
+            )));
+        }
+
+        let mut header: Self =
+            bincode::deserialize_from(reader).map_err(|e| bincode_to_io_error(*e))?;
+        header.version = version;
+
+        Ok(header)
+    }
+
+    /// Returns an `InvalidData` error if the chunks can't be decoded as `N`-dimensional chunks of `chunk_shape`, even with a
+    /// migration.
+    pub fn check_compatible<N>(&self, chunk_shape: PointN<N>) -> io::Result<()>
+    where
+        PointN<N>: IntegerPoint<N>,
+    {
+        let dimensions = PointN::<N>::basis().len() as u32;
+        if self.dimensions != dimensions {
+            return Err(invalid_data(format!(
+                "Expected {}-dimensional chunks, but the file has {} dimensions",
+                dimensions, self.dimensions
+            )));
+        }
+        let chunk_shape: Vec<i32> = (0..dimensions as usize)
+            .map(|i| chunk_shape.at(i))
+            .collect();
+        if self.chunk_shape != chunk_shape {
+            return Err(invalid_data(format!(
+                "Expected chunk shape {:?}, but the file has {:?}",
+                chunk_shape, self.chunk_shape
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` iff chunks described by this header were compressed with `B`.
+    pub fn is_current_codec<B>(&self) -> bool
+    where
+        B: BytesCompression,
+    {
+        self.codec == B::CODEC_ID
+    }
+
+    /// Returns `true` iff chunks described by this header can be deserialized directly as `Ch`, without a migration.
+    pub fn is_current_layout<Ch>(&self) -> bool
+    where
+        Ch: ChannelTypeNames,
+    {
+        self.version == CHUNK_FILE_VERSION
+            && self.channel_types == Ch::channel_type_names()
+            && self.array_layout.as_deref() == Ch::array_layout()
+    }
+
+    /// The chunk shape as a point, if the file has the same dimensionality as `N`.
+    pub fn chunk_shape_point<N>(&self) -> Option<PointN<N>>
+    where
+        PointN<N>: IntegerPoint<N>,
+    {
+        let basis = PointN::<N>::basis();
+        if self.chunk_shape.len() != basis.len() {
+            return None;
+        }
+
+        Some(
+            basis
+                .into_iter()
+                .zip(self.chunk_shape.iter())
+                .fold(PointN::ZERO, |shape, (axis, &c)| shape + axis * c),
+        )
+    }
+}
+
+/// Converts chunks with an old layout or codec to the current chunk type `Ch` while they are loaded.
+///
+/// Any `FnMut(&ChunkFileHeader, &[u8]) -> io::Result<Ch>` is a `ChunkMigration`.
+pub trait ChunkMigration<Ch> {
+    /// Returns the current version of a chunk described by `header`. `serialized_chunk` is the decompressed `bincode`
+    /// representation of the old chunk, which can be read with `deserialize_old_chunk`.
+    fn migrate(&mut self, header: &ChunkFileHeader, serialized_chunk: &[u8]) -> io::Result<Ch>;
+
+    /// Decompresses a chunk that was compressed with `header.codec`, which is not the current codec. The result is passed to
+    /// `migrate` if the chunk also has an old layout.
+    ///
+    /// By default, this supports every `BytesCompression` in this crate that is enabled by a feature. Override it to support
+    /// other codecs.
+    fn decompress_old_chunk(
+        &mut self,
+        header: &ChunkFileHeader,
+        compressed_bytes: &[u8],
+    ) -> io::Result<Vec<u8>> {
+        decompress_with_builtin_codec(&header.codec, compressed_bytes)
+    }
+}
+
+impl<Ch, F> ChunkMigration<Ch> for F
+where
+    F: FnMut(&ChunkFileHeader, &[u8]) -> io::Result<Ch>,
+{
+    fn migrate(&mut self, header: &ChunkFileHeader, serialized_chunk: &[u8]) -> io::Result<Ch> {
+        (self)(header, serialized_chunk)
+    }
+}
+
+/// A `ChunkMigration` that fails for every chunk. This is used when only the current layout is supported.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoMigration;
+
+impl<Ch> ChunkMigration<Ch> for NoMigration {
+    fn migrate(&mut self, header: &ChunkFileHeader, _serialized_chunk: &[u8]) -> io::Result<Ch> {
+        Err(invalid_data(format!(
+            "No migration for chunk file version {} with channel types {:?} and array layout {:?}",
+            header.version, header.channel_types, header.array_layout
+        )))
+    }
+}
+
+/// Deserializes a chunk that was passed to a `ChunkMigration`.
+pub fn deserialize_old_chunk<T>(serialized_chunk: &[u8]) -> io::Result<T>
+where
+    T: DeserializeOwned,
+{
+    bincode::deserialize(serialized_chunk).map_err(|e| bincode_to_io_error(*e))
+}
+
+/// Decompresses a chunk that was compressed with `BincodeCompression<_, B>` or an old codec, then deserializes it, using
+/// `migration` for anything that doesn't match the current chunk layout or codec.
+pub(crate) fn load_old_chunk<Ch, B, M>(
+    header: &ChunkFileHeader,
+    compressed_bytes: &[u8],
+    migration: &mut M,
+) -> io::Result<Ch>
+where
+    Ch: ChannelTypeNames + DeserializeOwned,
+    B: BytesCompression,
+    M: ChunkMigration<Ch>,
+{
+    let serialized_chunk = if header.is_current_codec::<B>() {
+        let mut serialized_chunk = Vec::new();
+        B::decompress_bytes(compressed_bytes, &mut serialized_chunk);
+
+        serialized_chunk
+    } else {
+        migration.decompress_old_chunk(header, compressed_bytes)?
+    };
+
+    if header.is_current_layout::<Ch>() {
+        deserialize_old_chunk(&serialized_chunk)
+    } else {
+        migration.migrate(header, &serialized_chunk)
+    }
+}
+
+fn decompress_with_builtin_codec(codec: &str, compressed_bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match codec {
+        NoCompression::CODEC_ID => NoCompression::decompress_bytes(compressed_bytes, &mut bytes),
+        #[cfg(feature = "lz4")]
+        crate::Lz4::CODEC_ID => crate::Lz4::decompress_bytes(compressed_bytes, &mut bytes),
+        #[cfg(feature = "snap")]
+        crate::Snappy::CODEC_ID => crate::Snappy::decompress_bytes(compressed_bytes, &mut bytes),
+        #[cfg(feature = "zstd")]
+        <crate::Zstd>::CODEC_ID => {
+            <crate::Zstd>::try_decompress_bytes(compressed_bytes, &mut bytes)?
+        }
+        _ => {
+            return Err(invalid_data(format!(
+                "No migration for chunks compressed with codec {}",
+                codec
+            )))
+        }
+    }
+
+    Ok(bytes)
+}
+
+pub(crate) fn bincode_to_io_error(error: bincode::ErrorKind) -> io::Error {
+    match error {
+        bincode::ErrorKind::Io(error) => error,
+        other => invalid_data(other),
+    }
+}
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{Array, Array3x1, Array3x2, Channel, Morton};
+
+    #[test]
+    fn header_round_trip() {
+        let header =
+            ChunkFileHeader::new::<[i32; 3], Array3x2<u8, f32>, NoCompression>(PointN([16, 32, 8]));
+        assert_eq!(header.dimensions, 3);
+        assert_eq!(
+            header.channel_types,
+            vec!["u8".to_string(), "f32".to_string()]
+        );
+
+        let mut bytes = Vec::new();
+        header.write_to(&mut bytes).unwrap();
+        let read_header = ChunkFileHeader::read_from(bytes.as_slice()).unwrap();
+        assert_eq!(read_header, header);
+        assert_eq!(
+            read_header.chunk_shape_point::<[i32; 3]>(),
+            Some(PointN([16, 32, 8]))
+        );
+        assert!(read_header.is_current_layout::<Array3x2<u8, f32>>());
+        assert!(!read_header.is_current_layout::<Array3x1<u8>>());
+        assert_eq!(read_header.array_layout.as_deref(), Some("row_major"));
+        assert!(!read_header
+            .is_current_layout::<Array<[i32; 3], (Channel<u8>, Channel<f32>), Morton>>());
+        assert!(read_header.is_current_codec::<NoCompression>());
+        assert!(read_header
+            .check_compatible::<[i32; 3]>(PointN([16, 32, 8]))
+            .is_ok());
+        assert!(read_header
+            .check_compatible::<[i32; 3]>(PointN([16; 3]))
+            .is_err());
+        assert!(read_header
+            .check_compatible::<[i32; 2]>(PointN([16, 32]))
+            .is_err());
+    }
+
+    #[test]
+    fn reject_newer_version() {
+        let mut header =
+            ChunkFileHeader::new::<[i32; 3], Array3x1<u8>, NoCompression>(PointN([16; 3]));
+        header.version = CHUNK_FILE_VERSION + 1;
+
+        let mut bytes = Vec::new();
+        header.write_to(&mut bytes).unwrap();
+        let error = ChunkFileHeader::read_from(bytes.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,535 @@
+//! A region-file format for saving chunks to disk with random access.
+//!
+//! Unlike `SerializableChunks`, which must (de)serialize an entire map at once, a `RegionFile` stores a fixed-size region of
+//! chunks in a single file, much like Minecraft's Anvil format. This makes it possible to read or rewrite a single chunk without
+//! touching the rest of the world.
+//!
+//! The file is divided into sectors of `REGION_SECTOR_SIZE` bytes. The first sectors hold the header, which contains a table
+//! with one slot per chunk in the region. Each slot records the range of sectors holding that chunk's compressed bytes. When a
+//! chunk is rewritten and no longer fits in its sectors, it's moved to the first free range of sectors that fits, which
+//! eventually leaves holes in the file. `RegionFile::defragment` moves all chunks to the front of the file and truncates it.
+//!
+//! The header layout (all integers are little-endian):
+//!
+//! ```text
+//! | magic: b"BBRF" | info length: u32 | info: bincode RegionInfo<N> | slots: [first sector: u32, num sectors: u32, length: u32] |
+//! ```
+//!
+//! A world is split into regions by treating each region as a "chunk of chunks", so the region containing any chunk can be found
+//! with `RegionInfo::containing_chunk`.
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::{prelude::*, BincodeCompression, RegionInfo};
+//! # let path = std::env::temp_dir().join(format!("bb_region_file_doc_{}", std::process::id()));
+//! let builder = ChunkMapBuilder3x1::new(Point3i::fill(16), 0);
+//! let mut map = builder.clone().build_with_hash_map_storage();
+//! map.fill_extent(&Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(32)), 1);
+//!
+//! let info = RegionInfo::containing_chunk(Point3i::fill(16), Point3i::fill(8), Point3i::ZERO);
+//! let compression = BincodeCompression::new(Lz4 { level: 10 });
+//! let mut region = RegionFile::create(&path, info, compression).unwrap();
+//! for (&key, chunk) in map.storage().iter() {
+//!     region.write_chunk(key, chunk).unwrap();
+//! }
+//!
+//! // Later, load just one chunk, or the whole region.
+//! let mut region = RegionFile::<[i32; 3], Array3x1<i32>, _>::open(&path, compression).unwrap();
+//! assert!(region.read_chunk(Point3i::fill(16)).unwrap().is_some());
+//!
+//! let mut loaded_map = builder.build_with_hash_map_storage();
+//! region.load_into_storage(loaded_map.storage_mut()).unwrap();
+//! assert_eq!(loaded_map.get(Point3i::fill(31)), 1);
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+
+use crate::{
+    ArrayIndexer, BincodeCompression, BytesCompression, ChunkIndexer, ChunkWriteStorage,
+    Compression, Local,
+};
+
+use building_blocks_core::prelude::*;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// The unit of allocation within a `RegionFile`.
+pub const REGION_SECTOR_SIZE: u64 = 4096;
+
+const REGION_FILE_MAGIC: &[u8; 4] = b"BBRF";
+const SLOT_SIZE: u64 = 12;
+
+/// Describes which chunks are stored in a `RegionFile`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RegionInfo<N> {
+    /// The minimum point of the region, which is also the key of its minimum chunk.
+    pub region_key: PointN<N>,
+    pub chunk_shape: PointN<N>,
+    /// The number of chunks along each axis of the region.
+    pub chunks_per_region: PointN<N>,
+}
+
+impl<N> RegionInfo<N>
+where
+    PointN<N>: IntegerPoint<N>,
+{
+    /// Returns the region with `chunks_per_region` chunks of `chunk_shape` that contains the chunk at `chunk_key`.
+    pub fn containing_chunk(
+        chunk_shape: PointN<N>,
+        chunks_per_region: PointN<N>,
+        chunk_key: PointN<N>,
+    ) -> Self {
+        let region_indexer = ChunkIndexer::new(chunk_shape * chunks_per_region);
+
+        Self {
+            region_key: region_indexer.chunk_key_containing_point(chunk_key),
+            chunk_shape,
+            chunks_per_region,
+        }
+    }
+
+    /// The extent of all points covered by this region.
+    pub fn region_extent(&self) -> ExtentN<N> {
+        ExtentN::from_min_and_shape(self.region_key, self.chunk_shape * self.chunks_per_region)
+    }
+
+    pub fn num_chunk_slots(&self) -> usize {
+        ExtentN::from_min_and_shape(PointN::ZERO, self.chunks_per_region).num_points()
+    }
+
+    /// Returns `true` iff `chunk_key` is the key of a chunk inside of this region.
+    pub fn contains_chunk_key(&self, chunk_key: PointN<N>) -> bool {
+        self.region_extent().contains(chunk_key)
+            && ChunkIndexer::new(self.chunk_shape).chunk_key_is_valid(chunk_key)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct ChunkSlot {
+    first_sector: u32,
+    num_sectors: u32,
+    len: u32,
+}
+
+impl ChunkSlot {
+    fn is_occupied(&self) -> bool {
+        self.num_sectors > 0
+    }
+
+    fn sector_range(&self) -> std::ops::Range<usize> {
+        self.first_sector as usize..(self.first_sector + self.num_sectors) as usize
+    }
+
+    fn to_bytes(self) -> [u8; SLOT_SIZE as usize] {
+        let mut bytes = [0; SLOT_SIZE as usize];
+        bytes[0..4].copy_from_slice(&self.first_sector.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.num_sectors.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.len.to_le_bytes());
+
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let u32_at = |i: usize| {
+            let mut word = [0; 4];
+            word.copy_from_slice(&bytes[i..i + 4]);
+            u32::from_le_bytes(word)
+        };
+
+        Self {
+            first_sector: u32_at(0),
+            num_sectors: u32_at(4),
+            len: u32_at(8),
+        }
+    }
+}
+
+/// A file containing all of the chunks for a single region, supporting random access reads and writes of individual chunks. Each
+/// chunk is serialized with `bincode`, then compressed using some `BytesCompression`.
+pub struct RegionFile<N, Ch, B> {
+    file: File,
+    info: RegionInfo<N>,
+    compression: BincodeCompression<Ch, B>,
+    slots: Vec<ChunkSlot>,
+    slot_table_offset: u64,
+    sector_is_used: Vec<bool>,
+}
+
+impl<N, Ch, B> RegionFile<N, Ch, B>
+where
+    N: ArrayIndexer<N> + DeserializeOwned + Serialize,
+    PointN<N>: IntegerPoint<N>,
+    Ch: DeserializeOwned + Serialize,
+    B: BytesCompression,
+{
+    /// Creates a new, empty region file at `path`. If the file already exists, it will be truncated.
+    pub fn create(
+        path: impl AsRef<Path>,
+        info: RegionInfo<N>,
+        compression: BincodeCompression<Ch, B>,
+    ) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        let info_bytes = bincode::serialize(&info).expect("Failed to serialize region info");
+        let slots = vec![ChunkSlot::default(); info.num_chunk_slots()];
+        let slot_table_offset = 8 + info_bytes.len() as u64;
+
+        let mut header =
+            Vec::with_capacity((slot_table_offset + SLOT_SIZE * slots.len() as u64) as usize);
+        header.extend_from_slice(REGION_FILE_MAGIC);
+        header.extend_from_slice(&(info_bytes.len() as u32).to_le_bytes());
+        header.extend_from_slice(&info_bytes);
+        for slot in slots.iter() {
+            header.extend_from_slice(&slot.to_bytes());
+        }
+        let header_sectors = sectors_for_len(header.len() as u64);
+        header.resize(header_sectors * REGION_SECTOR_SIZE as usize, 0);
+        file.write_all(&header)?;
+
+        Ok(Self {
+            file,
+            info,
+            compression,
+            slots,
+            slot_table_offset,
+            sector_is_used: vec![true; header_sectors],
+        })
+    }
+
+    /// Opens an existing region file at `path`.
+    pub fn open(
+        path: impl AsRef<Path>,
+        compression: BincodeCompression<Ch, B>,
+    ) -> io::Result<Self> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        let mut prefix = [0; 8];
+        file.read_exact(&mut prefix)?;
+        if &prefix[0..4] != REGION_FILE_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a region file",
+            ));
+        }
+        let mut info_len = [0; 4];
+        info_len.copy_from_slice(&prefix[4..8]);
+        let mut info_bytes = vec![0; u32::from_le_bytes(info_len) as usize];
+        file.read_exact(&mut info_bytes)?;
+        let info: RegionInfo<N> = bincode::deserialize(&info_bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let num_slots = info.num_chunk_slots();
+        let mut slot_bytes = vec![0; num_slots * SLOT_SIZE as usize];
+        file.read_exact(&mut slot_bytes)?;
+        let slots: Vec<_> = slot_bytes
+            .chunks_exact(SLOT_SIZE as usize)
+            .map(ChunkSlot::from_bytes)
+            .collect();
+
+        let slot_table_offset = 8 + info_bytes.len() as u64;
+        let header_sectors = sectors_for_len(slot_table_offset + SLOT_SIZE * num_slots as u64);
+        let mut sector_is_used = vec![true; header_sectors];
+        // Free sectors at the end of the file aren't referenced by any slot.
+        let file_sectors = sectors_for_len(file.metadata()?.len());
+        sector_is_used.resize(file_sectors.max(header_sectors), false);
+        for slot in slots.iter().filter(|s| s.is_occupied()) {
+            mark_sectors(&mut sector_is_used, slot.sector_range(), true);
+        }
+
+        Ok(Self {
+            file,
+            info,
+            compression,
+            slots,
+            slot_table_offset,
+            sector_is_used,
+        })
+    }
+
+    pub fn info(&self) -> &RegionInfo<N> {
+        &self.info
+    }
+
+    /// Returns `true` iff there is a chunk stored at `key`.
+    pub fn contains_chunk(&self, key: PointN<N>) -> bool {
+        self.slots[self.slot_index(key)].is_occupied()
+    }
+
+    /// The keys of all chunks stored in this region.
+    pub fn chunk_keys(&self) -> impl Iterator<Item = PointN<N>> + '_ {
+        ChunkIndexer::new(self.info.chunk_shape)
+            .chunk_keys_for_extent(&self.info.region_extent())
+            .filter(move |&key| self.contains_chunk(key))
+    }
+
+    /// The number of unused sectors between the header and the end of the file. These can be reclaimed with `defragment`.
+    pub fn num_free_sectors(&self) -> usize {
+        self.sector_is_used.iter().filter(|used| !**used).count()
+    }
+
+    /// The number of sectors in the file, including the header.
+    pub fn num_sectors(&self) -> usize {
+        self.sector_is_used.len()
+    }
+
+    /// Reads and decompresses the chunk at `key`, if it exists.
+    pub fn read_chunk(&mut self, key: PointN<N>) -> io::Result<Option<Ch>> {
+        let slot = self.slots[self.slot_index(key)];
+        if !slot.is_occupied() {
+            return Ok(None);
+        }
+
+        let bytes = self.read_slot_bytes(slot)?;
+
+        Ok(Some(BincodeCompression::<Ch, B>::decompress(&bytes)))
+    }
+
+    /// Compresses `chunk` and writes it at `key`, replacing any chunk that was there before. The chunk is written in place if it
+    /// fits in the sectors of the old chunk. Otherwise it's moved to the first range of free sectors that fits.
+    pub fn write_chunk(&mut self, key: PointN<N>, chunk: &Ch) -> io::Result<()> {
+        let slot_index = self.slot_index(key);
+        let bytes = self.compression.compress(chunk).take();
+        let num_sectors = sectors_for_len(bytes.len() as u64);
+
+        let old_slot = self.slots[slot_index];
+        mark_sectors(&mut self.sector_is_used, old_slot.sector_range(), false);
+        let first_sector = if num_sectors <= old_slot.num_sectors as usize {
+            old_slot.first_sector as usize
+        } else {
+            self.find_free_sectors(num_sectors)
+        };
+
+        let new_slot = ChunkSlot {
+            first_sector: first_sector as u32,
+            num_sectors: num_sectors as u32,
+            len: bytes.len() as u32,
+        };
+        mark_sectors(&mut self.sector_is_used, new_slot.sector_range(), true);
+        self.write_sectors(first_sector, &bytes)?;
+        self.write_slot(slot_index, new_slot)
+    }
+
+    /// Removes the chunk at `key`. Returns `true` iff there was a chunk to remove.
+    ///
+    /// The freed sectors are not reclaimed until they are reused by another chunk or the file is defragmented.
+    pub fn delete_chunk(&mut self, key: PointN<N>) -> io::Result<bool> {
+        let slot_index = self.slot_index(key);
+        let old_slot = self.slots[slot_index];
+        if !old_slot.is_occupied() {
+            return Ok(false);
+        }
+
+        mark_sectors(&mut self.sector_is_used, old_slot.sector_range(), false);
+        self.write_slot(slot_index, ChunkSlot::default())?;
+
+        Ok(true)
+    }
+
+    /// Moves all chunks to the front of the file, in their current order, so there are no free sectors between them. Then
+    /// truncates the file.
+    ///
+    /// This rewrites the file in place, so the region may be corrupted if the process is interrupted.
+    pub fn defragment(&mut self) -> io::Result<()> {
+        let mut occupied: Vec<usize> = (0..self.slots.len())
+            .filter(|&i| self.slots[i].is_occupied())
+            .collect();
+        occupied.sort_by_key(|&i| self.slots[i].first_sector);
+
+        let mut next_sector =
+            sectors_for_len(self.slot_table_offset + SLOT_SIZE * self.slots.len() as u64);
+
+        for slot_index in occupied.into_iter() {
+            let slot = self.slots[slot_index];
+            if slot.first_sector as usize != next_sector {
+                // Chunks only move toward the front, so this never overwrites a chunk that hasn't been moved yet.
+                let bytes = self.read_slot_bytes(slot)?;
+                self.write_sectors(next_sector, &bytes)?;
+                self.write_slot(
+                    slot_index,
+                    ChunkSlot {
+                        first_sector: next_sector as u32,
+                        ..slot
+                    },
+                )?;
+            }
+            next_sector += slot.num_sectors as usize;
+        }
+
+        self.sector_is_used = vec![true; next_sector];
+        self.file.set_len(next_sector as u64 * REGION_SECTOR_SIZE)?;
+
+        self.file.flush()
+    }
+
+    /// Decompresses every chunk in this region and writes it into `storage`.
+    pub fn load_into_storage<Store>(&mut self, storage: &mut Store) -> io::Result<()>
+    where
+        Store: ChunkWriteStorage<N, Ch>,
+    {
+        let keys: Vec<_> = self.chunk_keys().collect();
+        for key in keys.into_iter() {
+            let chunk = self.read_chunk(key)?.unwrap();
+            storage.write(key, chunk);
+        }
+
+        Ok(())
+    }
+
+    fn slot_index(&self, key: PointN<N>) -> usize {
+        assert!(
+            self.info.contains_chunk_key(key),
+            "Chunk key is not in this region"
+        );
+
+        let local_chunk = (key - self.info.region_key).vector_div_floor(self.info.chunk_shape);
+
+        N::stride_from_local_point(self.info.chunks_per_region, Local(local_chunk)).0
+    }
+
+    fn find_free_sectors(&mut self, num_sectors: usize) -> usize {
+        let mut run_start = 0;
+        let mut run_len = 0;
+        for (i, used) in self.sector_is_used.iter().enumerate() {
+            if *used {
+                run_len = 0;
+                run_start = i + 1;
+            } else {
+                run_len += 1;
+                if run_len == num_sectors {
+                    return run_start;
+                }
+            }
+        }
+
+        // A free run at the end of the file can be extended.
+        self.sector_is_used.resize(run_start + num_sectors, false);
+
+        run_start
+    }
+
+    fn read_slot_bytes(&mut self, slot: ChunkSlot) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0; slot.len as usize];
+        self.file.seek(SeekFrom::Start(
+            slot.first_sector as u64 * REGION_SECTOR_SIZE,
+        ))?;
+        self.file.read_exact(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    fn write_sectors(&mut self, first_sector: usize, bytes: &[u8]) -> io::Result<()> {
+        let padding =
+            sectors_for_len(bytes.len() as u64) * REGION_SECTOR_SIZE as usize - bytes.len();
+        self.file
+            .seek(SeekFrom::Start(first_sector as u64 * REGION_SECTOR_SIZE))?;
+        self.file.write_all(bytes)?;
+        self.file.write_all(&vec![0; padding])
+    }
+
+    fn write_slot(&mut self, slot_index: usize, slot: ChunkSlot) -> io::Result<()> {
+        self.slots[slot_index] = slot;
+        self.file.seek(SeekFrom::Start(
+            self.slot_table_offset + slot_index as u64 * SLOT_SIZE,
+        ))?;
+
+        self.file.write_all(&slot.to_bytes())
+    }
+}
+
+fn sectors_for_len(len: u64) -> usize {
+    len.div_ceil(REGION_SECTOR_SIZE) as usize
+}
+
+fn mark_sectors(sector_is_used: &mut Vec<bool>, range: std::ops::Range<usize>, used: bool) {
+    if range.end > sector_is_used.len() {
+        sector_is_used.resize(range.end, false);
+    }
+    for is_used in sector_is_used[range].iter_mut() {
+        *is_used = used;
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(all(test, feature = "lz4"))]
+mod test {
+    use super::*;
+
+    use crate::{prelude::*, Lz4};
+
+    #[test]
+    fn rewrite_delete_and_defragment() {
+        let path = std::env::temp_dir().join(format!(
+            "bb_region_file_rewrite_delete_and_defragment_{}",
+            std::process::id()
+        ));
+        let compression = BincodeCompression::new(Lz4 { level: 10 });
+        let info =
+            RegionInfo::containing_chunk(Point3i::fill(16), Point3i::fill(4), Point3i::fill(-16));
+        assert_eq!(info.region_key, Point3i::fill(-64));
+
+        let mut map = BUILDER.build_with_hash_map_storage();
+        let extent = Extent3i::from_min_and_shape(Point3i::fill(-64), Point3i::fill(64));
+        map.for_each_mut(&extent, |p: Point3i, value| *value = p.x() ^ p.y() ^ p.z());
+
+        let mut region = RegionFile::create(&path, info, compression).unwrap();
+        for (&key, chunk) in map.storage().iter() {
+            region.write_chunk(key, chunk).unwrap();
+        }
+        assert_eq!(region.chunk_keys().count(), 64);
+        assert_eq!(region.num_free_sectors(), 0);
+
+        // Homogeneous chunks compress well, so they will leave free sectors behind.
+        let ambient_chunk =
+            Array3x1::fill(map.indexer.extent_for_chunk_at_key(Point3i::fill(-64)), 0);
+        region
+            .write_chunk(Point3i::fill(-64), &ambient_chunk)
+            .unwrap();
+        assert!(region.delete_chunk(Point3i::fill(-16)).unwrap());
+        assert!(!region.delete_chunk(Point3i::fill(-16)).unwrap());
+        assert!(region.num_free_sectors() > 0);
+        drop(region);
+
+        let mut region =
+            RegionFile::<[i32; 3], Array3x1<i32>, _>::open(&path, compression).unwrap();
+        assert_eq!(region.info(), &info);
+        assert!(region.num_free_sectors() > 0);
+        let num_sectors_before = region.num_sectors();
+        region.defragment().unwrap();
+        assert_eq!(region.num_free_sectors(), 0);
+        assert!(region.num_sectors() < num_sectors_before);
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len(),
+            region.num_sectors() as u64 * REGION_SECTOR_SIZE
+        );
+
+        let mut loaded_map = BUILDER.build_with_hash_map_storage();
+        region.load_into_storage(loaded_map.storage_mut()).unwrap();
+        assert_eq!(loaded_map.storage().len(), 63);
+        loaded_map.for_each(&extent, |p: Point3i, value| {
+            let key = loaded_map.indexer.chunk_key_containing_point(p);
+            let expected = if key == Point3i::fill(-64) || key == Point3i::fill(-16) {
+                0
+            } else {
+                p.x() ^ p.y() ^ p.z()
+            };
+            assert_eq!(value, expected);
+        });
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    const BUILDER: ChunkMapBuilder3x1<i32> = ChunkMapBuilder3x1::new(PointN([16; 3]), 0);
+}
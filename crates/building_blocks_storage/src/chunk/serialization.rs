@@ -1,13 +1,20 @@
-use crate::{BincodeCompression, BytesCompression, ChunkWriteStorage, Compressed, Compression};
+use crate::{
+    bincode_to_io_error, load_old_chunk, BincodeCompression, BytesCompression, ChannelTypeNames,
+    ChunkFileHeader, ChunkMigration, ChunkWriteStorage, Compressed, Compression,
+};
 
 use building_blocks_core::prelude::*;
 
 use futures::future::join_all;
 use itertools::Itertools;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io::{self, Read, Write};
 
 /// A simple format for serializing a collection of chunks. All chunks are serialized with `bincode`, then compressed using some
 /// `BytesCompression`.
+///
+/// Use `write_to` and `read_from` to prefix the serialized chunks with a `ChunkFileHeader`, so old saves can be detected and
+/// migrated after the chunk type changes.
 #[derive(Deserialize, Serialize)]
 #[serde(bound(deserialize = "Ch: DeserializeOwned"))]
 pub struct SerializableChunks<N, Ch, B>
@@ -75,6 +82,90 @@ where
     }
 }
 
+impl<N, Ch, B> SerializableChunks<N, Ch, B>
+where
+    N: DeserializeOwned + Serialize,
+    PointN<N>: IntegerPoint<N>,
+    Ch: ChannelTypeNames + DeserializeOwned + Serialize,
+    B: BytesCompression + DeserializeOwned + Serialize,
+{
+    /// Writes a `ChunkFileHeader` for chunks of `chunk_shape`, followed by the `bincode` representation of `self`.
+    pub fn write_to(&self, mut writer: impl Write, chunk_shape: PointN<N>) -> io::Result<()> {
+        ChunkFileHeader::new::<N, Ch, B>(chunk_shape).write_to(&mut writer)?;
+
+        bincode::serialize_into(writer, self).map_err(|e| bincode_to_io_error(*e))
+    }
+
+    /// Reads chunks that were written by `write_to`. Returns an `InvalidData` error if they have a different chunk layout, codec
+    /// or chunk shape.
+    pub fn read_from(
+        reader: impl Read,
+        chunk_shape: PointN<N>,
+    ) -> io::Result<(ChunkFileHeader, Self)> {
+        let (header, chunks) = Self::read_with_header(reader, chunk_shape)?;
+        if !header.is_current_codec::<B>() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Expected codec {}, but the file has {}",
+                    B::CODEC_ID,
+                    header.codec
+                ),
+            ));
+        }
+        if !header.is_current_layout::<Ch>() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Expected channel types {:?} with array layout {:?}, but the file has {:?} with {:?}",
+                    Ch::channel_type_names(),
+                    Ch::array_layout(),
+                    header.channel_types,
+                    header.array_layout
+                ),
+            ));
+        }
+
+        Ok((header, chunks))
+    }
+
+    /// Reads chunks that were written by `write_to`. If they have an old chunk layout or codec, each chunk is decompressed,
+    /// converted by `migration`, then compressed again with `compression`.
+    pub fn read_from_with_migration(
+        reader: impl Read,
+        chunk_shape: PointN<N>,
+        compression: BincodeCompression<Ch, B>,
+        mut migration: impl ChunkMigration<Ch>,
+    ) -> io::Result<(ChunkFileHeader, Self)> {
+        let (header, mut chunks) = Self::read_with_header(reader, chunk_shape)?;
+        if header.is_current_layout::<Ch>() && header.is_current_codec::<B>() {
+            return Ok((header, chunks));
+        }
+
+        for (_key, compressed_chunk) in chunks.compressed_chunks.iter_mut() {
+            let chunk = load_old_chunk::<Ch, B, _>(
+                &header,
+                &compressed_chunk.compressed_data,
+                &mut migration,
+            )?;
+            *compressed_chunk = compression.compress(&chunk);
+        }
+
+        Ok((header, chunks))
+    }
+
+    fn read_with_header(
+        mut reader: impl Read,
+        chunk_shape: PointN<N>,
+    ) -> io::Result<(ChunkFileHeader, Self)> {
+        let header = ChunkFileHeader::read_from(&mut reader)?;
+        header.check_compatible(chunk_shape)?;
+        let chunks = bincode::deserialize_from(reader).map_err(|e| bincode_to_io_error(*e))?;
+
+        Ok((header, chunks))
+    }
+}
+
 // ████████╗███████╗███████╗████████╗
 // ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
 //    ██║   █████╗  ███████╗   ██║
@@ -128,6 +219,18 @@ mod test {
         );
     }
 
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn compressible_map_serialize_and_deserialize_round_trip_zstd() {
+        use crate::Zstd;
+
+        let compression = Zstd::new(10);
+        do_serialize_and_deserialize_round_trip_test(
+            FastCompressibleChunkStorageNx1::with_bytes_compression(compression),
+            compression,
+        );
+    }
+
     fn do_serialize_and_deserialize_round_trip_test<B, Store>(storage: Store, compression: B)
     where
         Store: ChunkWriteStorage<[i32; 3], Array3x1<i32>>
@@ -151,5 +254,56 @@ mod test {
         map.for_each(&filled_extent, |_p, val| assert_eq!(val, 1));
     }
 
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn migrate_old_channel_type_on_read() {
+        use crate::{deserialize_old_chunk, Lz4};
+
+        let compression = Lz4 { level: 10 };
+        let mut old_map =
+            ChunkMapBuilder3x1::new(PointN([16; 3]), 0u8).build_with_hash_map_storage();
+        let filled_extent = Extent3i::from_min_and_shape(Point3i::fill(-20), Point3i::fill(40));
+        old_map.fill_extent(&filled_extent, 7);
+        let serializable = futures::executor::block_on(SerializableChunks::from_iter(
+            BincodeCompression::new(compression),
+            old_map.take_storage(),
+        ));
+        let mut bytes = Vec::new();
+        serializable.write_to(&mut bytes, PointN([16; 3])).unwrap();
+
+        assert!(
+            SerializableChunks::<[i32; 3], Array3x1<i32>, Lz4>::read_from(
+                bytes.as_slice(),
+                PointN([16; 3])
+            )
+            .is_err()
+        );
+
+        let migration =
+            |_header: &ChunkFileHeader, serialized_chunk: &[u8]| -> io::Result<Array3x1<i32>> {
+                let old_chunk: Array3x1<u8> = deserialize_old_chunk(serialized_chunk)?;
+                let mut new_chunk = Array3x1::fill(*old_chunk.extent(), 0);
+                new_chunk.for_each_mut(old_chunk.extent(), |p: Point3i, value| {
+                    *value = -(old_chunk.get(p) as i32)
+                });
+
+                Ok(new_chunk)
+            };
+        let (header, migrated) =
+            SerializableChunks::<[i32; 3], Array3x1<i32>, Lz4>::read_from_with_migration(
+                bytes.as_slice(),
+                PointN([16; 3]),
+                BincodeCompression::new(compression),
+                migration,
+            )
+            .unwrap();
+        assert_eq!(header.channel_types, vec!["u8".to_string()]);
+
+        let mut storage = SmallKeyHashMap::default();
+        futures::executor::block_on(migrated.fill_storage(&mut storage));
+        let map = BUILDER.build_with_rw_storage(storage);
+        map.for_each(&filled_extent, |_p, val| assert_eq!(val, -7));
+    }
+
     const BUILDER: ChunkMapBuilder3x1<i32> = ChunkMapBuilder3x1::new(PointN([16; 3]), 0);
 }
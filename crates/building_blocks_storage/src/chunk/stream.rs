@@ -0,0 +1,342 @@
+//! Streaming serialization of chunks to any `std::io::Write`, and deserialization from any `std::io::Read`.
+//!
+//! `SerializableChunks` needs every compressed chunk in memory at once. A `ChunkStreamWriter` instead compresses and writes one
+//! chunk at a time, and a `ChunkStreamReader` reads them back one at a time, so arbitrarily large maps can be saved and loaded
+//! with bounded memory.
+//!
+//! The stream starts with a `ChunkFileHeader`, which is checked by the reader, so a stream can't be decoded with the wrong
+//! chunk type or compression. Every chunk is serialized with `bincode`, then compressed using some `BytesCompression`. Streams
+//! with an old chunk layout can be loaded with `ChunkStreamReader::with_migration`, and streams compressed with a different
+//! codec in this crate are converted automatically.
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::{prelude::*, BincodeCompression, ChunkStreamReader, ChunkStreamWriter};
+//! let builder = ChunkMapBuilder3x1::new(Point3i::fill(16), 0);
+//! let mut map = builder.clone().build_with_hash_map_storage();
+//! map.fill_extent(&Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(64)), 1);
+//!
+//! // This could be a `BufWriter<File>`.
+//! let compression = BincodeCompression::new(Lz4 { level: 10 });
+//! let mut writer = ChunkStreamWriter::new(Vec::new(), builder.chunk_shape, compression).unwrap();
+//! writer.write_chunks(map.take_storage()).unwrap();
+//! let bytes = writer.finish().unwrap();
+//!
+//! let mut reader = ChunkStreamReader::<_, [i32; 3], Array3x1<i32>, Lz4>::new(bytes.as_slice(), builder.chunk_shape).unwrap();
+//! assert_eq!(reader.header().chunk_shape, vec![16; 3]);
+//! let mut loaded_map = builder.build_with_hash_map_storage();
+//! reader.fill_storage(loaded_map.storage_mut()).unwrap();
+//! assert_eq!(loaded_map.get(Point3i::fill(63)), 1);
+//! ```
+
+use crate::{
+    bincode_to_io_error, load_old_chunk, BincodeCompression, BytesCompression, ChannelTypeNames,
+    ChunkFileHeader, ChunkMigration, ChunkWriteStorage, Compression, NoMigration,
+};
+
+use building_blocks_core::prelude::*;
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+/// Compresses chunks and writes them to a `W: Write`, one at a time.
+///
+/// The stream is terminated by `finish`. A stream that is dropped without finishing can't be read to completion.
+pub struct ChunkStreamWriter<W, N, Ch, B> {
+    writer: W,
+    compression: BincodeCompression<Ch, B>,
+    marker: PhantomData<N>,
+}
+
+impl<W, N, Ch, B> ChunkStreamWriter<W, N, Ch, B>
+where
+    W: Write,
+    PointN<N>: IntegerPoint<N> + Serialize,
+    Ch: ChannelTypeNames + DeserializeOwned + Serialize,
+    B: BytesCompression,
+{
+    /// Writes the header for chunks of `chunk_shape` to `writer`.
+    pub fn new(
+        mut writer: W,
+        chunk_shape: PointN<N>,
+        compression: BincodeCompression<Ch, B>,
+    ) -> io::Result<Self> {
+        ChunkFileHeader::new::<N, Ch, B>(chunk_shape).write_to(&mut writer)?;
+
+        Ok(Self {
+            writer,
+            compression,
+            marker: PhantomData,
+        })
+    }
+
+    /// Compresses `chunk` and writes it to the stream.
+    pub fn write_chunk(&mut self, key: PointN<N>, chunk: &Ch) -> io::Result<()> {
+        let compressed_bytes = self.compression.compress(chunk).take();
+
+        bincode::serialize_into(&mut self.writer, &Some((key, compressed_bytes)))
+            .map_err(|e| bincode_to_io_error(*e))
+    }
+
+    /// Writes every chunk in `chunks` to the stream. Only one chunk is compressed at a time.
+    pub fn write_chunks(
+        &mut self,
+        chunks: impl IntoIterator<Item = (PointN<N>, Ch)>,
+    ) -> io::Result<()> {
+        for (key, chunk) in chunks.into_iter() {
+            self.write_chunk(key, &chunk)?;
+        }
+
+        Ok(())
+    }
+
+    /// Terminates the stream, flushes it, and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        bincode::serialize_into(&mut self.writer, &None::<(PointN<N>, Vec<u8>)>)
+            .map_err(|e| bincode_to_io_error(*e))?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+/// Reads chunks from a `R: Read` that were written by a `ChunkStreamWriter`, one at a time. Chunks with an old layout are
+/// converted by the `ChunkMigration` `M`.
+///
+/// This is also an `Iterator` over the chunks in the stream.
+pub struct ChunkStreamReader<R, N, Ch, B, M = NoMigration> {
+    reader: R,
+    header: ChunkFileHeader,
+    needs_migration: bool,
+    migration: M,
+    finished: bool,
+    marker: PhantomData<(N, Ch, B)>,
+}
+
+impl<R, N, Ch, B> ChunkStreamReader<R, N, Ch, B, NoMigration>
+where
+    R: Read,
+    PointN<N>: IntegerPoint<N> + DeserializeOwned,
+    Ch: ChannelTypeNames + DeserializeOwned + Serialize,
+    B: BytesCompression,
+{
+    /// Reads the header from `reader`. Returns an `InvalidData` error if the stream holds a different chunk layout or chunks of
+    /// a shape other than `chunk_shape`.
+    pub fn new(reader: R, chunk_shape: PointN<N>) -> io::Result<Self> {
+        let stream_reader = Self::with_migration(reader, chunk_shape, NoMigration)?;
+        if !stream_reader.header.is_current_layout::<Ch>() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Expected channel types {:?} with array layout {:?}, but the stream has {:?} with {:?}",
+                    Ch::channel_type_names(),
+                    Ch::array_layout(),
+                    stream_reader.header.channel_types,
+                    stream_reader.header.array_layout
+                ),
+            ));
+        }
+
+        Ok(stream_reader)
+    }
+}
+
+impl<R, N, Ch, B, M> ChunkStreamReader<R, N, Ch, B, M>
+where
+    R: Read,
+    PointN<N>: IntegerPoint<N> + DeserializeOwned,
+    Ch: ChannelTypeNames + DeserializeOwned + Serialize,
+    B: BytesCompression,
+    M: ChunkMigration<Ch>,
+{
+    /// Reads the header from `reader`. If the stream has an old chunk layout or codec, every chunk will be converted by
+    /// `migration`. Returns an `InvalidData` error if the stream has a different dimensionality or chunk shape.
+    pub fn with_migration(mut reader: R, chunk_shape: PointN<N>, migration: M) -> io::Result<Self> {
+        let header = ChunkFileHeader::read_from(&mut reader)?;
+        header.check_compatible(chunk_shape)?;
+        let needs_migration = !header.is_current_layout::<Ch>() || !header.is_current_codec::<B>();
+
+        Ok(Self {
+            reader,
+            header,
+            needs_migration,
+            migration,
+            finished: false,
+            marker: PhantomData,
+        })
+    }
+
+    pub fn header(&self) -> &ChunkFileHeader {
+        &self.header
+    }
+
+    /// Reads and decompresses the next chunk. Returns `None` at the end of the stream.
+    pub fn read_chunk(&mut self) -> io::Result<Option<(PointN<N>, Ch)>> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let next: Option<(PointN<N>, Vec<u8>)> =
+            bincode::deserialize_from(&mut self.reader).map_err(|e| bincode_to_io_error(*e))?;
+
+        let (key, compressed_bytes) = match next {
+            Some(next) => next,
+            None => {
+                self.finished = true;
+
+                return Ok(None);
+            }
+        };
+
+        let chunk = if self.needs_migration {
+            load_old_chunk::<Ch, B, M>(&self.header, &compressed_bytes, &mut self.migration)?
+        } else {
+            BincodeCompression::<Ch, B>::decompress(&compressed_bytes)
+        };
+
+        Ok(Some((key, chunk)))
+    }
+
+    /// Reads every remaining chunk and writes it into `storage`. Only one chunk is decompressed at a time.
+    pub fn fill_storage<Store>(&mut self, storage: &mut Store) -> io::Result<()>
+    where
+        Store: ChunkWriteStorage<N, Ch>,
+    {
+        while let Some((key, chunk)) = self.read_chunk()? {
+            storage.write(key, chunk);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the inner reader, positioned after the last chunk that was read.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R, N, Ch, B, M> Iterator for ChunkStreamReader<R, N, Ch, B, M>
+where
+    R: Read,
+    PointN<N>: IntegerPoint<N> + DeserializeOwned,
+    Ch: ChannelTypeNames + DeserializeOwned + Serialize,
+    B: BytesCompression,
+    M: ChunkMigration<Ch>,
+{
+    type Item = io::Result<(PointN<N>, Ch)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_chunk().transpose()
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(all(test, feature = "lz4"))]
+mod test {
+    use super::*;
+
+    use crate::{prelude::*, Lz4, NoCompression};
+
+    #[test]
+    fn stream_round_trip() {
+        let compression = BincodeCompression::new(Lz4 { level: 10 });
+        let mut map = BUILDER.build_with_hash_map_storage();
+        let extent = Extent3i::from_min_and_shape(Point3i::fill(-40), Point3i::fill(80));
+        map.for_each_mut(&extent, |p: Point3i, value| *value = p.x() - p.y() + p.z());
+        let num_chunks = map.storage().len();
+
+        let mut writer =
+            ChunkStreamWriter::new(Vec::new(), BUILDER.chunk_shape, compression).unwrap();
+        for (&key, chunk) in map.storage().iter() {
+            writer.write_chunk(key, chunk).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+
+        let reader = ChunkStreamReader::<_, [i32; 3], Array3x1<i32>, Lz4>::new(
+            bytes.as_slice(),
+            BUILDER.chunk_shape,
+        )
+        .unwrap();
+        let mut storage = SmallKeyHashMap::default();
+        for result in reader {
+            let (key, chunk) = result.unwrap();
+            storage.write(key, chunk);
+        }
+        assert_eq!(storage.len(), num_chunks);
+
+        let loaded_map = BUILDER.build_with_rw_storage(storage);
+        loaded_map.for_each(&extent, |p: Point3i, value| {
+            assert_eq!(value, p.x() - p.y() + p.z())
+        });
+    }
+
+    #[test]
+    fn reader_rejects_wrong_chunk_type() {
+        let compression = BincodeCompression::new(Lz4 { level: 10 });
+        let writer = ChunkStreamWriter::<_, [i32; 3], Array3x1<i32>, _>::new(
+            Vec::new(),
+            BUILDER.chunk_shape,
+            compression,
+        )
+        .unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let result = ChunkStreamReader::<_, [i32; 3], Array3x1<u8>, Lz4>::new(
+            bytes.as_slice(),
+            BUILDER.chunk_shape,
+        );
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reader_rejects_wrong_chunk_shape() {
+        let compression = BincodeCompression::new(Lz4 { level: 10 });
+        let writer = ChunkStreamWriter::<_, [i32; 3], Array3x1<i32>, _>::new(
+            Vec::new(),
+            BUILDER.chunk_shape,
+            compression,
+        )
+        .unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let result = ChunkStreamReader::<_, [i32; 3], Array3x1<i32>, Lz4>::new(
+            bytes.as_slice(),
+            Point3i::fill(32),
+        );
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reader_converts_old_codec() {
+        let mut map = BUILDER.build_with_hash_map_storage();
+        let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(32));
+        map.for_each_mut(&extent, |p: Point3i, value| *value = p.x() * p.y());
+
+        let compression = BincodeCompression::new(NoCompression);
+        let mut writer =
+            ChunkStreamWriter::new(Vec::new(), BUILDER.chunk_shape, compression).unwrap();
+        writer.write_chunks(map.take_storage()).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = ChunkStreamReader::<_, [i32; 3], Array3x1<i32>, Lz4>::new(
+            bytes.as_slice(),
+            BUILDER.chunk_shape,
+        )
+        .unwrap();
+        assert_eq!(reader.header().codec, NoCompression::CODEC_ID);
+        let mut loaded_map = BUILDER.build_with_hash_map_storage();
+        reader.fill_storage(loaded_map.storage_mut()).unwrap();
+        loaded_map.for_each(&extent, |p: Point3i, value| {
+            assert_eq!(value, p.x() * p.y())
+        });
+    }
+
+    const BUILDER: ChunkMapBuilder3x1<i32> = ChunkMapBuilder3x1::new(PointN([16; 3]), 0);
+}
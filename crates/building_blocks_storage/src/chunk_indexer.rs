@@ -1,29 +1,66 @@
 use building_blocks_core::prelude::*;
 
 use core::ops::{Div, Mul};
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize};
 
 /// Translates from lattice coordinates to chunk key space.
 ///
 /// The key for a chunk is the minimum point of that chunk's extent.
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+///
+/// Any positive chunk shape is supported, but when all dimensions are powers of 2, chunk keys are calculated with bitwise
+/// operations instead of division, which is significantly faster.
+///
+/// Only the chunk shape is trusted when deserializing; everything else is derived from it again.
+#[derive(Clone, Copy, Debug, Serialize)]
 pub struct ChunkIndexer<N> {
     chunk_shape: PointN<N>,
     chunk_shape_mask: PointN<N>,
     chunk_shape_log2: PointN<N>,
+    #[serde(skip_serializing)]
+    shape_is_pow2: bool,
+}
+
+// The serialized fields of a `ChunkIndexer`.
+#[derive(Deserialize)]
+#[serde(bound = "PointN<N>: Deserialize<'de>")]
+struct ChunkIndexerFields<N> {
+    chunk_shape: PointN<N>,
+    #[allow(dead_code)]
+    chunk_shape_mask: PointN<N>,
+    #[allow(dead_code)]
+    chunk_shape_log2: PointN<N>,
+}
+
+impl<'de, N> Deserialize<'de> for ChunkIndexer<N>
+where
+    PointN<N>: IntegerPoint<N> + Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let ChunkIndexerFields { chunk_shape, .. } = ChunkIndexerFields::deserialize(deserializer)?;
+        if chunk_shape > PointN::ZERO {
+            Ok(Self::new(chunk_shape))
+        } else {
+            Err(de::Error::custom("chunk shape must be positive"))
+        }
+    }
 }
 
 impl<N> ChunkIndexer<N>
 where
     PointN<N>: IntegerPoint<N>,
 {
+    /// All dimensions of `chunk_shape` must be positive.
     pub fn new(chunk_shape: PointN<N>) -> Self {
-        assert!(chunk_shape.dimensions_are_powers_of_2());
+        assert!(chunk_shape > PointN::ZERO);
 
         Self {
             chunk_shape,
             chunk_shape_mask: !(chunk_shape - PointN::ONES),
             chunk_shape_log2: chunk_shape.map_components_unary(|c| c.trailing_zeros() as i32),
+            shape_is_pow2: chunk_shape.dimensions_are_powers_of_2(),
         }
     }
 
@@ -37,25 +74,53 @@ where
         self.chunk_shape
     }
 
-    /// The mask used for calculating the chunk key of a chunk that contains a given point.
+    /// Returns `true` iff all dimensions of the chunk shape are powers of 2, which enables the fast indexing path.
+    pub fn chunk_shape_is_power_of_2(&self) -> bool {
+        self.shape_is_pow2
+    }
+
+    /// The mask used for calculating the chunk key of a chunk that contains a given point. Only meaningful when
+    /// `chunk_shape_is_power_of_2`.
     pub fn chunk_shape_mask(&self) -> PointN<N> {
         self.chunk_shape_mask
     }
 
     /// Returns the key of the chunk that contains `point`.
     pub fn chunk_key_containing_point(&self, point: PointN<N>) -> PointN<N> {
-        self.chunk_shape_mask() & point
+        if self.shape_is_pow2 {
+            self.chunk_shape_mask() & point
+        } else {
+            self.chunk_shape * point.vector_div_floor(self.chunk_shape)
+        }
+    }
+
+    /// Returns the coordinates of the chunk that contains `point` on the lattice of chunks, i.e. its key divided by the chunk
+    /// shape.
+    pub fn chunk_coords_containing_point(&self, point: PointN<N>) -> PointN<N> {
+        if self.shape_is_pow2 {
+            point >> self.chunk_shape_log2
+        } else {
+            point.vector_div_floor(self.chunk_shape)
+        }
     }
 
     /// Returns an iterator over all chunk keys for chunks that overlap the given extent.
     pub fn chunk_keys_for_extent(&self, extent: &ExtentN<N>) -> impl Iterator<Item = PointN<N>> {
-        let key_min = extent.minimum >> self.chunk_shape_log2;
-        let key_max = extent.max() >> self.chunk_shape_log2;
+        let key_min = self.chunk_coords_containing_point(extent.minimum);
+        let key_max = self.chunk_coords_containing_point(extent.max());
+        let shape_is_pow2 = self.shape_is_pow2;
         let shape_log2 = self.chunk_shape_log2;
+        let shape = self.chunk_shape;
 
         ExtentN::from_min_and_max(key_min, key_max)
             .iter_points()
-            .map(move |p| p << shape_log2)
+            .map(move |p| {
+                if shape_is_pow2 {
+                    p << shape_log2
+                } else {
+                    p * shape
+                }
+            })
     }
 
     /// The extent spanned by the chunk at `key`.
@@ -75,7 +140,7 @@ where
 mod tests {
     use super::*;
 
-    use building_blocks_core::Extent3i;
+    use building_blocks_core::{Extent2i, Extent3i};
 
     #[test]
     fn chunk_keys_for_extent_gives_keys_for_chunks_overlapping_extent() {
@@ -105,4 +170,84 @@ mod tests {
         let key = indexer.chunk_key_containing_point(p);
         assert_eq!(key, Point3i::fill(-16));
     }
+
+    #[test]
+    fn non_power_of_2_chunk_keys_containing_points() {
+        let indexer = ChunkIndexer::new(PointN([24, 48, 24]));
+        assert!(!indexer.chunk_shape_is_power_of_2());
+
+        assert_eq!(
+            indexer.chunk_key_containing_point(PointN([0, 47, 23])),
+            Point3i::ZERO
+        );
+        assert_eq!(
+            indexer.chunk_key_containing_point(PointN([24, 48, -1])),
+            PointN([24, 48, -24])
+        );
+        assert_eq!(
+            indexer.chunk_key_containing_point(PointN([-25, -49, -24])),
+            PointN([-48, -96, -24])
+        );
+        assert!(indexer.chunk_key_is_valid(PointN([-48, -96, -24])));
+        assert!(!indexer.chunk_key_is_valid(PointN([-32, 0, 0])));
+    }
+
+    #[test]
+    fn deserialize_recomputes_fields_from_chunk_shape() {
+        #[derive(Serialize)]
+        struct Fields {
+            chunk_shape: Point3i,
+            chunk_shape_mask: Point3i,
+            chunk_shape_log2: Point3i,
+        }
+
+        // The fields other than the chunk shape are ignored, so they can't disagree with it.
+        let bytes = bincode::serialize(&Fields {
+            chunk_shape: PointN([24, 48, 24]),
+            chunk_shape_mask: Point3i::fill(-16),
+            chunk_shape_log2: Point3i::fill(4),
+        })
+        .unwrap();
+        let indexer: ChunkIndexer<[i32; 3]> = bincode::deserialize(&bytes).unwrap();
+        assert!(!indexer.chunk_shape_is_power_of_2());
+        assert_eq!(
+            indexer.chunk_key_containing_point(PointN([25, 0, 0])),
+            PointN([24, 0, 0])
+        );
+
+        let indexer = ChunkIndexer::new(Point3i::fill(16));
+        let bytes = bincode::serialize(&indexer).unwrap();
+        assert_eq!(
+            bytes.len(),
+            bincode::serialized_size(&Point3i::ZERO).unwrap() as usize * 3
+        );
+        let indexer: ChunkIndexer<[i32; 3]> = bincode::deserialize(&bytes).unwrap();
+        assert!(indexer.chunk_shape_is_power_of_2());
+        assert_eq!(indexer.chunk_shape_mask(), Point3i::fill(!15));
+
+        let bytes = bincode::serialize(&Point3i::fill(0)).unwrap().repeat(3);
+        assert!(bincode::deserialize::<ChunkIndexer<[i32; 3]>>(&bytes).is_err());
+    }
+
+    #[test]
+    fn non_power_of_2_chunk_keys_for_extent() {
+        let indexer = ChunkIndexer::new(Point2i::fill(24));
+        let query_extent = Extent2i::from_min_and_shape(Point2i::fill(-1), Point2i::fill(26));
+        let chunk_keys: Vec<_> = indexer.chunk_keys_for_extent(&query_extent).collect();
+
+        assert_eq!(
+            chunk_keys,
+            vec![
+                PointN([-24, -24]),
+                PointN([0, -24]),
+                PointN([24, -24]),
+                PointN([-24, 0]),
+                PointN([0, 0]),
+                PointN([24, 0]),
+                PointN([-24, 24]),
+                PointN([0, 24]),
+                PointN([24, 24]),
+            ]
+        );
+    }
 }
@@ -4,8 +4,8 @@
 //!
 //! The data can either be addressed by chunk key with the `get_chunk*` methods or by individual points using the `Get*` and
 //! `ForEach*` trait impls. The map of chunks uses `Point3i` keys. The key for a chunk is the minimum point in that chunk, which
-//! is always a multiple of the chunk shape. Chunk shape dimensions must be powers of 2, which allows for efficiently
-//! calculating a chunk key from any point in the chunk.
+//! is always a multiple of the chunk shape. Chunk shape dimensions can be any positive integers, but powers of 2 allow for
+//! efficiently calculating a chunk key from any point in the chunk with bitwise operations instead of division.
 //!
 //! If you require iteration over large, but very sparse regions, you might want an additional `OctreeChunkIndex` to track the
 //! set of occupied chunks. Traversing that index can be faster than doing hash map lookups on all of the possible chunks in a
@@ -17,11 +17,37 @@
 //! `ChunkWriteStorage`. A storage can be as simple as a `HashMap`, which provides good performance for both iteration and
 //! random access. It could also be something more memory efficient like `FastCompressibleChunkStorage` or
 //! `CompressibleChunkStorageReader`, which perform nearly as well but involve some extra management of the cache.
+//! For maps that don't fit in memory, `DiskChunkStorage` spills compressed chunks to a log file instead.
+//!
+//! # Ambient Chunks
+//!
+//! A chunk that takes the ambient value at every point is redundant. Such chunks can be deleted with
+//! `ChunkMap::delete_ambient_chunks`, or automatically after every write by enabling
+//! `ChunkMap::set_delete_ambient_chunks_on_write`. To make chunks filled with any single value cheap to store, compress them
+//! with a `HomogeneousArrayCompression`.
+//!
+//! # Merging
+//!
+//! One map can be overlaid onto another with `ChunkMap::merge`, which takes a per-voxel combine function and an optional
+//! translation.
+//!
+//! # Diffs
+//!
+//! Two versions of a map can be compared with `ChunkMap::diff`, which produces a `ChunkMapDiff` of added, removed and changed
+//! chunks. Applying it to the older version with `ChunkMap::apply_diff` reproduces the newer version.
 //!
 //! # Serialization
 //!
 //! In order to efficiently serialize a `ChunkMap`, you can first use `SerializableChunks::from_iter` to create a compact
 //! serializable representation. It will compress the bincode representation of the chunks.
+//! For maps that are too large to hold in memory twice, `ChunkStreamWriter` and `ChunkStreamReader` serialize the chunks one
+//! at a time to any `std::io::Write` or from any `std::io::Read`.
+//!
+//! Both `ChunkStreamWriter` and `SerializableChunks::write_to` start with a versioned `ChunkFileHeader`, which describes the
+//! chunk layout. Saves with an old layout can be converted while loading by a `ChunkMigration`.
+//!
+//! To save a large world incrementally, use a `RegionFile` per region of chunks instead. Individual chunks can then be read and
+//! rewritten without touching the rest of the world.
 //!
 //! # Example `ChunkHashMap` Usage
 //! ```
@@ -69,6 +95,11 @@
 //! let query_extent = Extent3i::from_min_and_shape(Point3i::fill(10), Point3i::fill(32));
 //! let mut dense_map = Array3x1::fill(query_extent, ambient_value);
 //! copy_extent(&query_extent, &map, &mut dense_map);
+//!
+//! // If you only need to read a chunk and a border around it, you can borrow the neighboring chunks instead of copying them.
+//! let neighborhood = map.padded_chunk_neighborhood(Point3i::ZERO, 1);
+//! assert_eq!(neighborhood.get(Point3i::fill(-1)), 0);
+//! neighborhood.for_each(neighborhood.extent(), |p, value| assert_eq!(value, map.get(p)));
 //! ```
 //!
 //! # Example `CompressibleChunkMap` Usage
@@ -94,6 +125,12 @@
 //! // decompressed and cached.
 //! map.storage_mut().compress_lru();
 //!
+//! // Or let the storage compress chunks automatically to stay under a memory budget.
+//! map.storage_mut().set_budget(CompressionBudget {
+//!     max_decompressed_bytes: Some(64 << 20),
+//!     max_compressed_bytes: None,
+//! });
+//!
 //! // In order to use the read-only access traits, you need to construct a `CompressibleChunkStorageReader`.
 //! let local_cache = LocalChunkCache3::new();
 //! let reader = map.reader(&local_cache);
@@ -109,7 +146,37 @@
 //!
 //! // For efficient caching, you should flush your local cache back into the main storage when you are done with it.
 //! map.storage_mut().flush_local_cache(local_cache);
+//!
+//! // A `LocalChunkCache` can't be shared between threads. To read from many threads at once, use a `SharedChunkCache`, which
+//! // only decompresses each chunk once no matter how many threads read it.
+//! let shared_cache = SharedChunkCache3::new();
+//! let shared_reader = &map.shared_reader(&shared_cache);
+//! std::thread::scope(|s| {
+//!     for &p in write_points.iter() {
+//!         s.spawn(move || assert_eq!(shared_reader.get(p), 1));
+//!     }
+//! });
+//! map.storage_mut().flush_shared_cache(shared_cache);
 //! ```
+//!
+//! # Parallelism
+//!
+//! With the `rayon` feature enabled, there are parallel versions of the chunk visitors, like `par_visit_chunks` and
+//! `par_visit_mut_chunks`, as well as `par_for_each_mut`, `par_fill_extent` and `par_copy_extent`.
+
+mod ambient_chunks;
+mod diff;
+mod merge;
+mod neighborhood;
+#[cfg(feature = "rayon")]
+mod par_iter;
+
+pub(crate) use ambient_chunks::DeleteIfAmbientFn;
+pub use diff::*;
+pub use neighborhood::*;
+
+#[cfg(feature = "rayon")]
+pub use par_iter::*;
 
 use crate::{
     Array, ArrayCopySrc, ArrayIndexer, Channel, Chunk, ChunkHashMap, ChunkIndexer,
@@ -145,6 +212,7 @@ pub struct ChunkMap<N, T, Bldr, Store> {
     storage: Store,
     builder: Bldr,
     ambient_value: T, // Needed for GetRef to return a reference to non-temporary value
+    delete_if_ambient: Option<DeleteIfAmbientFn<N, T, Store>>,
 }
 
 /// A 2-dimensional `ChunkMap`.
@@ -283,7 +351,7 @@ where
 {
     /// Creates a map using the given `storage`.
     ///
-    /// All dimensions of `chunk_shape` must be powers of 2.
+    /// All dimensions of `chunk_shape` must be positive.
     fn new(builder: Bldr, storage: Store) -> Self {
         let indexer = ChunkIndexer::new(builder.chunk_shape());
         let ambient_value = builder.ambient_value();
@@ -293,6 +361,7 @@ where
             storage,
             ambient_value,
             builder,
+            delete_if_ambient: None,
         }
     }
 }
@@ -443,6 +512,8 @@ where
     }
 
     /// Call `visitor` on all chunks that overlap `extent`. Vacant chunks will be created first with ambient value.
+    ///
+    /// If `set_delete_ambient_chunks_on_write` is enabled, visited chunks that are left with only ambient values are deleted.
     #[inline]
     pub fn visit_mut_chunks(
         &mut self,
@@ -451,6 +522,15 @@ where
     ) {
         for chunk_key in self.indexer.chunk_keys_for_extent(extent) {
             visitor(self.get_mut_chunk_or_insert_ambient(chunk_key));
+            self.delete_chunk_if_ambient_on_write(chunk_key);
+        }
+    }
+
+    /// Applies the policy set by `set_delete_ambient_chunks_on_write`.
+    #[inline]
+    fn delete_chunk_if_ambient_on_write(&mut self, key: PointN<N>) {
+        if let Some(delete_if_ambient) = self.delete_if_ambient {
+            delete_if_ambient(&mut self.storage, &self.ambient_value, key);
         }
     }
 
@@ -818,6 +898,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn non_power_of_2_chunk_shape_copy_extent_then_read() {
+        let builder = ChunkMapBuilder3x1::new(PointN([24, 48, 24]), 0);
+        let mut map = builder.build_with_hash_map_storage();
+
+        let extent_to_copy = Extent3i::from_min_and_shape(Point3i::fill(-30), Point3i::fill(60));
+        let array = Array3x1::fill_with(extent_to_copy, |p| p.x() + p.y() + p.z());
+        copy_extent(&extent_to_copy, &array, &mut map);
+
+        let read_extent = extent_to_copy.padded(5);
+        for p in read_extent.iter_points() {
+            if extent_to_copy.contains(p) {
+                assert_eq!(map.get(p), p.x() + p.y() + p.z());
+            } else {
+                assert_eq!(map.get(p), 0);
+            }
+        }
+    }
+
     #[test]
     fn multichannel_accessors() {
         let builder = ChunkMapBuilder3x2::new(CHUNK_SHAPE, (0, 'a'));
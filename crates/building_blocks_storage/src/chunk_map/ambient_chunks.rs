@@ -0,0 +1,203 @@
+//! Garbage collection of chunks that are entirely ambient.
+//!
+//! Chunks are never removed from a `ChunkMap` implicitly, so after a large region is carved out (filled with the ambient
+//! value), the chunks in that region still occupy memory and serialization space even though they contribute nothing to the
+//! map. A chunk that takes the ambient value at every point is indistinguishable from a vacant chunk, so it can be deleted.
+//!
+//! There are two ways to get rid of these chunks:
+//!
+//! 1. Explicitly, by calling `delete_ambient_chunks` or `delete_ambient_chunks_in_extent`.
+//! 2. Automatically, by enabling `set_delete_ambient_chunks_on_write`. Then any chunk that is entirely ambient after being
+//!    written by `visit_mut_chunks` (and any of the methods built on it, like `for_each_mut`, `fill_extent` and
+//!    `copy_extent`) will be deleted. Writes through `GetMut` are *not* covered, since the mutable reference outlives the call.
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::prelude::*;
+//! let builder = ChunkMapBuilder3x1::new(Point3i::fill(16), 0);
+//! let mut map = builder.build_with_hash_map_storage();
+//!
+//! let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(32));
+//! map.fill_extent(&extent, 1);
+//! assert_eq!(map.storage().len(), 8);
+//!
+//! // Carve out everything.
+//! map.fill_extent(&extent, 0);
+//! assert_eq!(map.delete_ambient_chunks(), 8);
+//! assert_eq!(map.storage().len(), 0);
+//! ```
+
+use crate::{Array, Chunk, ChunkMap, ChunkMapBuilder, ChunkWriteStorage, Get, IterChunkKeys};
+
+use building_blocks_core::prelude::*;
+
+/// Deletes the chunk at `key` from `storage` if every point in the chunk has the `ambient_value`. Returns `true` iff the chunk
+/// was deleted.
+pub(crate) type DeleteIfAmbientFn<N, T, Store> = fn(&mut Store, &T, PointN<N>) -> bool;
+
+fn delete_chunk_if_ambient<N, T, Chan, Idx, Bldr, Store>(
+    storage: &mut Store,
+    ambient_value: &T,
+    key: PointN<N>,
+) -> bool
+where
+    PointN<N>: IntegerPoint<N>,
+    T: PartialEq,
+    Bldr: ChunkMapBuilder<N, T>,
+    Bldr::Chunk: Chunk<Array = Array<N, Chan, Idx>>,
+    Chan: Get<usize, Item = T>,
+    Store: ChunkWriteStorage<N, Bldr::Chunk>,
+{
+    let is_ambient = storage
+        .get_mut(key)
+        .map(|chunk| chunk.array().is_filled_with(ambient_value))
+        .unwrap_or(false);
+
+    if is_ambient {
+        storage.delete(key);
+    }
+
+    is_ambient
+}
+
+impl<N, T, Chan, Idx, Bldr, Store> ChunkMap<N, T, Bldr, Store>
+where
+    PointN<N>: IntegerPoint<N>,
+    T: PartialEq,
+    Bldr: ChunkMapBuilder<N, T>,
+    Bldr::Chunk: Chunk<Array = Array<N, Chan, Idx>>,
+    Chan: Get<usize, Item = T>,
+    Store: ChunkWriteStorage<N, Bldr::Chunk>,
+{
+    /// Deletes all chunks overlapping `extent` that take the ambient value at every point. Returns the number of chunks deleted.
+    pub fn delete_ambient_chunks_in_extent(&mut self, extent: &ExtentN<N>) -> usize {
+        let Self {
+            indexer,
+            storage,
+            ambient_value,
+            ..
+        } = self;
+
+        indexer
+            .chunk_keys_for_extent(extent)
+            .filter(|&key| {
+                delete_chunk_if_ambient::<N, T, Chan, Idx, Bldr, Store>(storage, ambient_value, key)
+            })
+            .count()
+    }
+
+    /// When `enable` is `true`, every chunk written by `visit_mut_chunks` will be deleted if it's left with only ambient values.
+    ///
+    /// This adds the cost of scanning each written chunk, but the scan stops at the first non-ambient value.
+    pub fn set_delete_ambient_chunks_on_write(&mut self, enable: bool) {
+        self.delete_if_ambient = if enable {
+            Some(delete_chunk_if_ambient::<N, T, Chan, Idx, Bldr, Store>)
+        } else {
+            None
+        };
+    }
+}
+
+impl<N, T, Chan, Idx, Bldr, Store> ChunkMap<N, T, Bldr, Store>
+where
+    PointN<N>: IntegerPoint<N>,
+    T: PartialEq,
+    Bldr: ChunkMapBuilder<N, T>,
+    Bldr::Chunk: Chunk<Array = Array<N, Chan, Idx>>,
+    Chan: Get<usize, Item = T>,
+    Store: ChunkWriteStorage<N, Bldr::Chunk> + for<'r> IterChunkKeys<'r, N>,
+{
+    /// Deletes all chunks that take the ambient value at every point. Returns the number of chunks deleted.
+    ///
+    /// Every chunk will be accessed mutably, so a compressed chunk storage will decompress all of its chunks.
+    pub fn delete_ambient_chunks(&mut self) -> usize {
+        let keys: Vec<_> = self.storage.chunk_keys().cloned().collect();
+
+        let Self {
+            storage,
+            ambient_value,
+            ..
+        } = self;
+
+        keys.into_iter()
+            .filter(|&key| {
+                delete_chunk_if_ambient::<N, T, Chan, Idx, Bldr, Store>(storage, ambient_value, key)
+            })
+            .count()
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    use building_blocks_core::prelude::*;
+
+    const BUILDER: ChunkMapBuilder3x1<i32> = ChunkMapBuilder3x1::new(PointN([16; 3]), 0);
+
+    #[test]
+    fn delete_only_ambient_chunks() {
+        let mut map = BUILDER.build_with_hash_map_storage();
+
+        let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(32));
+        map.fill_extent(&extent, 1);
+        *map.get_mut(Point3i::fill(-1)) = 0;
+        *map.get_mut(Point3i::fill(40)) = 2;
+        assert_eq!(map.storage().len(), 10);
+
+        // Carve out all but one voxel.
+        map.fill_extent(&extent, 0);
+        *map.get_mut(Point3i::fill(1)) = 1;
+
+        assert_eq!(map.delete_ambient_chunks(), 8);
+        assert_eq!(map.storage().len(), 2);
+        assert_eq!(map.get(Point3i::fill(1)), 1);
+        assert_eq!(map.get(Point3i::fill(40)), 2);
+        assert_eq!(map.get(Point3i::fill(-1)), 0);
+    }
+
+    #[test]
+    fn delete_ambient_chunks_in_extent_ignores_outside() {
+        let mut map = BUILDER.build_with_hash_map_storage();
+
+        *map.get_mut(Point3i::fill(-1)) = 0;
+        *map.get_mut(Point3i::fill(1)) = 0;
+
+        let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(16));
+        assert_eq!(map.delete_ambient_chunks_in_extent(&extent), 1);
+        assert!(map.get_chunk(Point3i::fill(-16)).is_some());
+    }
+
+    #[test]
+    fn delete_ambient_chunks_on_write() {
+        let mut map = BUILDER.build_with_hash_map_storage();
+        map.set_delete_ambient_chunks_on_write(true);
+
+        let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(32));
+        map.fill_extent(&extent, 1);
+        assert_eq!(map.storage().len(), 8);
+
+        // Only carve out the first chunk.
+        map.fill_extent(
+            &Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(16)),
+            0,
+        );
+        assert_eq!(map.storage().len(), 7);
+
+        // Writing ambient values into vacant space doesn't leave any chunks behind.
+        let far_extent = Extent3i::from_min_and_shape(Point3i::fill(100), Point3i::fill(20));
+        map.fill_extent(&far_extent, 0);
+        assert_eq!(map.storage().len(), 7);
+
+        map.set_delete_ambient_chunks_on_write(false);
+        map.fill_extent(&far_extent, 0);
+        assert_eq!(map.storage().len(), 15);
+    }
+}
@@ -0,0 +1,240 @@
+//! Differences between two versions of a `ChunkMap`.
+//!
+//! A `ChunkMapDiff` describes how to get from an older version of a map to a newer one, chunk by chunk. This is useful for
+//! sending incremental updates over the network or saving them to disk, since only the changed voxels need to be stored.
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::prelude::*;
+//! let builder = ChunkMapBuilder3x1::new(Point3i::fill(16), 0);
+//!
+//! let mut old_map = builder.clone().build_with_hash_map_storage();
+//! old_map.fill_extent(&Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(32)), 1);
+//!
+//! let mut new_map = builder.build_with_hash_map_storage();
+//! new_map.fill_extent(&Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(32)), 1);
+//! *new_map.get_mut(Point3i::fill(5)) = 2;
+//! *new_map.get_mut(Point3i::fill(-5)) = 3;
+//!
+//! let diff = old_map.diff(&new_map);
+//! old_map.apply_diff(diff);
+//!
+//! assert_eq!(old_map.get(Point3i::fill(5)), 2);
+//! assert_eq!(old_map.get(Point3i::fill(-5)), 3);
+//! ```
+
+use crate::{
+    Chunk, ChunkMap, ChunkMapBuilder, ChunkReadStorage, ChunkWriteStorage, ForEach, Get, GetMutPtr,
+    IndexedArray, IterChunkKeys, Local, MultiMutPtr, Stride,
+};
+
+use building_blocks_core::{bounding_extent, prelude::*};
+
+use serde::{Deserialize, Serialize};
+
+/// How a single chunk differs between two versions of a `ChunkMap`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum ChunkDiff<N, T, Ch> {
+    /// The chunk only exists in the newer map.
+    Added(Ch),
+    /// The chunk only exists in the older map.
+    Removed,
+    /// The chunk exists in both maps, but some of its points have different values.
+    Changed(ChunkDelta<N, T>),
+}
+
+/// The points of a chunk that changed, along with their new values.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChunkDelta<N, T> {
+    /// The smallest extent that bounds all of the changed points.
+    pub changed_extent: ExtentN<N>,
+    /// The new value at each changed point.
+    pub new_values: Vec<(PointN<N>, T)>,
+}
+
+/// All of the chunks that differ between two versions of a `ChunkMap`. Chunks that are identical in both versions are omitted.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChunkMapDiff<N, T, Ch> {
+    pub chunk_diffs: Vec<(PointN<N>, ChunkDiff<N, T, Ch>)>,
+}
+
+impl<N, T, Ch> ChunkMapDiff<N, T, Ch> {
+    /// Returns `true` iff the two versions of the map are identical.
+    pub fn is_empty(&self) -> bool {
+        self.chunk_diffs.is_empty()
+    }
+}
+
+impl<'a, N, T, Bldr, Store> ChunkMap<N, T, Bldr, Store>
+where
+    PointN<N>: IntegerPoint<N>,
+    T: PartialEq,
+    Bldr: ChunkMapBuilder<N, T>,
+    Bldr::Chunk: 'a + Clone,
+    <Bldr::Chunk as Chunk>::Array:
+        IndexedArray<N> + ForEach<N, (PointN<N>, Stride), Item = T> + Get<Stride, Item = T>,
+    Store: ChunkReadStorage<N, Bldr::Chunk> + IterChunkKeys<'a, N>,
+{
+    /// Returns the differences from `self` (the older version) to `new_map` (the newer version). Applying the returned diff to
+    /// `self` with `apply_diff` will reproduce `new_map`.
+    ///
+    /// Both maps must have the same chunk shape. Chunks are compared by key, so a chunk that is vacant in one map and entirely
+    /// ambient in the other is still considered `Added` or `Removed`.
+    pub fn diff<NewStore>(
+        &'a self,
+        new_map: &'a ChunkMap<N, T, Bldr, NewStore>,
+    ) -> ChunkMapDiff<N, T, Bldr::Chunk>
+    where
+        NewStore: ChunkReadStorage<N, Bldr::Chunk> + IterChunkKeys<'a, N>,
+    {
+        assert!(self.indexer.chunk_shape() == new_map.indexer.chunk_shape());
+
+        let mut chunk_diffs = Vec::new();
+
+        for &key in self.storage().chunk_keys() {
+            let old_chunk = self.get_chunk(key).unwrap();
+            match new_map.get_chunk(key) {
+                Some(new_chunk) => {
+                    if let Some(delta) = chunk_delta(old_chunk.array(), new_chunk.array()) {
+                        chunk_diffs.push((key, ChunkDiff::Changed(delta)));
+                    }
+                }
+                None => chunk_diffs.push((key, ChunkDiff::Removed)),
+            }
+        }
+
+        for &key in new_map.storage().chunk_keys() {
+            if self.get_chunk(key).is_none() {
+                let new_chunk = new_map.get_chunk(key).unwrap().clone();
+                chunk_diffs.push((key, ChunkDiff::Added(new_chunk)));
+            }
+        }
+
+        ChunkMapDiff { chunk_diffs }
+    }
+}
+
+fn chunk_delta<N, T, A>(old_array: &A, new_array: &A) -> Option<ChunkDelta<N, T>>
+where
+    PointN<N>: IntegerPoint<N>,
+    T: PartialEq,
+    A: IndexedArray<N> + ForEach<N, (PointN<N>, Stride), Item = T> + Get<Stride, Item = T>,
+{
+    debug_assert!(old_array.extent() == new_array.extent());
+
+    let mut new_values = Vec::new();
+    old_array.for_each(
+        old_array.extent(),
+        |(p, stride): (PointN<N>, Stride), old_value| {
+            let new_value = new_array.get(stride);
+            if new_value != old_value {
+                new_values.push((p, new_value));
+            }
+        },
+    );
+
+    if new_values.is_empty() {
+        return None;
+    }
+
+    let changed_extent = bounding_extent(new_values.iter().map(|(p, _)| *p));
+
+    Some(ChunkDelta {
+        changed_extent,
+        new_values,
+    })
+}
+
+impl<N, T, Bldr, Store> ChunkMap<N, T, Bldr, Store>
+where
+    PointN<N>: IntegerPoint<N>,
+    Bldr: ChunkMapBuilder<N, T>,
+    Store: ChunkWriteStorage<N, Bldr::Chunk>,
+{
+    /// Applies a diff returned by `diff`, transforming the older version of the map into the newer version.
+    ///
+    /// If a changed chunk is missing from `self`, it will be created with the ambient value first.
+    pub fn apply_diff<MutPtr>(&mut self, diff: ChunkMapDiff<N, T, Bldr::Chunk>)
+    where
+        <Bldr::Chunk as Chunk>::Array: IndexedArray<N> + GetMutPtr<Stride, Item = MutPtr>,
+        MutPtr: MultiMutPtr<Data = T>,
+    {
+        for (key, chunk_diff) in diff.chunk_diffs.into_iter() {
+            match chunk_diff {
+                ChunkDiff::Added(chunk) => self.write_chunk(key, chunk),
+                ChunkDiff::Removed => self.delete_chunk(key),
+                ChunkDiff::Changed(delta) => {
+                    let array = self.get_mut_chunk_or_insert_ambient(key).array_mut();
+                    let array_min = array.layout_extent().minimum;
+                    for (p, value) in delta.new_values.into_iter() {
+                        let stride = array.stride_from_local_point(Local(p - array_min));
+                        unsafe {
+                            array.get_mut_ptr(stride).write(value);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::prelude::*;
+
+    const BUILDER: ChunkMapBuilder3x1<i32> = ChunkMapBuilder3x1::new(PointN([16; 3]), 0);
+
+    #[test]
+    fn diff_and_apply_reproduces_new_map() {
+        let extent = Extent3i::from_min_and_shape(Point3i::fill(-16), Point3i::fill(48));
+
+        let mut old_map = BUILDER.build_with_hash_map_storage();
+        old_map.fill_extent(&extent, 1);
+        *old_map.get_mut(Point3i::fill(100)) = 1;
+
+        let mut new_map = BUILDER.build_with_hash_map_storage();
+        new_map.fill_extent(&extent, 1);
+        *new_map.get_mut(Point3i::fill(-100)) = 2;
+        let changed_extent = Extent3i::from_min_and_shape(Point3i::fill(2), Point3i::fill(3));
+        new_map.fill_extent(&changed_extent, 3);
+
+        let diff = old_map.diff(&new_map);
+
+        let mut num_added = 0;
+        let mut num_removed = 0;
+        let mut num_changed = 0;
+        for (key, chunk_diff) in diff.chunk_diffs.iter() {
+            match chunk_diff {
+                ChunkDiff::Added(_) => {
+                    assert_eq!(*key, Point3i::fill(-112));
+                    num_added += 1;
+                }
+                ChunkDiff::Removed => {
+                    assert_eq!(*key, Point3i::fill(96));
+                    num_removed += 1;
+                }
+                ChunkDiff::Changed(delta) => {
+                    assert_eq!(*key, Point3i::ZERO);
+                    assert_eq!(delta.changed_extent, changed_extent);
+                    assert_eq!(delta.new_values.len(), 27);
+                    num_changed += 1;
+                }
+            }
+        }
+        assert_eq!((num_added, num_removed, num_changed), (1, 1, 1));
+
+        old_map.apply_diff(diff);
+
+        assert_eq!(old_map.storage().len(), new_map.storage().len());
+        assert!(old_map.diff(&new_map).is_empty());
+    }
+}
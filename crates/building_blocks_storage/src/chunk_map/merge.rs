@@ -0,0 +1,214 @@
+//! Merging one `ChunkMap` into another, voxel by voxel.
+//!
+//! This is the way to overlay a "prefab" map onto a larger world map. The caller chooses how two overlapping values are
+//! combined, so the same method covers rules like "keep the max", "replace where non-empty" or a signed distance union.
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::prelude::*;
+//! let builder = ChunkMapBuilder3x1::new(Point3i::fill(16), 0);
+//!
+//! let mut world = builder.clone().build_with_hash_map_storage();
+//! world.fill_extent(&Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(8)), 1);
+//!
+//! let mut prefab = builder.build_with_hash_map_storage();
+//! prefab.fill_extent(&Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(4)), 2);
+//!
+//! // Place the prefab's origin at (6, 6, 6) and keep the larger value where they overlap.
+//! world.merge(&prefab, Some(Point3i::fill(6)), |world_value, prefab_value| world_value.max(prefab_value));
+//!
+//! assert_eq!(world.get(Point3i::fill(5)), 1);
+//! assert_eq!(world.get(Point3i::fill(7)), 2);
+//! assert_eq!(world.get(Point3i::fill(9)), 2);
+//! assert_eq!(world.get(Point3i::fill(10)), 0);
+//! ```
+
+use crate::{
+    Chunk, ChunkMap, ChunkMapBuilder, ChunkReadStorage, ChunkWriteStorage, ForEach, Get, GetMutPtr,
+    IterChunkKeys, MultiMutPtr, Stride,
+};
+
+use building_blocks_core::prelude::*;
+
+impl<N, T, Bldr, Store> ChunkMap<N, T, Bldr, Store>
+where
+    PointN<N>: IntegerPoint<N>,
+    Bldr: ChunkMapBuilder<N, T>,
+    Store: ChunkWriteStorage<N, Bldr::Chunk>,
+{
+    /// Merges `src_map` into `self`. For every point covered by an occupied chunk of either map, the value of `self` at that
+    /// point (translated by `translation`, if any) is replaced by `combine(self_value, src_value)`. Where the source chunk is
+    /// vacant, `src_value` is the source's ambient value, so rules like "keep the min" also clear the parts of `self` that
+    /// `src_map` doesn't cover.
+    ///
+    /// The merge happens chunk by chunk. Vacant chunks of `self` that overlap an occupied source chunk are created with the
+    /// ambient value first. Points where both maps are vacant are left alone, which assumes that
+    /// `combine(self_ambient, src_ambient) == self_ambient`. If `set_delete_ambient_chunks_on_write` is enabled, chunks of `self`
+    /// will be deleted if the merge leaves them entirely ambient.
+    pub fn merge<'a, S, SrcBldr, SrcStore, MutPtr>(
+        &mut self,
+        src_map: &'a ChunkMap<N, S, SrcBldr, SrcStore>,
+        translation: Option<PointN<N>>,
+        mut combine: impl FnMut(T, S) -> T,
+    ) where
+        Store: for<'r> IterChunkKeys<'r, N>,
+        <Bldr::Chunk as Chunk>::Array:
+            ForEach<N, (PointN<N>, Stride), Item = T> + GetMutPtr<Stride, Item = MutPtr>,
+        MutPtr: MultiMutPtr<Data = T>,
+        SrcBldr: ChunkMapBuilder<N, S>,
+        <SrcBldr::Chunk as Chunk>::Array: Get<PointN<N>, Item = S>,
+        SrcStore: ChunkReadStorage<N, SrcBldr::Chunk> + IterChunkKeys<'a, N>,
+    {
+        let translation = translation.unwrap_or(PointN::ZERO);
+
+        // Reused for each destination chunk, since we can't read and write a chunk in the same pass.
+        let mut combined_values = Vec::new();
+
+        // First combine with all of the occupied source chunks.
+        for &src_key in src_map.storage().chunk_keys() {
+            let src_chunk = match src_map.get_chunk(src_key) {
+                Some(chunk) => chunk.array(),
+                None => continue,
+            };
+            let dst_extent = src_map.indexer.extent_for_chunk_at_key(src_key) + translation;
+
+            self.visit_mut_chunks(&dst_extent, |dst_chunk| {
+                combine_in_extent(
+                    dst_chunk.array_mut(),
+                    &dst_extent,
+                    &mut combined_values,
+                    &mut combine,
+                    |p| src_chunk.get(p - translation),
+                );
+            });
+        }
+
+        // Then combine the rest of the occupied destination chunks with the source's ambient value.
+        let dst_keys: Vec<_> = self.storage.chunk_keys().cloned().collect();
+        for dst_key in dst_keys {
+            let dst_chunk_extent = self.indexer.extent_for_chunk_at_key(dst_key);
+            for src_key in src_map
+                .indexer
+                .chunk_keys_for_extent(&(dst_chunk_extent - translation))
+            {
+                if src_map.get_chunk(src_key).is_some() {
+                    continue;
+                }
+                let vacant_extent = (src_map.indexer.extent_for_chunk_at_key(src_key)
+                    + translation)
+                    .intersection(&dst_chunk_extent);
+                if let Some(dst_chunk) = self.get_mut_chunk(dst_key) {
+                    combine_in_extent(
+                        dst_chunk.array_mut(),
+                        &vacant_extent,
+                        &mut combined_values,
+                        &mut combine,
+                        |_| src_map.builder().ambient_value(),
+                    );
+                }
+            }
+            self.delete_chunk_if_ambient_on_write(dst_key);
+        }
+    }
+}
+
+/// Replaces every value of `dst_array` in `extent` with `combine(dst_value, src_value(p))`.
+fn combine_in_extent<N, T, S, A, MutPtr>(
+    dst_array: &mut A,
+    extent: &ExtentN<N>,
+    combined_values: &mut Vec<(Stride, T)>,
+    combine: &mut impl FnMut(T, S) -> T,
+    src_value: impl Fn(PointN<N>) -> S,
+) where
+    A: ForEach<N, (PointN<N>, Stride), Item = T> + GetMutPtr<Stride, Item = MutPtr>,
+    MutPtr: MultiMutPtr<Data = T>,
+{
+    combined_values.clear();
+    dst_array.for_each(extent, |(p, stride): (PointN<N>, Stride), dst_value| {
+        combined_values.push((stride, combine(dst_value, src_value(p))));
+    });
+
+    for (stride, value) in combined_values.drain(..) {
+        unsafe {
+            dst_array.get_mut_ptr(stride).write(value);
+        }
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    use building_blocks_core::prelude::*;
+
+    const BUILDER: ChunkMapBuilder3x1<i32> = ChunkMapBuilder3x1::new(PointN([16; 3]), 0);
+
+    #[test]
+    fn merge_translated_with_different_chunk_shapes() {
+        let mut dst = BUILDER.build_with_hash_map_storage();
+        let dst_extent = Extent3i::from_min_and_shape(Point3i::fill(-10), Point3i::fill(20));
+        dst.fill_extent(&dst_extent, 1);
+
+        let mut src = ChunkMapBuilder3x1::new(Point3i::fill(8), 0).build_with_hash_map_storage();
+        let src_extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(12));
+        src.for_each_mut(&src_extent, |p: Point3i, value| *value = p.x());
+
+        let translation = PointN([3, -5, 7]);
+        dst.merge(&src, Some(translation), |d, s| d + s);
+
+        let read_extent = dst_extent.padded(10);
+        dst.for_each(&read_extent, |p, value| {
+            let expected_dst = dst_extent.contains(p) as i32;
+            let expected_src = if src_extent.contains(p - translation) {
+                (p - translation).x()
+            } else {
+                0
+            };
+            assert_eq!(value, expected_dst + expected_src);
+        });
+    }
+
+    #[test]
+    fn merge_combines_vacant_src_chunks_with_src_ambient() {
+        let mut dst = BUILDER.build_with_hash_map_storage();
+        let dst_extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(32));
+        dst.fill_extent(&dst_extent, 1);
+
+        let mut src = BUILDER.build_with_hash_map_storage();
+        *src.get_mut(Point3i::fill(1)) = 1;
+
+        // Intersection: only the points set in both maps are kept.
+        dst.merge(&src, Some(Point3i::fill(2)), |d, s| d.min(s));
+
+        dst.for_each(&dst_extent, |p: Point3i, value| {
+            assert_eq!(value, (p == Point3i::fill(3)) as i32);
+        });
+    }
+
+    #[test]
+    fn merge_skips_vacant_src_chunks() {
+        let mut dst = BUILDER.build_with_hash_map_storage();
+        dst.fill_extent(
+            &Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(32)),
+            1,
+        );
+
+        let mut src = BUILDER.build_with_hash_map_storage();
+        *src.get_mut(Point3i::fill(1)) = 5;
+
+        // Replace where the source is non-empty.
+        dst.merge(&src, None, |d, s| if s != 0 { s } else { d });
+
+        assert_eq!(dst.get(Point3i::fill(1)), 5);
+        assert_eq!(dst.get(Point3i::fill(2)), 1);
+        assert_eq!(dst.get(Point3i::fill(20)), 1);
+        assert_eq!(dst.storage().len(), 8);
+    }
+}
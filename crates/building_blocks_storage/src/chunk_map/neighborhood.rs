@@ -0,0 +1,302 @@
+use crate::{
+    AmbientExtent, Array, ArrayCopySrc, ArrayIndexer, ArrayNx1, Chunk, ChunkCopySrc,
+    ChunkCopySrcIter, ChunkIndexer, ChunkMap, ChunkMapBuilder, ChunkReadStorage, ForEach, Get,
+    GetRef, MultiRef, ReadExtent,
+};
+
+use building_blocks_core::prelude::*;
+
+use either::Either;
+
+/// Borrows all of the chunks of a `ChunkMap` that overlap some extent, so the points in that extent can be read without copying
+/// them into a new `Array`.
+///
+/// Unlike the `ChunkMap` itself, a neighborhood only hashes the chunk keys once, on construction. After that, finding the chunk
+/// for a point is just a lookup in a small array of chunk references. Vacant chunks take the ambient value.
+///
+/// Each chunk keeps its own `Stride`s, so kernel-based algorithms that need strides should use `visit_chunks` to run on each
+/// chunk separately, or copy the extent into an `Array` with `copy_extent`. The neighborhood implements `ReadExtent`, so that
+/// copy is done one chunk at a time with strides, like copying from the `ChunkMap` itself, just without hashing any keys.
+pub struct ChunkNeighborhood<'a, N, T, A> {
+    extent: ExtentN<N>,
+    indexer: ChunkIndexer<N>,
+    // Indexed by chunk coordinates, i.e. the chunk key divided by the chunk shape.
+    chunks: ArrayNx1<N, Option<&'a A>>,
+    ambient_value: T,
+}
+
+impl<N, T, Bldr, Store> ChunkMap<N, T, Bldr, Store>
+where
+    N: ArrayIndexer<N>,
+    PointN<N>: IntegerPoint<N>,
+    Bldr: ChunkMapBuilder<N, T>,
+    Store: ChunkReadStorage<N, Bldr::Chunk>,
+{
+    /// Borrows all of the chunks that overlap `extent`.
+    pub fn neighborhood(
+        &self,
+        extent: ExtentN<N>,
+    ) -> ChunkNeighborhood<'_, N, T, <Bldr::Chunk as Chunk>::Array> {
+        let chunk_shape = self.indexer.chunk_shape();
+        let chunks = if extent.is_empty() {
+            // The maximum of an empty extent is less than its minimum, so there are no chunk coordinates to cover.
+            Array::fill(
+                ExtentN::from_min_and_shape(PointN::ZERO, PointN::ZERO),
+                None,
+            )
+        } else {
+            let chunk_coords_extent = ExtentN::from_min_and_max(
+                self.indexer.chunk_coords_containing_point(extent.minimum),
+                self.indexer.chunk_coords_containing_point(extent.max()),
+            );
+            Array::fill_with(chunk_coords_extent, |coords| {
+                self.get_chunk(coords * chunk_shape)
+                    .map(|chunk| chunk.array())
+            })
+        };
+
+        ChunkNeighborhood {
+            extent,
+            indexer: ChunkIndexer::new(chunk_shape),
+            chunks,
+            ambient_value: self.builder.ambient_value(),
+        }
+    }
+
+    /// Borrows the chunk at `key`, along with the neighboring chunks that overlap a border of `padding` points around it. This
+    /// is the input for algorithms that need a padded chunk, like meshing.
+    pub fn padded_chunk_neighborhood(
+        &self,
+        key: PointN<N>,
+        padding: i32,
+    ) -> ChunkNeighborhood<'_, N, T, <Bldr::Chunk as Chunk>::Array> {
+        self.neighborhood(self.indexer.extent_for_chunk_at_key(key).padded(padding))
+    }
+}
+
+impl<'a, N, T, A> ChunkNeighborhood<'a, N, T, A>
+where
+    N: ArrayIndexer<N>,
+    PointN<N>: IntegerPoint<N>,
+{
+    /// The extent that can be accessed.
+    #[inline]
+    pub fn extent(&self) -> &ExtentN<N> {
+        &self.extent
+    }
+
+    /// Calls `visitor` on every chunk that overlaps `extent`, along with the part of `extent` that's in the chunk. Vacant chunks
+    /// are `None`. Within each sub-extent, `Stride`s of the chunk array can be used as usual.
+    pub fn visit_chunks(
+        &self,
+        extent: &ExtentN<N>,
+        mut visitor: impl FnMut(&ExtentN<N>, Option<&'a A>),
+    ) {
+        let extent = extent.intersection(&self.extent);
+        if extent.is_empty() {
+            return;
+        }
+        let chunk_coords_extent = ExtentN::from_min_and_max(
+            self.indexer.chunk_coords_containing_point(extent.minimum),
+            self.indexer.chunk_coords_containing_point(extent.max()),
+        );
+        let chunk_shape = self.indexer.chunk_shape();
+        for coords in chunk_coords_extent.iter_points() {
+            let chunk_extent = self.indexer.extent_for_chunk_at_key(coords * chunk_shape);
+            visitor(
+                &extent.intersection(&chunk_extent),
+                *self.chunks.get_ref(coords),
+            );
+        }
+    }
+
+    #[inline]
+    fn chunk_containing_point(&self, p: PointN<N>) -> Option<&'a A> {
+        debug_assert!(self.extent.contains(p));
+
+        *self
+            .chunks
+            .get_ref(self.indexer.chunk_coords_containing_point(p))
+    }
+}
+
+impl<'a, N, T, A> Get<PointN<N>> for ChunkNeighborhood<'a, N, T, A>
+where
+    N: ArrayIndexer<N>,
+    PointN<N>: IntegerPoint<N>,
+    T: Clone,
+    A: Get<PointN<N>, Item = T>,
+{
+    type Item = T;
+
+    #[inline]
+    fn get(&self, p: PointN<N>) -> Self::Item {
+        self.chunk_containing_point(p)
+            .map(|chunk| chunk.get(p))
+            .unwrap_or_else(|| self.ambient_value.clone())
+    }
+}
+
+impl<'a, 'b, N, T, A, Ref> GetRef<'b, PointN<N>> for ChunkNeighborhood<'a, N, T, A>
+where
+    'a: 'b,
+    N: ArrayIndexer<N>,
+    PointN<N>: IntegerPoint<N>,
+    A: GetRef<'b, PointN<N>, Item = Ref>,
+    Ref: MultiRef<'b, Data = T>,
+{
+    type Item = Ref;
+
+    #[inline]
+    fn get_ref(&'b self, p: PointN<N>) -> Self::Item {
+        self.chunk_containing_point(p)
+            .map(|chunk| chunk.get_ref(p))
+            .unwrap_or_else(|| Ref::from_data_ref(&self.ambient_value))
+    }
+}
+
+impl<'a, N, T, A> ForEach<N, PointN<N>> for ChunkNeighborhood<'a, N, T, A>
+where
+    N: ArrayIndexer<N>,
+    PointN<N>: IntegerPoint<N>,
+    T: Clone,
+    A: ForEach<N, PointN<N>, Item = T>,
+{
+    type Item = T;
+
+    /// Iterates over each chunk with its own (strided) `ForEach` impl.
+    #[inline]
+    fn for_each(&self, extent: &ExtentN<N>, mut f: impl FnMut(PointN<N>, Self::Item)) {
+        self.visit_chunks(extent, |sub_extent, chunk| match chunk {
+            Some(chunk) => chunk.for_each(sub_extent, &mut f),
+            None => {
+                for p in sub_extent.iter_points() {
+                    f(p, self.ambient_value.clone());
+                }
+            }
+        });
+    }
+}
+
+impl<'a, 'b, N, T, A> ReadExtent<'b, N> for ChunkNeighborhood<'a, N, T, A>
+where
+    N: ArrayIndexer<N>,
+    PointN<N>: IntegerPoint<N>,
+    T: Clone,
+{
+    type Src = ChunkCopySrc<N, T, &'a A>;
+    type SrcIter = ChunkCopySrcIter<N, T, &'a A>;
+
+    fn read_extent(&'b self, extent: &ExtentN<N>) -> Self::SrcIter {
+        let mut srcs = Vec::new();
+        self.visit_chunks(extent, |sub_extent, chunk| {
+            let src = chunk
+                .map(|chunk| Either::Left(ArrayCopySrc(chunk)))
+                .unwrap_or_else(|| Either::Right(AmbientExtent::new(self.ambient_value.clone())));
+            srcs.push((*sub_extent, src));
+        });
+
+        srcs.into_iter()
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use crate::{copy_extent, prelude::*};
+
+    use building_blocks_core::prelude::*;
+
+    fn sparse_map(chunk_shape: Point3i) -> ChunkHashMap3x1<i32> {
+        let mut map = ChunkMapBuilder3x1::new(chunk_shape, -1).build_with_hash_map_storage();
+        // Leave some of the chunks around the origin vacant.
+        map.fill_extent(
+            &Extent3i::from_min_and_shape(Point3i::fill(-20), PointN([40, 20, 40])),
+            0,
+        );
+        for p in Extent3i::from_min_and_shape(Point3i::fill(-20), Point3i::fill(40)).iter_points() {
+            if p.y() < 0 {
+                *map.get_mut(p) = p.x() + p.z();
+            }
+        }
+
+        map
+    }
+
+    #[test]
+    fn padded_neighborhood_matches_map() {
+        for chunk_shape in [Point3i::fill(8), PointN([5, 6, 7])] {
+            let map = sparse_map(chunk_shape);
+            let key = map.indexer.chunk_key_containing_point(Point3i::ZERO);
+            let neighborhood = map.padded_chunk_neighborhood(key, 1);
+            let padded_extent = map.indexer.extent_for_chunk_at_key(key).padded(1);
+            assert_eq!(neighborhood.extent(), &padded_extent);
+
+            let mut num_points = 0;
+            neighborhood.for_each(
+                &Extent3i::from_min_and_shape(Point3i::fill(-50), Point3i::fill(100)),
+                |p, value| {
+                    assert!(padded_extent.contains(p));
+                    assert_eq!(value, map.get(p));
+                    assert_eq!(neighborhood.get(p), value);
+                    assert_eq!(*neighborhood.get_ref(p), value);
+                    num_points += 1;
+                },
+            );
+            assert_eq!(num_points, padded_extent.num_points());
+        }
+    }
+
+    #[test]
+    fn copy_from_neighborhood_matches_copy_from_map() {
+        let map = sparse_map(PointN([5, 6, 7]));
+        let neighborhood = map.padded_chunk_neighborhood(Point3i::ZERO, 1);
+        let padded_extent = *neighborhood.extent();
+
+        let mut from_neighborhood = Array3x1::fill(padded_extent, 100);
+        copy_extent(&padded_extent, &neighborhood, &mut from_neighborhood);
+        let mut from_map = Array3x1::fill(padded_extent, 100);
+        copy_extent(&padded_extent, &map, &mut from_map);
+
+        assert_eq!(from_neighborhood, from_map);
+    }
+
+    #[test]
+    fn empty_neighborhood_has_no_chunks() {
+        let map = sparse_map(Point3i::fill(8));
+        let neighborhood = map.neighborhood(Extent3i::from_min_and_shape(
+            Point3i::fill(3),
+            PointN([4, 0, -2]),
+        ));
+
+        let mut num_chunks = 0;
+        neighborhood.visit_chunks(neighborhood.extent(), |_, _| num_chunks += 1);
+        assert_eq!(num_chunks, 0);
+        neighborhood.for_each(neighborhood.extent(), |_, _: i32| panic!());
+    }
+
+    #[test]
+    fn visit_chunks_splits_extent() {
+        let map = sparse_map(Point3i::fill(8));
+        let neighborhood = map.padded_chunk_neighborhood(Point3i::ZERO, 1);
+
+        let mut num_chunks = 0;
+        let mut num_vacant = 0;
+        let mut num_points = 0;
+        neighborhood.visit_chunks(neighborhood.extent(), |sub_extent, chunk| {
+            num_chunks += 1;
+            num_vacant += chunk.is_none() as i32;
+            num_points += sub_extent.num_points();
+        });
+        assert_eq!(num_chunks, 27);
+        // Only the chunks with y < 0 were written.
+        assert_eq!(num_vacant, 18);
+        assert_eq!(num_points, neighborhood.extent().num_points());
+    }
+}
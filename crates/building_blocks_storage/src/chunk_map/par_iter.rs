@@ -0,0 +1,238 @@
+//! Parallel iteration over the chunks of a `ChunkMap`, powered by [rayon](https://docs.rs/rayon).
+//!
+//! Work is always split by chunk, so each chunk is visited by exactly one thread. Mutable parallel visits temporarily take the
+//! chunks out of the chunk storage and write them back when all visitors are done, so any `ChunkWriteStorage` is supported.
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::prelude::*;
+//! let builder = ChunkMapBuilder3x1::new(Point3i::fill(16), 0);
+//! let mut map = builder.build_with_hash_map_storage();
+//!
+//! let extent = Extent3i::from_min_and_shape(Point3i::fill(-50), Point3i::fill(100));
+//! map.par_for_each_mut(&extent, |p, value| *value = p.x());
+//!
+//! map.par_visit_occupied_chunks(&extent, |chunk| {
+//!     let written = extent.intersection(chunk.extent());
+//!     chunk.for_each(&written, |p: Point3i, value| assert_eq!(value, p.x()));
+//! });
+//! ```
+
+use crate::{
+    AmbientExtent, Chunk, ChunkMap, ChunkMapBuilder, ChunkReadStorage, ChunkWriteStorage,
+    ForEachMutPtr, IntoMultiMut, MultiMutPtr, ReadExtent, WriteExtent,
+};
+
+use building_blocks_core::prelude::*;
+
+use either::Either;
+use rayon::prelude::*;
+
+impl<N, T, Bldr, Store> ChunkMap<N, T, Bldr, Store>
+where
+    Self: Sync,
+    PointN<N>: IntegerPoint<N> + Send,
+    Bldr: ChunkMapBuilder<N, T>,
+    Bldr::Chunk: Sync,
+    Store: ChunkReadStorage<N, Bldr::Chunk>,
+{
+    /// The same as `visit_chunks`, but chunks are visited in parallel.
+    pub fn par_visit_chunks(
+        &self,
+        extent: &ExtentN<N>,
+        visitor: impl Fn(Either<&Bldr::Chunk, (&ExtentN<N>, AmbientExtent<N, T>)>) + Send + Sync,
+    ) {
+        let chunk_keys: Vec<_> = self.indexer.chunk_keys_for_extent(extent).collect();
+
+        chunk_keys.into_par_iter().for_each(|chunk_key| {
+            if let Some(chunk) = self.get_chunk(chunk_key) {
+                visitor(Either::Left(chunk))
+            } else {
+                let chunk_extent = self.indexer.extent_for_chunk_at_key(chunk_key);
+                visitor(Either::Right((
+                    &chunk_extent,
+                    AmbientExtent::new(self.builder.ambient_value()),
+                )))
+            }
+        });
+    }
+
+    /// The same as `visit_occupied_chunks`, but chunks are visited in parallel.
+    pub fn par_visit_occupied_chunks(
+        &self,
+        extent: &ExtentN<N>,
+        visitor: impl Fn(&Bldr::Chunk) + Send + Sync,
+    ) {
+        let chunk_keys: Vec<_> = self.indexer.chunk_keys_for_extent(extent).collect();
+
+        chunk_keys.into_par_iter().for_each(|chunk_key| {
+            if let Some(chunk) = self.get_chunk(chunk_key) {
+                visitor(chunk)
+            }
+        });
+    }
+}
+
+impl<N, T, Bldr, Store> ChunkMap<N, T, Bldr, Store>
+where
+    PointN<N>: IntegerPoint<N> + Send + Sync,
+    Bldr: ChunkMapBuilder<N, T>,
+    Bldr::Chunk: Send,
+    Store: ChunkWriteStorage<N, Bldr::Chunk>,
+{
+    /// The same as `visit_mut_chunks`, but chunks are visited in parallel. Vacant chunks will be created first with ambient
+    /// value.
+    pub fn par_visit_mut_chunks(
+        &mut self,
+        extent: &ExtentN<N>,
+        visitor: impl Fn(&mut Bldr::Chunk) + Send + Sync,
+    ) {
+        self.par_visit_mut_chunks_with_extents(extent, |_chunk_extent, chunk| visitor(chunk));
+    }
+
+    /// Takes all chunks overlapping `extent` out of storage (inserting ambient chunks where vacant), visits them in parallel,
+    /// then writes them back.
+    fn par_visit_mut_chunks_with_extents(
+        &mut self,
+        extent: &ExtentN<N>,
+        visitor: impl Fn(&ExtentN<N>, &mut Bldr::Chunk) + Send + Sync,
+    ) {
+        let mut taken_chunks: Vec<_> = self
+            .indexer
+            .chunk_keys_for_extent(extent)
+            .map(|chunk_key| {
+                let chunk_extent = self.indexer.extent_for_chunk_at_key(chunk_key);
+                let chunk = self
+                    .pop_chunk(chunk_key)
+                    .unwrap_or_else(|| self.builder.new_ambient(chunk_extent));
+
+                (chunk_extent, chunk)
+            })
+            .collect();
+
+        taken_chunks
+            .par_iter_mut()
+            .for_each(|(chunk_extent, chunk)| visitor(chunk_extent, chunk));
+
+        for (chunk_extent, chunk) in taken_chunks.into_iter() {
+            self.write_chunk(chunk_extent.minimum, chunk);
+            self.delete_chunk_if_ambient_on_write(chunk_extent.minimum);
+        }
+    }
+
+    /// The same as `ForEachMut::for_each_mut`, but chunks are visited in parallel.
+    pub fn par_for_each_mut<'a, MutPtr>(
+        &'a mut self,
+        extent: &ExtentN<N>,
+        f: impl Fn(PointN<N>, <MutPtr as IntoMultiMut<'a>>::MultiMut) + Send + Sync,
+    ) where
+        <Bldr::Chunk as Chunk>::Array: ForEachMutPtr<N, PointN<N>, Item = MutPtr>,
+        MutPtr: IntoMultiMut<'a>,
+    {
+        self.par_visit_mut_chunks(extent, |chunk| unsafe {
+            chunk
+                .array_mut()
+                .for_each_mut_ptr(extent, |p, ptr| f(p, ptr.into_multi_mut()))
+        });
+    }
+
+    /// The same as `fill_extent`, but chunks are filled in parallel.
+    pub fn par_fill_extent<MutPtr>(&mut self, extent: &ExtentN<N>, value: T)
+    where
+        <Bldr::Chunk as Chunk>::Array: ForEachMutPtr<N, PointN<N>, Item = MutPtr>,
+        T: Clone + Send + Sync,
+        MutPtr: MultiMutPtr<Data = T>,
+    {
+        self.par_visit_mut_chunks(extent, |chunk| unsafe {
+            chunk
+                .array_mut()
+                .for_each_mut_ptr(extent, |_p, ptr| ptr.write(value.clone()))
+        });
+    }
+}
+
+/// The same as `copy_extent`, but the destination chunks are written in parallel.
+///
+/// Each destination chunk reads its own overlapping portion of `src_map`, so `src_map` must be shareable across threads.
+pub fn par_copy_extent<'a, N, T, Bldr, Store, Src, Ms>(
+    extent: &ExtentN<N>,
+    src_map: &'a Ms,
+    dst_map: &mut ChunkMap<N, T, Bldr, Store>,
+) where
+    PointN<N>: IntegerPoint<N> + Send + Sync,
+    Bldr: ChunkMapBuilder<N, T>,
+    Bldr::Chunk: Send,
+    <Bldr::Chunk as Chunk>::Array: WriteExtent<N, Src>,
+    Store: ChunkWriteStorage<N, Bldr::Chunk>,
+    Ms: ReadExtent<'a, N, Src = Src> + Sync,
+{
+    dst_map.par_visit_mut_chunks_with_extents(extent, |chunk_extent, chunk| {
+        for (sub_extent, extent_src) in src_map.read_extent(&extent.intersection(chunk_extent)) {
+            chunk.array_mut().write_extent(&sub_extent, extent_src);
+        }
+    });
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{copy_extent, prelude::*};
+
+    const BUILDER: ChunkMapBuilder3x1<i32> = ChunkMapBuilder3x1::new(PointN([16; 3]), 0);
+
+    #[test]
+    fn par_for_each_mut_matches_sequential() {
+        let extent = Extent3i::from_min_and_shape(Point3i::fill(-20), Point3i::fill(50));
+
+        let mut seq_map = BUILDER.build_with_hash_map_storage();
+        seq_map.for_each_mut(&extent, |p, value| *value = p.x() + p.y() * p.z());
+
+        let mut par_map = BUILDER.build_with_hash_map_storage();
+        par_map.par_for_each_mut(&extent, |p, value| *value = p.x() + p.y() * p.z());
+
+        let read_extent = extent.padded(1);
+        par_map.for_each(&read_extent, |p, value| assert_eq!(value, seq_map.get(p)));
+        assert_eq!(par_map.bounding_extent(), seq_map.bounding_extent());
+    }
+
+    #[test]
+    fn par_fill_extent_then_read() {
+        let mut map = BUILDER.build_with_hash_map_storage();
+        let fill_extent = Extent3i::from_min_and_shape(Point3i::fill(10), Point3i::fill(40));
+        map.par_fill_extent(&fill_extent, 1);
+
+        let read_extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(64));
+        map.par_visit_chunks(&read_extent, |chunk| match chunk {
+            Either::Left(array) => array.for_each(array.extent(), |p: Point3i, value| {
+                assert_eq!(value, fill_extent.contains(p) as i32)
+            }),
+            Either::Right((chunk_extent, ambient)) => {
+                assert!(chunk_extent.intersection(&fill_extent).is_empty());
+                assert_eq!(ambient.get(), 0);
+            }
+        });
+    }
+
+    #[test]
+    fn par_copy_extent_from_array_matches_sequential() {
+        let src_extent = Extent3i::from_min_and_shape(Point3i::fill(-30), Point3i::fill(60));
+        let src = Array3x1::fill_with(src_extent, |p| p.x() * p.y() - p.z());
+        let copy_extent_ = Extent3i::from_min_and_shape(Point3i::fill(-25), Point3i::fill(40));
+
+        let mut seq_map = BUILDER.build_with_hash_map_storage();
+        copy_extent(&copy_extent_, &src, &mut seq_map);
+
+        let mut par_map = BUILDER.build_with_hash_map_storage();
+        par_copy_extent(&copy_extent_, &src, &mut par_map);
+
+        par_map.for_each(&src_extent, |p, value| assert_eq!(value, seq_map.get(p)));
+    }
+}
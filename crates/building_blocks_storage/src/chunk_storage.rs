@@ -1,9 +1,13 @@
 pub mod compressible;
 pub mod compressible_reader;
+pub mod compression_worker;
+pub mod disk;
 pub mod hash_map;
 
 pub use compressible::*;
 pub use compressible_reader::*;
+pub use compression_worker::*;
+pub use disk::*;
 pub use hash_map::*;
 
 use building_blocks_core::prelude::*;
@@ -1,26 +1,198 @@
 use crate::{
-    CacheEntry, ChunkMap, ChunkMapBuilder, ChunkWriteStorage, Compressed,
-    CompressibleChunkMapReader, CompressibleChunkStorageReader, Compression, FastArrayCompression,
+    ByteSize, CacheEntry, ChunkMap, ChunkMapBuilder, ChunkWriteStorage, Compressed,
+    CompressibleChunkMapReader, CompressibleChunkMapSharedReader, CompressibleChunkStorageReader,
+    CompressibleChunkStorageSharedReader, Compression, FastArrayCompression,
     FastChannelsCompression, FromBytesCompression, IterChunkKeys, LocalChunkCache, LruCacheEntries,
-    LruCacheIntoIter, LruCacheKeys, MaybeCompressed, SmallKeyLruCache,
+    LruCacheIntoIter, LruCacheKeys, LruPolicy, MaybeCompressed, ReplacementPolicy,
+    SharedChunkCache, SmallKeyHashMap, SmallKeyPolicyCache,
 };
 
 use building_blocks_core::prelude::*;
 
 use core::hash::Hash;
+use core::sync::atomic::{AtomicU64, Ordering};
 use slab::Slab;
+use std::collections::VecDeque;
+use std::io;
+use std::sync::Arc;
 
 /// A two-tier chunk storage. The first tier is an LRU cache of uncompressed chunks. The second tier is a `Slab` of compressed
 /// chunks.
-pub struct CompressibleChunkStorage<N, Compr>
+///
+/// By default, chunks are only compressed when `compress_lru` is called. With a `CompressionBudget`, the storage compresses
+/// chunks automatically to keep the memory usage of the decompressed tier under budget, and it can spill compressed chunks to a
+/// `SpillSink`, like a `DiskChunkStorage`, to keep the compressed tier under budget too. Spilled chunks are loaded back on
+/// access.
+///
+/// Which decompressed chunk gets compressed next is decided by the `ReplacementPolicy` `P`. The `LruPolicy` is the default,
+/// but scan-heavy workloads may do better with a `ClockPolicy` or `LfuPolicy`; see `with_policy`. Every mutable access is
+/// recorded by the policy, and so are reads through a reader, once its cache is flushed back into the storage.
+pub struct CompressibleChunkStorage<N, Compr, P = LruPolicy<PointN<N>>>
 where
     Compr: Compression,
 {
-    pub(crate) cache: SmallKeyLruCache<PointN<N>, Compr::Data, CompressedLocation>,
+    pub(crate) cache: SmallKeyPolicyCache<PointN<N>, Compr::Data, CompressedLocation, P>,
     pub(crate) compression: Compr,
     pub(crate) compressed: CompressedChunks<Compr>,
+    budget: CompressionBudget,
+    // Only set once memory is being tracked, i.e. after a budget is set.
+    byte_sizes: Option<ByteSizeFns<Compr>>,
+    // The size of each decompressed chunk at the time it was last measured, so the same number of bytes is subtracted from the
+    // counters when the chunk leaves the cache.
+    decompressed_sizes: SmallKeyHashMap<PointN<N>, usize>,
+    // The last chunk that was borrowed mutably. It could have changed size, so it's measured again before the budget is
+    // enforced.
+    borrowed: Option<PointN<N>>,
+    // The order in which chunks were compressed, used to choose which chunks to spill. Entries are not removed when a chunk is
+    // decompressed, so they must be checked against the cache before spilling.
+    compression_order: VecDeque<(PointN<N>, CompressedLocation)>,
+    // Where chunks are spilled to. Their cache entries are evicted to `CompressedLocation::SPILLED`.
+    spill_sink: Option<Box<dyn SpillSink<N, Compr> + Send + Sync>>,
+    // The error from the last chunk that failed to spill. No chunks are spilled until it's taken.
+    spill_error: Option<io::Error>,
+    pub(crate) counters: StorageCounters,
+    // Chunks that are being compressed in the background. Their cache entries are evicted to `CompressedLocation::IN_FLIGHT`.
+    // A chunk is removed from this map when it's modified, so the stale compressed version is not committed.
+    pub(crate) in_flight: SmallKeyHashMap<PointN<N>, InFlightChunk<Compr::Data>>,
+    next_generation: u64,
 }
 
+/// A chunk that should be compressed off of the main thread, e.g. by a `CompressionWorker`. Returned by
+/// `CompressibleChunkStorage::take_lru_for_compression`.
+///
+/// The chunk is shared with the storage, so it can still be read while it's being compressed. Drop the job's reference before
+/// committing the result, so the storage doesn't need to copy the chunk if it's accessed mutably.
+pub struct CompressionJob<N, Ch> {
+    pub key: PointN<N>,
+    /// Identifies this job, so a result can only be committed if the chunk was not modified after the job was created.
+    pub generation: u64,
+    pub chunk: Arc<Ch>,
+}
+
+// A chunk that's shared with a `CompressionJob` until the job's result is committed.
+pub(crate) struct InFlightChunk<Ch> {
+    generation: u64,
+    pub chunk: Arc<Ch>,
+    // Copies the chunk if it's needed back while the job still holds it. Only `take_lru_for_compression` requires `Clone`, so
+    // it's captured there.
+    clone_chunk: fn(&Ch) -> Ch,
+}
+
+impl<Ch> InFlightChunk<Ch> {
+    fn into_chunk(self) -> Ch {
+        let clone_chunk = self.clone_chunk;
+
+        Arc::try_unwrap(self.chunk).unwrap_or_else(|chunk| clone_chunk(&chunk))
+    }
+}
+
+/// Memory limits for each tier of a `CompressibleChunkStorage`. A limit of `None` means the tier is unbounded.
+///
+/// The budget is enforced after every insert and access. The chunk that was just inserted or accessed is never compressed, so
+/// the decompressed tier can exceed its budget by that one chunk.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CompressionBudget {
+    /// While the decompressed chunks use more bytes than this, the decompressed chunk chosen by the storage's
+    /// `ReplacementPolicy` is compressed.
+    pub max_decompressed_bytes: Option<usize>,
+    /// While the compressed chunks use more bytes than this, the chunk that was compressed the longest time ago is spilled to
+    /// the storage's `SpillSink`. This limit requires a sink; see `CompressibleChunkStorage::set_spill_sink`.
+    pub max_compressed_bytes: Option<usize>,
+}
+
+/// Somewhere to put the compressed chunks that don't fit in the `CompressionBudget` of a `CompressibleChunkStorage`, usually
+/// on disk.
+///
+/// The storage keeps track of which chunks are spilled. It loads a chunk back with `load` when a reader needs it, and it moves
+/// the chunk back into memory with `load` and then `remove` when it's accessed mutably, overwritten or removed.
+pub trait SpillSink<N, Compr>
+where
+    Compr: Compression,
+{
+    /// Saves `chunk` at `key`, replacing any chunk that was spilled there before. If this fails, the chunk stays in memory.
+    fn spill(&mut self, key: PointN<N>, chunk: &Compressed<Compr>) -> io::Result<()>;
+
+    /// Reads and decompresses the chunk that was spilled at `key`.
+    fn load(&self, key: PointN<N>) -> io::Result<Option<Compr::Data>>;
+
+    /// Removes the chunk that was spilled at `key`.
+    fn remove(&mut self, key: PointN<N>) -> io::Result<()>;
+}
+
+/// Statistics for a `CompressibleChunkStorage`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CompressibleChunkStorageStats {
+    /// The number of accesses to a decompressed chunk, including compressed chunks that a reader had already decompressed into
+    /// its cache.
+    pub hits: u64,
+    /// The number of accesses that had to decompress a chunk.
+    pub misses: u64,
+    /// The number of chunks that have been compressed.
+    pub compressions: u64,
+    /// The number of compressed chunks that have been spilled.
+    pub spills: u64,
+    /// The number of decompressed chunks.
+    pub num_decompressed: usize,
+    /// The number of compressed chunks.
+    pub num_compressed: usize,
+    /// The number of chunks that are currently spilled.
+    pub num_spilled: usize,
+    /// The approximate bytes used by decompressed chunks.
+    pub decompressed_bytes: usize,
+    /// The approximate bytes used by compressed chunks.
+    pub compressed_bytes: usize,
+}
+
+// Hits and misses are atomic so they can be counted by readers with only `&self`.
+#[derive(Default)]
+pub(crate) struct StorageCounters {
+    pub hits: AtomicU64,
+    pub misses: AtomicU64,
+    compressions: u64,
+    spills: u64,
+    decompressed_bytes: usize,
+    compressed_bytes: usize,
+    num_spilled: usize,
+}
+
+impl StorageCounters {
+    pub fn count_hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count_miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count_hit_or_miss(&self, missed: bool) {
+        if missed {
+            self.count_miss();
+        } else {
+            self.count_hit();
+        }
+    }
+}
+
+// Captured from the `ByteSize` impls when memory tracking starts, so the rest of the storage doesn't need `ByteSize` bounds.
+struct ByteSizeFns<Compr>
+where
+    Compr: Compression,
+{
+    decompressed: fn(&Compr::Data) -> usize,
+    compressed: fn(&Compressed<Compr>) -> usize,
+}
+
+impl<Compr> Clone for ByteSizeFns<Compr>
+where
+    Compr: Compression,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Compr> Copy for ByteSizeFns<Compr> where Compr: Compression {}
+
 pub type FastCompressibleChunkStorage<N, By, Chan> =
     CompressibleChunkStorage<N, FastArrayCompression<N, FastChannelsCompression<By, Chan>>>;
 
@@ -38,13 +210,45 @@ where
 
 pub type CompressedChunks<Compr> = Slab<Compressed<Compr>>;
 
-impl<N, Compr> CompressibleChunkStorage<N, Compr>
+impl<N, Compr, P> CompressibleChunkStorage<N, Compr, P>
 where
     Compr: Compression,
 {
     pub fn compression(&self) -> &Compr {
         &self.compression
     }
+
+    pub fn budget(&self) -> CompressionBudget {
+        self.budget
+    }
+
+    /// Sets the `SpillSink` that compressed chunks are spilled to when the compressed tier is over budget.
+    ///
+    /// The sink should be empty, since chunks that are already in it are not part of the storage.
+    ///
+    /// # Panics
+    ///
+    /// If chunks were already spilled to another sink.
+    pub fn set_spill_sink(&mut self, sink: impl 'static + SpillSink<N, Compr> + Send + Sync) {
+        assert_eq!(
+            self.counters.num_spilled, 0,
+            "Can't replace a spill sink that holds chunks"
+        );
+        self.spill_sink = Some(Box::new(sink));
+    }
+
+    /// The number of chunks that are spilled to the `SpillSink`.
+    pub fn len_spilled(&self) -> usize {
+        self.counters.num_spilled
+    }
+
+    /// Takes the error from the last chunk that failed to spill.
+    ///
+    /// When a chunk fails to spill, it stays compressed in memory, and the storage stops spilling chunks until the error is
+    /// taken, so the compressed tier can exceed its budget in the meantime.
+    pub fn take_spill_error(&mut self) -> Option<io::Error> {
+        self.spill_error.take()
+    }
 }
 
 impl<N, Compr> CompressibleChunkStorage<N, Compr>
@@ -53,10 +257,53 @@ where
     Compr: Compression,
 {
     pub fn new(compression: Compr) -> Self {
+        Self::with_policy(compression)
+    }
+
+    pub fn with_budget(compression: Compr, budget: CompressionBudget) -> Self
+    where
+        Compr::Data: ByteSize,
+        Compr::CompressedData: ByteSize,
+    {
+        let mut storage = Self::new(compression);
+        storage.set_budget(budget);
+
+        storage
+    }
+}
+
+impl<N, Compr, P> CompressibleChunkStorage<N, Compr, P>
+where
+    PointN<N>: Hash + IntegerPoint<N>,
+    Compr: Compression,
+    P: ReplacementPolicy<PointN<N>>,
+{
+    /// Creates a storage that chooses which chunks to compress with the `ReplacementPolicy` `P`.
+    ///
+    /// ```
+    /// # use building_blocks_core::prelude::*;
+    /// # use building_blocks_storage::prelude::*;
+    /// # use building_blocks_storage::{ClockPolicy, CompressibleChunkStorage, FastArrayCompressionNx1};
+    /// let compression = FastArrayCompressionNx1::from_bytes_compression(Lz4 { level: 10 });
+    /// let storage: CompressibleChunkStorage<_, _, ClockPolicy<Point3i>> = CompressibleChunkStorage::with_policy(compression);
+    /// let mut map = ChunkMapBuilder3x1::new(Point3i::fill(16), 0).build_with_write_storage(storage);
+    /// *map.get_mut(Point3i::ZERO) = 1;
+    /// ```
+    pub fn with_policy(compression: Compr) -> Self {
         Self {
-            cache: SmallKeyLruCache::default(),
+            cache: SmallKeyPolicyCache::default(),
             compression,
             compressed: Slab::new(),
+            budget: CompressionBudget::default(),
+            byte_sizes: None,
+            decompressed_sizes: SmallKeyHashMap::default(),
+            borrowed: None,
+            compression_order: VecDeque::new(),
+            spill_sink: None,
+            spill_error: None,
+            counters: StorageCounters::default(),
+            in_flight: SmallKeyHashMap::default(),
+            next_generation: 0,
         }
     }
 
@@ -65,20 +312,39 @@ where
     }
 
     pub fn len_compressed(&self) -> usize {
-        self.cache.len_cached()
+        self.cache.len_evicted() - self.in_flight.len() - self.counters.num_spilled
+    }
+
+    /// Resets the hit, miss, compression and spill counts to zero.
+    pub fn reset_stats(&mut self) {
+        self.counters.hits = AtomicU64::new(0);
+        self.counters.misses = AtomicU64::new(0);
+        self.counters.compressions = 0;
+        self.counters.spills = 0;
     }
 
     /// Returns a reader that implements `ChunkReadStorage`.
     pub fn reader<'a>(
         &'a self,
         local_cache: &'a LocalChunkCache<N, Compr::Data>,
-    ) -> CompressibleChunkStorageReader<'a, N, Compr> {
+    ) -> CompressibleChunkStorageReader<'a, N, Compr, P> {
         CompressibleChunkStorageReader {
             storage: self,
             local_cache,
         }
     }
 
+    /// Returns a reader that implements `ChunkReadStorage` and can be shared by many threads.
+    pub fn shared_reader<'a>(
+        &'a self,
+        shared_cache: &'a SharedChunkCache<N, Compr::Data>,
+    ) -> CompressibleChunkStorageSharedReader<'a, N, Compr, P> {
+        CompressibleChunkStorageSharedReader {
+            storage: self,
+            shared_cache,
+        }
+    }
+
     /// Returns a copy of the `Chunk` at `key`.
     ///
     /// WARNING: the cache will not be updated. This method should be used for a read-modify-write workflow where it would be
@@ -93,6 +359,12 @@ where
     {
         self.cache.get(&key).map(|entry| match entry {
             CacheEntry::Cached(chunk) => MaybeCompressed::Decompressed(chunk.clone()),
+            CacheEntry::Evicted(CompressedLocation::IN_FLIGHT) => {
+                MaybeCompressed::Decompressed(self.in_flight_chunk(&key).clone())
+            }
+            CacheEntry::Evicted(CompressedLocation::SPILLED) => {
+                MaybeCompressed::Decompressed(self.load_spilled(&key))
+            }
             CacheEntry::Evicted(location) => {
                 MaybeCompressed::Compressed(self.compressed.get(location.0).unwrap().clone())
             }
@@ -104,24 +376,15 @@ where
         &mut self,
         key: PointN<N>,
     ) -> Option<MaybeCompressed<Compr::Data, Compressed<Compr>>> {
-        self.cache.remove(&key).map(|entry| match entry {
-            CacheEntry::Cached(chunk) => MaybeCompressed::Decompressed(chunk),
-            CacheEntry::Evicted(location) => {
-                MaybeCompressed::Compressed(self.compressed.remove(location.0))
-            }
-        })
+        self.cache
+            .remove(&key)
+            .map(|entry| self.take_old_entry(key, entry))
     }
 
     /// Compress the least-recently-used, cached chunk. On access, compressed chunks will be
     /// decompressed and cached.
     pub fn compress_lru(&mut self) {
-        let compressed_entry = self.compressed.vacant_entry();
-        if let Some((_, lru_chunk)) = self
-            .cache
-            .evict_lru(CompressedLocation(compressed_entry.key()))
-        {
-            compressed_entry.insert(self.compression.compress(&lru_chunk));
-        }
+        self.compress_lru_except(None);
     }
 
     /// Remove the least-recently-used, cached chunk.
@@ -129,7 +392,10 @@ where
     /// This is useful for removing a batch of chunks at a time before compressing them in parallel. Then call
     /// `insert_compressed`.
     pub fn remove_lru(&mut self) -> Option<(PointN<N>, Compr::Data)> {
-        self.cache.remove_lru()
+        let (key, chunk) = self.cache.remove_lru()?;
+        self.untrack_decompressed(key);
+
+        Some((key, chunk))
     }
 
     /// Insert a compressed chunk. Returns the old chunk if one exists.
@@ -138,57 +404,577 @@ where
         key: PointN<N>,
         compressed_chunk: Compressed<Compr>,
     ) -> Option<MaybeCompressed<Compr::Data, Compressed<Compr>>> {
-        let compressed_entry = self.compressed.vacant_entry();
-        let old_entry = self
-            .cache
-            .evict(key, CompressedLocation(compressed_entry.key()));
-        compressed_entry.insert(compressed_chunk);
+        let location = CompressedLocation(self.compressed.vacant_key());
+        let old_entry = self.cache.evict(key, location);
+        self.counters.compressed_bytes += self.compressed_size(&compressed_chunk);
+        self.counters.compressions += 1;
+        self.compressed.insert(compressed_chunk);
+        self.track_compressed(key, location);
 
-        old_entry.map(|entry| match entry {
-            CacheEntry::Cached(chunk) => MaybeCompressed::Decompressed(chunk),
-            CacheEntry::Evicted(old_location) => {
-                MaybeCompressed::Compressed(self.compressed.remove(old_location.0))
-            }
-        })
+        let old_chunk = old_entry.map(|entry| self.take_old_entry(key, entry));
+        self.enforce_budget();
+
+        old_chunk
     }
 
     /// Consumes and flushes the chunk cache into the chunk map. This is not strictly necessary, but
     /// it will help with caching efficiency.
-    pub fn flush_local_cache(&mut self, local_cache: LocalChunkCache<N, Compr::Data>) {
+    ///
+    /// Each decompressed chunk that the reader accessed is touched once in the replacement policy.
+    pub fn flush_local_cache(&mut self, mut local_cache: LocalChunkCache<N, Compr::Data>) {
+        for key in local_cache.take_touched() {
+            self.cache.touch_if_cached(key);
+        }
         for (key, chunk) in local_cache.flush_iter() {
             self.insert_chunk(key, chunk);
         }
     }
 
+    /// Consumes and flushes the shared chunk cache into the chunk map, so the chunks decompressed by all of the threads that
+    /// used a `CompressibleChunkStorageSharedReader` become cached. This is not strictly necessary, but it will help with
+    /// caching efficiency.
+    ///
+    /// Each decompressed chunk that the readers accessed is touched once in the replacement policy.
+    pub fn flush_shared_cache(&mut self, mut shared_cache: SharedChunkCache<N, Compr::Data>) {
+        for key in shared_cache.take_touched() {
+            self.cache.touch_if_cached(key);
+        }
+        for (key, chunk) in shared_cache.flush_iter() {
+            self.insert_chunk(key, chunk);
+        }
+    }
+
     /// Inserts `chunk` at `key` and returns the old chunk.
     pub fn insert_chunk(
         &mut self,
         key: PointN<N>,
         chunk: Compr::Data,
     ) -> Option<MaybeCompressed<Compr::Data, Compressed<Compr>>> {
-        self.cache
+        let num_bytes = self.decompressed_size(&chunk);
+        let old_chunk = self
+            .cache
             .insert(key, chunk)
-            .map(|old_entry| match old_entry {
-                CacheEntry::Cached(old_chunk) => MaybeCompressed::Decompressed(old_chunk),
+            .map(|old_entry| self.take_old_entry(key, old_entry));
+        self.track_decompressed(key, num_bytes);
+        self.enforce_budget_keeping(key);
+
+        old_chunk
+    }
+
+    /// Compresses and spills chunks until every tier is within the `CompressionBudget`. This is called automatically after
+    /// every insert and access.
+    pub fn enforce_budget(&mut self) {
+        self.compress_over_budget(None);
+    }
+
+    /// Takes up to `max_chunks` of the least-recently-used, cached chunks, so they can be compressed on another thread.
+    ///
+    /// The chunks are not copied. They stay readable until the jobs are finished, and a mutable access takes a chunk back. Compress
+    /// each job's chunk and pass the result to `commit_compressed`, or to `cancel_compression` if it couldn't be compressed.
+    pub fn take_lru_for_compression(
+        &mut self,
+        max_chunks: usize,
+    ) -> Vec<CompressionJob<N, Compr::Data>>
+    where
+        Compr::Data: Clone,
+    {
+        // The chunks are still decompressed, so they keep their tracked sizes. Make sure those are current.
+        self.measure_borrowed();
+
+        let mut jobs = Vec::new();
+        for _ in 0..max_chunks.min(self.cache.len_cached()) {
+            let (key, chunk) = self.cache.evict_lru(CompressedLocation::IN_FLIGHT).unwrap();
+            let chunk = Arc::new(chunk);
+            let generation = self.next_generation;
+            self.next_generation += 1;
+            self.in_flight.insert(
+                key,
+                InFlightChunk {
+                    generation,
+                    chunk: chunk.clone(),
+                    clone_chunk: Compr::Data::clone,
+                },
+            );
+            jobs.push(CompressionJob {
+                key,
+                generation,
+                chunk,
+            });
+        }
+
+        jobs
+    }
+
+    /// Replaces the chunk at `key` with `compressed_chunk`, which was compressed from the `CompressionJob` with `generation`. If
+    /// the chunk was modified, removed or resubmitted since the job was created, then `compressed_chunk` is stale, so it's
+    /// dropped and this returns `false`.
+    ///
+    /// Any mutable access counts as a modification, since it can't be known whether the chunk was actually written.
+    pub fn commit_compressed(
+        &mut self,
+        key: PointN<N>,
+        generation: u64,
+        compressed_chunk: Compressed<Compr>,
+    ) -> bool {
+        if !self.is_in_flight(key, generation) {
+            return false;
+        }
+        self.in_flight.remove(&key);
+        self.untrack_decompressed(key);
+
+        let location = CompressedLocation(self.compressed.vacant_key());
+        self.counters.compressed_bytes += self.compressed_size(&compressed_chunk);
+        self.counters.compressions += 1;
+        self.compressed.insert(compressed_chunk);
+        // Replaces the `IN_FLIGHT` entry.
+        self.cache.evict(key, location);
+        self.track_compressed(key, location);
+        self.spill_over_budget();
+
+        true
+    }
+
+    /// Puts the chunk of the `CompressionJob` with `generation` back in the cache, e.g. because compressing it failed. Returns
+    /// `false` if the chunk was already modified, removed or resubmitted.
+    pub fn cancel_compression(&mut self, key: PointN<N>, generation: u64) -> bool {
+        if !self.is_in_flight(key, generation) {
+            return false;
+        }
+        let chunk = self.in_flight.remove(&key).unwrap().into_chunk();
+        self.cache.insert(key, chunk);
+
+        true
+    }
+
+    /// The number of chunks that were taken for compression and have not been committed or modified.
+    pub fn len_in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    fn is_in_flight(&self, key: PointN<N>, generation: u64) -> bool {
+        matches!(self.in_flight.get(&key), Some(chunk) if chunk.generation == generation)
+    }
+
+    pub(crate) fn in_flight_chunk(&self, key: &PointN<N>) -> &Compr::Data {
+        &self.in_flight[key].chunk
+    }
+
+    // Decompresses the chunk at `key`, which was evicted to `location`, without taking it out of the storage.
+    pub(crate) fn decompress_evicted(
+        &self,
+        key: &PointN<N>,
+        location: CompressedLocation,
+    ) -> Compr::Data {
+        if location == CompressedLocation::SPILLED {
+            self.load_spilled(key)
+        } else {
+            self.compressed[location.0].decompress()
+        }
+    }
+
+    fn load_spilled(&self, key: &PointN<N>) -> Compr::Data {
+        load_spilled(self.spill_sink.as_deref().unwrap(), key)
+    }
+
+    // Compresses the least-recently-used chunk other than `keep`. Returns `false` if there is no such chunk.
+    fn compress_lru_except(&mut self, keep: Option<PointN<N>>) -> bool {
+        let location = CompressedLocation(self.compressed.vacant_key());
+        let lru = match keep {
+            Some(keep) => self.cache.evict_lru_except(&keep, location),
+            None => self.cache.evict_lru(location),
+        };
+        let (key, lru_chunk) = match lru {
+            Some(lru) => lru,
+            None => return false,
+        };
+
+        let compressed_chunk = self.compression.compress(&lru_chunk);
+        self.untrack_decompressed(key);
+        self.counters.compressed_bytes += self.compressed_size(&compressed_chunk);
+        self.counters.compressions += 1;
+        self.compressed.insert(compressed_chunk);
+        self.track_compressed(key, location);
+
+        true
+    }
+
+    // Compresses least-recently-used chunks other than `keep` while the decompressed tier is over budget. Then spills the
+    // compressed tier down to its budget.
+    fn compress_over_budget(&mut self, keep: Option<PointN<N>>) {
+        if self.byte_sizes.is_none() {
+            return;
+        }
+
+        if let Some(max_bytes) = self.budget.max_decompressed_bytes {
+            self.measure_borrowed();
+            while self.counters.decompressed_bytes > max_bytes {
+                if !self.compress_lru_except(keep) {
+                    break;
+                }
+            }
+        }
+        self.spill_over_budget();
+    }
+
+    fn spill_over_budget(&mut self) {
+        if self.spill_error.is_some() {
+            return;
+        }
+        let max_bytes = match self.budget.max_compressed_bytes {
+            Some(max_bytes) if self.byte_sizes.is_some() && self.spill_sink.is_some() => max_bytes,
+            _ => return,
+        };
+
+        while self.counters.compressed_bytes > max_bytes {
+            if !self.spill_oldest_compressed() {
+                break;
+            }
+        }
+    }
+
+    fn track_compressed(&mut self, key: PointN<N>, location: CompressedLocation) {
+        self.compression_order.push_back((key, location));
+
+        // Drop entries for chunks that are no longer compressed, so the queue doesn't grow without bound.
+        if self.compression_order.len() > 2 * self.compressed.len() + 64 {
+            let cache = &self.cache;
+            self.compression_order.retain(|(key, location)| {
+                matches!(cache.get(key), Some(CacheEntry::Evicted(l)) if l == *location)
+            });
+        }
+    }
+
+    fn spill_oldest_compressed(&mut self) -> bool {
+        while let Some((key, location)) = self.compression_order.pop_front() {
+            if !matches!(self.cache.get(&key), Some(CacheEntry::Evicted(l)) if l == location) {
+                continue;
+            }
+
+            let spilled = self
+                .spill_sink
+                .as_mut()
+                .unwrap()
+                .spill(key, &self.compressed[location.0]);
+            if let Err(e) = spilled {
+                // Keep the chunk in memory, so it can be spilled once the error is taken.
+                self.compression_order.push_front((key, location));
+                self.spill_error = Some(e);
+
+                return false;
+            }
+
+            // Replaces the entry for the compressed location.
+            self.cache.evict(key, CompressedLocation::SPILLED);
+            let compressed_chunk = self.compressed.remove(location.0);
+            self.counters.compressed_bytes -= self.compressed_size(&compressed_chunk);
+            self.counters.spills += 1;
+            self.counters.num_spilled += 1;
+
+            return true;
+        }
+
+        false
+    }
+
+    // Enforces the budget without compressing the chunk at `key`.
+    fn enforce_budget_keeping(&mut self, key: PointN<N>) {
+        debug_assert!(matches!(self.cache.get(&key), Some(CacheEntry::Cached(_))));
+        self.compress_over_budget(Some(key));
+    }
+
+    fn decompressed_size(&self, chunk: &Compr::Data) -> usize {
+        self.byte_sizes
+            .map_or(0, |sizes| (sizes.decompressed)(chunk))
+    }
+
+    fn compressed_size(&self, chunk: &Compressed<Compr>) -> usize {
+        self.byte_sizes.map_or(0, |sizes| (sizes.compressed)(chunk))
+    }
+
+    fn track_decompressed(&mut self, key: PointN<N>, num_bytes: usize) {
+        if self.byte_sizes.is_none() {
+            return;
+        }
+
+        self.counters.decompressed_bytes += num_bytes;
+        if let Some(old_bytes) = self.decompressed_sizes.insert(key, num_bytes) {
+            self.counters.decompressed_bytes -= old_bytes;
+        }
+    }
+
+    fn untrack_decompressed(&mut self, key: PointN<N>) {
+        if self.borrowed == Some(key) {
+            self.borrowed = None;
+        }
+        if let Some(num_bytes) = self.decompressed_sizes.remove(&key) {
+            self.counters.decompressed_bytes -= num_bytes;
+        }
+    }
+
+    // Measures the last chunk that was borrowed mutably again, since it might have been resized.
+    fn measure_borrowed(&mut self) {
+        if let Some(key) = self.borrowed.take() {
+            if let Some(CacheEntry::Cached(chunk)) = self.cache.get(&key) {
+                let num_bytes = self.decompressed_size(chunk);
+                self.track_decompressed(key, num_bytes);
+            }
+        }
+    }
+
+    // Called after the chunk at `key` is borrowed mutably, before handing out the reference.
+    fn track_access(&mut self, key: PointN<N>, repopulated: bool) {
+        if repopulated {
+            let num_bytes = match self.cache.get(&key) {
+                Some(CacheEntry::Cached(chunk)) => self.decompressed_size(chunk),
+                _ => unreachable!(),
+            };
+            self.track_decompressed(key, num_bytes);
+        }
+        self.enforce_budget_keeping(key);
+        self.borrowed = Some(key);
+    }
+
+    // Gets the chunk at `key`, decompressing it if necessary. Also returns whether the chunk had to be decompressed.
+    fn get_mut_or_repopulate(&mut self, key: PointN<N>) -> Option<(&mut Compr::Data, bool)> {
+        // A repopulated chunk is inserted into the replacement policy, but a chunk that's already cached needs to be touched.
+        self.cache.touch_if_cached(key);
+
+        let Self {
+            cache,
+            compressed,
+            in_flight,
+            spill_sink,
+            counters,
+            byte_sizes,
+            ..
+        } = self;
+
+        let mut repopulated = false;
+        let mut missed = false;
+        let chunk = cache.get_mut_or_repopulate_with(key, |location| {
+            repopulated = true;
+            if location == CompressedLocation::IN_FLIGHT {
+                in_flight.remove(&key).unwrap().into_chunk()
+            } else {
+                missed = true;
+                if location == CompressedLocation::SPILLED {
+                    take_spilled(spill_sink.as_deref_mut().unwrap(), counters, key)
+                } else {
+                    decompress_for_cache(compressed, counters, *byte_sizes, location)
+                }
+            }
+        })?;
+        if !missed {
+            counters.count_hit();
+        }
+
+        Some((chunk, repopulated))
+    }
+
+    // Like `get_mut_or_repopulate`, but creates the chunk if it doesn't exist.
+    fn get_mut_or_repopulate_or_insert_with(
+        &mut self,
+        key: PointN<N>,
+        create_chunk: impl FnOnce() -> Compr::Data,
+    ) -> (&mut Compr::Data, bool) {
+        self.cache.touch_if_cached(key);
+
+        let Self {
+            cache,
+            compressed,
+            in_flight,
+            spill_sink,
+            counters,
+            byte_sizes,
+            ..
+        } = self;
+
+        let mut repopulated = false;
+        let mut missed = false;
+        let mut created = false;
+        let chunk = cache.get_mut_or_insert_with(
+            key,
+            |location| {
+                repopulated = true;
+                if location == CompressedLocation::IN_FLIGHT {
+                    in_flight.remove(&key).unwrap().into_chunk()
+                } else {
+                    missed = true;
+                    if location == CompressedLocation::SPILLED {
+                        take_spilled(spill_sink.as_deref_mut().unwrap(), counters, key)
+                    } else {
+                        decompress_for_cache(compressed, counters, *byte_sizes, location)
+                    }
+                }
+            },
+            || {
+                created = true;
+                create_chunk()
+            },
+        );
+        if !missed && !created {
+            counters.count_hit();
+        }
+
+        (chunk, repopulated || created)
+    }
+
+    fn take_old_entry(
+        &mut self,
+        key: PointN<N>,
+        entry: CacheEntry<Compr::Data, CompressedLocation>,
+    ) -> MaybeCompressed<Compr::Data, Compressed<Compr>> {
+        match entry {
+            CacheEntry::Cached(chunk) => {
+                self.untrack_decompressed(key);
+
+                MaybeCompressed::Decompressed(chunk)
+            }
+            CacheEntry::Evicted(CompressedLocation::IN_FLIGHT) => {
+                self.untrack_decompressed(key);
+
+                MaybeCompressed::Decompressed(self.in_flight.remove(&key).unwrap().into_chunk())
+            }
+            CacheEntry::Evicted(CompressedLocation::SPILLED) => {
+                let chunk = self.load_spilled(&key);
+                self.spill_sink
+                    .as_mut()
+                    .unwrap()
+                    .remove(key)
+                    .expect("Failed to remove spilled chunk");
+                self.counters.num_spilled -= 1;
+
+                MaybeCompressed::Decompressed(chunk)
+            }
+            CacheEntry::Evicted(location) => {
+                let compressed_chunk = self.compressed.remove(location.0);
+                self.counters.compressed_bytes -= self.compressed_size(&compressed_chunk);
+
+                MaybeCompressed::Compressed(compressed_chunk)
+            }
+        }
+    }
+}
+
+impl<N, Compr, P> CompressibleChunkStorage<N, Compr, P>
+where
+    PointN<N>: Hash + IntegerPoint<N>,
+    Compr: Compression,
+    Compr::Data: ByteSize,
+    Compr::CompressedData: ByteSize,
+    P: ReplacementPolicy<PointN<N>>,
+{
+    /// Sets the budget, which will be enforced on the next insert or access.
+    ///
+    /// The memory used by each tier is tracked from then on, so the first call measures every chunk in the storage.
+    ///
+    /// # Panics
+    ///
+    /// If the budget has a `max_compressed_bytes` but there is no `SpillSink` to spill chunks to.
+    pub fn set_budget(&mut self, budget: CompressionBudget) {
+        assert!(
+            budget.max_compressed_bytes.is_none() || self.spill_sink.is_some(),
+            "A compressed memory budget requires a spill sink"
+        );
+        self.budget = budget;
+        self.track_memory();
+    }
+
+    pub fn stats(&self) -> CompressibleChunkStorageStats {
+        let (decompressed_bytes, compressed_bytes) = if self.byte_sizes.is_some() {
+            let mut decompressed_bytes = self.counters.decompressed_bytes;
+            if let Some(key) = self.borrowed {
+                if let Some(CacheEntry::Cached(chunk)) = self.cache.get(&key) {
+                    decompressed_bytes =
+                        decompressed_bytes - self.decompressed_sizes[&key] + chunk.byte_size();
+                }
+            }
+
+            (decompressed_bytes, self.counters.compressed_bytes)
+        } else {
+            self.measure_memory()
+        };
+
+        CompressibleChunkStorageStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            compressions: self.counters.compressions,
+            spills: self.counters.spills,
+            num_decompressed: self.len_cached(),
+            num_compressed: self.len_compressed(),
+            num_spilled: self.counters.num_spilled,
+            decompressed_bytes,
+            compressed_bytes,
+        }
+    }
+
+    fn track_memory(&mut self) {
+        if self.byte_sizes.is_some() {
+            return;
+        }
+
+        self.byte_sizes = Some(ByteSizeFns {
+            decompressed: <Compr::Data as ByteSize>::byte_size,
+            compressed: <Compressed<Compr> as ByteSize>::byte_size,
+        });
+        self.decompressed_sizes.clear();
+        for (key, entry) in self.cache.entries() {
+            match entry {
+                CacheEntry::Cached(chunk) => {
+                    self.decompressed_sizes.insert(*key, chunk.byte_size());
+                }
+                CacheEntry::Evicted(CompressedLocation::IN_FLIGHT) => {
+                    self.decompressed_sizes
+                        .insert(*key, self.in_flight_chunk(key).byte_size());
+                }
+                CacheEntry::Evicted(_) => (),
+            }
+        }
+        let (decompressed_bytes, compressed_bytes) = self.measure_memory();
+        self.counters.decompressed_bytes = decompressed_bytes;
+        self.counters.compressed_bytes = compressed_bytes;
+    }
+
+    fn measure_memory(&self) -> (usize, usize) {
+        let mut decompressed_bytes = 0;
+        let mut compressed_bytes = 0;
+        for (key, entry) in self.cache.entries() {
+            match entry {
+                CacheEntry::Cached(chunk) => decompressed_bytes += chunk.byte_size(),
+                CacheEntry::Evicted(CompressedLocation::IN_FLIGHT) => {
+                    decompressed_bytes += self.in_flight_chunk(key).byte_size()
+                }
+                // Spilled chunks don't use any memory.
+                CacheEntry::Evicted(CompressedLocation::SPILLED) => (),
                 CacheEntry::Evicted(location) => {
-                    MaybeCompressed::Compressed(self.compressed.remove(location.0))
+                    compressed_bytes += self.compressed[location.0].byte_size()
                 }
-            })
+            }
+        }
+
+        (decompressed_bytes, compressed_bytes)
     }
 }
 
-impl<N, Compr> ChunkWriteStorage<N, Compr::Data> for CompressibleChunkStorage<N, Compr>
+impl<N, Compr, P> ChunkWriteStorage<N, Compr::Data> for CompressibleChunkStorage<N, Compr, P>
 where
     PointN<N>: Hash + IntegerPoint<N>,
     Compr: Compression,
+    P: ReplacementPolicy<PointN<N>>,
 {
     #[inline]
     fn get_mut(&mut self, key: PointN<N>) -> Option<&mut Compr::Data> {
-        let Self {
-            cache, compressed, ..
-        } = self;
+        if self.byte_sizes.is_none() {
+            return self.get_mut_or_repopulate(key).map(|(chunk, _)| chunk);
+        }
 
-        cache.get_mut_or_repopulate_with(key, |location| compressed.remove(location.0).decompress())
+        // Enforcing the budget could move the chunk, so look it up again afterwards.
+        let (_, missed) = self.get_mut_or_repopulate(key)?;
+        self.track_access(key, missed);
+
+        self.cache
+            .get_mut(&key)
+            .and_then(CacheEntry::some_if_cached)
     }
 
     #[inline]
@@ -197,14 +983,19 @@ where
         key: PointN<N>,
         create_chunk: impl FnOnce() -> Compr::Data,
     ) -> &mut Compr::Data {
-        let Self {
-            cache, compressed, ..
-        } = self;
-        cache.get_mut_or_insert_with(
-            key,
-            |location| compressed.remove(location.0).decompress(),
-            create_chunk,
-        )
+        if self.byte_sizes.is_none() {
+            return self
+                .get_mut_or_repopulate_or_insert_with(key, create_chunk)
+                .0;
+        }
+
+        let (_, repopulated) = self.get_mut_or_repopulate_or_insert_with(key, create_chunk);
+        self.track_access(key, repopulated);
+
+        self.cache
+            .get_mut(&key)
+            .and_then(CacheEntry::some_if_cached)
+            .unwrap()
     }
 
     #[inline]
@@ -232,12 +1023,61 @@ where
     }
 }
 
-impl<'a, N, Compr> IterChunkKeys<'a, N> for CompressibleChunkStorage<N, Compr>
+fn decompress_for_cache<Compr>(
+    compressed: &mut CompressedChunks<Compr>,
+    counters: &mut StorageCounters,
+    byte_sizes: Option<ByteSizeFns<Compr>>,
+    location: CompressedLocation,
+) -> Compr::Data
+where
+    Compr: Compression,
+{
+    let compressed_chunk = compressed.remove(location.0);
+    counters.count_miss();
+    if let Some(sizes) = byte_sizes {
+        counters.compressed_bytes -= (sizes.compressed)(&compressed_chunk);
+    }
+
+    compressed_chunk.decompress()
+}
+
+fn take_spilled<N, Compr>(
+    spill_sink: &mut (dyn SpillSink<N, Compr> + Send + Sync),
+    counters: &mut StorageCounters,
+    key: PointN<N>,
+) -> Compr::Data
+where
+    PointN<N>: Copy,
+    Compr: Compression,
+{
+    let chunk = load_spilled(spill_sink, &key);
+    spill_sink
+        .remove(key)
+        .expect("Failed to remove spilled chunk");
+    counters.count_miss();
+    counters.num_spilled -= 1;
+
+    chunk
+}
+
+fn load_spilled<N, Compr>(spill_sink: &dyn SpillSink<N, Compr>, key: &PointN<N>) -> Compr::Data
+where
+    PointN<N>: Copy,
+    Compr: Compression,
+{
+    spill_sink
+        .load(*key)
+        .expect("Failed to load spilled chunk")
+        .expect("Spilled chunk is missing from the spill sink")
+}
+
+impl<'a, N, Compr, P> IterChunkKeys<'a, N> for CompressibleChunkStorage<N, Compr, P>
 where
     N: 'a,
     PointN<N>: Hash + IntegerPoint<N>,
     Compr::Data: 'a,
     Compr: Compression,
+    P: ReplacementPolicy<PointN<N>>,
 {
     type Iter = LruChunkCacheKeys<'a, N, Compr::Data>;
 
@@ -246,9 +1086,10 @@ where
     }
 }
 
-impl<N, Compr> IntoIterator for CompressibleChunkStorage<N, Compr>
+impl<N, Compr, P> IntoIterator for CompressibleChunkStorage<N, Compr, P>
 where
     N: 'static,
+    PointN<N>: Hash + IntegerPoint<N>,
     Compr: 'static + Compression,
 {
     type IntoIter = Box<dyn Iterator<Item = Self::Item>>;
@@ -258,44 +1099,72 @@ where
         let Self {
             cache,
             mut compressed,
+            mut in_flight,
+            spill_sink,
             ..
         } = self;
 
         Box::new(cache.into_iter().map(move |(key, entry)| match entry {
             CacheEntry::Cached(chunk) => (key, chunk),
+            CacheEntry::Evicted(CompressedLocation::IN_FLIGHT) => {
+                (key, in_flight.remove(&key).unwrap().into_chunk())
+            }
+            CacheEntry::Evicted(CompressedLocation::SPILLED) => {
+                (key, load_spilled(spill_sink.as_deref().unwrap(), &key))
+            }
             CacheEntry::Evicted(location) => (key, compressed.remove(location.0).decompress()),
         }))
     }
 }
 
-impl<N, T, Bldr, Compr> CompressibleChunkMap<N, T, Bldr, Compr>
+impl<N, T, Bldr, Compr, P> CompressibleChunkMap<N, T, Bldr, Compr, P>
 where
     PointN<N>: Hash + IntegerPoint<N>,
     Bldr: ChunkMapBuilder<N, T> + Clone,
     Compr: Compression<Data = Bldr::Chunk>,
+    P: ReplacementPolicy<PointN<N>>,
 {
     /// Construct a reader for this map.
     pub fn reader<'a>(
         &'a self,
         local_cache: &'a LocalChunkCache<N, Bldr::Chunk>,
-    ) -> CompressibleChunkMapReader<'a, N, T, Bldr, Compr> {
+    ) -> CompressibleChunkMapReader<'a, N, T, Bldr, Compr, P> {
         self.builder()
             .clone()
             .build_with_read_storage(self.storage().reader(local_cache))
     }
+
+    /// Construct a reader for this map that can be shared by many threads.
+    pub fn shared_reader<'a>(
+        &'a self,
+        shared_cache: &'a SharedChunkCache<N, Bldr::Chunk>,
+    ) -> CompressibleChunkMapSharedReader<'a, N, T, Bldr, Compr, P> {
+        self.builder()
+            .clone()
+            .build_with_read_storage(self.storage().shared_reader(shared_cache))
+    }
 }
 
 /// An index into a compressed chunk slab.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct CompressedLocation(pub usize);
 
+impl CompressedLocation {
+    /// The location of a chunk that was taken by `CompressibleChunkStorage::take_lru_for_compression` and whose compressed
+    /// version hasn't been committed yet. It's not in the slab.
+    pub const IN_FLIGHT: Self = CompressedLocation(usize::MAX);
+
+    /// The location of a chunk that was spilled to the storage's `SpillSink`. It's not in the slab.
+    pub const SPILLED: Self = CompressedLocation(usize::MAX - 1);
+}
+
 pub type LruChunkCacheKeys<'a, N, Ch> = LruCacheKeys<'a, PointN<N>, Ch, CompressedLocation>;
 pub type LruChunkCacheEntries<'a, N, Ch> = LruCacheEntries<'a, PointN<N>, Ch, CompressedLocation>;
 pub type LruChunkCacheIntoIter<N, Ch> = LruCacheIntoIter<PointN<N>, Ch, CompressedLocation>;
 
 /// A `ChunkMap` using `CompressibleChunkStorage` as chunk storage.
-pub type CompressibleChunkMap<N, T, Bldr, Compr> =
-    ChunkMap<N, T, Bldr, CompressibleChunkStorage<N, Compr>>;
+pub type CompressibleChunkMap<N, T, Bldr, Compr, P = LruPolicy<PointN<N>>> =
+    ChunkMap<N, T, Bldr, CompressibleChunkStorage<N, Compr, P>>;
 
 pub mod multichannel_aliases {
     use super::*;
@@ -349,3 +1218,541 @@ pub mod multichannel_aliases {
 }
 
 pub use multichannel_aliases::*;
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(all(test, feature = "lz4"))]
+mod test {
+    use super::*;
+
+    use crate::{
+        prelude::*, BincodeCompression, ClockPolicy, DiskChunkStorage, FastArrayCompressionNx1,
+        LfuPolicy, Lz4,
+    };
+
+    #[test]
+    fn decompressed_budget_compresses_lru_chunks() {
+        let chunk_bytes = chunk(0).byte_size();
+        let mut storage =
+            FastCompressibleChunkStorageNx1::with_bytes_compression(Lz4 { level: 10 });
+        storage.set_budget(CompressionBudget {
+            max_decompressed_bytes: Some(3 * chunk_bytes),
+            max_compressed_bytes: None,
+        });
+
+        for i in 0..8 {
+            storage.write(PointN([i, 0, 0]), chunk(i));
+        }
+        assert_eq!(storage.len_cached(), 3);
+        assert_eq!(storage.len_compressed(), 5);
+
+        let stats = storage.stats();
+        assert_eq!(stats.compressions, 5);
+        assert_eq!(stats.decompressed_bytes, 3 * chunk_bytes);
+        assert!(stats.compressed_bytes > 0);
+
+        // The oldest chunk was compressed, so this access is a miss.
+        assert_eq!(storage.get_mut(PointN([0, 0, 0])), Some(&mut chunk(0)));
+        assert_eq!(storage.get_mut(PointN([7, 0, 0])), Some(&mut chunk(7)));
+        let stats = storage.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(stats.num_decompressed, 3);
+        assert_eq!(stats.decompressed_bytes, 3 * chunk_bytes);
+
+        for i in 0..8 {
+            storage.remove(PointN([i, 0, 0]));
+        }
+        let stats = storage.stats();
+        assert_eq!(stats.decompressed_bytes, 0);
+        assert_eq!(stats.compressed_bytes, 0);
+    }
+
+    #[test]
+    fn accessed_chunk_is_not_compressed() {
+        let mut storage =
+            FastCompressibleChunkStorageNx1::with_bytes_compression(Lz4 { level: 10 });
+        storage.write(PointN([0, 0, 0]), chunk(0));
+        storage.write(PointN([1, 0, 0]), chunk(1));
+        storage.compress_lru();
+        storage.set_budget(CompressionBudget {
+            max_decompressed_bytes: Some(0),
+            max_compressed_bytes: None,
+        });
+
+        assert_eq!(storage.get_mut(PointN([0, 0, 0])), Some(&mut chunk(0)));
+        assert_eq!(storage.len_cached(), 1);
+        assert_eq!(
+            storage.get_mut_or_insert_with(PointN([2, 0, 0]), || chunk(2)),
+            &mut chunk(2)
+        );
+        assert_eq!(storage.len_cached(), 1);
+        assert_eq!(storage.len_compressed(), 2);
+    }
+
+    #[test]
+    fn accessed_chunk_is_not_compressed_when_budget_is_set() {
+        let chunk_bytes = chunk(0).byte_size();
+        let mut storage =
+            FastCompressibleChunkStorageNx1::with_bytes_compression(Lz4 { level: 10 });
+        for i in 0..3 {
+            storage.write(PointN([i, 0, 0]), chunk(i));
+        }
+        storage.set_budget(CompressionBudget {
+            max_decompressed_bytes: Some(chunk_bytes),
+            max_compressed_bytes: None,
+        });
+
+        // Chunk 0 is the least recently used, but it's the one being accessed.
+        assert_eq!(storage.get_mut(PointN([0, 0, 0])), Some(&mut chunk(0)));
+        assert_eq!(storage.len_cached(), 1);
+        assert_eq!(
+            storage.get_mut_or_insert_with(PointN([1, 0, 0]), || chunk(1)),
+            &mut chunk(1)
+        );
+        assert_eq!(storage.len_cached(), 1);
+    }
+
+    #[test]
+    fn accessed_chunk_is_not_compressed_after_growing() {
+        let mut storage =
+            FastCompressibleChunkStorageNx1::with_bytes_compression(Lz4 { level: 10 });
+        storage.set_budget(CompressionBudget {
+            max_decompressed_bytes: Some(2 * chunk(0).byte_size()),
+            max_compressed_bytes: None,
+        });
+        storage.write(PointN([0, 0, 0]), chunk(0));
+        storage.write(PointN([1, 0, 0]), chunk(1));
+
+        let big_chunk = Array3x1::fill(
+            Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(32)),
+            0,
+        );
+        *storage.get_mut(PointN([0, 0, 0])).unwrap() = big_chunk.clone();
+
+        // The growth is only measured on the next access, which must not compress the grown chunk.
+        assert_eq!(
+            storage.get_mut(PointN([0, 0, 0])),
+            Some(&mut big_chunk.clone())
+        );
+        assert_eq!(
+            storage.get_mut_or_insert_with(PointN([0, 0, 0]), || chunk(0)),
+            &mut big_chunk.clone()
+        );
+        assert_eq!(storage.len_cached(), 1);
+        assert_eq!(storage.len_compressed(), 1);
+    }
+
+    #[test]
+    fn lru_budget_keeps_accessed_chunk() {
+        check_budget_keeps_accessed_chunk::<LruPolicy<Point3i>>();
+    }
+
+    #[test]
+    fn clock_budget_keeps_accessed_chunk() {
+        check_budget_keeps_accessed_chunk::<ClockPolicy<Point3i>>();
+    }
+
+    #[test]
+    fn lfu_budget_keeps_accessed_chunk() {
+        check_budget_keeps_accessed_chunk::<LfuPolicy<Point3i>>();
+    }
+
+    fn check_budget_keeps_accessed_chunk<P: ReplacementPolicy<Point3i>>() {
+        let compression = FastArrayCompressionNx1::from_bytes_compression(Lz4 { level: 10 });
+        let mut storage: CompressibleChunkStorage<_, _, P> =
+            CompressibleChunkStorage::with_policy(compression);
+        storage.set_budget(CompressionBudget {
+            max_decompressed_bytes: Some(chunk(0).byte_size()),
+            max_compressed_bytes: None,
+        });
+
+        // Overwriting chunk 0 makes it more frequently used than any new chunk.
+        storage.write(PointN([0, 0, 0]), chunk(0));
+        storage.write(PointN([0, 0, 0]), chunk(0));
+        assert_eq!(
+            storage.get_mut_or_insert_with(PointN([1, 0, 0]), || chunk(1)),
+            &mut chunk(1)
+        );
+        assert_eq!(storage.get_mut(PointN([0, 0, 0])), Some(&mut chunk(0)));
+        storage.write(PointN([2, 0, 0]), chunk(2));
+        assert_eq!(storage.get_mut(PointN([2, 0, 0])), Some(&mut chunk(2)));
+        assert_eq!(storage.len_cached(), 1);
+        assert_eq!(storage.len_compressed(), 2);
+    }
+
+    #[test]
+    fn budget_tracks_chunks_resized_through_get_mut() {
+        let mut storage =
+            FastCompressibleChunkStorageNx1::with_bytes_compression(Lz4 { level: 10 });
+        storage.set_budget(CompressionBudget {
+            max_decompressed_bytes: None,
+            max_compressed_bytes: None,
+        });
+
+        let big_chunk = Array3x1::fill(
+            Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(32)),
+            0,
+        );
+        let big_bytes = big_chunk.byte_size();
+        storage.write(PointN([0, 0, 0]), chunk(0));
+        *storage.get_mut(PointN([0, 0, 0])).unwrap() = big_chunk;
+        assert_eq!(storage.stats().decompressed_bytes, big_bytes);
+
+        // Shrink it again before it's measured, then remove it.
+        *storage.get_mut(PointN([0, 0, 0])).unwrap() = chunk(0);
+        storage.compress_lru();
+        storage.remove(PointN([0, 0, 0]));
+        let stats = storage.stats();
+        assert_eq!(stats.decompressed_bytes, 0);
+        assert_eq!(stats.compressed_bytes, 0);
+    }
+
+    #[test]
+    fn compressed_budget_spills_oldest_chunks_to_disk() {
+        let path = temp_log_path("compressed_budget_spills_oldest_chunks_to_disk");
+        let mut storage =
+            FastCompressibleChunkStorageNx1::with_bytes_compression(Lz4 { level: 10 });
+        storage.set_spill_sink(DiskChunkStorage::create(&path, disk_compression()).unwrap());
+        storage.set_budget(CompressionBudget {
+            max_decompressed_bytes: Some(0),
+            max_compressed_bytes: Some(0),
+        });
+
+        for i in 0..5 {
+            storage.write(PointN([i, 0, 0]), chunk(i));
+        }
+        storage.enforce_budget();
+
+        let stats = storage.stats();
+        assert_eq!(stats.num_decompressed, 0);
+        assert_eq!(stats.num_compressed, 0);
+        assert_eq!(stats.compressed_bytes, 0);
+        assert_eq!(stats.spills, 5);
+        assert_eq!(stats.num_spilled, 5);
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+
+        // Spilled chunks are still part of the storage.
+        let mut keys: Vec<_> = storage.chunk_keys().map(|key| key.x()).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, vec![0, 1, 2, 3, 4]);
+
+        // Reading leaves the chunk spilled, but a mutable access loads it back.
+        let local_cache = LocalChunkCache3::new();
+        let reader = storage.reader(&local_cache);
+        assert_eq!(reader.get(PointN([2, 0, 0])), Some(&chunk(2)));
+        assert_eq!(storage.len_spilled(), 5);
+        assert_eq!(storage.get_mut(PointN([2, 0, 0])), Some(&mut chunk(2)));
+        assert_eq!(storage.len_spilled(), 4);
+        assert_eq!(storage.len_cached(), 1);
+
+        assert!(matches!(
+            storage.remove(PointN([3, 0, 0])),
+            Some(MaybeCompressed::Decompressed(c)) if c == chunk(3)
+        ));
+        assert_eq!(storage.len_spilled(), 3);
+        assert_eq!(storage.pop(PointN([4, 0, 0])), Some(chunk(4)));
+        assert_eq!(storage.len_spilled(), 2);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn chunk_map_reads_spilled_chunks() {
+        let path = temp_log_path("chunk_map_reads_spilled_chunks");
+        let mut storage =
+            FastCompressibleChunkStorageNx1::with_bytes_compression(Lz4 { level: 10 });
+        storage.set_spill_sink(DiskChunkStorage::create(&path, disk_compression()).unwrap());
+        storage.set_budget(CompressionBudget {
+            max_decompressed_bytes: Some(0),
+            max_compressed_bytes: Some(0),
+        });
+        let mut map =
+            ChunkMapBuilder3x1::new(Point3i::fill(16), 0).build_with_write_storage(storage);
+
+        let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(64));
+        map.fill_extent(&extent, 7);
+        let stats = map.storage().stats();
+        assert_eq!(stats.num_decompressed, 1);
+        assert_eq!(stats.num_compressed, 0);
+        assert_eq!(stats.num_spilled, 63);
+
+        let local_cache = LocalChunkCache3::new();
+        let reader = map.reader(&local_cache);
+        let mut num_sevens = 0;
+        reader.for_each(&extent, |_: Point3i, value| {
+            if value == 7 {
+                num_sevens += 1;
+            }
+        });
+        assert_eq!(num_sevens, extent.num_points());
+
+        map.for_each_mut(&extent, |_: Point3i, value| assert_eq!(*value, 7));
+        assert_eq!(map.storage().len_spilled(), 63);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn chunks_that_fail_to_spill_stay_compressed() {
+        let mut storage =
+            FastCompressibleChunkStorageNx1::with_bytes_compression(Lz4 { level: 10 });
+        storage.set_spill_sink(FailingSink);
+        storage.set_budget(CompressionBudget {
+            max_decompressed_bytes: Some(0),
+            max_compressed_bytes: Some(0),
+        });
+
+        for i in 0..3 {
+            storage.write(PointN([i, 0, 0]), chunk(i));
+        }
+        storage.enforce_budget();
+        let stats = storage.stats();
+        assert_eq!(stats.num_compressed, 3);
+        assert_eq!(stats.spills, 0);
+        assert_eq!(stats.num_spilled, 0);
+        assert!(stats.compressed_bytes > 0);
+        assert_eq!(storage.get_mut(PointN([1, 0, 0])), Some(&mut chunk(1)));
+
+        // Spilling resumes once the error is taken.
+        let path = temp_log_path("chunks_that_fail_to_spill_stay_compressed");
+        storage.set_spill_sink(DiskChunkStorage::create(&path, disk_compression()).unwrap());
+        assert_eq!(
+            storage.take_spill_error().unwrap().kind(),
+            io::ErrorKind::Other
+        );
+        storage.enforce_budget();
+        assert_eq!(storage.len_spilled(), 3);
+        for i in 0..3 {
+            assert_eq!(storage.get_mut(PointN([i, 0, 0])), Some(&mut chunk(i)));
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "requires a spill sink")]
+    fn compressed_budget_requires_spill_sink() {
+        let mut storage =
+            FastCompressibleChunkStorageNx1::<[i32; 3], _, i32>::with_bytes_compression(Lz4 {
+                level: 10,
+            });
+        storage.set_budget(CompressionBudget {
+            max_decompressed_bytes: None,
+            max_compressed_bytes: Some(0),
+        });
+    }
+
+    #[test]
+    fn decompressed_chunks_are_not_spilled() {
+        let path = temp_log_path("decompressed_chunks_are_not_spilled");
+        let mut storage =
+            FastCompressibleChunkStorageNx1::with_bytes_compression(Lz4 { level: 10 });
+        storage.set_spill_sink(DiskChunkStorage::create(&path, disk_compression()).unwrap());
+        for i in 0..4 {
+            storage.write(PointN([i, 0, 0]), chunk(i));
+        }
+        for _ in 0..4 {
+            storage.compress_lru();
+        }
+
+        // Chunk 0 was compressed first, but it's decompressed again, so chunk 1 is the oldest compressed chunk.
+        assert_eq!(storage.get_mut(PointN([0, 0, 0])), Some(&mut chunk(0)));
+        let compressed_bytes = storage.stats().compressed_bytes;
+        storage.set_budget(CompressionBudget {
+            max_decompressed_bytes: None,
+            max_compressed_bytes: Some(compressed_bytes - 1),
+        });
+        storage.enforce_budget();
+
+        assert_eq!(storage.len_spilled(), 1);
+        assert_eq!(storage.len_cached(), 1);
+        assert_eq!(storage.len_compressed(), 2);
+        assert_eq!(storage.get_mut(PointN([3, 0, 0])), Some(&mut chunk(3)));
+        assert_eq!(storage.get_mut(PointN([1, 0, 0])), Some(&mut chunk(1)));
+        assert_eq!(storage.len_spilled(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn stats_measure_storage_without_budget() {
+        let mut storage =
+            FastCompressibleChunkStorageNx1::with_bytes_compression(Lz4 { level: 10 });
+        for i in 0..4 {
+            storage.write(PointN([i, 0, 0]), chunk(i));
+        }
+        storage.compress_lru();
+
+        let stats = storage.stats();
+        assert_eq!(stats.decompressed_bytes, 3 * chunk(0).byte_size());
+        assert!(stats.compressed_bytes > 0);
+
+        // Setting a budget starts tracking from the same measurements.
+        storage.set_budget(CompressionBudget::default());
+        assert_eq!(storage.stats(), stats);
+    }
+
+    #[test]
+    fn storage_with_clock_policy() {
+        let compression = FastArrayCompressionNx1::from_bytes_compression(Lz4 { level: 10 });
+        let mut storage: CompressibleChunkStorage<_, _, ClockPolicy<Point3i>> =
+            CompressibleChunkStorage::with_policy(compression);
+        for i in 0..4 {
+            storage.write(PointN([i, 0, 0]), chunk(i));
+        }
+
+        // Chunk 0 is accessed mutably and chunk 2 is read, so both get a second chance.
+        assert_eq!(storage.get_mut(PointN([0, 0, 0])), Some(&mut chunk(0)));
+        let local_cache = LocalChunkCache3::new();
+        assert_eq!(
+            storage.reader(&local_cache).get(PointN([2, 0, 0])),
+            Some(&chunk(2))
+        );
+        storage.flush_local_cache(local_cache);
+
+        storage.compress_lru();
+        storage.compress_lru();
+        assert_eq!(storage.len_cached(), 2);
+        assert_eq!(storage.len_compressed(), 2);
+        for i in 0..4 {
+            assert_eq!(
+                matches!(
+                    storage.copy_without_caching(PointN([i, 0, 0])),
+                    Some(MaybeCompressed::Compressed(_))
+                ),
+                i % 2 == 1
+            );
+        }
+
+        let local_cache = LocalChunkCache3::new();
+        let reader = storage.reader(&local_cache);
+        for i in 0..4 {
+            assert_eq!(reader.get(PointN([i, 0, 0])), Some(&chunk(i)));
+        }
+    }
+
+    #[test]
+    fn frequently_read_chunk_survives_scan_with_lfu_policy() {
+        let compression = FastArrayCompressionNx1::from_bytes_compression(Lz4 { level: 10 });
+        let mut storage: CompressibleChunkStorage<_, _, LfuPolicy<Point3i>> =
+            CompressibleChunkStorage::with_policy(compression);
+        storage.set_budget(CompressionBudget {
+            max_decompressed_bytes: Some(2 * chunk(0).byte_size()),
+            max_compressed_bytes: None,
+        });
+        storage.write(PointN([0, 0, 0]), chunk(0));
+        for _ in 0..3 {
+            let shared_cache = SharedChunkCache3::new();
+            assert_eq!(
+                storage.shared_reader(&shared_cache).get(PointN([0, 0, 0])),
+                Some(&chunk(0))
+            );
+            storage.flush_shared_cache(shared_cache);
+        }
+
+        // Scan through many chunks, reading each of them once.
+        for i in 1..8 {
+            storage.write(PointN([i, 0, 0]), chunk(i));
+            let local_cache = LocalChunkCache3::new();
+            assert_eq!(
+                storage.reader(&local_cache).get(PointN([i, 0, 0])),
+                Some(&chunk(i))
+            );
+            storage.flush_local_cache(local_cache);
+        }
+
+        assert_eq!(storage.len_cached(), 2);
+        assert!(matches!(
+            storage.copy_without_caching(PointN([0, 0, 0])),
+            Some(MaybeCompressed::Decompressed(c)) if c == chunk(0)
+        ));
+    }
+
+    #[test]
+    fn reader_counts_one_miss_per_decompression() {
+        let mut storage =
+            FastCompressibleChunkStorageNx1::with_bytes_compression(Lz4 { level: 10 });
+        storage.write(PointN([0, 0, 0]), chunk(0));
+        storage.compress_lru();
+
+        let local_cache = LocalChunkCache3::new();
+        let reader = storage.reader(&local_cache);
+        for _ in 0..3 {
+            assert_eq!(reader.get(PointN([0, 0, 0])), Some(&chunk(0)));
+        }
+        let stats = storage.stats();
+        assert_eq!((stats.hits, stats.misses), (2, 1));
+    }
+
+    #[test]
+    fn shared_reader_decompresses_once_and_flushes_back() {
+        let mut storage =
+            FastCompressibleChunkStorageNx1::with_bytes_compression(Lz4 { level: 10 });
+        for i in 0..8 {
+            storage.write(PointN([i, 0, 0]), chunk(i));
+        }
+        for _ in 0..6 {
+            storage.compress_lru();
+        }
+
+        let shared_cache = SharedChunkCache3::new();
+        let reader = storage.shared_reader(&shared_cache);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for i in 0..8 {
+                        assert_eq!(reader.get(PointN([i, 0, 0])), Some(&chunk(i)));
+                    }
+                });
+            }
+        });
+        assert_eq!(shared_cache.len(), 6);
+        let stats = storage.stats();
+        // Every compressed chunk is decompressed by exactly one thread.
+        assert_eq!((stats.hits, stats.misses), (26, 6));
+
+        storage.flush_shared_cache(shared_cache);
+        assert_eq!(storage.len_cached(), 8);
+        assert_eq!(storage.len_compressed(), 0);
+    }
+
+    struct FailingSink;
+
+    impl<Compr: Compression> SpillSink<[i32; 3], Compr> for FailingSink {
+        fn spill(&mut self, _key: Point3i, _chunk: &Compressed<Compr>) -> io::Result<()> {
+            Err(io::Error::new(io::ErrorKind::Other, "disk is full"))
+        }
+
+        fn load(&self, _key: Point3i) -> io::Result<Option<Compr::Data>> {
+            Ok(None)
+        }
+
+        fn remove(&mut self, _key: Point3i) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn disk_compression() -> BincodeCompression<Array3x1<i32>, Lz4> {
+        BincodeCompression::new(Lz4 { level: 10 })
+    }
+
+    fn temp_log_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "bb_compressible_chunk_storage_{}_{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    fn chunk(value: i32) -> Array3x1<i32> {
+        Array3x1::fill(
+            Extent3i::from_min_and_shape(Point3i::fill(value * 16), Point3i::fill(16)),
+            value,
+        )
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
-    CacheEntry, Channel, ChunkMap, ChunkReadStorage, CompressedChunks, CompressibleChunkStorage,
-    Compression, IterChunkKeys, LocalCache, LruChunkCacheEntries, LruChunkCacheKeys,
-    SmallKeyBuildHasher,
+    CacheEntry, Channel, ChunkMap, ChunkReadStorage, CompressedLocation, CompressibleChunkStorage,
+    Compression, IterChunkKeys, LocalCache, LruChunkCacheEntries, LruChunkCacheKeys, LruPolicy,
+    ReplacementPolicy, SharedCache, SmallKeyBuildHasher,
 };
 
 use building_blocks_core::prelude::*;
@@ -11,41 +11,66 @@ use core::hash::Hash;
 /// An object for reading from `CompressibleChunkStorage` with only `&self`. Easily construct one of these using the
 /// `CompressibleChunkStorage::reader` method.
 ///
-/// This works by using a `LocalChunkCache` for storing decompressed `Chunk`s from cache misses.
-pub struct CompressibleChunkStorageReader<'a, N, Compr>
+/// This works by using a `LocalChunkCache` for storing decompressed `Chunk`s from cache misses. The cache also remembers which
+/// decompressed `Chunk`s were read, so they count as accesses for the storage's `ReplacementPolicy` once the cache is flushed
+/// with `CompressibleChunkStorage::flush_local_cache`.
+pub struct CompressibleChunkStorageReader<'a, N, Compr, P = LruPolicy<PointN<N>>>
 where
     Compr: Compression,
 {
-    pub storage: &'a CompressibleChunkStorage<N, Compr>,
+    pub storage: &'a CompressibleChunkStorage<N, Compr, P>,
     pub local_cache: &'a LocalChunkCache<N, Compr::Data>,
 }
 
-impl<'a, N, Compr> ChunkReadStorage<N, Compr::Data> for CompressibleChunkStorageReader<'a, N, Compr>
+impl<'a, N, Compr, P> ChunkReadStorage<N, Compr::Data>
+    for CompressibleChunkStorageReader<'a, N, Compr, P>
 where
     PointN<N>: Hash + IntegerPoint<N>,
     Compr: Compression,
+    P: ReplacementPolicy<PointN<N>>,
 {
     #[inline]
     fn get(&self, key: PointN<N>) -> Option<&Compr::Data> {
         let Self {
-            storage: CompressibleChunkStorage {
-                cache, compressed, ..
-            },
+            storage,
             local_cache,
         } = self;
+        let CompressibleChunkStorage {
+            cache, counters, ..
+        } = storage;
 
         cache.get(&key).map(|entry| match entry {
-            CacheEntry::Cached(value) => value,
-            CacheEntry::Evicted(location) => local_cache
-                .get_or_insert_with(key, || compressed.get(location.0).unwrap().decompress()),
+            CacheEntry::Cached(value) => {
+                counters.count_hit();
+                local_cache.touch(key);
+
+                value
+            }
+            CacheEntry::Evicted(CompressedLocation::IN_FLIGHT) => {
+                counters.count_hit();
+
+                storage.in_flight_chunk(&key)
+            }
+            CacheEntry::Evicted(location) => {
+                // Only count a miss if the chunk isn't in the local cache yet.
+                let mut missed = false;
+                let chunk = local_cache.get_or_insert_with(key, || {
+                    missed = true;
+                    storage.decompress_evicted(&key, location)
+                });
+                counters.count_hit_or_miss(missed);
+
+                chunk
+            }
         })
     }
 }
 
-impl<'a, N, Compr> IterChunkKeys<'a, N> for CompressibleChunkStorageReader<'a, N, Compr>
+impl<'a, N, Compr, P> IterChunkKeys<'a, N> for CompressibleChunkStorageReader<'a, N, Compr, P>
 where
     PointN<N>: Hash + IntegerPoint<N>,
     Compr: Compression,
+    P: ReplacementPolicy<PointN<N>>,
 {
     type Iter = LruChunkCacheKeys<'a, N, Compr::Data>;
 
@@ -54,12 +79,13 @@ where
     }
 }
 
-impl<'a, N, Compr> IntoIterator for &'a CompressibleChunkStorageReader<'a, N, Compr>
+impl<'a, N, Compr, P> IntoIterator for &'a CompressibleChunkStorageReader<'a, N, Compr, P>
 where
     PointN<N>: Hash + IntegerPoint<N>,
     Compr: Compression,
+    P: ReplacementPolicy<PointN<N>>,
 {
-    type IntoIter = CompressibleChunkStorageReaderIntoIter<'a, N, Compr>;
+    type IntoIter = CompressibleChunkStorageReaderIntoIter<'a, N, Compr, P>;
     type Item = (&'a PointN<N>, &'a Compr::Data);
 
     fn into_iter(self) -> Self::IntoIter {
@@ -71,24 +97,25 @@ where
         CompressibleChunkStorageReaderIntoIter {
             cache_entries: storage.cache.entries(),
             local_cache,
-            compressed: &storage.compressed,
+            storage,
         }
     }
 }
 
-pub struct CompressibleChunkStorageReaderIntoIter<'a, N, Compr>
+pub struct CompressibleChunkStorageReaderIntoIter<'a, N, Compr, P>
 where
     Compr: Compression,
 {
     cache_entries: LruChunkCacheEntries<'a, N, Compr::Data>,
     local_cache: &'a LocalChunkCache<N, Compr::Data>,
-    compressed: &'a CompressedChunks<Compr>,
+    storage: &'a CompressibleChunkStorage<N, Compr, P>,
 }
 
-impl<'a, N, Compr> Iterator for CompressibleChunkStorageReaderIntoIter<'a, N, Compr>
+impl<'a, N, Compr, P> Iterator for CompressibleChunkStorageReaderIntoIter<'a, N, Compr, P>
 where
     PointN<N>: Hash + IntegerPoint<N>,
     Compr: Compression,
+    P: ReplacementPolicy<PointN<N>>,
 {
     type Item = (&'a PointN<N>, &'a Compr::Data);
 
@@ -97,16 +124,105 @@ where
             .next()
             .map(move |(key, entry)| match entry {
                 CacheEntry::Cached(chunk) => (key, chunk),
+                CacheEntry::Evicted(CompressedLocation::IN_FLIGHT) => {
+                    (key, self.storage.in_flight_chunk(key))
+                }
                 CacheEntry::Evicted(location) => (
                     key,
                     self.local_cache.get_or_insert_with(*key, || {
-                        self.compressed.get(location.0).unwrap().decompress()
+                        self.storage.decompress_evicted(key, location)
                     }),
                 ),
             })
     }
 }
 
+/// An object for reading from `CompressibleChunkStorage` with only `&self`, which can be shared by many threads. Easily
+/// construct one of these using the `CompressibleChunkStorage::shared_reader` method.
+///
+/// This works by using a `SharedChunkCache` for storing decompressed `Chunk`s from cache misses. Each missing chunk is only
+/// decompressed once, even if many threads read it at the same time. Afterwards, the decompressed chunks can be merged back
+/// into the storage with `CompressibleChunkStorage::flush_shared_cache`, which also records the reads of chunks that were
+/// already decompressed with the storage's `ReplacementPolicy`.
+pub struct CompressibleChunkStorageSharedReader<'a, N, Compr, P = LruPolicy<PointN<N>>>
+where
+    Compr: Compression,
+{
+    pub storage: &'a CompressibleChunkStorage<N, Compr, P>,
+    pub shared_cache: &'a SharedChunkCache<N, Compr::Data>,
+}
+
+impl<'a, N, Compr, P> Clone for CompressibleChunkStorageSharedReader<'a, N, Compr, P>
+where
+    Compr: Compression,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, N, Compr, P> Copy for CompressibleChunkStorageSharedReader<'a, N, Compr, P> where
+    Compr: Compression
+{
+}
+
+impl<'a, N, Compr, P> ChunkReadStorage<N, Compr::Data>
+    for CompressibleChunkStorageSharedReader<'a, N, Compr, P>
+where
+    PointN<N>: Hash + IntegerPoint<N>,
+    Compr: Compression,
+    P: ReplacementPolicy<PointN<N>>,
+{
+    #[inline]
+    fn get(&self, key: PointN<N>) -> Option<&Compr::Data> {
+        let Self {
+            storage,
+            shared_cache,
+        } = self;
+        let CompressibleChunkStorage {
+            cache, counters, ..
+        } = storage;
+
+        cache.get(&key).map(|entry| match entry {
+            CacheEntry::Cached(value) => {
+                counters.count_hit();
+                shared_cache.touch(key);
+
+                value
+            }
+            CacheEntry::Evicted(CompressedLocation::IN_FLIGHT) => {
+                counters.count_hit();
+
+                storage.in_flight_chunk(&key)
+            }
+            CacheEntry::Evicted(location) => {
+                // Only the thread that decompresses the chunk counts a miss.
+                let mut missed = false;
+                let chunk = shared_cache.get_or_insert_with(key, || {
+                    missed = true;
+                    storage.decompress_evicted(&key, location)
+                });
+                counters.count_hit_or_miss(missed);
+
+                chunk
+            }
+        })
+    }
+}
+
+impl<'a, N, Compr, P> IterChunkKeys<'a, N> for CompressibleChunkStorageSharedReader<'a, N, Compr, P>
+where
+    PointN<N>: Hash + IntegerPoint<N>,
+    Compr: Compression,
+    P: ReplacementPolicy<PointN<N>>,
+{
+    type Iter = LruChunkCacheKeys<'a, N, Compr::Data>;
+
+    fn chunk_keys(&'a self) -> Self::Iter {
+        self.storage.cache.keys()
+    }
+}
+
 /// A `LocalCache` of chunks.
 pub type LocalChunkCache<N, Ch> = LocalCache<PointN<N>, Ch, SmallKeyBuildHasher>;
 /// A `LocalCache` of 2D chunks.
@@ -114,9 +230,20 @@ pub type LocalChunkCache2<Ch> = LocalChunkCache<[i32; 2], Ch>;
 /// A `LocalCache` of 3D chunks.
 pub type LocalChunkCache3<Ch> = LocalChunkCache<[i32; 3], Ch>;
 
+/// A `SharedCache` of chunks.
+pub type SharedChunkCache<N, Ch> = SharedCache<PointN<N>, Ch, SmallKeyBuildHasher>;
+/// A `SharedCache` of 2D chunks.
+pub type SharedChunkCache2<Ch> = SharedChunkCache<[i32; 2], Ch>;
+/// A `SharedCache` of 3D chunks.
+pub type SharedChunkCache3<Ch> = SharedChunkCache<[i32; 3], Ch>;
+
 /// A `ChunkMap` backed by a `CompressibleChunkStorageReader`.
-pub type CompressibleChunkMapReader<'a, N, T, Bldr, Compr> =
-    ChunkMap<N, T, Bldr, CompressibleChunkStorageReader<'a, N, Compr>>;
+pub type CompressibleChunkMapReader<'a, N, T, Bldr, Compr, P = LruPolicy<PointN<N>>> =
+    ChunkMap<N, T, Bldr, CompressibleChunkStorageReader<'a, N, Compr, P>>;
+
+/// A `ChunkMap` backed by a `CompressibleChunkStorageSharedReader`.
+pub type CompressibleChunkMapSharedReader<'a, N, T, Bldr, Compr, P = LruPolicy<PointN<N>>> =
+    ChunkMap<N, T, Bldr, CompressibleChunkStorageSharedReader<'a, N, Compr, P>>;
 
 pub mod multichannel_aliases {
     use super::*;
@@ -0,0 +1,339 @@
+//! Compression of chunks on background threads.
+//!
+//! `CompressibleChunkStorage::compress_lru` compresses on the calling thread, which can cause hitches when it's called from a
+//! frame loop. A `CompressionWorker` instead compresses the least-recently-used chunks on a pool of threads, while the storage
+//! can still read them. The results are committed back into the storage on the main thread, but only if the chunk was not
+//! modified in the meantime.
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::{prelude::*, CompressionWorker};
+//! let builder = ChunkMapBuilder3x1::new(Point3i::fill(16), 0);
+//! let storage = FastCompressibleChunkStorageNx1::with_bytes_compression(Lz4 { level: 10 });
+//! let mut map = builder.build_with_write_storage(storage);
+//! map.fill_extent(&Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(64)), 1);
+//!
+//! let mut worker = CompressionWorker::new(*map.storage().compression(), 2);
+//!
+//! // Every frame: submit some chunks, then commit whatever has finished.
+//! worker.compress_lru(map.storage_mut(), 32);
+//! worker.commit_finished(map.storage_mut());
+//!
+//! // Block until all of the submitted chunks are compressed.
+//! worker.wait_and_commit_all(map.storage_mut());
+//! assert_eq!(map.storage().len_compressed(), 32);
+//! ```
+
+use crate::{Compressed, CompressibleChunkStorage, Compression, CompressionJob, ReplacementPolicy};
+
+use building_blocks_core::prelude::*;
+
+use core::hash::Hash;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+
+/// A pool of threads that compress chunks for a `CompressibleChunkStorage`.
+///
+/// The threads are joined when the worker is dropped. Results that were not committed are discarded.
+pub struct CompressionWorker<N, Compr>
+where
+    Compr: Compression,
+{
+    jobs: Option<Sender<CompressionJob<N, Compr::Data>>>,
+    results: Receiver<CompressionResult<N, Compr>>,
+    threads: Vec<JoinHandle<()>>,
+    num_pending: usize,
+}
+
+struct CompressionResult<N, Compr>
+where
+    Compr: Compression,
+{
+    key: PointN<N>,
+    generation: u64,
+    // `None` if compression panicked.
+    compressed_chunk: Option<Compressed<Compr>>,
+}
+
+impl<N, Compr> CompressionWorker<N, Compr>
+where
+    N: 'static + Send,
+    PointN<N>: Hash + IntegerPoint<N>,
+    Compr: 'static + Clone + Compression + Send,
+    Compr::Data: Clone + Send + Sync,
+    Compr::CompressedData: Send,
+{
+    /// Spawns `num_threads` threads that compress chunks with `compression`.
+    pub fn new(compression: Compr, num_threads: usize) -> Self {
+        assert!(num_threads > 0);
+
+        let (job_sender, job_receiver) = channel::<CompressionJob<N, Compr::Data>>();
+        let (result_sender, result_receiver) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let threads = (0..num_threads)
+            .map(|_| {
+                let compression = compression.clone();
+                let job_receiver = job_receiver.clone();
+                let result_sender = result_sender.clone();
+
+                std::thread::spawn(move || loop {
+                    // Only hold the lock while receiving, so other threads can receive while this one compresses. Receiving
+                    // can't leave the receiver in a bad state, so a poisoned lock is still usable.
+                    let job = match job_receiver
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .recv()
+                    {
+                        Ok(job) => job,
+                        Err(_) => return,
+                    };
+                    let CompressionJob {
+                        key,
+                        generation,
+                        chunk,
+                    } = job;
+                    // A panic must still produce a result, or else the job would be pending forever.
+                    let compressed_chunk =
+                        catch_unwind(AssertUnwindSafe(|| compression.compress(&chunk))).ok();
+                    // Release the chunk before the result can be committed, so the storage doesn't have to copy it.
+                    drop(chunk);
+
+                    let result = CompressionResult {
+                        key,
+                        generation,
+                        compressed_chunk,
+                    };
+                    if result_sender.send(result).is_err() {
+                        return;
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            jobs: Some(job_sender),
+            results: result_receiver,
+            threads,
+            num_pending: 0,
+        }
+    }
+
+    /// The number of submitted jobs whose results have not been committed yet.
+    pub fn num_pending(&self) -> usize {
+        self.num_pending
+    }
+
+    /// Sends `jobs` to the worker threads.
+    pub fn submit(&mut self, jobs: impl IntoIterator<Item = CompressionJob<N, Compr::Data>>) {
+        let sender = self.jobs.as_ref().unwrap();
+        for job in jobs.into_iter() {
+            sender.send(job).unwrap();
+            self.num_pending += 1;
+        }
+    }
+
+    /// Submits up to `max_chunks` of the least-recently-used, cached chunks in `storage` for compression.
+    pub fn compress_lru<P>(
+        &mut self,
+        storage: &mut CompressibleChunkStorage<N, Compr, P>,
+        max_chunks: usize,
+    ) where
+        P: ReplacementPolicy<PointN<N>>,
+    {
+        self.submit(storage.take_lru_for_compression(max_chunks));
+    }
+
+    /// Commits all of the results that have finished so far, without blocking. Returns the number of chunks that were
+    /// replaced by their compressed versions; results for chunks that were modified after being submitted are dropped, and
+    /// chunks that failed to compress are put back in the cache.
+    pub fn commit_finished<P>(
+        &mut self,
+        storage: &mut CompressibleChunkStorage<N, Compr, P>,
+    ) -> usize
+    where
+        P: ReplacementPolicy<PointN<N>>,
+    {
+        let mut num_committed = 0;
+        while let Ok(result) = self.results.try_recv() {
+            num_committed += self.commit(storage, result) as usize;
+        }
+
+        num_committed
+    }
+
+    /// Blocks until every submitted job is finished, and commits all of the results. Returns the number of chunks that were
+    /// replaced by their compressed versions.
+    pub fn wait_and_commit_all<P>(
+        &mut self,
+        storage: &mut CompressibleChunkStorage<N, Compr, P>,
+    ) -> usize
+    where
+        P: ReplacementPolicy<PointN<N>>,
+    {
+        let mut num_committed = 0;
+        while self.num_pending > 0 {
+            match self.results.recv() {
+                Ok(result) => num_committed += self.commit(storage, result) as usize,
+                // Every thread has exited, so the remaining results will never arrive.
+                Err(_) => self.num_pending = 0,
+            }
+        }
+
+        num_committed
+    }
+
+    fn commit<P>(
+        &mut self,
+        storage: &mut CompressibleChunkStorage<N, Compr, P>,
+        result: CompressionResult<N, Compr>,
+    ) -> bool
+    where
+        P: ReplacementPolicy<PointN<N>>,
+    {
+        self.num_pending -= 1;
+
+        match result.compressed_chunk {
+            Some(compressed_chunk) => {
+                storage.commit_compressed(result.key, result.generation, compressed_chunk)
+            }
+            None => {
+                storage.cancel_compression(result.key, result.generation);
+
+                false
+            }
+        }
+    }
+}
+
+impl<N, Compr> Drop for CompressionWorker<N, Compr>
+where
+    Compr: Compression,
+{
+    fn drop(&mut self) {
+        // Closing the job channel stops the threads after they finish their current job.
+        self.jobs = None;
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(all(test, feature = "lz4"))]
+mod test {
+    use super::*;
+
+    use crate::{prelude::*, Lz4};
+
+    #[test]
+    fn modified_chunks_are_not_committed() {
+        let mut storage =
+            FastCompressibleChunkStorageNx1::with_bytes_compression(Lz4 { level: 10 });
+        for i in 0..4 {
+            storage.write(PointN([i, 0, 0]), chunk(i));
+        }
+
+        let mut worker = CompressionWorker::new(*storage.compression(), 2);
+        worker.compress_lru(&mut storage, 4);
+        assert_eq!(storage.len_in_flight(), 4);
+        assert_eq!(storage.len_cached(), 0);
+        assert_eq!(storage.len_compressed(), 0);
+
+        // In-flight chunks can still be read.
+        let local_cache = LocalChunkCache3::new();
+        assert_eq!(
+            storage.reader(&local_cache).get(PointN([3, 0, 0])),
+            Some(&chunk(3))
+        );
+        assert_eq!(local_cache.flush_iter().count(), 0);
+
+        // Modify a chunk while it's being compressed.
+        *storage.get_mut(PointN([1, 0, 0])).unwrap() = chunk(100);
+
+        assert_eq!(worker.wait_and_commit_all(&mut storage), 3);
+        assert_eq!(worker.num_pending(), 0);
+        assert_eq!(storage.len_in_flight(), 0);
+        assert_eq!(storage.len_cached(), 1);
+        assert_eq!(storage.len_compressed(), 3);
+
+        assert_eq!(storage.get_mut(PointN([1, 0, 0])), Some(&mut chunk(100)));
+        assert_eq!(storage.get_mut(PointN([2, 0, 0])), Some(&mut chunk(2)));
+    }
+
+    #[test]
+    fn resubmitted_chunk_only_commits_latest_generation() {
+        let mut storage =
+            FastCompressibleChunkStorageNx1::with_bytes_compression(Lz4 { level: 10 });
+        storage.write(PointN([0, 0, 0]), chunk(0));
+
+        let first_jobs = storage.take_lru_for_compression(1);
+        // Taking the chunk back and resubmitting it makes the first job stale.
+        storage.get_mut(PointN([0, 0, 0]));
+        let second_jobs = storage.take_lru_for_compression(1);
+        let compression = *storage.compression();
+        let compress =
+            |job: &CompressionJob<[i32; 3], Array3x1<i32>>| compression.compress(&job.chunk);
+
+        assert!(!storage.commit_compressed(
+            first_jobs[0].key,
+            first_jobs[0].generation,
+            compress(&first_jobs[0])
+        ));
+        assert!(storage.commit_compressed(
+            second_jobs[0].key,
+            second_jobs[0].generation,
+            compress(&second_jobs[0])
+        ));
+        assert_eq!(storage.len_compressed(), 1);
+    }
+
+    #[test]
+    fn panicking_compression_returns_chunk_to_cache() {
+        let mut storage = CompressibleChunkStorage::new(PanicOnNegative);
+        for i in -1..3 {
+            storage.write(PointN([i, 0, 0]), chunk(i));
+        }
+
+        let mut worker = CompressionWorker::new(PanicOnNegative, 2);
+        worker.compress_lru(&mut storage, 4);
+        assert_eq!(worker.wait_and_commit_all(&mut storage), 3);
+        assert_eq!(storage.len_in_flight(), 0);
+        assert_eq!(storage.len_cached(), 1);
+        assert_eq!(storage.get_mut(PointN([-1, 0, 0])), Some(&mut chunk(-1)));
+    }
+
+    // Stores chunks as they are, but panics on chunks filled with negative values.
+    #[derive(Clone, Copy)]
+    struct PanicOnNegative;
+
+    impl Compression for PanicOnNegative {
+        type Data = Array3x1<i32>;
+        type CompressedData = Array3x1<i32>;
+
+        fn compress(&self, data: &Self::Data) -> Compressed<Self> {
+            assert!(data.get(data.extent().minimum) >= 0);
+
+            Compressed::new(data.clone())
+        }
+
+        fn decompress(compressed: &Self::CompressedData) -> Self::Data {
+            compressed.clone()
+        }
+    }
+
+    fn chunk(value: i32) -> Array3x1<i32> {
+        Array3x1::fill(
+            Extent3i::from_min_and_shape(Point3i::fill(value * 16), Point3i::fill(16)),
+            value,
+        )
+    }
+}
@@ -0,0 +1,869 @@
+//! A chunk storage that spills compressed chunks to a file on disk.
+//!
+//! `DiskChunkStorage` is the same two-tier design as `CompressibleChunkStorage`, except that the second tier is an append-only
+//! log file rather than an in-memory `Slab`. This allows for maps that are much larger than the available memory. Chunks are
+//! cached in memory by an `LruCache`, and when the least-recently-used chunks are spilled with `spill_lru`, they are compressed
+//! and appended to the log. Accessing a spilled chunk reads it back from disk and caches it again.
+//!
+//! The log is also persistent. `flush` spills all cached chunks and syncs the file, and `DiskChunkStorage::open` rebuilds the
+//! index of chunks from an existing log. A chunk that was read back into the cache keeps its record in the log until it's
+//! spilled again or removed, so the log always has the last flushed version of every chunk, even if the process exits without
+//! flushing. Since the log is append-only, overwritten and deleted chunks leave garbage behind, which can be reclaimed with
+//! `compact`.
+//!
+//! A `DiskChunkStorage` can also be the `SpillSink` of a `CompressibleChunkStorage`, so that a `CompressionBudget` can limit
+//! the memory used by compressed chunks. Each spilled chunk is decompressed and compressed again with the disk storage's
+//! `Compression`, which doesn't need to be the same.
+//!
+//! Each record in the log has the following layout (all integers are little-endian):
+//!
+//! ```text
+//! | key length: u32 | key: bincode PointN<N> | tombstone: u8 | data length: u64 | data: bincode Compr::CompressedData |
+//! ```
+//!
+//! I/O errors that happen while accessing chunks through the `ChunkWriteStorage` or `ChunkReadStorage` traits will panic.
+//!
+//! ```
+//! # use building_blocks_core::prelude::*;
+//! # use building_blocks_storage::{prelude::*, BincodeCompression};
+//! # let path = std::env::temp_dir().join(format!("bb_disk_chunk_storage_doc_{}", std::process::id()));
+//! let builder = ChunkMapBuilder3x1::new(Point3i::fill(16), 0u16);
+//! let compression = BincodeCompression::new(Lz4 { level: 10 });
+//! let mut map = builder.build_with_write_storage(DiskChunkStorage::create(&path, compression).unwrap());
+//!
+//! let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(64));
+//! map.fill_extent(&extent, 1);
+//!
+//! // Write all chunks to disk and free the memory.
+//! map.storage_mut().flush().unwrap();
+//! assert_eq!(map.storage().len_cached(), 0);
+//!
+//! // Spilled chunks are read back from disk on access.
+//! assert_eq!(map.get_mut(Point3i::fill(1)), &mut 1);
+//!
+//! // The log can be loaded again later.
+//! map.storage_mut().flush().unwrap();
+//! drop(map);
+//! let storage = DiskChunkStorage::<[i32; 3], _>::open(&path, compression).unwrap();
+//! assert_eq!(storage.len_on_disk(), 64);
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+
+use crate::{
+    CacheEntry, ChunkMap, ChunkMapBuilder, ChunkReadStorage, ChunkWriteStorage, Compressed,
+    Compression, IterChunkKeys, LocalChunkCache, LruCacheKeys, MaybeCompressed, SmallKeyHashMap,
+    SmallKeyLruCache, SpillSink,
+};
+
+use building_blocks_core::prelude::*;
+
+use core::hash::Hash;
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// A two-tier chunk storage. The first tier is an LRU cache of uncompressed chunks. The second tier is an append-only log file
+/// of compressed chunks.
+pub struct DiskChunkStorage<N, Compr>
+where
+    Compr: Compression,
+{
+    cache: SmallKeyLruCache<PointN<N>, Compr::Data, DiskLocation>,
+    // The records of cached chunks that were read back from the log. They stay live until the chunk is spilled again.
+    persisted: SmallKeyHashMap<PointN<N>, DiskLocation>,
+    compression: Compr,
+    path: PathBuf,
+    // Only locked for reads through `&self`.
+    file: Mutex<File>,
+    file_len: u64,
+    garbage_bytes: u64,
+}
+
+/// The location of a compressed chunk's data within the log file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DiskLocation {
+    pub offset: u64,
+    pub len: u64,
+}
+
+impl<N, Compr> DiskChunkStorage<N, Compr>
+where
+    PointN<N>: Hash + IntegerPoint<N> + Serialize + DeserializeOwned,
+    Compr: Compression,
+    Compr::CompressedData: Serialize + DeserializeOwned,
+{
+    /// Creates a new storage with an empty log file at `path`. If the file already exists, it will be truncated.
+    pub fn create(path: impl AsRef<Path>, compression: Compr) -> io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+
+        Ok(Self {
+            cache: SmallKeyLruCache::default(),
+            persisted: SmallKeyHashMap::default(),
+            compression,
+            path,
+            file: Mutex::new(file),
+            file_len: 0,
+            garbage_bytes: 0,
+        })
+    }
+
+    /// Opens the log file at `path`, creating it if it doesn't exist. All chunks in an existing log will be tracked as spilled
+    /// to disk; none of them are loaded until accessed. If the log ends with an incomplete record, e.g. from a crash during
+    /// `spill_lru`, it is truncated to the last complete record.
+    pub fn open(path: impl AsRef<Path>, compression: Compr) -> io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        let mut file_len = file.metadata()?.len();
+        let mut cache: SmallKeyLruCache<PointN<N>, Compr::Data, DiskLocation> =
+            SmallKeyLruCache::default();
+        let mut live_bytes = 0;
+        let mut offset = 0;
+        while offset < file_len {
+            let (key, is_tombstone, location) =
+                match read_record_header(&mut file, offset, file_len) {
+                    Ok(record) if record.2.offset + record.2.len <= file_len => record,
+                    Ok(_) => break,
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                    Err(e) => return Err(e),
+                };
+            if let Some(CacheEntry::Evicted(old_location)) = cache.remove(&key) {
+                live_bytes -= old_location.len;
+            }
+            if !is_tombstone {
+                cache.evict(key, location);
+                live_bytes += location.len;
+            }
+            offset = location.offset + location.len;
+            file.seek(SeekFrom::Start(offset))?;
+        }
+
+        // A crash while appending can leave a partial record at the end of the log. Drop it so new records are appended after
+        // the last complete one.
+        if offset < file_len {
+            file.set_len(offset)?;
+            file_len = offset;
+        }
+
+        Ok(Self {
+            cache,
+            persisted: SmallKeyHashMap::default(),
+            compression,
+            path,
+            file: Mutex::new(file),
+            file_len,
+            garbage_bytes: file_len - live_bytes,
+        })
+    }
+}
+
+impl<N, Compr> DiskChunkStorage<N, Compr>
+where
+    Compr: Compression,
+{
+    pub fn compression(&self) -> &Compr {
+        &self.compression
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The size of the log file in bytes.
+    pub fn file_len(&self) -> u64 {
+        self.file_len
+    }
+
+    /// The approximate number of bytes in the log file that are no longer referenced and could be reclaimed by `compact`.
+    pub fn garbage_bytes(&self) -> u64 {
+        self.garbage_bytes
+    }
+}
+
+impl<N, Compr> DiskChunkStorage<N, Compr>
+where
+    PointN<N>: Hash + IntegerPoint<N>,
+    Compr: Compression,
+{
+    /// The number of chunks cached in memory.
+    pub fn len_cached(&self) -> usize {
+        self.cache.len_cached()
+    }
+
+    /// The number of chunks that only exist on disk.
+    pub fn len_on_disk(&self) -> usize {
+        self.cache.len_evicted()
+    }
+}
+
+impl<N, Compr> DiskChunkStorage<N, Compr>
+where
+    PointN<N>: Hash + IntegerPoint<N> + Serialize + DeserializeOwned,
+    Compr: Compression,
+    Compr::CompressedData: Serialize + DeserializeOwned,
+{
+    /// Returns a reader that implements `ChunkReadStorage`.
+    pub fn reader<'a>(
+        &'a self,
+        local_cache: &'a LocalChunkCache<N, Compr::Data>,
+    ) -> DiskChunkStorageReader<'a, N, Compr> {
+        DiskChunkStorageReader {
+            storage: self,
+            local_cache,
+        }
+    }
+
+    /// Compresses the least-recently-used, cached chunk and appends it to the log. On further access, the chunk will be read
+    /// back from disk and cached. Returns `false` iff there were no cached chunks.
+    pub fn spill_lru(&mut self) -> io::Result<bool> {
+        if let Some((key, chunk)) = self.cache.remove_lru() {
+            let location = self.append_record(key, Some(&chunk))?;
+            self.cache.evict(key, location);
+            if let Some(old_location) = self.persisted.remove(&key) {
+                self.garbage_bytes += old_location.len;
+            }
+
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Compresses `chunk` and appends it to the log without caching it. Any chunk at `key` is replaced.
+    pub fn write_to_disk(&mut self, key: PointN<N>, chunk: &Compr::Data) -> io::Result<()> {
+        let location = self.append_record(key, Some(chunk))?;
+        let old_record = match self.cache.evict(key, location) {
+            Some(CacheEntry::Evicted(old_location)) => Some(old_location),
+            Some(CacheEntry::Cached(_)) => self.persisted.remove(&key),
+            None => None,
+        };
+        if let Some(old_location) = old_record {
+            self.garbage_bytes += old_location.len;
+        }
+
+        Ok(())
+    }
+
+    /// Spills all cached chunks to disk and syncs the file, so the log contains the entire map.
+    pub fn flush(&mut self) -> io::Result<()> {
+        while self.spill_lru()? {}
+
+        let file = self.file.get_mut().unwrap();
+        file.flush()?;
+        file.sync_data()
+    }
+
+    /// Consumes and flushes the chunk cache into the storage. This is not strictly necessary, but it will help with caching
+    /// efficiency.
+    pub fn flush_local_cache(&mut self, local_cache: LocalChunkCache<N, Compr::Data>) {
+        for (key, chunk) in local_cache.flush_iter() {
+            self.insert_chunk(key, chunk);
+        }
+    }
+
+    /// Inserts `chunk` at `key` and returns the old chunk. If the old chunk was on disk, only its location is returned, and it
+    /// can be read with `read_from_disk` until the next `compact`.
+    ///
+    /// The old chunk's record stays in the log until the new chunk is spilled.
+    pub fn insert_chunk(
+        &mut self,
+        key: PointN<N>,
+        chunk: Compr::Data,
+    ) -> Option<MaybeCompressed<Compr::Data, DiskLocation>> {
+        self.cache
+            .insert(key, chunk)
+            .map(|old_entry| match old_entry {
+                CacheEntry::Cached(chunk) => MaybeCompressed::Decompressed(chunk),
+                CacheEntry::Evicted(location) => {
+                    self.persisted.insert(key, location);
+
+                    MaybeCompressed::Compressed(location)
+                }
+            })
+    }
+
+    /// Remove the `Chunk` at `key`. If the chunk was on disk, only its location is returned, and it can be read with
+    /// `read_from_disk` until the next `compact`.
+    pub fn remove(&mut self, key: PointN<N>) -> Option<MaybeCompressed<Compr::Data, DiskLocation>> {
+        let (removed, record) = match self.cache.remove(&key) {
+            Some(CacheEntry::Cached(chunk)) => (
+                Some(MaybeCompressed::Decompressed(chunk)),
+                self.persisted.remove(&key),
+            ),
+            Some(CacheEntry::Evicted(location)) => {
+                (Some(MaybeCompressed::Compressed(location)), Some(location))
+            }
+            None => (None, None),
+        };
+
+        // Make sure the old record won't be loaded again by `open`.
+        if let Some(record) = record {
+            self.garbage_bytes += record.len;
+            self.append_record(key, None)
+                .expect("Failed to append tombstone to chunk log");
+        }
+
+        removed
+    }
+
+    /// Rewrites the log file so that it only contains the live record of each chunk, reclaiming all garbage. Cached chunks are
+    /// left in memory, but the records they were loaded from are kept, so they aren't lost if the process exits before the next
+    /// `flush`.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let compact_path = self.path.with_extension("compacting");
+        let mut compact_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&compact_path)?;
+
+        let file = self.file.get_mut().unwrap();
+        let mut new_evicted = Vec::with_capacity(self.cache.len_evicted());
+        let mut new_persisted = SmallKeyHashMap::default();
+        let mut compact_len = 0;
+        for (key, entry) in self.cache.entries() {
+            let (location, is_evicted) = match entry {
+                CacheEntry::Evicted(location) => (location, true),
+                CacheEntry::Cached(_) => match self.persisted.get(key) {
+                    Some(&location) => (location, false),
+                    None => continue,
+                },
+            };
+            let data = read_data(file, location)?;
+            let new_location = write_record(&mut compact_file, compact_len, key, Some(&data))?;
+            compact_len = new_location.offset + new_location.len;
+            if is_evicted {
+                new_evicted.push((*key, new_location));
+            } else {
+                new_persisted.insert(*key, new_location);
+            }
+        }
+        compact_file.flush()?;
+        compact_file.sync_data()?;
+        drop(compact_file);
+
+        std::fs::rename(&compact_path, &self.path)?;
+        *file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+
+        for (key, location) in new_evicted.into_iter() {
+            self.cache.evict(key, location);
+        }
+        self.persisted = new_persisted;
+        self.file_len = compact_len;
+        self.garbage_bytes = 0;
+
+        Ok(())
+    }
+
+    /// Reads and decompresses the chunk at `location`.
+    ///
+    /// WARNING: the cache will not be updated.
+    pub fn read_from_disk(&self, location: DiskLocation) -> Compr::Data {
+        let mut file = self.file.lock().unwrap();
+        let data = read_data(&mut file, location).expect("Failed to read chunk from disk");
+
+        Compr::decompress(
+            &bincode::deserialize(&data).expect("Failed to deserialize chunk from disk"),
+        )
+    }
+
+    fn load_old_entry(&self, entry: MaybeCompressed<Compr::Data, DiskLocation>) -> Compr::Data {
+        match entry {
+            MaybeCompressed::Decompressed(chunk) => chunk,
+            MaybeCompressed::Compressed(location) => self.read_from_disk(location),
+        }
+    }
+
+    fn append_record(
+        &mut self,
+        key: PointN<N>,
+        chunk: Option<&Compr::Data>,
+    ) -> io::Result<DiskLocation> {
+        let data = chunk.map(|chunk| {
+            bincode::serialize(&self.compression.compress(chunk).take())
+                .expect("Failed to serialize compressed chunk")
+        });
+        let file = self.file.get_mut().unwrap();
+        let location = write_record(file, self.file_len, &key, data.as_deref())?;
+        self.file_len = location.offset + location.len;
+
+        Ok(location)
+    }
+}
+
+fn write_record<N>(
+    file: &mut File,
+    offset: u64,
+    key: &PointN<N>,
+    data: Option<&[u8]>,
+) -> io::Result<DiskLocation>
+where
+    PointN<N>: Serialize,
+{
+    let key_bytes = bincode::serialize(key).expect("Failed to serialize chunk key");
+    let data = data.unwrap_or(&[]);
+    let is_tombstone = data.is_empty();
+
+    let mut header = Vec::with_capacity(4 + key_bytes.len() + 1 + 8);
+    header.extend_from_slice(&(key_bytes.len() as u32).to_le_bytes());
+    header.extend_from_slice(&key_bytes);
+    header.push(is_tombstone as u8);
+    header.extend_from_slice(&(data.len() as u64).to_le_bytes());
+
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(&header)?;
+    file.write_all(data)?;
+
+    Ok(DiskLocation {
+        offset: offset + header.len() as u64,
+        len: data.len() as u64,
+    })
+}
+
+/// Reads the header of the record at `offset`. A header that claims to extend past `file_len` is reported as
+/// `io::ErrorKind::UnexpectedEof`, like a record that was only partially written.
+fn read_record_header<N>(
+    file: &mut File,
+    offset: u64,
+    file_len: u64,
+) -> io::Result<(PointN<N>, bool, DiskLocation)>
+where
+    PointN<N>: DeserializeOwned,
+{
+    file.seek(SeekFrom::Start(offset))?;
+
+    let mut u32_bytes = [0; 4];
+    file.read_exact(&mut u32_bytes)?;
+    let key_len = u32::from_le_bytes(u32_bytes) as usize;
+    // Don't trust the length enough to allocate for it before checking that the key fits in the file.
+    if offset + 4 + key_len as u64 + 1 + 8 > file_len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "chunk record header extends past the end of the log",
+        ));
+    }
+
+    let mut key_bytes = vec![0; key_len];
+    file.read_exact(&mut key_bytes)?;
+    let key = bincode::deserialize(&key_bytes)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut tombstone_byte = [0; 1];
+    file.read_exact(&mut tombstone_byte)?;
+
+    let mut u64_bytes = [0; 8];
+    file.read_exact(&mut u64_bytes)?;
+    let data_len = u64::from_le_bytes(u64_bytes);
+
+    let location = DiskLocation {
+        offset: offset + 4 + key_len as u64 + 1 + 8,
+        len: data_len,
+    };
+
+    Ok((key, tombstone_byte[0] != 0, location))
+}
+
+fn read_data(file: &mut File, location: DiskLocation) -> io::Result<Vec<u8>> {
+    let mut data = vec![0; location.len as usize];
+    file.seek(SeekFrom::Start(location.offset))?;
+    file.read_exact(&mut data)?;
+
+    Ok(data)
+}
+
+impl<N, Compr> ChunkWriteStorage<N, Compr::Data> for DiskChunkStorage<N, Compr>
+where
+    PointN<N>: Hash + IntegerPoint<N> + Serialize + DeserializeOwned,
+    Compr: Compression,
+    Compr::CompressedData: Serialize + DeserializeOwned,
+{
+    #[inline]
+    fn get_mut(&mut self, key: PointN<N>) -> Option<&mut Compr::Data> {
+        let Self {
+            cache,
+            persisted,
+            file,
+            ..
+        } = self;
+
+        // The record stays live until the chunk is spilled again, since the cached copy could be lost before that.
+        cache.get_mut_or_repopulate_with(key, |location| {
+            persisted.insert(key, location);
+            load_chunk::<Compr>(file.get_mut().unwrap(), location)
+        })
+    }
+
+    #[inline]
+    fn get_mut_or_insert_with(
+        &mut self,
+        key: PointN<N>,
+        create_chunk: impl FnOnce() -> Compr::Data,
+    ) -> &mut Compr::Data {
+        let Self {
+            cache,
+            persisted,
+            file,
+            ..
+        } = self;
+
+        cache.get_mut_or_insert_with(
+            key,
+            |location| {
+                persisted.insert(key, location);
+                load_chunk::<Compr>(file.get_mut().unwrap(), location)
+            },
+            create_chunk,
+        )
+    }
+
+    #[inline]
+    fn replace(&mut self, key: PointN<N>, chunk: Compr::Data) -> Option<Compr::Data> {
+        self.insert_chunk(key, chunk)
+            .map(|old_entry| self.load_old_entry(old_entry))
+    }
+
+    #[inline]
+    fn write(&mut self, key: PointN<N>, chunk: Compr::Data) {
+        self.insert_chunk(key, chunk);
+    }
+
+    #[inline]
+    fn delete(&mut self, key: PointN<N>) {
+        self.remove(key);
+    }
+
+    #[inline]
+    fn pop(&mut self, key: PointN<N>) -> Option<Compr::Data> {
+        self.remove(key)
+            .map(|old_entry| self.load_old_entry(old_entry))
+    }
+}
+
+impl<N, Compr, SpillCompr> SpillSink<N, SpillCompr> for DiskChunkStorage<N, Compr>
+where
+    PointN<N>: Hash + IntegerPoint<N> + Serialize + DeserializeOwned,
+    Compr: Compression<Data = SpillCompr::Data>,
+    Compr::CompressedData: Serialize + DeserializeOwned,
+    SpillCompr: Compression,
+{
+    fn spill(&mut self, key: PointN<N>, chunk: &Compressed<SpillCompr>) -> io::Result<()> {
+        self.write_to_disk(key, &chunk.decompress())
+    }
+
+    fn load(&self, key: PointN<N>) -> io::Result<Option<Compr::Data>> {
+        let location = match self.cache.get(&key) {
+            Some(CacheEntry::Evicted(location)) => location,
+            // Spilled chunks are never cached here.
+            _ => return Ok(None),
+        };
+        let mut file = self.file.lock().unwrap();
+        let data = read_data(&mut file, location)?;
+        let compressed_data = bincode::deserialize(&data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(Some(Compr::decompress(&compressed_data)))
+    }
+
+    fn remove(&mut self, key: PointN<N>) -> io::Result<()> {
+        if let Some(CacheEntry::Evicted(location)) = self.cache.remove(&key) {
+            self.garbage_bytes += location.len;
+            self.append_record(key, None)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn load_chunk<Compr>(file: &mut File, location: DiskLocation) -> Compr::Data
+where
+    Compr: Compression,
+    Compr::CompressedData: DeserializeOwned,
+{
+    let data = read_data(file, location).expect("Failed to read chunk from disk");
+
+    Compr::decompress(&bincode::deserialize(&data).expect("Failed to deserialize chunk from disk"))
+}
+
+impl<'a, N, Compr> IterChunkKeys<'a, N> for DiskChunkStorage<N, Compr>
+where
+    N: 'a,
+    PointN<N>: Hash + IntegerPoint<N>,
+    Compr::Data: 'a,
+    Compr: Compression,
+{
+    type Iter = LruCacheKeys<'a, PointN<N>, Compr::Data, DiskLocation>;
+
+    fn chunk_keys(&'a self) -> Self::Iter {
+        self.cache.keys()
+    }
+}
+
+/// An object for reading from `DiskChunkStorage` with only `&self`. Easily construct one of these using the
+/// `DiskChunkStorage::reader` method.
+///
+/// This works by using a `LocalChunkCache` for storing decompressed `Chunk`s that were read from disk.
+pub struct DiskChunkStorageReader<'a, N, Compr>
+where
+    Compr: Compression,
+{
+    pub storage: &'a DiskChunkStorage<N, Compr>,
+    pub local_cache: &'a LocalChunkCache<N, Compr::Data>,
+}
+
+impl<'a, N, Compr> ChunkReadStorage<N, Compr::Data> for DiskChunkStorageReader<'a, N, Compr>
+where
+    PointN<N>: Hash + IntegerPoint<N> + Serialize + DeserializeOwned,
+    Compr: Compression,
+    Compr::CompressedData: Serialize + DeserializeOwned,
+{
+    #[inline]
+    fn get(&self, key: PointN<N>) -> Option<&Compr::Data> {
+        let Self {
+            storage,
+            local_cache,
+        } = self;
+
+        storage.cache.get(&key).map(|entry| match entry {
+            CacheEntry::Cached(value) => value,
+            CacheEntry::Evicted(location) => {
+                local_cache.get_or_insert_with(key, || storage.read_from_disk(location))
+            }
+        })
+    }
+}
+
+impl<'a, N, Compr> IterChunkKeys<'a, N> for DiskChunkStorageReader<'a, N, Compr>
+where
+    PointN<N>: Hash + IntegerPoint<N>,
+    Compr: Compression,
+{
+    type Iter = LruCacheKeys<'a, PointN<N>, Compr::Data, DiskLocation>;
+
+    fn chunk_keys(&'a self) -> Self::Iter {
+        self.storage.cache.keys()
+    }
+}
+
+/// A `ChunkMap` using `DiskChunkStorage` as chunk storage.
+pub type DiskChunkMap<N, T, Bldr, Compr> = ChunkMap<N, T, Bldr, DiskChunkStorage<N, Compr>>;
+
+/// A `ChunkMap` backed by a `DiskChunkStorageReader`.
+pub type DiskChunkMapReader<'a, N, T, Bldr, Compr> =
+    ChunkMap<N, T, Bldr, DiskChunkStorageReader<'a, N, Compr>>;
+
+impl<N, T, Bldr, Compr> DiskChunkMap<N, T, Bldr, Compr>
+where
+    PointN<N>: Hash + IntegerPoint<N> + Serialize + DeserializeOwned,
+    Bldr: ChunkMapBuilder<N, T> + Clone,
+    Compr: Compression<Data = Bldr::Chunk>,
+    Compr::CompressedData: Serialize + DeserializeOwned,
+{
+    /// Construct a reader for this map.
+    pub fn reader<'a>(
+        &'a self,
+        local_cache: &'a LocalChunkCache<N, Bldr::Chunk>,
+    ) -> DiskChunkMapReader<'a, N, T, Bldr, Compr> {
+        self.builder()
+            .clone()
+            .build_with_read_storage(self.storage().reader(local_cache))
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(all(test, feature = "lz4"))]
+mod tests {
+    use super::*;
+
+    use crate::{prelude::*, BincodeCompression, Lz4};
+
+    fn temp_log_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "bb_disk_chunk_storage_{}_{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn spill_and_reload_chunks() {
+        let path = temp_log_path("spill_and_reload_chunks");
+        let compression = BincodeCompression::new(Lz4 { level: 10 });
+        let builder = ChunkMapBuilder3x1::new(Point3i::fill(16), 0);
+        let mut map = builder
+            .clone()
+            .build_with_write_storage(DiskChunkStorage::create(&path, compression).unwrap());
+
+        let extent = Extent3i::from_min_and_shape(Point3i::fill(-20), Point3i::fill(40));
+        map.for_each_mut(&extent, |p: Point3i, value| *value = p.x() * p.y() * p.z());
+        let num_chunks = map.storage().len_cached();
+        assert_eq!(num_chunks, 64);
+
+        map.storage_mut().flush().unwrap();
+        assert_eq!(map.storage().len_on_disk(), num_chunks);
+        assert_eq!(map.storage().garbage_bytes(), 0);
+
+        // Read through `&self`.
+        let local_cache = LocalChunkCache::new();
+        let reader = map.reader(&local_cache);
+        reader.for_each(&extent, |p: Point3i, value| {
+            assert_eq!(value, p.x() * p.y() * p.z())
+        });
+        map.storage_mut().flush_local_cache(local_cache);
+        assert_eq!(map.storage().len_cached(), num_chunks);
+
+        // Delete one chunk and overwrite another before persisting.
+        map.delete_chunk(Point3i::fill(-32));
+        *map.get_mut(Point3i::ZERO) = 7;
+        map.storage_mut().flush().unwrap();
+        assert!(map.storage().garbage_bytes() > 0);
+        drop(map);
+
+        let mut map =
+            builder.build_with_write_storage(DiskChunkStorage::open(&path, compression).unwrap());
+        assert_eq!(map.storage().len_on_disk(), num_chunks - 1);
+        assert!(map.get_mut_chunk(Point3i::fill(-32)).is_none());
+
+        let file_len_before = map.storage().file_len();
+        map.storage_mut().compact().unwrap();
+        assert!(map.storage().file_len() < file_len_before);
+        assert_eq!(map.storage().len_on_disk(), num_chunks - 1);
+
+        let read_extent = Extent3i::from_min_and_shape(Point3i::fill(-16), Point3i::fill(36));
+        map.for_each_mut(&read_extent, |p: Point3i, value| {
+            if p == Point3i::ZERO {
+                assert_eq!(*value, 7);
+            } else {
+                assert_eq!(*value, p.x() * p.y() * p.z());
+            }
+        });
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn open_truncates_torn_record() {
+        let path = temp_log_path("open_truncates_torn_record");
+        let compression = BincodeCompression::new(Lz4 { level: 10 });
+        let builder = ChunkMapBuilder3x1::new(Point3i::fill(16), 0);
+        let mut map = builder
+            .clone()
+            .build_with_write_storage(DiskChunkStorage::create(&path, compression).unwrap());
+
+        let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(32));
+        map.fill_extent(&extent, 1);
+        map.storage_mut().flush().unwrap();
+        let file_len = map.storage().file_len();
+
+        // Removing a chunk that doesn't exist doesn't need a tombstone.
+        map.delete_chunk(Point3i::fill(-16));
+        assert_eq!(map.storage().file_len(), file_len);
+        drop(map);
+
+        // Simulate a crash in the middle of appending a record.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[8, 0, 0, 0, 1, 2]).unwrap();
+        drop(file);
+
+        let mut map = builder
+            .clone()
+            .build_with_write_storage(DiskChunkStorage::open(&path, compression).unwrap());
+        assert_eq!(map.storage().len_on_disk(), 8);
+        assert_eq!(map.storage().file_len(), file_len);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), file_len);
+
+        *map.get_mut(Point3i::ZERO) = 2;
+        map.storage_mut().flush().unwrap();
+        drop(map);
+
+        let mut map = builder
+            .clone()
+            .build_with_write_storage(DiskChunkStorage::open(&path, compression).unwrap());
+        assert_eq!(map.get_mut(Point3i::ZERO), &mut 2);
+        assert_eq!(map.get_mut(Point3i::fill(31)), &mut 1);
+        drop(map);
+
+        // A corrupt key length must not be trusted for allocation.
+        let file_len = std::fs::metadata(&path).unwrap().len();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0xff, 0xff, 0xff, 0xff, 1, 2, 3]).unwrap();
+        drop(file);
+
+        let map =
+            builder.build_with_write_storage(DiskChunkStorage::open(&path, compression).unwrap());
+        assert_eq!(map.storage().len_on_disk(), 8);
+        assert_eq!(map.storage().file_len(), file_len);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn loaded_chunks_keep_their_records_until_spilled() {
+        let path = temp_log_path("loaded_chunks_keep_their_records_until_spilled");
+        let compression = BincodeCompression::new(Lz4 { level: 10 });
+        let builder = ChunkMapBuilder3x1::new(Point3i::fill(16), 0);
+        let mut map = builder
+            .clone()
+            .build_with_write_storage(DiskChunkStorage::create(&path, compression).unwrap());
+
+        let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(32));
+        map.fill_extent(&extent, 1);
+        map.storage_mut().flush().unwrap();
+        assert_eq!(map.storage().garbage_bytes(), 0);
+
+        // Loading chunks for writing doesn't make their records garbage, and neither does compacting while they're cached.
+        *map.get_mut(Point3i::ZERO) = 2;
+        *map.get_mut(Point3i::fill(16)) = 3;
+        assert_eq!(map.storage().garbage_bytes(), 0);
+        map.storage_mut().compact().unwrap();
+        assert_eq!(map.storage().len_cached(), 2);
+        drop(map);
+
+        // The modifications were never flushed, but the chunks are still in the log.
+        let mut map = builder
+            .clone()
+            .build_with_write_storage(DiskChunkStorage::open(&path, compression).unwrap());
+        assert_eq!(map.storage().len_on_disk(), 8);
+        assert_eq!(map.get_mut(Point3i::ZERO), &mut 1);
+        assert_eq!(map.get_mut(Point3i::fill(16)), &mut 1);
+
+        // Spilling the loaded chunk retires its old record.
+        *map.get_mut(Point3i::ZERO) = 2;
+        map.storage_mut().flush().unwrap();
+        let record_len = map.storage().garbage_bytes();
+        assert!(record_len > 0);
+
+        // Removing a loaded chunk retires its record too.
+        map.get_mut(Point3i::fill(16));
+        map.delete_chunk(Point3i::fill(16));
+        assert!(map.storage().garbage_bytes() > record_len);
+        drop(map);
+
+        let mut map =
+            builder.build_with_write_storage(DiskChunkStorage::open(&path, compression).unwrap());
+        assert_eq!(map.storage().len_on_disk(), 7);
+        assert_eq!(map.get_mut(Point3i::ZERO), &mut 2);
+        assert!(map.get_mut_chunk(Point3i::fill(16)).is_none());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
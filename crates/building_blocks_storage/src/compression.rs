@@ -4,6 +4,8 @@ mod compressed_bincode;
 mod lz4_compression;
 #[cfg(feature = "snap")]
 mod snappy_compression;
+#[cfg(feature = "zstd")]
+mod zstd_compression;
 
 pub use compressed_bincode::BincodeCompression;
 
@@ -11,6 +13,8 @@ pub use compressed_bincode::BincodeCompression;
 pub use lz4_compression::Lz4;
 #[cfg(feature = "snap")]
 pub use snappy_compression::Snappy;
+#[cfg(feature = "zstd")]
+pub use zstd_compression::{NoDictionary, PreparedZstdDictionary, Zstd, ZstdDictionary};
 
 use serde::{Deserialize, Serialize};
 
@@ -61,6 +65,10 @@ where
 
 /// A compression algorithm that acts directly on a slice of bytes.
 pub trait BytesCompression {
+    /// Identifies the codec in a `ChunkFileHeader`. This must never change, or else old saves will look like they were
+    /// compressed with a different codec.
+    const CODEC_ID: &'static str;
+
     fn compress_bytes(&self, bytes: &[u8], compressed_bytes: impl std::io::Write);
     fn decompress_bytes(compressed_bytes: &[u8], bytes: &mut impl std::io::Write);
 }
@@ -86,3 +94,20 @@ impl<A: Compression> MaybeCompressed<A::Data, Compressed<A>> {
         }
     }
 }
+
+/// A `BytesCompression` that leaves the bytes unchanged. This is useful for encodings that are already compact on their own,
+/// like `RunLengthChannelsCompression` and `PaletteChannelsCompression`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct NoCompression;
+
+impl BytesCompression for NoCompression {
+    const CODEC_ID: &'static str = "none";
+
+    fn compress_bytes(&self, bytes: &[u8], mut compressed_bytes: impl std::io::Write) {
+        compressed_bytes.write_all(bytes).unwrap();
+    }
+
+    fn decompress_bytes(compressed_bytes: &[u8], bytes: &mut impl std::io::Write) {
+        bytes.write_all(compressed_bytes).unwrap();
+    }
+}
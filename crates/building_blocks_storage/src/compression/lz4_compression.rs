@@ -11,6 +11,8 @@ pub struct Lz4 {
 }
 
 impl BytesCompression for Lz4 {
+    const CODEC_ID: &'static str = "lz4";
+
     fn compress_bytes(&self, bytes: &[u8], compressed_bytes: impl std::io::Write) {
         let mut encoder = lz4::EncoderBuilder::new()
             .level(self.level)
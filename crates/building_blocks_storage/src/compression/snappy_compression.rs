@@ -8,6 +8,8 @@ use serde::{Deserialize, Serialize};
 pub struct Snappy;
 
 impl BytesCompression for Snappy {
+    const CODEC_ID: &'static str = "snappy";
+
     fn compress_bytes(&self, bytes: &[u8], compressed_bytes: impl std::io::Write) {
         let mut encoder = snap::write::FrameEncoder::new(compressed_bytes);
         std::io::copy(&mut std::io::Cursor::new(bytes), &mut encoder).unwrap();
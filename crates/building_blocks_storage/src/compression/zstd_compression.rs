@@ -0,0 +1,323 @@
+use super::BytesCompression;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::marker::PhantomData;
+use std::sync::{Arc, RwLock};
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+
+/// The [Zstandard compression algorithm](https://en.wikipedia.org/wiki/Zstandard). It's slower than `Lz4`, but it achieves a
+/// much better compression rate, which makes it a good choice for archival saves and network transfer.
+///
+/// Since `Zstd` carries a dictionary type parameter, it can't be built with a struct literal. Use `Zstd::new(level)` to compress
+/// without a dictionary, or `Zstd::<D>::with_dictionary(level)` to compress with `D`'s dictionary.
+///
+/// ```
+/// # use building_blocks_storage::Zstd;
+/// let compression = Zstd::new(3);
+/// assert_eq!(compression.level, 3);
+/// ```
+///
+/// Small chunks don't have much redundancy within themselves, so they can be compressed much better with a dictionary trained
+/// on samples of similar data. Since decompression only has access to the compressed bytes, the dictionary is part of the
+/// compression type: `Zstd<D>` compresses and decompresses with the dictionary provided by `D: ZstdDictionary`.
+///
+/// ```
+/// # use building_blocks_storage::{PreparedZstdDictionary, Zstd, ZstdDictionary};
+/// # use std::sync::OnceLock;
+/// struct TerrainDictionary;
+///
+/// impl ZstdDictionary for TerrainDictionary {
+///     fn dictionary() -> Option<&'static PreparedZstdDictionary> {
+///         static DICTIONARY: OnceLock<PreparedZstdDictionary> = OnceLock::new();
+///
+///         // This would usually be loaded from an asset, e.g. with `include_bytes!`.
+///         Some(DICTIONARY.get_or_init(|| {
+///             let samples: Vec<Vec<u8>> = (0..500u32)
+///                 .map(|i| (0..64u32).flat_map(|j| ((j * 7919) ^ (i % 5)).to_le_bytes()).collect())
+///                 .collect();
+///             let bytes = PreparedZstdDictionary::train(&samples, 4096).unwrap();
+///
+///             PreparedZstdDictionary::new(bytes).unwrap()
+///         }))
+///     }
+/// }
+///
+/// let compression = Zstd::<TerrainDictionary>::with_dictionary(3);
+/// ```
+///
+/// The `BytesCompression` methods panic if a frame was compressed with a dictionary other than `D`'s. Use
+/// `try_decompress_bytes` to get an error instead.
+#[derive(Deserialize, Serialize)]
+#[serde(bound = "")]
+pub struct Zstd<D = NoDictionary> {
+    /// The compression level, from 1 to 22. 1 is fastest and least aggressive. 22 is slowest and most aggressive. 0 selects the
+    /// default level.
+    pub level: i32,
+    #[serde(skip)]
+    marker: PhantomData<D>,
+}
+
+impl<D> Clone for Zstd<D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<D> Copy for Zstd<D> {}
+
+impl<D> std::fmt::Debug for Zstd<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Zstd").field("level", &self.level).finish()
+    }
+}
+
+impl Zstd {
+    /// Compresses without a dictionary.
+    pub fn new(level: i32) -> Self {
+        Self::with_dictionary(level)
+    }
+}
+
+impl<D> Zstd<D> {
+    /// Compresses with the dictionary provided by `D`.
+    pub fn with_dictionary(level: i32) -> Self {
+        Self {
+            level,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<D> Zstd<D>
+where
+    D: ZstdDictionary,
+{
+    /// Same as `compress_bytes`, but returns an error instead of panicking.
+    pub fn try_compress_bytes(
+        &self,
+        bytes: &[u8],
+        mut compressed_bytes: impl io::Write,
+    ) -> io::Result<()> {
+        // The bulk API knows the source size up front, so it can use much smaller tables than the streaming API for small chunks.
+        let compressed = match D::dictionary() {
+            Some(dictionary) => {
+                let encoder = dictionary.encoder(self.level);
+                let mut compressor = zstd::bulk::Compressor::with_prepared_dictionary(&encoder)?;
+                compressor.compress(bytes)?
+            }
+            None => zstd::bulk::Compressor::new(self.level)?.compress(bytes)?,
+        };
+
+        compressed_bytes.write_all(&compressed)
+    }
+
+    /// Same as `decompress_bytes`, but returns an `InvalidData` error if the frame was compressed with a dictionary other than
+    /// `D`'s.
+    pub fn try_decompress_bytes(
+        compressed_bytes: &[u8],
+        bytes: &mut impl io::Write,
+    ) -> io::Result<()> {
+        match zstd::zstd_safe::get_dict_id_from_frame(compressed_bytes) {
+            Some(id) => {
+                let dictionary = D::dictionary()
+                    .filter(|dictionary| dictionary.id == id.get())
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("Zstd frame needs dictionary {}", id),
+                        )
+                    })?;
+                let mut decoder = zstd::stream::read::Decoder::with_prepared_dictionary(
+                    compressed_bytes,
+                    &dictionary.decoder,
+                )?;
+                io::copy(&mut decoder, bytes)?;
+            }
+            None => {
+                let mut decoder = zstd::stream::read::Decoder::with_buffer(compressed_bytes)?;
+                io::copy(&mut decoder, bytes)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<D> BytesCompression for Zstd<D>
+where
+    D: ZstdDictionary,
+{
+    const CODEC_ID: &'static str = "zstd";
+
+    fn compress_bytes(&self, bytes: &[u8], compressed_bytes: impl io::Write) {
+        self.try_compress_bytes(bytes, compressed_bytes).unwrap();
+    }
+
+    fn decompress_bytes(compressed_bytes: &[u8], bytes: &mut impl io::Write) {
+        Self::try_decompress_bytes(compressed_bytes, bytes).unwrap();
+    }
+}
+
+/// Provides the dictionary used by `Zstd<Self>`.
+///
+/// The dictionary is needed for every compression and decompression, so it should be prepared once and kept in a `static`.
+pub trait ZstdDictionary {
+    fn dictionary() -> Option<&'static PreparedZstdDictionary>;
+}
+
+/// The `ZstdDictionary` of plain `Zstd` compression.
+pub struct NoDictionary;
+
+impl ZstdDictionary for NoDictionary {
+    fn dictionary() -> Option<&'static PreparedZstdDictionary> {
+        None
+    }
+}
+
+/// A dictionary, digested for faster compression and decompression.
+pub struct PreparedZstdDictionary {
+    id: u32,
+    bytes: Vec<u8>,
+    decoder: DecoderDictionary<'static>,
+    // Encoder dictionaries depend on the compression level, so they're prepared lazily for each level that's used.
+    encoders: RwLock<HashMap<i32, Arc<EncoderDictionary<'static>>>>,
+}
+
+impl PreparedZstdDictionary {
+    /// Trains a dictionary of at most `max_size` bytes on `samples`. For chunks, each sample should be the bytes of one chunk,
+    /// as they would be passed to `compress_bytes`.
+    ///
+    /// Zstandard needs a decent number of samples (on the order of 100) to train a useful dictionary.
+    pub fn train<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> io::Result<Vec<u8>> {
+        zstd::dict::from_samples(samples, max_size)
+    }
+
+    /// Prepares `dictionary` for use with `Zstd`.
+    ///
+    /// The dictionary must have been created by `PreparedZstdDictionary::train` (or the `zstd` CLI), since raw content
+    /// dictionaries don't have an ID to check against the compressed frames; otherwise an `InvalidInput` error is returned.
+    pub fn new(dictionary: Vec<u8>) -> io::Result<Self> {
+        let id = zstd::zstd_safe::get_dict_id(&dictionary)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Zstd dictionary has no ID")
+            })?
+            .get();
+
+        Ok(Self {
+            id,
+            decoder: DecoderDictionary::copy(&dictionary),
+            encoders: Default::default(),
+            bytes: dictionary,
+        })
+    }
+
+    /// The ID that Zstandard stores in every frame compressed with this dictionary.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn encoder(&self, level: i32) -> Arc<EncoderDictionary<'static>> {
+        if let Some(encoder) = self.encoders.read().unwrap().get(&level) {
+            return encoder.clone();
+        }
+
+        self.encoders
+            .write()
+            .unwrap()
+            .entry(level)
+            .or_insert_with(|| Arc::new(EncoderDictionary::copy(&self.bytes, level)))
+            .clone()
+    }
+}
+
+// ████████╗███████╗███████╗████████╗███████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝██╔════╝
+//    ██║   █████╗  ███████╗   ██║   ███████╗
+//    ██║   ██╔══╝  ╚════██║   ██║   ╚════██║
+//    ██║   ███████╗███████║   ██║   ███████║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝   ╚══════╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::OnceLock;
+
+    #[test]
+    fn compress_and_decompress_serializable_type() {
+        let bytes: Vec<u8> = (0u8..100).collect();
+
+        let mut compressed_bytes = Vec::new();
+        Zstd::new(19).compress_bytes(&bytes, &mut compressed_bytes);
+        let mut decompressed_bytes = Vec::new();
+        Zstd::<NoDictionary>::decompress_bytes(&compressed_bytes, &mut decompressed_bytes);
+
+        assert_eq!(bytes, decompressed_bytes);
+    }
+
+    #[test]
+    fn compress_and_decompress_with_trained_dictionary() {
+        let sample = &samples(5)[3];
+        let mut compressed_without_dictionary = Vec::new();
+        Zstd::new(3).compress_bytes(sample, &mut compressed_without_dictionary);
+        let mut compressed_with_dictionary = Vec::new();
+        Zstd::<TestDictionary>::with_dictionary(3)
+            .compress_bytes(sample, &mut compressed_with_dictionary);
+        assert!(compressed_with_dictionary.len() < compressed_without_dictionary.len());
+
+        let mut decompressed_bytes = Vec::new();
+        Zstd::<TestDictionary>::decompress_bytes(
+            &compressed_with_dictionary,
+            &mut decompressed_bytes,
+        );
+        assert_eq!(sample, &decompressed_bytes);
+    }
+
+    #[test]
+    fn decompressing_without_the_dictionary_is_an_error() {
+        let mut compressed_bytes = Vec::new();
+        Zstd::<TestDictionary>::with_dictionary(3)
+            .compress_bytes(&samples(5)[0], &mut compressed_bytes);
+
+        let error = Zstd::<NoDictionary>::try_decompress_bytes(&compressed_bytes, &mut Vec::new())
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn raw_content_dictionary_is_rejected() {
+        let error = PreparedZstdDictionary::new(vec![1; 256]).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    struct TestDictionary;
+
+    impl ZstdDictionary for TestDictionary {
+        fn dictionary() -> Option<&'static PreparedZstdDictionary> {
+            static DICTIONARY: OnceLock<PreparedZstdDictionary> = OnceLock::new();
+
+            Some(DICTIONARY.get_or_init(|| {
+                let bytes = PreparedZstdDictionary::train(&samples(5), 4096).unwrap();
+
+                PreparedZstdDictionary::new(bytes).unwrap()
+            }))
+        }
+    }
+
+    // Small samples that share a lot of structure with each other, but not much within themselves.
+    fn samples(period: u32) -> Vec<Vec<u8>> {
+        (0..500u32)
+            .map(|i| {
+                (0..64u32)
+                    .flat_map(|j| ((j * 7919) ^ (i % period)).to_le_bytes())
+                    .collect()
+            })
+            .collect()
+    }
+}
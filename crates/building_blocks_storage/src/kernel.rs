@@ -0,0 +1,480 @@
+//! Neighborhood operations, like blurs and morphology, that apply a kernel to every point of an extent.
+//!
+//! All of the operations read the points around each point of `extent` using precomputed `Stride` offsets, so the source array
+//! must contain `extent` padded by the radius of the kernel. To run a kernel over a region of a `ChunkMap`, first copy out the
+//! padded region with `ChunkMap::padded_array`; any vacant chunks will be read as the ambient value.
+//!
+//! Convolutions are done with a `SeparableKernel`, one axis at a time:
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{prelude::*, convolve_separable, SeparableKernel};
+//!
+//! let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(16));
+//! let mut array = Array3x1::fill(extent, 0.0f32);
+//! *array.get_mut(PointN([8, 8, 8])) = 1.0;
+//!
+//! let kernel = SeparableKernel::gaussian(1.0);
+//! let blurred = convolve_separable(&array, &extent.padded(-kernel.radius()), &kernel);
+//! assert!(blurred.get(PointN([8, 8, 8])) < 1.0);
+//! assert!(blurred.get(PointN([9, 8, 8])) > 0.0);
+//! ```
+//!
+//! Morphological operations use a `StructuringElement`:
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{prelude::*, morphology, MorphologyOp, StructuringElement};
+//!
+//! let mut map = ChunkMapBuilder3x1::new(Point3i::fill(16), false).build_with_hash_map_storage();
+//! *map.get_mut(Point3i::ZERO) = true;
+//!
+//! let element = StructuringElement::ball(2);
+//! let extent = Extent3i::from_min_and_shape(Point3i::fill(-8), Point3i::fill(16));
+//! let padded = map.padded_array(&extent, MorphologyOp::Dilate.padding(&element));
+//! let dilated = morphology(&padded, &extent, MorphologyOp::Dilate, &element);
+//!
+//! assert!(dilated.get(PointN([0, 2, 0])));
+//! assert!(!dilated.get(PointN([0, 2, 1])));
+//! ```
+
+use crate::{
+    copy_extent, Array, ArrayIndexer, ArrayNx1, ChunkMap, ChunkMapBuilder, Get, GetMut,
+    IndexedArray, Local, ReadExtent, ResampleValue, Stride, WriteExtent,
+};
+
+use building_blocks_core::prelude::*;
+
+/// A 1-dimensional kernel that gets applied along every axis in turn. Blurring with a separable kernel of radius `r` only costs
+/// `N * (2r + 1)` reads per point instead of `(2r + 1)^N`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeparableKernel {
+    weights: Vec<f32>,
+}
+
+impl SeparableKernel {
+    /// `weights` must have an odd length, and the middle weight is applied to the center point.
+    pub fn new(weights: Vec<f32>) -> Self {
+        assert!(weights.len() % 2 == 1);
+
+        Self { weights }
+    }
+
+    /// A normalized Gaussian with standard deviation `sigma`, truncated at `3 * sigma`.
+    pub fn gaussian(sigma: f32) -> Self {
+        assert!(sigma > 0.0);
+
+        let radius = (3.0 * sigma).ceil() as i32;
+        let weights: Vec<f32> = (-radius..=radius)
+            .map(|k| (-((k * k) as f32) / (2.0 * sigma * sigma)).exp())
+            .collect();
+        let sum: f32 = weights.iter().sum();
+
+        Self::new(weights.into_iter().map(|w| w / sum).collect())
+    }
+
+    /// Takes the mean of the `2 * radius + 1` points on each axis.
+    pub fn box_filter(radius: i32) -> Self {
+        assert!(radius >= 0);
+
+        let size = 2 * radius + 1;
+
+        Self::new(vec![1.0 / size as f32; size as usize])
+    }
+
+    #[inline]
+    pub fn radius(&self) -> i32 {
+        (self.weights.len() / 2) as i32
+    }
+
+    #[inline]
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+}
+
+/// Convolves `src` with `kernel` along every axis, writing the results for `extent` into a new array.
+///
+/// `src` must contain `extent.padded(kernel.radius())`. Values are converted to `f64` with `ResampleValue` for filtering, so
+/// integer values are rounded to the nearest integer and clamped to the range of the type.
+pub fn convolve_separable<N, Src, T>(
+    src: &Src,
+    extent: &ExtentN<N>,
+    kernel: &SeparableKernel,
+) -> ArrayNx1<N, T>
+where
+    N: ArrayIndexer<N>,
+    PointN<N>: IntegerPoint<N>,
+    T: 'static + Clone + ResampleValue,
+    Src: IndexedArray<N, Indexer = N> + Get<Stride, Item = T>,
+{
+    let radius = kernel.radius();
+    let padded_extent = extent.padded(radius);
+    assert!(padded_extent.is_subset_of(src.extent()));
+
+    // Ping-pong between two buffers, one pass per axis. Each pass can skip the padding on the axes that have already been
+    // filtered.
+    let mut read_buffer = ArrayNx1::fill(padded_extent, 0.0f64);
+    let mut write_buffer = ArrayNx1::fill(padded_extent, 0.0f64);
+    N::for_each_stride_parallel_global_unchecked(
+        &padded_extent,
+        &padded_extent,
+        src.layout_extent(),
+        |s_buffer, s_src| *read_buffer.get_mut(s_buffer) = src.get(s_src).to_f64(),
+    );
+
+    let mut pass_extent = padded_extent;
+    let mut tap_strides = vec![Stride(0); kernel.weights().len()];
+    for axis in PointN::<N>::basis() {
+        let tap_offsets: Vec<_> = (-radius..=radius).map(|k| Local(axis * k)).collect();
+        read_buffer.strides_from_local_points(&tap_offsets, &mut tap_strides);
+        pass_extent = ExtentN::from_min_and_shape(
+            pass_extent.minimum + axis * radius,
+            pass_extent.shape - axis * (2 * radius),
+        );
+
+        N::for_each_stride_parallel_global_unchecked(
+            &pass_extent,
+            &padded_extent,
+            &padded_extent,
+            |s_write, s_read| {
+                let mut sum = 0.0;
+                for (w, tap) in kernel.weights().iter().zip(tap_strides.iter()) {
+                    sum += *w as f64 * read_buffer.get(s_read + *tap);
+                }
+                *write_buffer.get_mut(s_write) = sum;
+            },
+        );
+        std::mem::swap(&mut read_buffer, &mut write_buffer);
+    }
+
+    let mut values = Vec::with_capacity(extent.num_points());
+    N::for_each_stride_parallel_global_unchecked(extent, extent, &padded_extent, |_, s| {
+        values.push(T::from_f64(read_buffer.get(s)))
+    });
+
+    Array::new_one_channel(*extent, values)
+}
+
+/// A set of offsets that defines the neighborhood of each point for morphological operations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructuringElement<N> {
+    offsets: Vec<Local<N>>,
+    radius: i32,
+}
+
+impl<N> StructuringElement<N>
+where
+    PointN<N>: IntegerPoint<N> + MinMaxComponent<Scalar = i32>,
+{
+    /// Any nonempty set of `offsets` from the center point. The center point is only part of the neighborhood if
+    /// `offsets` contains `Local(PointN::ZERO)`.
+    pub fn new(offsets: Vec<Local<N>>) -> Self {
+        let radius = offsets
+            .iter()
+            .map(|o| o.0.abs().max_component())
+            .max()
+            .expect("Structuring element must have at least one offset");
+
+        Self { offsets, radius }
+    }
+
+    /// All offsets in the box `[-radius, radius]^N`.
+    pub fn cube(radius: i32) -> Self {
+        Self::new(Local::localize_points_slice(
+            &Self::cube_extent(radius).iter_points().collect::<Vec<_>>(),
+        ))
+    }
+
+    /// All offsets within Euclidean distance `radius` of the center.
+    pub fn ball(radius: i32) -> Self
+    where
+        PointN<N>: DotProduct<Scalar = i32>,
+    {
+        Self::new(Local::localize_points_slice(
+            &Self::cube_extent(radius)
+                .iter_points()
+                .filter(|p| p.dot(*p) <= radius * radius)
+                .collect::<Vec<_>>(),
+        ))
+    }
+
+    fn cube_extent(radius: i32) -> ExtentN<N> {
+        assert!(radius >= 0);
+
+        ExtentN::from_min_and_shape(PointN::fill(-radius), PointN::fill(2 * radius + 1))
+    }
+}
+
+impl<N> StructuringElement<N> {
+    #[inline]
+    pub fn offsets(&self) -> &[Local<N>] {
+        &self.offsets
+    }
+
+    /// The largest component of any offset.
+    #[inline]
+    pub fn radius(&self) -> i32 {
+        self.radius
+    }
+}
+
+/// A morphological operation. "Solid" points are the ones with larger values, e.g. `true` for `bool` voxels.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MorphologyOp {
+    /// Takes the maximum value in the neighborhood of each point, growing solid regions.
+    Dilate,
+    /// Takes the minimum value in the neighborhood of each point, shrinking solid regions.
+    Erode,
+    /// Erodes then dilates, removing solid features smaller than the structuring element.
+    Open,
+    /// Dilates then erodes, filling holes smaller than the structuring element.
+    Close,
+}
+
+impl MorphologyOp {
+    /// How much the source array must be padded around the output extent.
+    #[inline]
+    pub fn padding<N>(&self, element: &StructuringElement<N>) -> i32 {
+        match self {
+            MorphologyOp::Dilate | MorphologyOp::Erode => element.radius(),
+            MorphologyOp::Open | MorphologyOp::Close => 2 * element.radius(),
+        }
+    }
+}
+
+/// Applies `op` with the structuring `element` to all points of `extent`, writing the results into a new array.
+///
+/// `src` must contain `extent.padded(op.padding(element))`.
+pub fn morphology<N, Src, T>(
+    src: &Src,
+    extent: &ExtentN<N>,
+    op: MorphologyOp,
+    element: &StructuringElement<N>,
+) -> ArrayNx1<N, T>
+where
+    N: ArrayIndexer<N>,
+    PointN<N>: IntegerPoint<N>,
+    T: 'static + Clone + PartialOrd,
+    Src: IndexedArray<N, Indexer = N> + Get<Stride, Item = T>,
+{
+    assert!(extent
+        .padded(op.padding(element))
+        .is_subset_of(src.extent()));
+
+    let dilate = |a: &T, b: &T| b > a;
+    let erode = |a: &T, b: &T| b < a;
+
+    match op {
+        MorphologyOp::Dilate => rank_filter(src, extent, element, dilate),
+        MorphologyOp::Erode => rank_filter(src, extent, element, erode),
+        MorphologyOp::Open => {
+            let eroded = rank_filter(src, &extent.padded(element.radius()), element, erode);
+
+            rank_filter(&eroded, extent, element, dilate)
+        }
+        MorphologyOp::Close => {
+            let dilated = rank_filter(src, &extent.padded(element.radius()), element, dilate);
+
+            rank_filter(&dilated, extent, element, erode)
+        }
+    }
+}
+
+// Replaces each value with the "best" value in its neighborhood, where `b` is better than `a` if `is_better(a, b)`.
+fn rank_filter<N, Src, T>(
+    src: &Src,
+    extent: &ExtentN<N>,
+    element: &StructuringElement<N>,
+    is_better: impl Fn(&T, &T) -> bool,
+) -> ArrayNx1<N, T>
+where
+    N: ArrayIndexer<N>,
+    PointN<N>: IntegerPoint<N>,
+    Src: IndexedArray<N, Indexer = N> + Get<Stride, Item = T>,
+{
+    let mut offset_strides = vec![Stride(0); element.offsets().len()];
+    src.strides_from_local_points(element.offsets(), &mut offset_strides);
+    let (first_offset, other_offsets) = offset_strides.split_first().unwrap();
+
+    let mut values = Vec::with_capacity(extent.num_points());
+    N::for_each_stride_parallel_global_unchecked(extent, extent, src.layout_extent(), |_, s| {
+        let mut best = src.get(s + *first_offset);
+        for offset in other_offsets.iter() {
+            let value = src.get(s + *offset);
+            if is_better(&best, &value) {
+                best = value;
+            }
+        }
+        values.push(best);
+    });
+
+    Array::new_one_channel(*extent, values)
+}
+
+impl<N, T, Bldr, Store> ChunkMap<N, T, Bldr, Store>
+where
+    PointN<N>: IntegerPoint<N>,
+    T: 'static + Clone,
+    Bldr: ChunkMapBuilder<N, T>,
+{
+    /// Copies `extent.padded(padding)` into a new array, so that a kernel can be applied to `extent`. Vacant chunks are read as
+    /// the ambient value.
+    pub fn padded_array<'a>(&'a self, extent: &ExtentN<N>, padding: i32) -> ArrayNx1<N, T>
+    where
+        Self: ReadExtent<'a, N>,
+        ArrayNx1<N, T>: WriteExtent<N, <Self as ReadExtent<'a, N>>::Src>,
+    {
+        let padded_extent = extent.padded(padding);
+        let mut array = Array::fill(padded_extent, self.builder().ambient_value());
+        copy_extent(&padded_extent, self, &mut array);
+
+        array
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn separable_box_filter_matches_brute_force() {
+        let src_extent = Extent3i::from_min_and_shape(PointN([-3, 2, 0]), PointN([10, 9, 8]));
+        let src = Array3x1::fill_with(src_extent, |p| ((p.x() * 7 + p.y() * 3 + p.z()) % 5) as f32);
+
+        let radius = 2;
+        let kernel = SeparableKernel::box_filter(radius);
+        let extent = src_extent.padded(-radius);
+        let blurred = convolve_separable(&src, &extent, &kernel);
+        assert_eq!(blurred.extent(), &extent);
+
+        let window =
+            Extent3i::from_min_and_shape(Point3i::fill(-radius), Point3i::fill(2 * radius + 1));
+        for p in extent.iter_points() {
+            let mean = (window + p).iter_points().map(|q| src.get(q)).sum::<f32>()
+                / window.num_points() as f32;
+            assert!((blurred.get(p) - mean).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn gaussian_blur_preserves_constant() {
+        let extent = Extent2i::from_min_and_shape(Point2i::ZERO, PointN([20, 12]));
+        let src = Array2x1::fill(extent, 3.0f32);
+
+        let kernel = SeparableKernel::gaussian(1.5);
+        assert_eq!(kernel.radius(), 5);
+        let blurred = convolve_separable(&src, &extent.padded(-5), &kernel);
+        for p in blurred.extent().iter_points() {
+            assert!((blurred.get(p) - 3.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn convolve_integer_values() {
+        let src_extent = Extent2i::from_min_and_shape(Point2i::ZERO, PointN([8, 3]));
+        let src = Array2x1::fill_with(src_extent, |p: Point2i| (p.x() * 100) as u16);
+
+        let kernel = SeparableKernel::box_filter(1);
+        let extent = src_extent.padded(-1);
+        let blurred = convolve_separable(&src, &extent, &kernel);
+        for p in extent.iter_points() {
+            assert_eq!(blurred.get(p), src.get(p));
+        }
+
+        let src = Array2x1::fill_with(src_extent, |p: Point2i| if p.x() == 3 { 255u8 } else { 0 });
+        let blurred = convolve_separable(&src, &extent, &kernel);
+        assert_eq!(blurred.get(PointN([2, 1])), 85);
+        assert_eq!(blurred.get(PointN([3, 1])), 85);
+        assert_eq!(blurred.get(PointN([5, 1])), 0);
+    }
+
+    #[test]
+    fn dilate_and_erode_ball() {
+        let extent = Extent3i::from_min_and_shape(Point3i::fill(-8), Point3i::fill(17));
+        let mut src = Array3x1::fill(extent, false);
+        *src.get_mut(Point3i::ZERO) = true;
+
+        let element = StructuringElement::ball(2);
+        let inner = extent.padded(-2);
+        let dilated = morphology(&src, &inner, MorphologyOp::Dilate, &element);
+        for p in inner.iter_points() {
+            assert_eq!(dilated.get(p), p.dot(p) <= 4);
+        }
+
+        let dilated = Array3x1::fill_with(extent, |p: Point3i| p.dot(p) <= 4);
+        let eroded = morphology(&dilated, &inner, MorphologyOp::Erode, &element);
+        for p in inner.iter_points() {
+            assert_eq!(eroded.get(p), p == Point3i::ZERO);
+        }
+    }
+
+    #[test]
+    fn element_without_origin_excludes_center() {
+        let extent = Extent2i::from_min_and_shape(Point2i::fill(-4), Point2i::fill(9));
+        let inner = extent.padded(-1);
+        let element = StructuringElement::new(vec![Local(PointN([-1, 0])), Local(PointN([1, 0]))]);
+
+        let src = Array2x1::fill_with(extent, |p: Point2i| p == Point2i::ZERO);
+        let dilated = morphology(&src, &inner, MorphologyOp::Dilate, &element);
+        for p in inner.iter_points() {
+            assert_eq!(dilated.get(p), p.y() == 0 && p.x().abs() == 1);
+        }
+
+        let src = Array2x1::fill_with(extent, |p: Point2i| p != Point2i::ZERO);
+        let eroded = morphology(&src, &inner, MorphologyOp::Erode, &element);
+        for p in inner.iter_points() {
+            assert_eq!(eroded.get(p), !(p.y() == 0 && p.x().abs() == 1));
+        }
+    }
+
+    #[test]
+    fn open_removes_specks_and_close_fills_holes() {
+        let extent = Extent2i::from_min_and_shape(Point2i::ZERO, PointN([16, 16]));
+        let solid = Extent2i::from_min_and_shape(Point2i::fill(4), Point2i::fill(8));
+        let speck = PointN([13, 2]);
+        let hole = PointN([7, 7]);
+        let src = Array2x1::fill_with(extent, |p: Point2i| {
+            (solid.contains(p) && p != hole) || p == speck
+        });
+
+        let element = StructuringElement::cube(1);
+        let inner = extent.padded(-MorphologyOp::Open.padding(&element));
+
+        let opened = morphology(&src, &inner, MorphologyOp::Open, &element);
+        assert!(!opened.get(speck));
+        assert!(opened.get(PointN([4, 4])));
+
+        let closed = morphology(&src, &inner, MorphologyOp::Close, &element);
+        assert!(closed.get(hole));
+        assert!(!closed.get(PointN([3, 4])));
+    }
+
+    #[test]
+    fn chunk_map_padded_array_uses_ambient_value() {
+        let mut map =
+            ChunkMapBuilder3x1::new(Point3i::fill(4), 1.0f32).build_with_hash_map_storage();
+        map.fill_extent(
+            &Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(4)),
+            0.0,
+        );
+
+        let kernel = SeparableKernel::box_filter(1);
+        let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(4));
+        let padded = map.padded_array(&extent, kernel.radius());
+        assert_eq!(padded.extent(), &extent.padded(1));
+
+        let blurred = convolve_separable(&padded, &extent, &kernel);
+        // The corner of the chunk sees 27 - 8 ambient points.
+        assert!((blurred.get(Point3i::ZERO) - 19.0 / 27.0).abs() < 1e-5);
+        assert_eq!(blurred.get(Point3i::fill(1)), 0.0);
+    }
+}
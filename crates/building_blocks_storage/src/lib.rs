@@ -7,7 +7,8 @@
 //! Various types of storage and indexing for voxels in 2 or 3 dimensions.
 //!
 //! If you need to store signed distance values in your voxels, consider using the `Sd8` and `Sd16` fixed-precision types which
-//! implement the `SignedDistance` trait required for smooth meshing.
+//! implement the `SignedDistance` trait required for smooth meshing. To build a signed distance field from voxels that only
+//! implement `IsEmpty`, use `signed_distance_transform`.
 //!
 //! The core storage types are:
 //!   - `Array`: N-dimensional, dense array
@@ -16,17 +17,21 @@
 //!
 //! Then there are "meta" lattice maps that provide some extra utility:
 //!   - `TransformMap`: a wrapper of any kind of lattice map that performs an arbitrary transformation
+//!   - `OrientedMap`: a wrapper of any kind of lattice map that rotates, mirrors, or transposes it
 //!   - `Fn(PointN<N>)`: some lattice map traits are implemented for functions (like SDFs)
 //!
 //! For multiresolution voxel data, there is an extension of `ChunkMap` called the `ChunkPyramid` which supports generic chunk
 //! downsampling via the `ChunkDownsampler` trait.
 //!
+//! For neighborhood operations like blurs and morphology, see the `kernel` module.
+//!
 //! For spatial indexing, there is the bounded `OctreeSet` and corresponding unbounded `ChunkedOctreeSet`. Specifically for
 //! indexing chunk keys and interacting with clipmaps, there is an `OctreeChunkIndex`.
 
 #[macro_use]
 pub mod access_traits;
 pub mod array;
+pub mod byte_size;
 pub mod caching;
 pub mod chunk;
 pub mod chunk_indexer;
@@ -35,16 +40,19 @@ pub mod chunk_storage;
 pub mod chunked_octree_set;
 pub mod compression;
 pub mod func;
+pub mod kernel;
 pub mod multi_ptr;
 pub mod multiresolution;
 pub mod octree_chunk_index;
 pub mod octree_set;
+pub mod oriented_map;
 pub mod raw_bytes;
 pub mod signed_distance;
 pub mod transform_map;
 
 pub use access_traits::*;
 pub use array::*;
+pub use byte_size::*;
 pub use caching::*;
 pub use chunk::*;
 pub use chunk_indexer::*;
@@ -53,10 +61,12 @@ pub use chunk_storage::*;
 pub use chunked_octree_set::*;
 pub use compression::*;
 pub use func::*;
+pub use kernel::*;
 pub use multi_ptr::*;
 pub use multiresolution::*;
 pub use octree_chunk_index::*;
 pub use octree_set::*;
+pub use oriented_map::*;
 pub use raw_bytes::*;
 pub use signed_distance::*;
 pub use transform_map::*;
@@ -81,11 +91,13 @@ pub mod prelude {
     pub use super::{
         copy_extent, Chunk, ChunkHashMapPyramid2, ChunkHashMapPyramid3, ChunkMapBuilder,
         ChunkReadStorage, ChunkWriteStorage, Compressed, CompressibleChunkMap,
-        CompressibleChunkMapReader, CompressibleChunkStorage, CompressibleChunkStorageReader,
-        Compression, FastCompressibleChunkStorage, FromBytesCompression, Func, IndexedArray,
-        IsEmpty, IterChunkKeys, Local, LocalChunkCache2, LocalChunkCache3, OctreeChunkIndex,
-        OctreeNode, OctreeSet, PointDownsampler, Sd16, Sd8, SdfMeanDownsampler, SerializableChunks,
-        SignedDistance, SmallKeyHashMap, Stride, TransformMap, VisitStatus,
+        CompressibleChunkMapReader, CompressibleChunkMapSharedReader, CompressibleChunkStorage,
+        CompressibleChunkStorageReader, CompressibleChunkStorageSharedReader, Compression,
+        CompressionBudget, DiskChunkMap, DiskChunkStorage, FastCompressibleChunkStorage,
+        FromBytesCompression, Func, IndexedArray, IsEmpty, IterChunkKeys, Local, LocalChunkCache2,
+        LocalChunkCache3, OctreeChunkIndex, OctreeNode, OctreeSet, OrientedMap, PointDownsampler,
+        RegionFile, Sd16, Sd8, SdfMeanDownsampler, SerializableChunks, SharedChunkCache2,
+        SharedChunkCache3, SignedDistance, SmallKeyHashMap, Stride, TransformMap, VisitStatus,
     };
 
     pub use super::access_traits::*;
@@ -99,6 +111,8 @@ pub mod prelude {
     pub use super::Lz4;
     #[cfg(feature = "snap")]
     pub use super::Snappy;
+    #[cfg(feature = "zstd")]
+    pub use super::Zstd;
 }
 
 #[cfg(feature = "dot_vox")]
@@ -7,6 +7,7 @@
 //! - [OctreeChunkIndex](crate::OctreeChunkIndex): an unbounded `OctreeSet` specifically for tracking the presence of chunks
 //! - [ChunkPyramid](self::ChunkPyramid): an ordered list of `ChunkMap` "levels", where each level decreases the sampling rate
 //! - [ChunkDownsampler](self::ChunkDownsampler): an algorithm for downsampling one chunk
+//! - [resample](self::resample()): standalone resampling of an `Array` by any scale factor
 //!
 //! You will generally want to have a `ChunkPyramid` and a corresponding `OctreeChunkIndex` that tracks the set of chunks that
 //! exist. Each node in the `OctreeChunkIndex` corresponds to a chunk at a particular level, i.e. an `LodChunkKey`. There is
@@ -69,10 +70,12 @@
 
 pub mod chunk_pyramid;
 pub mod clipmap;
+pub mod resample;
 pub mod sampling;
 
 pub use chunk_pyramid::*;
 pub use clipmap::*;
+pub use resample::*;
 pub use sampling::*;
 
 use building_blocks_core::PointN;
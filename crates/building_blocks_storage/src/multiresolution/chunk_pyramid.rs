@@ -1,7 +1,7 @@
 use crate::{
-    prelude::*, ArrayNx1, ChunkDownsampler, ChunkMap, ChunkMapBuilder, ChunkMapBuilderNx1,
-    ChunkMapNx1, FastArrayCompressionNx1, LodChunkKey, OctreeChunkIndex, OctreeNode,
-    SmallKeyHashMap, VisitStatus,
+    prelude::*, ArrayIndexer, ArrayNx1, ChunkDownsampler, ChunkMap, ChunkMapBuilder,
+    ChunkMapBuilderNx1, ChunkMapNx1, FastArrayCompressionNx1, LodChunkKey, OctreeChunkIndex,
+    OctreeNode, SmallKeyHashMap, VisitStatus,
 };
 
 use building_blocks_core::prelude::*;
@@ -67,6 +67,9 @@ where
     Store: ChunkWriteStorage<N, ArrayNx1<N, T>>,
 {
     /// Construct a new `ChunkPyramid` with height `num_levels`.
+    ///
+    /// Unlike a single `ChunkMap`, all dimensions of the chunk shape must be powers of 2, so that chunks can be downsampled
+    /// into the next level.
     pub fn new(
         builder: ChunkMapBuilderNx1<N, T>,
         storage_factory: impl Fn() -> Store,
@@ -75,6 +78,8 @@ where
     where
         ChunkMapBuilderNx1<N, T>: Clone,
     {
+        assert!(builder.chunk_shape.dimensions_are_powers_of_2());
+
         let mut levels = Vec::with_capacity(num_levels as usize);
         levels.resize_with(num_levels as usize, || {
             builder.clone().build_with_write_storage(storage_factory())
@@ -104,7 +109,7 @@ where
         dst_level: u8,
     ) where
         Samp: ChunkDownsampler<N, T, ArrayNx1<N, T>>,
-        ArrayNx1<N, T>: ForEachMutPtr<N, (), Item = *mut T>,
+        N: ArrayIndexer<N>,
     {
         let Self { levels, builder } = self;
 
@@ -148,7 +153,7 @@ where
         // Need to get tricky here to support both chunk references and TransformMaps returned from a closure.
         Lod0Ch: Borrow<Lod0ChBorrow>,
         Samp: ChunkDownsampler<N, T, Lod0ChBorrow>,
-        ArrayNx1<N, T>: ForEachMutPtr<N, (), Item = *mut T>,
+        N: ArrayIndexer<N>,
     {
         assert!(dst_lod > 0);
         let dst_level = dst_lod - 1;
@@ -188,7 +193,7 @@ where
         lod_delta: u8,
         dst_chunks: &mut ChunkMapNx1<N, T, Store>,
     ) where
-        ArrayNx1<N, T>: ForEachMutPtr<N, (), Item = *mut T>,
+        N: ArrayIndexer<N>,
     {
         let dst = DownsampleDestination::for_source_chunk(chunk_shape, src_chunk_key, lod_delta);
         let dst_chunk = dst_chunks.get_mut_chunk_or_insert_ambient(dst.dst_chunk_key);
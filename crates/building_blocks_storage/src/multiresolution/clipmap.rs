@@ -11,13 +11,15 @@ pub struct ClipMapConfig3 {
     /// The shape of every chunk, regardless of LOD. Note that while a chunk at a higher LOD takes up more world space, it has
     /// the same shape as chunks at lower levels, because the voxel size also changes.
     ///
-    /// **WARNING**: As of now, chunks must be cubes.
+    /// **WARNING**: As of now, chunks must be cubes with a power-of-two edge length, even though `ChunkIndexer` supports any
+    /// shape.
     pub chunk_shape: Point3i,
 }
 
 impl ClipMapConfig3 {
     pub fn chunk_edge_length_log2(&self) -> i32 {
         assert!(self.chunk_shape.is_cube());
+        assert!(self.chunk_shape.dimensions_are_powers_of_2());
 
         self.chunk_shape.x().trailing_zeros() as i32
     }
@@ -103,7 +105,7 @@ impl ClipMapUpdate3 {
         new_lod0_center: Point3i,
     ) -> Self {
         Self {
-            chunk_log2: config.chunk_shape.x().trailing_zeros() as i32,
+            chunk_log2: config.chunk_edge_length_log2(),
             num_lods: config.num_lods,
             low_lod_boundary: config.clip_box_radius,
             high_lod_boundary: config.clip_box_radius >> 1,
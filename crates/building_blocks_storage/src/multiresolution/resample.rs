@@ -0,0 +1,411 @@
+//! Standalone resampling of arrays to a different resolution, by any integer or fractional scale factor.
+//!
+//! Unlike the `ChunkDownsampler`s used by `ChunkPyramid`, these functions aren't limited to power-of-two reductions, and they
+//! can also upsample. Every function maps the entire source extent onto the entire destination extent, so the scale factor can
+//! even be different on each axis.
+//!
+//! The `Linear` and `Box` filters work on any `ResampleValue`, which includes all of the primitive numeric types. Values are
+//! filtered as `f64`, so integers of up to 32 bits come out exactly when all of the source values are equal, and `f64` values
+//! keep their full precision. 64-bit integers are only exact up to 2^53 in magnitude. Filtered integer values are rounded to
+//! the nearest integer, and clamped to the range of the type.
+//!
+//! ```
+//! use building_blocks_core::prelude::*;
+//! use building_blocks_storage::{prelude::*, resample, resample_by_factor, ResampleFilter};
+//!
+//! let src = Array3x1::fill_with(Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(4)), |p| p.x() as f32);
+//!
+//! // Upsample coarse generation output.
+//! let upsampled = resample_by_factor(&src, 2.5, ResampleFilter::Linear);
+//! assert_eq!(upsampled.extent().shape, Point3i::fill(10));
+//!
+//! // Or downsample to match the resolution of some other asset.
+//! let dst_extent = Extent3i::from_min_and_shape(Point3i::ZERO, PointN([2, 3, 1]));
+//! let downsampled = resample(&src, dst_extent, ResampleFilter::Box);
+//! assert_eq!(downsampled.get(PointN([0, 0, 0])), 0.5);
+//! ```
+
+use crate::{prelude::*, Array, ArrayIndexer, ArrayNx1};
+
+use building_blocks_core::prelude::*;
+
+/// How the values of source points are combined into a single destination point.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ResampleFilter {
+    /// Takes the source point whose center is closest. This is the only filter that works for non-numeric voxels.
+    Nearest,
+    /// Linearly interpolates the 2^N source points around each destination point's center (bilinear in 2D, trilinear in 3D).
+    /// Best for upsampling.
+    Linear,
+    /// Takes the mean of the source points covered by each destination point, weighted by the overlapping volume. Best for
+    /// downsampling.
+    Box,
+}
+
+/// A voxel value that can be combined by the `Linear` and `Box` filters, which take weighted sums of values.
+///
+/// `i64` and `u64` values with a magnitude greater than 2^53 lose precision in the conversion to `f64`.
+pub trait ResampleValue {
+    fn to_f64(&self) -> f64;
+    fn from_f64(value: f64) -> Self;
+}
+
+macro_rules! impl_resample_value_for_float {
+    ($($t:ty),+) => {
+        $(
+            impl ResampleValue for $t {
+                #[inline]
+                fn to_f64(&self) -> f64 {
+                    *self as f64
+                }
+
+                #[inline]
+                fn from_f64(value: f64) -> Self {
+                    value as $t
+                }
+            }
+        )+
+    };
+}
+
+macro_rules! impl_resample_value_for_int {
+    ($($t:ty),+) => {
+        $(
+            impl ResampleValue for $t {
+                #[inline]
+                fn to_f64(&self) -> f64 {
+                    *self as f64
+                }
+
+                /// Rounds to the nearest integer. Casting a float to an integer saturates, so this also clamps `value` to the
+                /// range of the type.
+                #[inline]
+                fn from_f64(value: f64) -> Self {
+                    value.round() as $t
+                }
+            }
+        )+
+    };
+}
+
+impl_resample_value_for_float!(f32, f64);
+impl_resample_value_for_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// Resamples all of `src` into a new array with `dst_extent`, using any `ResampleFilter`.
+///
+/// Values are converted to `f64` with `ResampleValue` for filtering. For non-numeric voxels, use `resample_nearest`.
+pub fn resample<N, Src, T>(
+    src: &Src,
+    dst_extent: ExtentN<N>,
+    filter: ResampleFilter,
+) -> ArrayNx1<N, T>
+where
+    N: ArrayIndexer<N> + AsRef<[i32]> + AsMut<[i32]>,
+    PointN<N>: IntegerPoint<N>,
+    T: 'static + Clone + ResampleValue,
+    Src: IndexedArray<N> + Get<PointN<N>, Item = T>,
+{
+    if filter == ResampleFilter::Nearest {
+        return resample_nearest(src, dst_extent);
+    }
+
+    let taps = AxisTaps::new(src.extent(), &dst_extent, filter);
+
+    Array::fill_with(dst_extent, |p| {
+        let mut sum = 0.0;
+        taps.visit(p - dst_extent.minimum, |src_p, weight| {
+            sum += weight * src.get(src_p).to_f64()
+        });
+
+        T::from_f64(sum)
+    })
+}
+
+/// Resamples all of `src` into a new array with `dst_extent`, using the `ResampleFilter::Nearest` filter. This works for any
+/// `Clone` voxel type.
+pub fn resample_nearest<N, Src, T>(src: &Src, dst_extent: ExtentN<N>) -> ArrayNx1<N, T>
+where
+    N: ArrayIndexer<N> + AsRef<[i32]> + AsMut<[i32]>,
+    PointN<N>: IntegerPoint<N>,
+    T: 'static + Clone,
+    Src: IndexedArray<N> + Get<PointN<N>, Item = T>,
+{
+    let taps = AxisTaps::new(src.extent(), &dst_extent, ResampleFilter::Nearest);
+
+    Array::fill_with(dst_extent, |p| {
+        let mut value = None;
+        taps.visit(p - dst_extent.minimum, |src_p, _| {
+            value = Some(src.get(src_p))
+        });
+
+        value.unwrap()
+    })
+}
+
+/// Like `resample`, but the destination extent is `scale_extent(src.extent(), factor)`.
+pub fn resample_by_factor<N, Src, T>(
+    src: &Src,
+    factor: f32,
+    filter: ResampleFilter,
+) -> ArrayNx1<N, T>
+where
+    N: ArrayIndexer<N> + AsRef<[i32]> + AsMut<[i32]>,
+    PointN<N>: IntegerPoint<N>,
+    T: 'static + Clone + ResampleValue,
+    Src: IndexedArray<N> + Get<PointN<N>, Item = T>,
+{
+    resample(src, scale_extent(src.extent(), factor), filter)
+}
+
+/// Like `resample_nearest`, but the destination extent is `scale_extent(src.extent(), factor)`.
+pub fn resample_nearest_by_factor<N, Src, T>(src: &Src, factor: f32) -> ArrayNx1<N, T>
+where
+    N: ArrayIndexer<N> + AsRef<[i32]> + AsMut<[i32]>,
+    PointN<N>: IntegerPoint<N>,
+    T: 'static + Clone,
+    Src: IndexedArray<N> + Get<PointN<N>, Item = T>,
+{
+    resample_nearest(src, scale_extent(src.extent(), factor))
+}
+
+/// Scales both corners of `extent` by `factor`, rounding to the nearest lattice point, but keeping at least one point on each
+/// axis. Because the corners are rounded independently, extents that tile the lattice still tile it after scaling, except where
+/// an extent would be scaled to less than one point on some axis. Then it's widened to one point, so it overlaps its neighbor.
+/// For example, with a factor of 0.3, `[0, 1)` and `[1, 2)` both become `[0, 1)`.
+pub fn scale_extent<N>(extent: &ExtentN<N>, factor: f32) -> ExtentN<N>
+where
+    N: AsMut<[i32]>,
+    PointN<N>: IntegerPoint<N>,
+{
+    assert!(factor > 0.0);
+
+    let mut min = extent.minimum;
+    let mut lub = extent.least_upper_bound();
+    for (min_c, lub_c) in min.0.as_mut().iter_mut().zip(lub.0.as_mut().iter_mut()) {
+        *min_c = (*min_c as f32 * factor).round() as i32;
+        *lub_c = ((*lub_c as f32 * factor).round() as i32).max(*min_c + 1);
+    }
+
+    ExtentN::from_min_and_lub(min, lub)
+}
+
+/// For each axis and each destination coordinate on that axis, the source coordinates and weights of the filter. Since all of
+/// the filters are separable, the weight of a source point is just the product of its weights on each axis.
+struct AxisTaps {
+    // [axis][dst offset] -> [(src coordinate, weight)]
+    taps: Vec<Vec<Vec<(i32, f64)>>>,
+}
+
+impl AxisTaps {
+    fn new<N>(src_extent: &ExtentN<N>, dst_extent: &ExtentN<N>, filter: ResampleFilter) -> Self
+    where
+        N: AsRef<[i32]>,
+        PointN<N>: IntegerPoint<N>,
+    {
+        assert!(!src_extent.is_empty());
+
+        let src_min = src_extent.minimum.0.as_ref();
+        let src_shape = src_extent.shape.0.as_ref();
+        let dst_shape = dst_extent.shape.0.as_ref();
+
+        let taps = src_min
+            .iter()
+            .zip(src_shape.iter().zip(dst_shape.iter()))
+            .map(|(&src_min, (&src_len, &dst_len))| {
+                (0..dst_len)
+                    .map(|x| Self::taps_for_coordinate(filter, x, src_len, dst_len))
+                    .map(|taps| taps.into_iter().map(|(c, w)| (src_min + c, w)).collect())
+                    .collect()
+            })
+            .collect();
+
+        Self { taps }
+    }
+
+    // Coordinates are relative to the minimum of each extent.
+    fn taps_for_coordinate(
+        filter: ResampleFilter,
+        x: i32,
+        src_len: i32,
+        dst_len: i32,
+    ) -> Vec<(i32, f64)> {
+        // The length of one destination point in source points.
+        let scale = src_len as f64 / dst_len as f64;
+        let clamp = |c: i32| c.max(0).min(src_len - 1);
+
+        match filter {
+            ResampleFilter::Nearest => {
+                vec![(clamp(((x as f64 + 0.5) * scale).floor() as i32), 1.0)]
+            }
+            ResampleFilter::Linear => {
+                // The center of the destination point, in the continuous source space where point centers are integers.
+                let center = (x as f64 + 0.5) * scale - 0.5;
+                let c0 = center.floor();
+                let t = center - c0;
+                let c0 = c0 as i32;
+
+                vec![(clamp(c0), 1.0 - t), (clamp(c0 + 1), t)]
+            }
+            ResampleFilter::Box => {
+                let start = x as f64 * scale;
+                let end = start + scale;
+
+                (start.floor() as i32..end.ceil() as i32)
+                    .filter_map(|c| {
+                        let overlap = end.min((c + 1) as f64) - start.max(c as f64);
+
+                        (overlap > 0.0).then(|| (clamp(c), overlap / scale))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn visit<N>(&self, dst_offset: PointN<N>, mut visitor: impl FnMut(PointN<N>, f64))
+    where
+        N: AsRef<[i32]> + AsMut<[i32]>,
+        PointN<N>: Copy,
+    {
+        self.visit_axis(0, dst_offset, dst_offset, 1.0, &mut visitor);
+    }
+
+    fn visit_axis<N>(
+        &self,
+        axis: usize,
+        dst_offset: PointN<N>,
+        mut src_p: PointN<N>,
+        weight: f64,
+        visitor: &mut impl FnMut(PointN<N>, f64),
+    ) where
+        N: AsRef<[i32]> + AsMut<[i32]>,
+        PointN<N>: Copy,
+    {
+        if axis == self.taps.len() {
+            visitor(src_p, weight);
+            return;
+        }
+
+        for &(c, w) in self.taps[axis][dst_offset.0.as_ref()[axis] as usize].iter() {
+            src_p.0.as_mut()[axis] = c;
+            self.visit_axis(axis + 1, dst_offset, src_p, weight * w, visitor);
+        }
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nearest_upsample_repeats_points() {
+        let src_extent = Extent3i::from_min_and_shape(PointN([-2, 0, 2]), Point3i::fill(3));
+        let src = Array3x1::fill_with(src_extent, |p| p);
+
+        let dst = resample_nearest_by_factor(&src, 2.0);
+        assert_eq!(dst.extent(), &(src_extent * Point3i::fill(2)));
+        for p in dst.extent().iter_points() {
+            assert_eq!(dst.get(p), p.vector_div_floor(Point3i::fill(2)));
+        }
+    }
+
+    #[test]
+    fn box_downsample_preserves_total() {
+        let src_extent = Extent3i::from_min_and_shape(Point3i::ZERO, PointN([6, 9, 4]));
+        let src = Array3x1::fill_with(src_extent, |p| (p.x() * p.y() + p.z()) as f32);
+        let src_total: f32 = src_extent.iter_points().map(|p| src.get(p)).sum();
+
+        for dst_shape in [PointN([3, 3, 2]), PointN([4, 6, 3]), PointN([5, 2, 1])] {
+            let dst_extent = Extent3i::from_min_and_shape(Point3i::ZERO, dst_shape);
+            let dst = resample(&src, dst_extent, ResampleFilter::Box);
+            let dst_total: f32 = dst_extent.iter_points().map(|p| dst.get(p)).sum();
+            let volume_ratio = src_extent.num_points() as f32 / dst_extent.num_points() as f32;
+            assert!((dst_total * volume_ratio - src_total).abs() < 1e-2 * src_total);
+        }
+
+        // An even factor is just the mean of each block.
+        let dst_extent = Extent3i::from_min_and_shape(Point3i::ZERO, PointN([3, 9, 2]));
+        let dst = resample(&src, dst_extent, ResampleFilter::Box);
+        assert_eq!(dst.get(PointN([0, 1, 0])), (0.0 + 1.0 + 1.0 + 2.0) / 4.0);
+    }
+
+    #[test]
+    fn linear_upsample_interpolates_ramp() {
+        let src_extent = Extent2i::from_min_and_shape(Point2i::ZERO, PointN([4, 2]));
+        let src = Array2x1::fill_with(src_extent, |p: Point2i| p.x() as f32);
+
+        let dst = resample_by_factor(&src, 4.0, ResampleFilter::Linear);
+        assert_eq!(dst.extent().shape, PointN([16, 8]));
+        // Points whose centers are between the first and last source centers get an exact interpolation.
+        for p in Extent2i::from_min_and_lub(PointN([2, 0]), PointN([14, 8])).iter_points() {
+            let expected = (p.x() as f32 + 0.5) / 4.0 - 0.5;
+            assert!((dst.get(p) - expected).abs() < 1e-5);
+        }
+        // Otherwise they're clamped to the border.
+        assert_eq!(dst.get(PointN([0, 0])), 0.0);
+        assert_eq!(dst.get(PointN([15, 7])), 3.0);
+    }
+
+    #[test]
+    fn integer_values_are_rounded_and_clamped() {
+        let src_extent = Extent2i::from_min_and_shape(Point2i::ZERO, PointN([2, 1]));
+        let src = Array2x1::fill_with(src_extent, |p: Point2i| if p.x() == 0 { 0u8 } else { 255 });
+
+        let dst = resample_by_factor(&src, 2.0, ResampleFilter::Linear);
+        let row: Vec<u8> = (0..4).map(|x| dst.get(PointN([x, 0]))).collect();
+        assert_eq!(row, [0, 64, 191, 255]);
+
+        let dst = resample(
+            &src,
+            Extent2i::from_min_and_shape(Point2i::ZERO, Point2i::fill(1)),
+            ResampleFilter::Box,
+        );
+        assert_eq!(dst.get(Point2i::ZERO), 128);
+
+        assert_eq!(u16::from_f64(-3.0), 0);
+        assert_eq!(u16::from_f64(1e9), u16::MAX);
+    }
+
+    #[test]
+    fn constant_values_survive_filtering() {
+        let src_extent = Extent3i::from_min_and_shape(Point3i::ZERO, PointN([6, 9, 4]));
+        let src = Array3x1::fill(src_extent, u32::MAX);
+        for filter in [ResampleFilter::Linear, ResampleFilter::Box] {
+            for factor in [0.5, 0.3, 1.7] {
+                let dst = resample_by_factor(&src, factor, filter);
+                assert!(dst.extent().iter_points().all(|p| dst.get(p) == u32::MAX));
+            }
+        }
+
+        // This can't be represented by an `f32`.
+        let value = 16_777_217.0f64;
+        let src = Array3x1::fill(src_extent, value);
+        let dst = resample_by_factor(&src, 0.5, ResampleFilter::Box);
+        assert!(dst.extent().iter_points().all(|p| dst.get(p) == value));
+    }
+
+    #[test]
+    fn scaled_extents_still_tile() {
+        let a = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(5));
+        let b = Extent3i::from_min_and_shape(PointN([5, 0, 0]), Point3i::fill(5));
+        let (a, b) = (scale_extent(&a, 0.3), scale_extent(&b, 0.3));
+        assert_eq!(a.least_upper_bound().x(), b.minimum.x());
+        assert_eq!(a.shape, PointN([2, 2, 2]));
+        assert_eq!(b.shape, PointN([1, 2, 2]));
+    }
+
+    #[test]
+    fn extents_scaled_below_one_point_overlap() {
+        let a = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(1));
+        let b = Extent3i::from_min_and_shape(PointN([1, 0, 0]), Point3i::fill(1));
+        let (a, b) = (scale_extent(&a, 0.3), scale_extent(&b, 0.3));
+        assert_eq!(a, b);
+        assert_eq!(a.shape, Point3i::fill(1));
+    }
+}
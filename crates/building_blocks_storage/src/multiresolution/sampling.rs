@@ -37,9 +37,12 @@ where
 
         let dst_shape = src_chunk.extent().shape >> lod_delta;
         debug_assert!(dst_shape > PointN::ZERO);
+        // `Local` points are relative to the layout extent, which is bigger than the extent for an `ArrayView`.
+        let src_min = src_chunk.extent().minimum - src_chunk.layout_extent().minimum;
 
         for p in ExtentN::from_min_and_shape(PointN::ZERO, dst_shape).iter_points() {
-            *dst_chunk.get_mut(Local(dst_min.0 + p)) = src_chunk.get(Local(p << lod_delta));
+            *dst_chunk.get_mut(Local(dst_min.0 + p)) =
+                src_chunk.get(Local(src_min + (p << lod_delta)));
         }
     }
 }
@@ -76,9 +79,10 @@ where
 
         let dst_shape = src_chunk.extent().shape >> lod_delta;
         debug_assert!(dst_shape > PointN::ZERO);
+        let src_chunk_min = src_chunk.extent().minimum - src_chunk.layout_extent().minimum;
 
         for p_dst in ExtentN::from_min_and_shape(PointN::ZERO, dst_shape).iter_points() {
-            let src_min = p_dst << lod_delta;
+            let src_min = src_chunk_min + (p_dst << lod_delta);
             let src_extent = ExtentN::from_min_and_shape(src_min, src_shape_per_point);
 
             let mut sum = 0.0;
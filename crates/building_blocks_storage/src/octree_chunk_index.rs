@@ -31,6 +31,9 @@ impl OctreeChunkIndex {
     }
 
     /// Same as `index_chunks`, but using the chunk keys and chunk shape from `chunk_map`.
+    ///
+    /// While a `ChunkMap` may have any chunk shape, the dimensions of `chunk_map`'s chunk shape must be powers of 2 to be
+    /// indexed by octrees.
     pub fn index_chunk_map<T, Ch, Store>(
         superchunk_shape: Point3i,
         chunk_map: &ChunkMap3<T, Ch, Store>,
@@ -58,8 +61,16 @@ impl OctreeChunkIndex {
         chunk_shape: Point3i,
         chunk_keys: impl Iterator<Item = &'a Point3i>,
     ) -> Self {
-        assert!(superchunk_shape.dimensions_are_powers_of_2());
-        assert!(chunk_shape.dimensions_are_powers_of_2());
+        assert!(
+            superchunk_shape.dimensions_are_powers_of_2(),
+            "OctreeChunkIndex requires a superchunk shape with power of 2 dimensions, got {:?}",
+            superchunk_shape
+        );
+        assert!(
+            chunk_shape.dimensions_are_powers_of_2(),
+            "OctreeChunkIndex requires a chunk shape with power of 2 dimensions, got {:?}",
+            chunk_shape
+        );
 
         let superchunk_log2 = superchunk_shape.map_components_unary(|c| c.trailing_zeros() as i32);
         let chunk_log2 = chunk_shape.map_components_unary(|c| c.trailing_zeros() as i32);
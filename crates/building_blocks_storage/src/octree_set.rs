@@ -114,7 +114,7 @@ impl OctreeSet {
     /// must have `0 < P <= 6`, because there is a maximum fixed depth of the octree.
     pub fn from_array3<A, T>(array: &A, extent: Extent3i) -> Self
     where
-        A: IndexedArray<[i32; 3]> + Get<Stride, Item = T>,
+        A: IndexedArray<[i32; 3], Indexer = [i32; 3]> + Get<Stride, Item = T>,
         T: Clone + IsEmpty,
     {
         let power = Self::check_extent(&extent);
@@ -133,7 +133,7 @@ impl OctreeSet {
         array.strides_from_local_points(&corner_offsets, &mut corner_strides);
 
         let mut nodes = SmallKeyHashMap::default();
-        let min_local = Local(extent.minimum - array.extent().minimum);
+        let min_local = Local(extent.minimum - array.layout_extent().minimum);
         let root_minimum = array.stride_from_local_point(min_local);
         let root_code = LocationCode::ROOT;
         let (root_exists, _full) = Self::partition_array(
@@ -162,7 +162,7 @@ impl OctreeSet {
         nodes: &mut SmallKeyHashMap<LocationCode, ChildBitMask>,
     ) -> (bool, bool)
     where
-        A: IndexedArray<[i32; 3]> + Get<Stride, Item = T>,
+        A: IndexedArray<[i32; 3], Indexer = [i32; 3]> + Get<Stride, Item = T>,
         T: Clone + IsEmpty,
     {
         // Base case where the octant is a single voxel. The `OctreeNode` is invalid and unnecessary in this case; we avoid using
@@ -1160,6 +1160,18 @@ mod tests {
         assert_eq!(non_empty_voxels, octant_voxels);
     }
 
+    #[test]
+    fn octree_from_view_matches_copied_array() {
+        let voxels = random_voxels();
+        let window = Extent3i::from_min_and_shape(Point3i::fill(16), Point3i::fill(32));
+        let from_view = OctreeSet::from_array3(&voxels.view(&window), window);
+
+        let mut copy = Array3x1::fill(window, Voxel(false));
+        crate::copy_extent(&window, &voxels, &mut copy);
+
+        assert_eq!(from_view, OctreeSet::from_array3(&copy, window));
+    }
+
     fn random_voxels() -> Array3x1<Voxel> {
         let mut rng = rand::thread_rng();
         let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(64));
@@ -0,0 +1,290 @@
+//! A lattice map that reorients a delegate lattice map by any of the axis-aligned `Orientation`s, i.e. rotations, mirrors, and
+//! transposes.
+//!
+//! Orientations always act about the origin, so a point `p` of the `OrientedMap` reads the delegate at
+//! `orientation.inverse().transform_point(p)`. Voxels that store a direction, like the facing of a stair block, also need their
+//! values reoriented; that's what the `transform_value` hook is for.
+//!
+//! As an example, here's how you could paste a "prefab" into a chunk map after rotating it a quarter turn about the Y axis:
+//!
+//! ```
+//! use building_blocks_core::{prelude::*, SignedAxis3};
+//! use building_blocks_storage::prelude::*;
+//!
+//! // Each voxel stores the direction it's facing.
+//! let prefab_extent = Extent3i::from_min_and_shape(Point3i::ZERO, PointN([4, 2, 1]));
+//! let prefab = Array3x1::fill(prefab_extent, SignedAxis3::new(1, Axis3::X));
+//!
+//! let orientation = Orientation3::quarter_turns(Axis3::Y, 1);
+//! let oriented = OrientedMap::new(&prefab, orientation, |facing: SignedAxis3, o: Orientation3| {
+//!     o.transform_signed_axis(facing)
+//! });
+//!
+//! // Translate the rotated extent to wherever the prefab should be pasted.
+//! let rotated_extent = orientation.transform_extent(prefab.extent());
+//! assert_eq!(rotated_extent.shape, PointN([1, 2, 4]));
+//!
+//! let builder = ChunkMapBuilder3x1::new(Point3i::fill(4), SignedAxis3::new(1, Axis3::Y));
+//! let mut map = builder.build_with_hash_map_storage();
+//! copy_extent(&rotated_extent, &oriented, &mut map);
+//!
+//! assert_eq!(map.get(rotated_extent.minimum), SignedAxis3::new(-1, Axis3::Z));
+//! ```
+//!
+//! If you just need the reoriented `Array`, use `Array::oriented`.
+
+use crate::{
+    Array, ArrayCopySrc, Channels, ChunkMap, ForEach, ForEachMutPtr, Get, IntoMultiMutPtr,
+    MultiMutPtr, ReadExtent, UninitChannels, WriteExtent,
+};
+
+use building_blocks_core::prelude::*;
+
+use core::iter::{once, Once};
+
+/// A lattice map that delegates look-ups to a different lattice map after undoing an `Orientation`, then transforms the result
+/// using some `Fn(In, O) -> Out`.
+pub struct OrientedMap<'a, Delegate, O, F> {
+    delegate: &'a Delegate,
+    orientation: O,
+    inverse: O,
+    transform_value: F,
+}
+
+impl<'a, Delegate, O, F> Clone for OrientedMap<'a, Delegate, O, F>
+where
+    O: Copy,
+    F: Clone,
+{
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            delegate: self.delegate,
+            orientation: self.orientation,
+            inverse: self.inverse,
+            transform_value: self.transform_value.clone(),
+        }
+    }
+}
+
+impl<'a, Delegate, O, F> Copy for OrientedMap<'a, Delegate, O, F>
+where
+    O: Copy,
+    F: Copy,
+{
+}
+
+impl<'a, Delegate, O, F> OrientedMap<'a, Delegate, O, F> {
+    #[inline]
+    pub fn new<N>(delegate: &'a Delegate, orientation: O, transform_value: F) -> Self
+    where
+        O: Orientation<N>,
+    {
+        Self {
+            delegate,
+            orientation,
+            inverse: orientation.inverse(),
+            transform_value,
+        }
+    }
+
+    #[inline]
+    pub fn orientation(&self) -> O
+    where
+        O: Copy,
+    {
+        self.orientation
+    }
+}
+
+impl<'a, N, Delegate, O, F, In, Out> Get<PointN<N>> for OrientedMap<'a, Delegate, O, F>
+where
+    O: Orientation<N>,
+    F: Fn(In, O) -> Out,
+    Delegate: Get<PointN<N>, Item = In>,
+{
+    type Item = Out;
+
+    #[inline]
+    fn get(&self, p: PointN<N>) -> Self::Item {
+        (self.transform_value)(
+            self.delegate.get(self.inverse.transform_point(p)),
+            self.orientation,
+        )
+    }
+}
+
+impl<'a, N, Delegate, O, F, Out> ForEach<N, PointN<N>> for OrientedMap<'a, Delegate, O, F>
+where
+    Self: Get<PointN<N>, Item = Out>,
+    PointN<N>: IntegerPoint<N>,
+{
+    type Item = Out;
+
+    #[inline]
+    fn for_each(&self, extent: &ExtentN<N>, mut f: impl FnMut(PointN<N>, Self::Item)) {
+        for p in extent.iter_points() {
+            f(p, self.get(p));
+        }
+    }
+}
+
+impl<'a, N, Chan, Idx, O, F> ReadExtent<'a, N> for OrientedMap<'a, Array<N, Chan, Idx>, O, F>
+where
+    Self: Clone,
+    PointN<N>: IntegerPoint<N>,
+    O: Orientation<N>,
+{
+    type Src = ArrayCopySrc<Self>;
+    type SrcIter = Once<(ExtentN<N>, Self::Src)>;
+
+    fn read_extent(&'a self, extent: &ExtentN<N>) -> Self::SrcIter {
+        let in_bounds_extent = self
+            .orientation
+            .transform_extent(self.delegate.extent())
+            .intersection(extent);
+
+        once((in_bounds_extent, ArrayCopySrc(self.clone())))
+    }
+}
+
+impl<'a, N, T, Bldr, Store, O, F> ReadExtent<'a, N>
+    for OrientedMap<'a, ChunkMap<N, T, Bldr, Store>, O, F>
+where
+    Self: Clone,
+    ExtentN<N>: Copy,
+{
+    type Src = ArrayCopySrc<Self>;
+    type SrcIter = Once<(ExtentN<N>, Self::Src)>;
+
+    /// Vacant chunks are read as the (transformed) ambient value.
+    fn read_extent(&'a self, extent: &ExtentN<N>) -> Self::SrcIter {
+        once((*extent, ArrayCopySrc(self.clone())))
+    }
+}
+
+impl<'a, N, Chan, Idx, Delegate, O, F> WriteExtent<N, ArrayCopySrc<OrientedMap<'a, Delegate, O, F>>>
+    for Array<N, Chan, Idx>
+where
+    Self: ForEachMutPtr<N, PointN<N>, Item = Chan::Ptr>,
+    OrientedMap<'a, Delegate, O, F>: Get<PointN<N>, Item = Chan::Data>,
+    PointN<N>: IntegerPoint<N>,
+    Chan: Channels,
+{
+    fn write_extent(
+        &mut self,
+        extent: &ExtentN<N>,
+        src: ArrayCopySrc<OrientedMap<'a, Delegate, O, F>>,
+    ) {
+        let in_bounds_extent = extent.intersection(self.extent());
+
+        unsafe {
+            self.for_each_mut_ptr(&in_bounds_extent, |p, ptr| ptr.write(src.0.get(p)));
+        }
+    }
+}
+
+impl<N, Chan, Idx, UninitChan> Array<N, Chan, Idx>
+where
+    Self: Get<PointN<N>, Item = Chan::Data>,
+    Array<N, UninitChan, Idx>: ForEachMutPtr<N, PointN<N>, Item = UninitChan::Ptr>,
+    PointN<N>: IntegerPoint<N>,
+    Chan: Channels<UninitSelf = UninitChan>,
+    UninitChan: UninitChannels<InitSelf = Chan>,
+    UninitChan::Ptr: IntoMultiMutPtr<Data = Chan::Data>,
+{
+    /// Creates a new array by applying `orientation` to this one. Values are passed through `transform_value`, which should
+    /// reorient any directional data, or else just return the value it's given.
+    ///
+    /// The orientation is about the origin, so the new array's extent is `orientation.transform_extent(self.extent())`. Use
+    /// `set_minimum` to move it somewhere else.
+    pub fn oriented<O>(
+        &self,
+        orientation: O,
+        transform_value: impl Fn(Chan::Data, O) -> Chan::Data,
+    ) -> Self
+    where
+        O: Orientation<N>,
+    {
+        let oriented = OrientedMap::new(self, orientation, transform_value);
+
+        Self::fill_with(orientation.transform_extent(self.extent()), |p| {
+            oriented.get(p)
+        })
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    use building_blocks_core::SignedAxis3;
+
+    #[test]
+    fn oriented_array_matches_transformed_points() {
+        let extent = Extent3i::from_min_and_shape(PointN([1, 2, 3]), PointN([2, 3, 4]));
+        let array = Array3x1::fill_with(extent, |p| p);
+
+        for o in Orientation3::all() {
+            let oriented = array.oriented(o, |p: Point3i, _| p);
+            assert_eq!(oriented.extent(), &o.transform_extent(&extent));
+            for p in extent.iter_points() {
+                assert_eq!(oriented.get(o.transform_point(p)), p);
+            }
+
+            // Undoing the orientation gets back the original array.
+            let restored = oriented.oriented(o.inverse(), |p: Point3i, _| p);
+            assert_eq!(restored.extent(), &extent);
+            assert!(extent.iter_points().all(|p| restored.get(p) == p));
+        }
+    }
+
+    #[test]
+    fn oriented_2d_array_mirror_and_transpose() {
+        let extent = Extent2i::from_min_and_shape(Point2i::ZERO, PointN([3, 2]));
+        let array = Array2x1::fill_with(extent, |p: Point2i| p.x() + 10 * p.y());
+
+        let transposed = array.oriented(Orientation2::transpose(), |v, _| v);
+        assert_eq!(transposed.extent().shape, PointN([2, 3]));
+        assert_eq!(transposed.get(PointN([1, 2])), 12);
+
+        let mirrored = array.oriented(Orientation2::mirror(Axis2::X), |v, _| v);
+        assert_eq!(mirrored.extent().minimum, PointN([-2, 0]));
+        assert_eq!(mirrored.get(PointN([-2, 1])), 12);
+    }
+
+    #[test]
+    fn copy_from_oriented_chunk_map_transforms_values() {
+        let builder = ChunkMapBuilder3x1::new(Point3i::fill(4), SignedAxis3::new(1, Axis3::Y));
+        let mut src = builder.build_with_hash_map_storage();
+        let src_extent = Extent3i::from_min_and_shape(Point3i::ZERO, PointN([2, 4, 8]));
+        src.fill_extent(&src_extent, SignedAxis3::new(1, Axis3::X));
+
+        let o = Orientation3::quarter_turns(Axis3::Z, 1);
+        let oriented = OrientedMap::new(&src, o, |a: SignedAxis3, o: Orientation3| {
+            o.transform_signed_axis(a)
+        });
+
+        let dst_extent = o.transform_extent(&src_extent).padded(1);
+        let mut dst = Array3x1::fill(dst_extent, SignedAxis3::new(0, Axis3::X));
+        copy_extent(&dst_extent, &oriented, &mut dst);
+
+        for p in dst_extent.iter_points() {
+            let expected = if src_extent.contains(o.inverse().transform_point(p)) {
+                SignedAxis3::new(1, Axis3::Y)
+            } else {
+                // The ambient value.
+                SignedAxis3::new(-1, Axis3::X)
+            };
+            assert_eq!(dst.get(p), expected);
+        }
+    }
+}
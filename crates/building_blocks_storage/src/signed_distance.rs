@@ -1,3 +1,9 @@
+mod distance_transform;
+
+pub use distance_transform::*;
+
+use crate::{ResampleValue, StableTypeName};
+
 use serde::{Deserialize, Serialize};
 
 pub trait SignedDistance: Into<f32> {
@@ -32,6 +38,14 @@ impl Sd16 {
     pub const ONE: Self = Self(std::i16::MAX);
 }
 
+impl StableTypeName for Sd8 {
+    const STABLE_TYPE_NAME: &'static str = "Sd8";
+}
+
+impl StableTypeName for Sd16 {
+    const STABLE_TYPE_NAME: &'static str = "Sd16";
+}
+
 impl From<Sd8> for f32 {
     #[inline]
     fn from(s: Sd8) -> f32 {
@@ -51,6 +65,18 @@ impl SignedDistance for Sd8 {
     }
 }
 
+impl ResampleValue for Sd8 {
+    #[inline]
+    fn to_f64(&self) -> f64 {
+        f32::from(*self) as f64
+    }
+
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        Self::from(value as f32)
+    }
+}
+
 impl From<Sd16> for f32 {
     #[inline]
     fn from(s: Sd16) -> f32 {
@@ -70,6 +96,18 @@ impl SignedDistance for Sd16 {
     }
 }
 
+impl ResampleValue for Sd16 {
+    #[inline]
+    fn to_f64(&self) -> f64 {
+        f32::from(*self) as f64
+    }
+
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        Self::from(value as f32)
+    }
+}
+
 // ████████╗███████╗███████╗████████╗
 // ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
 //    ██║   █████╗  ███████╗   ██║
@@ -0,0 +1,255 @@
+use crate::{prelude::*, Array, ArrayIndexer, ArrayNx1, IsEmpty};
+
+use building_blocks_core::prelude::*;
+
+/// Computes the exact Euclidean signed distance field of the non-empty points of `map` (as defined by the `IsEmpty` trait), for
+/// all points in `extent`.
+///
+/// Distances are measured between point centers, then shifted by half a voxel so that the zero crossing lies on the faces
+/// between empty and non-empty points. Non-empty points are negative. The distances are clamped to `clamp_distance` and then
+/// divided by it, so they land in the `[-1.0, 1.0]` range of `Sd8` and `Sd16`. Multiply by `clamp_distance` to get back to
+/// voxel units.
+///
+/// Only the points in `extent` are considered, so if there could be surfaces just outside of `extent`, you should pad it by
+/// `clamp_distance`.
+///
+/// This uses the separable algorithm of Felzenszwalb and Huttenlocher, which takes linear time in the number of points.
+///
+/// ```
+/// use building_blocks_core::prelude::*;
+/// use building_blocks_storage::{prelude::*, signed_distance_transform};
+///
+/// let extent = Extent3i::from_min_and_shape(Point3i::fill(-8), Point3i::fill(16));
+/// let solid = Array3x1::fill_with(extent, |p: Point3i| p.dot(p) <= 9);
+///
+/// let sdf: Array3x1<Sd8> = signed_distance_transform(&solid, &extent, 4.0);
+/// assert!(sdf.get(Point3i::ZERO).is_negative());
+/// assert!(!sdf.get(PointN([0, 0, 4])).is_negative());
+/// assert_eq!(sdf.get(PointN([7, 7, 7])), Sd8::ONE);
+/// ```
+pub fn signed_distance_transform<N, Map, V, T>(
+    map: &Map,
+    extent: &ExtentN<N>,
+    clamp_distance: f32,
+) -> ArrayNx1<N, T>
+where
+    N: ArrayIndexer<N>,
+    PointN<N>: IntegerPoint<N>,
+    Map: ForEach<N, PointN<N>, Item = V>,
+    V: IsEmpty,
+    T: 'static + Clone + From<f32>,
+{
+    assert!(clamp_distance > 0.0);
+
+    if extent.is_empty() {
+        return Array::new_one_channel(*extent, Vec::new());
+    }
+
+    // Squared distances to the nearest non-empty point and to the nearest empty point, respectively.
+    let mut to_solid = vec![FAR; extent.num_points()];
+    let mut to_empty = vec![FAR; extent.num_points()];
+    map.for_each(extent, |p, value| {
+        let Stride(s) = N::stride_from_local_point(extent.shape, Local(p - extent.minimum));
+        if value.is_empty() {
+            to_empty[s] = 0.0;
+        } else {
+            to_solid[s] = 0.0;
+        }
+    });
+
+    let mut transform = SquaredDistanceTransform::default();
+    for (axis_index, axis) in PointN::<N>::basis().into_iter().enumerate() {
+        transform.transform_lines(extent, axis_index, axis, &mut to_solid);
+        transform.transform_lines(extent, axis_index, axis, &mut to_empty);
+    }
+
+    let values = to_solid
+        .into_iter()
+        .zip(to_empty)
+        .map(|(to_solid, to_empty)| {
+            let distance = if to_solid > 0.0 {
+                to_solid.sqrt() - 0.5
+            } else {
+                0.5 - to_empty.sqrt()
+            };
+
+            T::from(distance.max(-clamp_distance).min(clamp_distance) / clamp_distance)
+        })
+        .collect();
+
+    Array::new_one_channel(*extent, values)
+}
+
+// Stands in for infinity, since the lower envelope calculation would produce NaNs from actual infinities.
+const FAR: f32 = 1e20;
+
+/// Scratch space for the 1-dimensional squared distance transform.
+#[derive(Default)]
+struct SquaredDistanceTransform {
+    line: Vec<f32>,
+    // Locations of the parabolas in the lower envelope.
+    parabolas: Vec<usize>,
+    // Boundaries between the parabolas in the lower envelope.
+    boundaries: Vec<f32>,
+    // The values of `line` at each parabola location.
+    roots: Vec<f32>,
+}
+
+impl SquaredDistanceTransform {
+    // Transforms every line of `values` (laid out in `extent`) that is parallel to the unit vector `axis`.
+    fn transform_lines<N>(
+        &mut self,
+        extent: &ExtentN<N>,
+        axis_index: usize,
+        axis: PointN<N>,
+        values: &mut [f32],
+    ) where
+        N: ArrayIndexer<N>,
+        PointN<N>: IntegerPoint<N>,
+    {
+        let line_length = extent.shape.at(axis_index) as usize;
+        let Stride(line_stride) = N::stride_from_local_point(extent.shape, Local(axis));
+        let line_starts = ExtentN::from_min_and_shape(
+            extent.minimum,
+            extent.shape - axis * (line_length as i32 - 1),
+        );
+
+        N::for_each_stride_parallel_global_unchecked(
+            &line_starts,
+            extent,
+            extent,
+            |Stride(start), _| {
+                self.line.clear();
+                self.line
+                    .extend((0..line_length).map(|i| values[start + i * line_stride]));
+                self.transform_line();
+                for (i, d) in self.line.iter().enumerate() {
+                    values[start + i * line_stride] = *d;
+                }
+            },
+        );
+    }
+
+    // Replaces each f(q) in `line` with min_p((q - p)^2 + f(p)).
+    fn transform_line(&mut self) {
+        let f = &mut self.line;
+        let n = f.len();
+        let v = &mut self.parabolas;
+        let z = &mut self.boundaries;
+        v.clear();
+        v.resize(n, 0);
+        z.clear();
+        z.resize(n + 1, 0.0);
+
+        // Find the lower envelope of the parabolas rooted at each point.
+        let mut k = 0;
+        z[0] = -f32::INFINITY;
+        z[1] = f32::INFINITY;
+        for q in 1..n {
+            let intersect = |p: usize| {
+                ((f[q] + (q * q) as f32) - (f[p] + (p * p) as f32)) / (2 * q - 2 * p) as f32
+            };
+            let mut s = intersect(v[k]);
+            while s <= z[k] {
+                k -= 1;
+                s = intersect(v[k]);
+            }
+            k += 1;
+            v[k] = q;
+            z[k] = s;
+            z[k + 1] = f32::INFINITY;
+        }
+
+        // Sample the lower envelope, writing the results back into `f`.
+        let roots = &mut self.roots;
+        roots.clear();
+        roots.extend(v[..=k].iter().map(|&p| f[p]));
+        let mut k = 0;
+        for (q, d) in f.iter_mut().enumerate() {
+            while z[k + 1] < q as f32 {
+                k += 1;
+            }
+            let offset = q as f32 - v[k] as f32;
+            *d = offset * offset + roots[k];
+        }
+    }
+}
+
+// ████████╗███████╗███████╗████████╗
+// ╚══██╔══╝██╔════╝██╔════╝╚══██╔══╝
+//    ██║   █████╗  ███████╗   ██║
+//    ██║   ██╔══╝  ╚════██║   ██║
+//    ██║   ███████╗███████║   ██║
+//    ╚═╝   ╚══════╝╚══════╝   ╚═╝
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Finds the distance to the nearest point on the opposite side of the surface by brute force.
+    fn brute_force_sdf<N>(solid: &ArrayNx1<N, bool>, extent: &ExtentN<N>, p: PointN<N>) -> f32
+    where
+        N: ArrayIndexer<N>,
+        PointN<N>: IntegerPoint<N> + Distance,
+    {
+        let is_solid = solid.get(p);
+        let nearest = extent
+            .iter_points()
+            .filter(|q| solid.get(*q) != is_solid)
+            .map(|q| (p.l2_distance_squared(q) as f32).sqrt())
+            .fold(f32::INFINITY, f32::min);
+
+        if is_solid {
+            0.5 - nearest
+        } else {
+            nearest - 0.5
+        }
+    }
+
+    #[test]
+    fn matches_brute_force_3d() {
+        let extent = Extent3i::from_min_and_shape(PointN([-3, 0, 2]), PointN([9, 7, 8]));
+        let solid = Array3x1::fill_with(extent, |p: Point3i| {
+            (p.x() * 7 + p.y() * 5 + p.z() * 3) % 11 == 0
+        });
+
+        let clamp = 100.0;
+        let sdf: Array3x1<f32> = signed_distance_transform(&solid, &extent, clamp);
+        for p in extent.iter_points() {
+            let expected = brute_force_sdf(&solid, &extent, p);
+            assert!((sdf.get(p) * clamp - expected).abs() < 1e-4, "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn matches_brute_force_2d() {
+        let extent = Extent2i::from_min_and_shape(PointN([1, -4]), PointN([13, 10]));
+        let solid = Array2x1::fill_with(extent, |p: Point2i| {
+            p.x() * p.x() + 2 * p.y() * p.y() < 20 || p == PointN([12, 5])
+        });
+
+        let clamp = 100.0;
+        let sdf: Array2x1<f32> = signed_distance_transform(&solid, &extent, clamp);
+        for p in extent.iter_points() {
+            let expected = brute_force_sdf(&solid, &extent, p);
+            assert!((sdf.get(p) * clamp - expected).abs() < 1e-4, "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn clamps_and_quantizes() {
+        let extent = Extent3i::from_min_and_shape(Point3i::ZERO, Point3i::fill(8));
+        let mut solid = Array3x1::fill(extent, false);
+        *solid.get_mut(Point3i::ZERO) = true;
+
+        let sdf: Array3x1<Sd16> = signed_distance_transform(&solid, &extent, 2.0);
+        assert_eq!(sdf.get(Point3i::ZERO), Sd16::from(-0.25));
+        assert_eq!(sdf.get(PointN([1, 0, 0])), Sd16::from(0.25));
+        assert_eq!(sdf.get(PointN([7, 7, 7])), Sd16::ONE);
+
+        // Without any solid points, everything is clamped.
+        let empty = Array3x1::fill(extent, false);
+        let sdf: Array3x1<Sd8> = signed_distance_transform(&empty, &extent, 2.0);
+        assert!(extent.iter_points().all(|p| sdf.get(p) == Sd8::ONE));
+    }
+}
@@ -113,12 +113,17 @@ where
     fn extent(&self) -> &ExtentN<N> {
         self.delegate.extent()
     }
+
+    #[inline]
+    fn layout_extent(&self) -> &ExtentN<N> {
+        self.delegate.layout_extent()
+    }
 }
 
 // TODO: try to make a generic ReadExtent impl, it's hard because we need a way to define the src types as a function of the
 // delegate src types (kinda hints at a monad or HKT)
 
-impl<'a, N, Chan, F> ReadExtent<'a, N> for TransformMap<'a, Array<N, Chan>, F>
+impl<'a, N, Chan, Idx, F> ReadExtent<'a, N> for TransformMap<'a, Array<N, Chan, Idx>, F>
 where
     Self: IndexedArray<N> + Clone,
     PointN<N>: IntegerPoint<N>,
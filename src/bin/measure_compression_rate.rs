@@ -16,6 +16,12 @@ fn main() {
 
     println!("Compressing with LZ4: \n");
     measure_compression_rate(Lz4 { level: 10 }, &vox_array);
+
+    #[cfg(feature = "zstd")]
+    {
+        println!("Compressing with Zstd: \n");
+        measure_compression_rate(Zstd::new(19), &vox_array);
+    }
 }
 
 fn measure_compression_rate<B: BytesCompression>(